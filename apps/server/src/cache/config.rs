@@ -25,7 +25,7 @@ impl ConfigCache {
 
         let cached_item = Arc::new(CachedItem::new(config, &self.cache_timeout));
 
-        self.configs.insert(String::from(domain), cached_item);
+        self.configs.insert(domain, cached_item);
 
         Ok(())
     }
@@ -33,7 +33,7 @@ impl ConfigCache {
     pub fn get(&self, email: &str) -> Option<Arc<CachedItem<Config>>> {
         let domain = get_domain_from_email(email).ok()?;
 
-        match self.configs.get(domain) {
+        match self.configs.get(&domain) {
             Some(entry) => {
                 if entry.expired() {
                     return None;
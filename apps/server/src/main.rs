@@ -63,6 +63,8 @@ fn rocket() -> _ {
                 .unwrap_or_else(|| generate_random_hex(32)),
         ));
 
+    sdk::detect::set_local_dir(config.autoconfig_dir().map(|dir| dir.to_path_buf()));
+
     let ip_state = state::IpState::new(
         config.rate_limit().max_queries(),
         config.rate_limit().time_span(),
@@ -88,6 +90,9 @@ fn rocket() -> _ {
             "/",
             routes![
                 routes::auto_detect_config_handler,
+                routes::auto_detect_config_progress_handler,
+                routes::autoconfig_handler,
+                routes::well_known_autoconfig_handler,
                 routes::settings_handler,
                 routes::version_handler,
                 routes::login_handler,
@@ -96,7 +101,11 @@ fn rocket() -> _ {
         )
         .mount(
             "/mail/",
-            routes![routes::mail_login_handler, routes::mail_logout_handler,],
+            routes![
+                routes::mail_login_handler,
+                routes::mail_logout_handler,
+                routes::mail_transcript_handler,
+            ],
         )
         .mount(
             "/mail/boxes",
@@ -104,7 +113,8 @@ fn rocket() -> _ {
                 routes::mail_box_list_handler,
                 routes::mail_get_box_handler,
                 routes::mail_box_messages_handler,
-                routes::mail_box_message_handler
+                routes::mail_box_message_handler,
+                routes::mail_box_attachment_handler
             ],
         )
         .mount(
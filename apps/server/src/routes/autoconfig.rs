@@ -0,0 +1,62 @@
+use dust_mail_utils::parse_addr_spec;
+use rocket::{
+    http::{uri::Host, ContentType},
+    State,
+};
+
+use crate::{guards::RateLimiter, state::Config};
+
+const AUTOCONFIG_SUBDOMAIN: &str = "autoconfig.";
+
+/// Mail clients send the users email address along, but if they don't we fall back to the domain the request was sent to.
+fn find_domain(emailaddress: Option<&str>, host: Option<&Host<'_>>) -> Option<String> {
+    if let Some(emailaddress) = emailaddress {
+        return parse_addr_spec(emailaddress)
+            .ok()
+            .map(|address| address.ascii_domain().to_string());
+    }
+
+    let domain = host?.domain().as_str();
+
+    Some(
+        domain
+            .strip_prefix(AUTOCONFIG_SUBDOMAIN)
+            .unwrap_or(domain)
+            .to_string(),
+    )
+}
+
+fn find_config(
+    config: &Config,
+    emailaddress: Option<&str>,
+    host: Option<&Host<'_>>,
+) -> Option<(ContentType, String)> {
+    let domain = find_domain(emailaddress, host)?;
+
+    let provider = config
+        .providers()
+        .iter()
+        .find(|provider| provider.serves(&domain))?;
+
+    Some((ContentType::XML, provider.to_autoconfig().to_xml()))
+}
+
+#[get("/mail/config-v1.1.xml?<emailaddress>")]
+pub fn autoconfig(
+    emailaddress: Option<String>,
+    host: Option<&Host<'_>>,
+    _rate_limiter: RateLimiter,
+    config: &State<Config>,
+) -> Option<(ContentType, String)> {
+    find_config(config, emailaddress.as_deref(), host)
+}
+
+#[get("/.well-known/autoconfig/mail/config-v1.1.xml?<emailaddress>")]
+pub fn well_known_autoconfig(
+    emailaddress: Option<String>,
+    host: Option<&Host<'_>>,
+    _rate_limiter: RateLimiter,
+    config: &State<Config>,
+) -> Option<(ContentType, String)> {
+    find_config(config, emailaddress.as_deref(), host)
+}
@@ -4,8 +4,12 @@ use crate::{
     types::{ErrResponse, ErrorKind, OkResponse, ResponseResult},
 };
 
-use rocket::State;
-use sdk::detect::{self, Config};
+use rocket::{
+    futures::StreamExt,
+    response::stream::{Event, EventStream},
+    State,
+};
+use sdk::detect::{self, Config, Progress};
 
 #[get("/detect/<email>")]
 pub async fn auto_detect_config(
@@ -16,7 +20,7 @@ pub async fn auto_detect_config(
 ) -> ResponseResult<Config> {
     match cache.get(&email) {
         Some(cached) => Ok(OkResponse::new(cached.item().clone())),
-        None => match detect::from_email(&email).await {
+        None => match detect::from_email(&email, None).await {
             Ok(config) => {
                 cache
                     .set(&email, &config)
@@ -31,3 +35,33 @@ pub async fn auto_detect_config(
         },
     }
 }
+
+/// Detects the config like `auto_detect_config`, but sends what is happening as server sent events, so the login screen can show the progress.
+///
+/// Every event is a JSON encoded `Progress`, the last one is either a `done` or a `failed` event.
+#[get("/detect/<email>/progress")]
+pub fn auto_detect_config_progress<'a>(
+    email: String,
+    _user: User,
+    _rate_limiter: RateLimiter,
+    cache: &'a State<ConfigCache>,
+) -> EventStream![Event + 'a] {
+    EventStream! {
+        if let Some(cached) = cache.get(&email) {
+            yield Event::json(&Progress::Done { config: cached.item().clone() });
+
+            return;
+        }
+
+        let mut events = detect::from_email_with_progress(&email, None);
+
+        while let Some(progress) = events.next().await {
+            if let Progress::Done { config } = &progress {
+                // The user can still use the config if we fail to cache it.
+                let _ = cache.set(&email, config);
+            }
+
+            yield Event::json(&progress);
+        }
+    }
+}
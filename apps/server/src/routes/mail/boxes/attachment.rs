@@ -0,0 +1,95 @@
+use rocket::{
+    http::{ContentType, Header, Status},
+    serde::json::Json,
+};
+use sdk::types::Attachment;
+
+use crate::{
+    guards::{RateLimiter, User},
+    types::{ErrResponse, Error},
+};
+
+/// The data of an attachment, served like a file so the browser can show or download it.
+#[derive(Responder)]
+pub struct AttachmentResponse {
+    data: Vec<u8>,
+    content_type: ContentType,
+    content_disposition: Header<'static>,
+}
+
+impl AttachmentResponse {
+    fn new(attachment: &Attachment, data: Vec<u8>) -> Self {
+        let content_type =
+            ContentType::parse_flexible(attachment.content_type()).unwrap_or(ContentType::Binary);
+
+        Self {
+            data,
+            content_type,
+            content_disposition: Header::new(
+                "Content-Disposition",
+                content_disposition(attachment.filename()),
+            ),
+        }
+    }
+}
+
+/// The value of the Content-Disposition header for an attachment with a given filename.
+///
+/// The filename is given twice, as described in https://www.rfc-editor.org/rfc/rfc6266#section-4.3: an ASCII only version for old clients
+/// and the percent encoded UTF-8 version of https://www.rfc-editor.org/rfc/rfc8187 for the others.
+fn content_disposition(filename: Option<&str>) -> String {
+    let filename = match filename {
+        Some(filename) if !filename.is_empty() => filename,
+        _ => return String::from("attachment"),
+    };
+
+    let ascii_filename: String = filename
+        .chars()
+        .map(|char| match char {
+            '"' | '\\' => '_',
+            char if char.is_ascii() && !char.is_ascii_control() => char,
+            _ => '_',
+        })
+        .collect();
+
+    let mut encoded_filename = String::new();
+
+    for byte in filename.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => encoded_filename.push(byte as char),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                encoded_filename.push(byte as char)
+            }
+            byte => encoded_filename.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        ascii_filename, encoded_filename
+    )
+}
+
+#[get("/<box_id>/<message_id>/<attachment_id>?<session_token>")]
+pub async fn get_attachment(
+    session_token: String,
+    box_id: String,
+    message_id: String,
+    attachment_id: String,
+    user: User,
+    _rate_limiter: RateLimiter,
+) -> Result<AttachmentResponse, (Status, Json<ErrResponse>)> {
+    let incoming_session = user
+        .mail_sessions()
+        .get_incoming(session_token)
+        .map_err(|err| ErrResponse::from(err).into())?;
+
+    let mut incoming_session_lock = incoming_session.lock().await;
+
+    let (attachment, data) = incoming_session_lock
+        .get_attachment(&box_id, &message_id, &attachment_id)
+        .await
+        .map_err(|err| ErrResponse::from(Error::from(err)).into())?;
+
+    Ok(AttachmentResponse::new(&attachment, data))
+}
@@ -5,11 +5,12 @@ use crate::{
     types::{ErrResponse, Error, OkResponse, ResponseResult},
 };
 
-#[get("/<box_id>/<message_id>?<session_token>")]
+#[get("/<box_id>/<message_id>?<session_token>&<mark_read>")]
 pub fn get_message(
     session_token: String,
     box_id: String,
     message_id: String,
+    mark_read: Option<bool>,
     user: User,
     _rate_limiter: RateLimiter,
 ) -> ResponseResult<Message> {
@@ -21,7 +22,7 @@ pub fn get_message(
     let mut incoming_session_lock = incoming_session.lock().unwrap();
 
     incoming_session_lock
-        .get_message(&box_id, &message_id)
+        .get_message(&box_id, &message_id, mark_read.unwrap_or(false))
         .map(|message| OkResponse::new(message))
         .map_err(|err| ErrResponse::from(Error::from(err)).into())
 }
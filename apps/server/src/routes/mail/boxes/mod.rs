@@ -1,8 +1,10 @@
+mod attachment;
 mod get;
 mod list;
 mod message;
 mod messages;
 
+pub use attachment::get_attachment as mail_box_attachment_handler;
 pub use get::get_box as mail_get_box_handler;
 pub use list::box_list as mail_box_list_handler;
 pub use message::get_message as mail_box_message_handler;
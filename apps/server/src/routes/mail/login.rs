@@ -8,14 +8,23 @@ use crate::{
 use rocket::{serde::json::Json, State};
 use sdk::session::{create_sessions, FullLoginOptions};
 
-#[post("/login", data = "<credentials>")]
+#[post("/login?<transcript>&<trusted_fingerprint>", data = "<credentials>")]
 pub async fn login(
     credentials: Json<FullLoginOptions>,
+    transcript: Option<bool>,
+    trusted_fingerprint: Option<String>,
     user: User,
     _rate_limiter: RateLimiter,
     config: &State<Config>,
 ) -> ResponseResult<String> {
-    let session_token = base64_encode::<String>(credentials.0.clone().into());
+    let mut credentials = credentials.into_inner();
+
+    // The user accepted a certificate the server presented earlier, we store it with the credentials in the session token so it is trusted on subsequent connections.
+    if let Some(fingerprint) = trusted_fingerprint {
+        credentials.trust_certificate(fingerprint);
+    }
+
+    let session_token = base64_encode::<String>(credentials.clone().into());
 
     let auth_config = config.authorization().cloned().unwrap_or_default();
 
@@ -39,7 +48,7 @@ pub async fn login(
             ),
         )),
         None => {
-            let mail_sessions = create_sessions(&credentials)
+            let mail_sessions = create_sessions(&credentials, transcript.unwrap_or(false))
                 .await
                 .map_err(|err| ErrResponse::from(Error::from(err)).into())?;
 
@@ -2,8 +2,10 @@ mod boxes;
 mod login;
 mod logout;
 mod oauth2;
+mod transcript;
 
 pub use boxes::*;
 pub use login::login as mail_login_handler;
 pub use logout::logout as mail_logout_handler;
 pub use oauth2::*;
+pub use transcript::transcript as mail_transcript_handler;
@@ -0,0 +1,27 @@
+use crate::{
+    guards::{RateLimiter, User},
+    types::{ErrResponse, ErrorKind, OkResponse, ResponseResult},
+};
+
+/// Exports the protocol transcript of a mail session, so it can be attached to a bug report.
+#[get("/transcript?<session_token>")]
+pub fn transcript(
+    session_token: String,
+    user: User,
+    _rate_limiter: RateLimiter,
+) -> ResponseResult<String> {
+    let mail_sessions = user.mail_sessions().get(&session_token).ok_or_else(|| {
+        ErrResponse::new(
+            ErrorKind::NotFound,
+            "Could not find a session with that token",
+        )
+    })?;
+
+    match mail_sessions.transcript() {
+        Some(transcript) => Ok(OkResponse::new(transcript.export())),
+        None => Err(ErrResponse::new(
+            ErrorKind::BadRequest,
+            "No transcript was recorded for this session, log in with 'transcript' enabled to record one",
+        )),
+    }
+}
@@ -1,3 +1,4 @@
+mod autoconfig;
 mod detect;
 mod login;
 mod logout;
@@ -5,7 +6,10 @@ mod mail;
 mod settings;
 mod version;
 
+pub use autoconfig::autoconfig as autoconfig_handler;
+pub use autoconfig::well_known_autoconfig as well_known_autoconfig_handler;
 pub use detect::auto_detect_config as auto_detect_config_handler;
+pub use detect::auto_detect_config_progress as auto_detect_config_progress_handler;
 pub use login::login as login_handler;
 pub use logout::logout as logout_handler;
 pub use settings::settings as settings_handler;
@@ -3,10 +3,14 @@ mod authorization;
 mod cache;
 mod limit;
 mod oauth2;
+mod provider;
 
 use rocket::serde::{Deserialize, Serialize};
 
-use std::env;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 use appearance::Appearance;
 use authorization::Authorization;
@@ -14,6 +18,7 @@ use cache::Cache;
 use limit::RateLimit;
 
 pub use authorization::{default_expiry_time, AuthType};
+pub use provider::Provider;
 
 use self::oauth2::OAuth2;
 
@@ -36,6 +41,11 @@ pub struct Config {
     #[serde(default)]
     oauth2: OAuth2,
     auth: Option<Authorization>,
+    /// A directory with autoconfig files for domains that are not in the ISPDB, such as the ones of an organization's own mail server.
+    autoconfig_dir: Option<PathBuf>,
+    /// The providers whose config is served to other mail clients through the autoconfig endpoints.
+    #[serde(default)]
+    providers: Vec<Provider>,
 }
 
 impl Config {
@@ -74,6 +84,14 @@ impl Config {
     pub fn oauth2(&self) -> &OAuth2 {
         &self.oauth2
     }
+
+    pub fn autoconfig_dir(&self) -> Option<&Path> {
+        self.autoconfig_dir.as_deref()
+    }
+
+    pub fn providers(&self) -> &Vec<Provider> {
+        &self.providers
+    }
 }
 
 impl Default for Config {
@@ -94,6 +112,8 @@ impl Default for Config {
             oauth2: OAuth2::default(),
             host: default_host(),
             port: default_port(),
+            autoconfig_dir: None,
+            providers: Vec::new(),
         }
     }
 }
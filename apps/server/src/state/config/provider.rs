@@ -0,0 +1,133 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use autoconfig::{
+    builder::{ConfigBuilder, ServerBuilder},
+    types::config::{
+        AuthenticationType as AutoConfigAuthenticationType, Config as AutoConfig,
+        SecurityType as AutoConfigSecurityType, Server as AutoConfigServer,
+        ServerType as AutoConfigServerType,
+    },
+};
+
+/// A mail provider the operator runs, whose config is served to mail clients through the autoconfig endpoints.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Provider {
+    domains: Vec<String>,
+    display_name: Option<String>,
+    #[serde(default)]
+    incoming: Vec<ProviderServer>,
+    #[serde(default)]
+    outgoing: Vec<ProviderServer>,
+}
+
+impl Provider {
+    pub fn serves(&self, domain: &str) -> bool {
+        self.domains
+            .iter()
+            .any(|provider_domain| provider_domain.eq_ignore_ascii_case(domain))
+    }
+
+    pub fn to_autoconfig(&self) -> AutoConfig {
+        let id = self.domains.first().cloned().unwrap_or_default();
+
+        let mut builder = ConfigBuilder::new(id);
+
+        for domain in &self.domains {
+            builder.domain(domain);
+        }
+
+        if let Some(display_name) = &self.display_name {
+            builder.display_name(display_name);
+        }
+
+        for server in &self.incoming {
+            builder.incoming_server(server.to_autoconfig());
+        }
+
+        for server in &self.outgoing {
+            builder.outgoing_server(server.to_autoconfig());
+        }
+
+        builder.build()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ProviderServer {
+    r#type: ServerType,
+    hostname: String,
+    port: u16,
+    security: Security,
+    #[serde(default = "default_username")]
+    username: String,
+    #[serde(default = "default_authentication")]
+    authentication: Vec<Authentication>,
+}
+
+impl ProviderServer {
+    fn to_autoconfig(&self) -> AutoConfigServer {
+        let server_type = match self.r#type {
+            ServerType::Imap => AutoConfigServerType::Imap,
+            ServerType::Pop3 => AutoConfigServerType::Pop3,
+            ServerType::Smtp => AutoConfigServerType::Smtp,
+        };
+
+        let security = match self.security {
+            Security::Plain => AutoConfigSecurityType::Plain,
+            Security::StartTls => AutoConfigSecurityType::Starttls,
+            Security::Tls => AutoConfigSecurityType::Tls,
+        };
+
+        let mut builder = ServerBuilder::new(server_type, &self.hostname, self.port, security);
+
+        builder.username(&self.username);
+
+        for authentication in &self.authentication {
+            builder.authentication(match authentication {
+                Authentication::PasswordCleartext => {
+                    AutoConfigAuthenticationType::PasswordCleartext
+                }
+                Authentication::PasswordEncrypted => {
+                    AutoConfigAuthenticationType::PasswordEncrypted
+                }
+                Authentication::OAuth2 => AutoConfigAuthenticationType::OAuth2,
+            });
+        }
+
+        builder.build()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ServerType {
+    Imap,
+    Pop3,
+    Smtp,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Security {
+    Plain,
+    StartTls,
+    Tls,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Authentication {
+    PasswordCleartext,
+    PasswordEncrypted,
+    OAuth2,
+}
+
+fn default_username() -> String {
+    String::from("%EMAILADDRESS%")
+}
+
+fn default_authentication() -> Vec<Authentication> {
+    vec![Authentication::PasswordCleartext]
+}
@@ -1,12 +1,14 @@
 use std::{error, fmt};
 
 use rocket::serde::Serialize;
-use sdk::types::Error as SdkError;
+use sdk::types::{CertificateInfo, Error as SdkError, ErrorKind as SdkErrorKind};
 
 #[derive(Serialize, Debug)]
 #[serde(crate = "rocket::serde")]
 pub enum ErrorKind {
     SdkError(SdkError),
+    /// The mail server presented a certificate that is not trusted, which the user can decide to trust.
+    UntrustedCertificate,
     CreateHttpRequest,
     BadConfig,
     Unauthorized,
@@ -22,13 +24,26 @@ pub enum ErrorKind {
 pub struct Error {
     message: String,
     kind: ErrorKind,
+    /// The certificates the mail server presented, starting with its own certificate, if it is not trusted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    certificates: Option<Vec<CertificateInfo>>,
 }
 
 impl From<SdkError> for Error {
     fn from(error: SdkError) -> Self {
+        // Include the certificates, so the user can decide whether to trust them.
+        if let SdkErrorKind::UntrustedCertificate(certificates) = error.kind() {
+            return Self {
+                message: error.to_string(),
+                certificates: Some(certificates.clone()),
+                kind: ErrorKind::UntrustedCertificate,
+            };
+        }
+
         Self {
             kind: ErrorKind::SdkError(error),
             message: String::from("Error with upstream mail server"),
+            certificates: None,
         }
     }
 }
@@ -38,6 +53,7 @@ impl Error {
         Self {
             kind,
             message: message.into(),
+            certificates: None,
         }
     }
 
@@ -37,6 +37,7 @@ impl ErrResponse {
             ErrorKind::NotFound => Status::NotFound,
             ErrorKind::Parse => Status::BadRequest,
             ErrorKind::SdkError(_) => Status::InternalServerError,
+            ErrorKind::UntrustedCertificate => Status::BadGateway,
         }
     }
 
@@ -6,6 +6,7 @@ use crate::{
     types::{Error, ErrorKind, Result},
 };
 use directories::BaseDirs;
+use dust_mail_utils::parse_addr_spec;
 use std::{
     env,
     fs::{create_dir_all, read_to_string, write},
@@ -16,18 +17,11 @@ pub fn create_mail_parse_error() -> Error {
     Error::new(ErrorKind::Parse, "Failed to parse email address")
 }
 
-pub fn get_domain_from_email<'a>(email: &'a str) -> Result<&'a str> {
-    let mut email_split = email.split('@');
+/// The domain of an email address in its ASCII form, so addresses with the same domain share their cached config.
+pub fn get_domain_from_email(email: &str) -> Result<String> {
+    let address = parse_addr_spec(email).map_err(|_| create_mail_parse_error())?;
 
-    match email_split.next() {
-        Some(_) => {}
-        None => return Err(create_mail_parse_error()),
-    };
-
-    match email_split.next() {
-        Some(domain) => Ok(domain),
-        None => Err(create_mail_parse_error()),
-    }
+    Ok(address.ascii_domain().to_string())
 }
 
 pub fn generate_random_string(n: usize) -> String {
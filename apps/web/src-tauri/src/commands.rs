@@ -1,25 +1,70 @@
+use futures::StreamExt;
 use sdk::{
-    detect::{self, Config},
+    detect::{self, Config, Progress},
     session::FullLoginOptions,
-    types::{MailBox, Message, Preview},
+    types::{MailBox, Message, Preview, ProxyConfig},
 };
 
 use crate::{identifier::Identifier, keyring, parse::to_json, sessions::Sessions, types::Result};
 
-use tauri::State;
+use serde::Serialize;
+use tauri::{State, Window};
 
 #[tauri::command(async)]
-pub async fn detect_config(email_address: String) -> Result<Config> {
-    Ok(detect::from_email(&email_address).await?)
+pub async fn detect_config(email_address: String, proxy: Option<ProxyConfig>) -> Result<Config> {
+    Ok(detect::from_email(&email_address, proxy.as_ref()).await?)
+}
+
+/// The payload of a `detect_progress` event, the id is the one the detection was started with,
+/// so the events of multiple detections that run at the same time can be told apart.
+#[derive(Serialize, Clone)]
+struct DetectProgress<'a> {
+    id: &'a str,
+    #[serde(flatten)]
+    progress: &'a Progress,
+}
+
+/// Detects the config like `detect_config`, but emits a `detect_progress` event to the window for everything that happens,
+/// so the login screen can show the progress. The last event contains the config or the reason it could not be detected.
+#[tauri::command(async)]
+pub async fn detect_config_progress(
+    window: Window,
+    id: String,
+    email_address: String,
+    proxy: Option<ProxyConfig>,
+) -> Result<()> {
+    let mut events = detect::from_email_with_progress(&email_address, proxy.as_ref());
+
+    while let Some(progress) = events.next().await {
+        let payload = DetectProgress {
+            id: &id,
+            progress: &progress,
+        };
+
+        // The window was closed, so nobody is waiting for the result anymore.
+        if window.emit("detect_progress", payload).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
 }
 
 #[tauri::command(async)]
 pub async fn login(
-    credentials: FullLoginOptions,
+    mut credentials: FullLoginOptions,
+    trusted_fingerprint: Option<String>,
+    record_transcript: Option<bool>,
     session_handler: State<'_, Sessions>,
 ) -> Result<String> {
+    // The user accepted a certificate the server presented earlier, we store it with the credentials so it is trusted on subsequent connections.
+    if let Some(fingerprint) = trusted_fingerprint {
+        credentials.trust_certificate(fingerprint);
+    }
+
     // Connect and login to the mail servers using the user provided credentials.
-    let mail_sessions = sdk::session::create_sessions(&credentials).await?;
+    let mail_sessions =
+        sdk::session::create_sessions(&credentials, record_transcript.unwrap_or(false)).await?;
 
     let mut identifier = Identifier::from(&credentials);
 
@@ -87,21 +132,58 @@ pub async fn messages(
 
 #[tauri::command(async)]
 /// Gets the full message data from a given mailbox and a given message id.
+///
+/// The message is only marked as read on the server when `mark_read` is set.
 pub async fn get_message(
     token: String,
     box_id: String,
     message_id: String,
+    mark_read: Option<bool>,
     sessions: State<'_, Sessions>,
 ) -> Result<Message> {
     let session = sessions.get_incoming_session(&token).await?;
 
     let mut session_lock = session.lock().await;
 
-    let message = session_lock.get_message(&box_id, &message_id).await?;
+    let message = session_lock
+        .get_message(&box_id, &message_id, mark_read.unwrap_or(false))
+        .await?;
 
     Ok(message)
 }
 
+#[tauri::command(async)]
+/// Downloads the data of an attachment of a given message, using the attachment id from the message's attachment list.
+pub async fn get_attachment(
+    token: String,
+    box_id: String,
+    message_id: String,
+    attachment_id: String,
+    sessions: State<'_, Sessions>,
+) -> Result<Vec<u8>> {
+    let session = sessions.get_incoming_session(&token).await?;
+
+    let mut session_lock = session.lock().await;
+
+    let (_, data) = session_lock
+        .get_attachment(&box_id, &message_id, &attachment_id)
+        .await?;
+
+    Ok(data)
+}
+
+#[tauri::command(async)]
+/// Exports the protocol transcript of the currently logged in account, so it can be attached to a bug report.
+///
+/// Returns nothing if the transcript was not recorded when logging in.
+pub async fn transcript(token: String, sessions: State<'_, Sessions>) -> Result<Option<String>> {
+    let mail_sessions = sessions.get_session(&token).await?;
+
+    Ok(mail_sessions
+        .transcript()
+        .map(|transcript| transcript.export()))
+}
+
 #[tauri::command(async)]
 /// Log out of the currently logged in account.
 pub async fn logout(identifier: String, sessions: State<'_, Sessions>) -> Result<()> {
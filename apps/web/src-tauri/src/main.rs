@@ -79,12 +79,15 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::detect_config,
+            commands::detect_config_progress,
             commands::login,
             commands::logout,
             commands::get,
             commands::messages,
             commands::get_message,
-            commands::list
+            commands::get_attachment,
+            commands::list,
+            commands::transcript
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
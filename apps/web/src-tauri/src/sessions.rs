@@ -50,7 +50,7 @@ impl Sessions {
 
                 let credentials = from_str(&credentials_json)?;
 
-                let mail_sessions = sdk::session::create_sessions(&credentials).await?;
+                let mail_sessions = sdk::session::create_sessions(&credentials, false).await?;
 
                 self.insert_session(identifier.clone(), mail_sessions)?;
 
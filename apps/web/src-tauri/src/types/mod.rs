@@ -8,7 +8,7 @@ use std::{
 // pub use credentials::Credentials;
 
 use keyring::Error as KeyringError;
-use sdk::types::Error as SdkError;
+use sdk::types::{Error as SdkError, ErrorKind as SdkErrorKind};
 use serde_json::Error as JsonError;
 
 use serde::{ser::SerializeStruct, Serialize};
@@ -68,6 +68,19 @@ impl Serialize for Error {
         S: serde::Serializer,
     {
         let source = self.source().unwrap_or(&self);
+
+        // Include the certificates, so the user can decide whether to trust them.
+        if let ErrorKind::Mail(sdk_error) = self.kind() {
+            if let SdkErrorKind::UntrustedCertificate(certificates) = sdk_error.kind() {
+                let mut state = serializer.serialize_struct("Error", 3)?;
+
+                state.serialize_field("message", &sdk_error.to_string())?;
+                state.serialize_field("kind", "UntrustedCertificate")?;
+                state.serialize_field("certificates", certificates)?;
+                return state.end();
+            }
+        }
+
         let mut state = serializer.serialize_struct("Error", 2)?;
 
         state.serialize_field("message", &source.to_string())?;
@@ -6,12 +6,16 @@ import React, {
 	FormEvent,
 	useMemo,
 	FormEventHandler,
-	useCallback
+	useCallback,
+	useRef
 } from "react";
 
 import {
 	incomingMailServerTypeList,
+	CertificateInfo,
 	Credentials,
+	DetectProgress,
+	MailConfig,
 	outgoingMailServerTypeList,
 	ServerType,
 	MailServerType,
@@ -263,6 +267,16 @@ const UnMemoizedServerConfigColumn: FC<{
 
 const ServerConfigColumn = memo(UnMemoizedServerConfigColumn);
 
+/**
+ * Detected configs are sorted by confidence, so the first config of a given server type is the best one.
+ */
+const isBestOfType = <T extends { type: string }>(
+	config: T,
+	index: number,
+	configs: T[]
+): boolean =>
+	configs.findIndex((other) => other.type === config.type) === index;
+
 const createCredentials = (
 	incomingConfig: MultiServerLoginOptions,
 	incomingType: IncomingMailServerType
@@ -289,6 +303,57 @@ const createCredentials = (
 	return options;
 };
 
+const formatTimestamp = (timestamp: number): string =>
+	new Date(timestamp * 1000).toLocaleDateString();
+
+/**
+ * Shows the certificates an untrusted server presented, so the user can decide whether to trust the certificate of the server.
+ */
+const UntrustedCertificatePrompt: FC<{
+	certificates: CertificateInfo[];
+	onTrust: () => void;
+}> = ({ certificates, onTrust }) => {
+	const fetching = useStore((state) => state.fetching);
+
+	return (
+		<Alert sx={{ textAlign: "left", mb: 2 }} severity="warning">
+			<AlertTitle>Untrusted certificate</AlertTitle>
+			<Typography variant="body2" gutterBottom>
+				The server presented a certificate that could not be verified. Only
+				trust it if you know it belongs to your mail server, otherwise someone
+				could be reading your credentials.
+			</Typography>
+			{certificates.map((certificate, i) => (
+				<Box key={certificate.fingerprint} sx={{ mb: 1 }}>
+					<Typography variant="subtitle2">
+						{i == 0 ? "Server certificate" : "Issuer certificate"}:{" "}
+						{certificate.subject}
+					</Typography>
+					<Typography variant="body2">
+						Issued by {certificate.issuer}, valid from{" "}
+						{formatTimestamp(certificate.notBefore)} until{" "}
+						{formatTimestamp(certificate.notAfter)}
+					</Typography>
+					<Typography
+						variant="body2"
+						sx={{ fontFamily: "monospace", wordBreak: "break-all" }}
+					>
+						{certificate.fingerprint}
+					</Typography>
+				</Box>
+			))}
+			<Button
+				disabled={fetching}
+				onClick={onTrust}
+				color="warning"
+				variant="outlined"
+			>
+				Trust this certificate and login
+			</Button>
+		</Alert>
+	);
+};
+
 const LoginOptionsMenu: FC = () => {
 	const theme = useTheme();
 
@@ -305,6 +370,9 @@ const LoginOptionsMenu: FC = () => {
 	const setError = useMultiServerLoginStore((state) => state.setError);
 	const error = useMultiServerLoginStore((state) => state.error);
 
+	const [untrustedCertificates, setUntrustedCertificates] =
+		useState<CertificateInfo[]>();
+
 	const fetching = useStore((state) => state.fetching);
 
 	const selectedMailServerTypes = useMultiServerLoginStore(
@@ -326,6 +394,7 @@ const LoginOptionsMenu: FC = () => {
 
 	const onClose = useCallback(() => {
 		resetToDefaults();
+		setUntrustedCertificates(undefined);
 		setOpen(false);
 	}, [resetToDefaults, setOpen]);
 
@@ -333,9 +402,7 @@ const LoginOptionsMenu: FC = () => {
 		return !incomingConfig.username || !incomingConfig.password;
 	}, [incomingConfig.username, incomingConfig.password]);
 
-	const onSubmit: FormEventHandler = async (e): Promise<void> => {
-		e.preventDefault();
-
+	const submit = async (trustedFingerprint?: string): Promise<void> => {
 		if (missingFields) {
 			setError("Missing required fields");
 
@@ -347,19 +414,32 @@ const LoginOptionsMenu: FC = () => {
 			selectedMailServerTypes.incoming
 		);
 
-		await login(credentials)
+		await login(credentials, trustedFingerprint)
 			.then((result) => {
 				if (result.ok) {
 					onClose();
 				} else {
 					const message = errorToString(result.error);
 
+					// The user can decide to trust the certificate and try again.
+					if (errorIsOfErrorKind(result.error, "UntrustedCertificate")) {
+						setUntrustedCertificates(result.error.certificates);
+					} else {
+						setUntrustedCertificates(undefined);
+					}
+
 					setError(message);
 				}
 			})
 			.catch(createErrorFromUnknown);
 	};
 
+	const onSubmit: FormEventHandler = async (e): Promise<void> => {
+		e.preventDefault();
+
+		await submit();
+	};
+
 	return (
 		<>
 			<Modal open={isOpen} onClose={onClose}>
@@ -416,6 +496,13 @@ const LoginOptionsMenu: FC = () => {
 							</Alert>
 						)}
 
+						{untrustedCertificates && untrustedCertificates.length != 0 && (
+							<UntrustedCertificatePrompt
+								certificates={untrustedCertificates}
+								onTrust={() => submit(untrustedCertificates[0].fingerprint)}
+							/>
+						)}
+
 						<Button
 							disabled={missingFields || fetching}
 							fullWidth
@@ -431,6 +518,32 @@ const LoginOptionsMenu: FC = () => {
 	);
 };
 
+/**
+ * A short description of a detection progress event, to show while the user is waiting.
+ */
+const describeDetectProgress = (
+	progress: DetectProgress
+): string | undefined => {
+	switch (progress.type) {
+		case "trying":
+			switch (progress.source.kind) {
+				case "ispdb":
+					return `Looking up ${progress.source.domain} in the list of known providers`;
+				case "autoconfig":
+					return `Looking for the configuration of ${progress.source.domain}`;
+				case "srv":
+					return `Looking up the mail servers of ${progress.source.domain}`;
+			}
+			break;
+		case "probing":
+			return `Checking ${progress.socket.domain}:${progress.socket.port}`;
+		case "probed":
+			return progress.serverType !== null
+				? `Found a ${progress.serverType} server at ${progress.socket.domain}:${progress.socket.port}`
+				: `No mail server found at ${progress.socket.domain}:${progress.socket.port}`;
+	}
+};
+
 const LoginForm: FC<{
 	children: React.ReactNode;
 	trailing?: React.ReactNode;
@@ -458,6 +571,13 @@ const LoginForm: FC<{
 
 	const [error, setError] = useState<string>();
 
+	// What the detection of the config is currently doing and the best config it found so far.
+	const [detectStatus, setDetectStatus] = useState<string>();
+	const [foundConfig, setFoundConfig] = useState<MailConfig>();
+
+	// Increases with every detection, so we can ignore the events of a detection the user is no longer waiting for.
+	const detectionRef = useRef(0);
+
 	const mailClient = useMailClient();
 	const oauthClient = useOAuth2Client();
 
@@ -470,30 +590,14 @@ const LoginForm: FC<{
 	const missingFields = !username || !password;
 
 	/**
-	 * Runs when the form should be submitted to the server
+	 * Fills in the login options with the default servers for the domain of the email address
 	 */
-	const onSubmit = async (e?: FormEvent): Promise<void> => {
-		if (e) e.preventDefault();
-
-		// Reject the form if there any fields empty
-		if (missingFields) {
-			setError("Missing required fields");
-			return;
-		}
-
-		setFetching(true);
-
-		const configResult = await mailClient
-			.detectConfig(username)
-			.catch((error: unknown) => setError(JSON.stringify(error)));
-
-		setFetching(false);
-
+	const setDefaultLoginOptions = (): boolean => {
 		const emailAddressResult = parseEmail(username);
 
 		if (!emailAddressResult.ok) {
 			setError(errorToString(emailAddressResult.error));
-			return;
+			return false;
 		}
 
 		const mailDomain = `mail.${emailAddressResult.data.domain}`;
@@ -516,26 +620,13 @@ const LoginForm: FC<{
 			})
 		);
 
-		if (!configResult) return;
-
-		if (!configResult.ok) {
-			if (errorIsOfErrorKind(configResult.error, "ConfigNotFound")) {
-				setMultiServerLoginError(
-					"Could not automagically detect your login servers, please fill the information in manually or try again."
-				);
-
-				setShowLoginOptionsMenu(true);
-			} else {
-				const message = errorToString(configResult.error);
-
-				setError(message);
-			}
-
-			return;
-		}
-
-		const config = configResult.data;
+		return true;
+	};
 
+	/**
+	 * Fills in the login options with the servers of a detected config and shows them to the user
+	 */
+	const applyDetectedConfig = (config: MailConfig): void => {
 		oauthClient.getGrant(
 			config.displayName,
 			config.oauth2?.oauthUrl ?? "",
@@ -551,10 +642,10 @@ const LoginForm: FC<{
 			const incomingConfigs: (MultiServerLoginOptions & {
 				type: IncomingMailServerType;
 			})[] = config.type.multiServer.incoming.map(
-				({ authType, ...config }) => ({
+				({ authType, username: detectedUsername, ...config }) => ({
 					...config,
 					loginType: authType,
-					username,
+					username: detectedUsername ?? username,
 					password
 				})
 			);
@@ -562,28 +653,106 @@ const LoginForm: FC<{
 			const outgoingConfigs: (MultiServerLoginOptions & {
 				type: OutgoingMailServerType;
 			})[] = config.type.multiServer.outgoing.map(
-				({ authType, ...config }) => ({
+				({ authType, username: detectedUsername, ...config }) => ({
 					...config,
 					loginType: authType,
-					username,
+					username: detectedUsername ?? username,
 					password
 				})
 			);
 
 			setProvider(config.displayName);
 
-			incomingConfigs.forEach(({ type, ...incomingConfig }) =>
-				setLoginOptions("incoming", type, incomingConfig)
-			);
+			incomingConfigs
+				.filter(isBestOfType)
+				.forEach(({ type, ...incomingConfig }) =>
+					setLoginOptions("incoming", type, incomingConfig)
+				);
 
-			outgoingConfigs.forEach(({ type, ...outgoingConfig }) =>
-				setLoginOptions("outgoing", type, outgoingConfig)
-			);
+			outgoingConfigs
+				.filter(isBestOfType)
+				.forEach(({ type, ...outgoingConfig }) =>
+					setLoginOptions("outgoing", type, outgoingConfig)
+				);
 
 			setShowLoginOptionsMenu(true);
 		}
 	};
 
+	const stopDetecting = (): void => {
+		// Any events of the detection that is still running are ignored from now on.
+		detectionRef.current++;
+
+		setFetching(false);
+		setDetectStatus(undefined);
+		setFoundConfig(undefined);
+	};
+
+	/**
+	 * Lets the user continue with a config that was found before the detection finished
+	 */
+	const pickFoundConfig = (config: MailConfig): void => {
+		stopDetecting();
+
+		if (setDefaultLoginOptions()) applyDetectedConfig(config);
+	};
+
+	/**
+	 * Runs when the form should be submitted to the server
+	 */
+	const onSubmit = async (e?: FormEvent): Promise<void> => {
+		if (e) e.preventDefault();
+
+		// Reject the form if there any fields empty
+		if (missingFields) {
+			setError("Missing required fields");
+			return;
+		}
+
+		setFetching(true);
+
+		const detection = ++detectionRef.current;
+
+		const configResult = await mailClient
+			.detectConfigProgress(username, (progress) => {
+				if (detectionRef.current !== detection) return;
+
+				if (progress.type == "found") {
+					setFoundConfig(progress.config);
+				} else {
+					setDetectStatus(describeDetectProgress(progress));
+				}
+			})
+			.catch((error: unknown) => setError(JSON.stringify(error)));
+
+		// The user already picked a config that was found earlier.
+		if (detectionRef.current !== detection) return;
+
+		stopDetecting();
+
+		if (!setDefaultLoginOptions()) return;
+
+		if (!configResult) return;
+
+		if (!configResult.ok) {
+			if (errorIsOfErrorKind(configResult.error, "ConfigNotFound")) {
+				setMultiServerLoginError(
+					"Could not automagically detect your login servers, please fill the information in manually or try again."
+				);
+
+				setShowLoginOptionsMenu(true);
+			} else {
+				const message = errorToString(configResult.error);
+
+				setError(message);
+			}
+
+			return;
+		}
+
+		applyDetectedConfig(configResult.data);
+	};
+
 	return (
 		<Stack direction="column" spacing={2}>
 			<form onSubmit={onSubmit}>
@@ -620,6 +789,32 @@ const LoginForm: FC<{
 					>
 						Login
 					</Button>
+					{fetching && detectStatus && (
+						<Typography variant="body2" textAlign="center">
+							{detectStatus}
+						</Typography>
+					)}
+					{fetching && foundConfig && (
+						<Alert
+							sx={{ textAlign: "left" }}
+							severity="info"
+							action={
+								<Button
+									color="inherit"
+									size="small"
+									onClick={() => pickFoundConfig(foundConfig)}
+								>
+									Use
+								</Button>
+							}
+						>
+							<AlertTitle>
+								Found servers for {foundConfig.displayName}
+							</AlertTitle>
+							We are still checking if there are better ones, but you can
+							already use these.
+						</Alert>
+					)}
 					{error && (
 						<Alert sx={{ textAlign: "left" }} severity="error">
 							<AlertTitle>Error</AlertTitle>
@@ -2,6 +2,8 @@ import { FC, memo, MouseEvent, useMemo, useState } from "react";
 
 import { Preview } from "@dust-mail/structures";
 
+import AttachFileIcon from "@mui/icons-material/AttachFile";
+import PriorityHighIcon from "@mui/icons-material/PriorityHigh";
 import Avatar from "@mui/material/Avatar";
 import Box from "@mui/material/Box";
 import Typography from "@mui/material/Typography";
@@ -127,20 +129,39 @@ const UnMemoizedMessageListItem: FC<{
 							{new Date((message.sent ?? 0) * 1000).toLocaleDateString()}
 						</Typography>
 
-						<Typography
-							noWrap
-							textOverflow="ellipsis"
-							variant="h6"
-							sx={{ fontWeight: unSeen ? "bold" : null }}
-						>
-							{!message.subject ||
-							(message.subject && message.subject.length == 0)
-								? "(No subject)"
-								: message.subject}
-						</Typography>
-						{/* <Typography variant="caption">
-						
-					</Typography> */}
+						<Box sx={{ display: "flex", alignItems: "center" }}>
+							{message.importance == "High" && (
+								<PriorityHighIcon
+									fontSize="small"
+									color="error"
+									titleAccess="High importance"
+								/>
+							)}
+							<Typography
+								noWrap
+								textOverflow="ellipsis"
+								variant="h6"
+								sx={{ flex: 1, fontWeight: unSeen ? "bold" : null }}
+							>
+								{!message.subject ||
+								(message.subject && message.subject.length == 0)
+									? "(No subject)"
+									: message.subject}
+							</Typography>
+							{message.hasAttachments && (
+								<AttachFileIcon fontSize="small" titleAccess="Attachments" />
+							)}
+						</Box>
+						{message.snippet && (
+							<Typography
+								noWrap
+								textOverflow="ellipsis"
+								variant="body2"
+								color="text.secondary"
+							>
+								{message.snippet}
+							</Typography>
+						)}
 					</Box>
 				</Box>
 			</Box>
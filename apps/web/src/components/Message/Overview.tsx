@@ -2,8 +2,9 @@ import useLocalStorageState from "use-local-storage-state";
 
 import { useEffect, useRef, useState, memo, FC, MouseEvent } from "react";
 
-import { Address } from "@dust-mail/structures";
+import { AddressListEntry, flattenAddressList } from "@dust-mail/structures";
 
+import Alert from "@mui/material/Alert";
 import Avatar from "@mui/material/Avatar";
 import Box from "@mui/material/Box";
 import Card from "@mui/material/Card";
@@ -79,11 +80,13 @@ const AddressListItem: FC<{ address: string | null; name: string | null }> = ({
 const ADDRESSES_TO_SHOW = 3;
 
 const AddressList: FC<{
-	data: Address[];
+	data: AddressListEntry[];
 	prefixText: string;
-}> = ({ data, prefixText }) => {
+}> = ({ data: entries, prefixText }) => {
 	const [showMore, setShowMore] = useState(false);
 
+	const data = flattenAddressList(entries);
+
 	return (
 		<Box sx={{ alignItems: "center" }}>
 			<Typography sx={{ display: "inline", mr: 1 }}>{prefixText}</Typography>
@@ -91,7 +94,7 @@ const AddressList: FC<{
 				data
 					.slice(0, showMore ? data.length : ADDRESSES_TO_SHOW)
 					.map((address, i) => (
-						<AddressListItem {...address} key={address.address ?? "" + i} />
+						<AddressListItem {...address} key={address.address + i} />
 					))}
 			{data && data.length > ADDRESSES_TO_SHOW && (
 				<Link
@@ -262,6 +265,9 @@ const UnMemoizedMessageOverview: FC = () => {
 											{data?.from && data?.from.length != 0 && (
 												<AddressList data={data.from} prefixText="From:" />
 											)}
+											{data?.replyTo && data?.replyTo.length != 0 && (
+												<AddressList data={data.replyTo} prefixText="Reply to:" />
+											)}
 											{data?.to && data?.to.length != 0 && (
 												<AddressList data={data.to} prefixText="To:" />
 											)}
@@ -395,16 +401,24 @@ const UnMemoizedMessageOverview: FC = () => {
 						)}
 						{!isFetching && data?.content && (
 							<>
+								{data.content.truncated && (
+									<Alert severity="warning" sx={{ m: 1 }}>
+										This message is too large to show completely, only the start
+										of it is shown.
+									</Alert>
+								)}
 								{!showTextOnly && data.content.html && (
 									<MessageDisplay content={data.content.html} />
 								)}
 								{(!data.content.html || showTextOnly) && data.content.text && (
 									<Box
-										sx={{ whiteSpace: "pre-line" }}
-										dangerouslySetInnerHTML={{
-											__html: data.content.text
+										sx={{
+											whiteSpace: "pre-wrap",
+											overflowWrap: "anywhere"
 										}}
-									/>
+									>
+										{data.content.text}
+									</Box>
 								)}
 								{!(
 									(data.content.html && !showTextOnly) ||
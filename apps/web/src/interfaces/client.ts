@@ -1,6 +1,7 @@
 import {
 	MailConfig,
 	Credentials,
+	DetectProgress,
 	MailBoxList,
 	MailBox,
 	Message,
@@ -15,10 +16,27 @@ import { Result } from "@interfaces/result";
 export default interface MailClient {
 	getVersion: () => Promise<Result<Version>>;
 	detectConfig: (emailAddress: string) => Promise<Result<MailConfig>>;
-	login: (options: Credentials) => Promise<Result<string>>;
+	// Detects the config like `detectConfig`, but calls `onProgress` for everything that happens while detecting it.
+	detectConfigProgress: (
+		emailAddress: string,
+		onProgress: (progress: DetectProgress) => void
+	) => Promise<Result<MailConfig>>;
+	login: (
+		options: Credentials,
+		trustedFingerprint?: string
+	) => Promise<Result<string>>;
 	logout: () => Promise<Result<void>>;
 	get: (boxId?: string) => Promise<Result<MailBox>>;
 	list: () => Promise<Result<MailBoxList>>;
 	messageList: (page: number, boxId?: string) => Promise<Result<Preview[]>>;
-	getMessage: (messageId?: string, boxId?: string) => Promise<Result<Message>>;
+	getMessage: (
+		messageId?: string,
+		boxId?: string,
+		markRead?: boolean
+	) => Promise<Result<Message>>;
+	getAttachment: (
+		attachmentId?: string,
+		messageId?: string,
+		boxId?: string
+	) => Promise<Result<number[]>>;
 }
@@ -19,6 +19,8 @@ type FetchFunction = (
 		sendAuth?: boolean;
 		useMailSessionToken?: boolean;
 		contentType?: "json" | "form" | "none";
+		// Files are returned as a list of bytes instead of being parsed as an api response.
+		responseType?: "json" | "bytes";
 	}
 ) => Promise<Result<unknown>>;
 
@@ -73,6 +75,19 @@ const useFetchClient = (): FetchFunction => {
 				headers
 			})
 			.then(async (response) => {
+				// Files are sent as they are, only errors are wrapped in an api response.
+				if (config?.responseType === "bytes" && response.ok) {
+					const buffer = await response.arrayBuffer().catch(() => null);
+
+					if (buffer === null)
+						return createBaseError({
+							kind: "InvalidResponseBody",
+							message: "Invalid response body from server response"
+						});
+
+					return { ok: true, data: Array.from(new Uint8Array(buffer)) } as const;
+				}
+
 				const responseString = await response.text().catch(() => null);
 
 				if (responseString === null)
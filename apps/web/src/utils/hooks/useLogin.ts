@@ -45,7 +45,8 @@ const createIdentifier = (
 };
 
 export const useMailLogin = (): ((
-	config: Credentials
+	config: Credentials,
+	trustedFingerprint?: string
 ) => Promise<Result<void>>) => {
 	const appVersion = useStore((state) => state.appVersion);
 	const setFetching = useStore((state) => state.setFetching);
@@ -56,7 +57,7 @@ export const useMailLogin = (): ((
 
 	const mailClient = useMailClient();
 
-	return async (config) => {
+	return async (config, trustedFingerprint) => {
 		// Show the fetching animation
 		setFetching(true);
 
@@ -88,7 +89,7 @@ export const useMailLogin = (): ((
 
 		// Request the login token
 		const loginResult = await mailClient
-			.login(config)
+			.login(config, trustedFingerprint)
 			// If there was anything wrong with the request, catch it
 			.catch(parseError);
 
@@ -1,11 +1,16 @@
+import { listen } from "@tauri-apps/api/event";
 import { invoke } from "@tauri-apps/api/tauri";
 import z from "zod";
 
 import useFetchClient from "./useFetchClient";
+import useSettings from "./useSettings";
 import useUser from "./useUser";
 
 import {
 	CredentialsModel,
+	DetectProgress,
+	DetectProgressModel,
+	MailConfig,
 	MailBoxListModel,
 	MailBoxModel,
 	MailConfigModel,
@@ -17,6 +22,7 @@ import {
 import { messageCountForPage } from "@src/constants";
 
 import MailClient from "@interfaces/client";
+import { Result } from "@interfaces/result";
 
 import { MissingRequiredParam, NotLoggedIn } from "@utils/defaultErrors";
 import parseEmail from "@utils/parseEmail";
@@ -34,6 +40,34 @@ const useMailClient = (): MailClient => {
 
 	const fetch = useFetchClient();
 
+	const [settings] = useSettings();
+
+	/**
+	 * Passes a progress event to the listener and returns the result of the detection if it is the last event.
+	 */
+	const handleDetectProgress = (
+		data: unknown,
+		onProgress: (progress: DetectProgress) => void
+	): Result<MailConfig> | undefined => {
+		const output = parseZodOutput(DetectProgressModel.safeParse(data));
+
+		if (!output.ok) return output;
+
+		const progress = output.data;
+
+		onProgress(progress);
+
+		switch (progress.type) {
+			case "done":
+				return { ok: true, data: progress.config };
+			case "failed":
+				return createBaseError({
+					kind: "ConfigNotFound",
+					message: progress.message
+				});
+		}
+	};
+
 	return {
 		async getVersion() {
 			if (isTauri) {
@@ -93,7 +127,96 @@ const useMailClient = (): MailClient => {
 				})
 				.catch(createResultFromUnknown);
 		},
-		async login(options) {
+		async detectConfigProgress(emailAddress, onProgress) {
+			const emailAddressParsed = parseEmail(emailAddress);
+
+			if (!emailAddressParsed.ok) {
+				return emailAddressParsed;
+			}
+
+			emailAddress = emailAddressParsed.data.full;
+
+			if (isTauri) {
+				// The events of every detection are sent to the same window, so we only listen to the ones with our id.
+				const id = crypto.randomUUID();
+
+				let unlisten: (() => void) | undefined;
+
+				const result = await new Promise<Result<MailConfig>>((resolve) => {
+					listen("detect_progress", (event) => {
+						const output = z
+							.object({ id: z.string() })
+							.safeParse(event.payload);
+
+						if (!output.success || output.data.id !== id) return;
+
+						const result = handleDetectProgress(event.payload, onProgress);
+
+						if (result !== undefined) resolve(result);
+					})
+						.then((stopListening) => {
+							unlisten = stopListening;
+
+							return invoke("detect_config_progress", { id, emailAddress });
+						})
+						.catch((error) => resolve(parseError(error)));
+				});
+
+				unlisten?.();
+
+				return result;
+			}
+
+			if (settings.httpServerUrl == null) {
+				return createBaseError({
+					kind: "NoBackendUrl",
+					message: "Backend url for api server is not set"
+				});
+			}
+
+			const url = new URL(
+				`/detect/${emailAddress}/progress`,
+				settings.httpServerUrl
+			);
+
+			return new Promise((resolve) => {
+				const events = new EventSource(url.toString(), {
+					withCredentials: true
+				});
+
+				events.onmessage = (event) => {
+					let data: unknown;
+
+					try {
+						data = JSON.parse(event.data);
+					} catch {
+						data = undefined;
+					}
+
+					const result = handleDetectProgress(data, onProgress);
+
+					if (result !== undefined) {
+						events.close();
+
+						resolve(result);
+					}
+				};
+
+				// The server closes the stream after the last event, so an error means it never got to send one.
+				events.onerror = () => {
+					events.close();
+
+					resolve(
+						createBaseError({
+							kind: "Network",
+							message:
+								"Lost the connection to the server while detecting the config"
+						})
+					);
+				};
+			});
+		},
+		async login(options, trustedFingerprint) {
 			const optionsResult = parseZodOutput(CredentialsModel.safeParse(options));
 
 			if (!optionsResult.ok) {
@@ -101,7 +224,10 @@ const useMailClient = (): MailClient => {
 			}
 
 			if (isTauri) {
-				return invoke("login", { credentials: optionsResult.data })
+				return invoke("login", {
+					credentials: optionsResult.data,
+					trustedFingerprint
+				})
 					.then((data: unknown) => {
 						const output = z.string().safeParse(data);
 
@@ -113,6 +239,10 @@ const useMailClient = (): MailClient => {
 			return fetch(`/mail/login`, {
 				method: "POST",
 				body: JSON.stringify(optionsResult.data),
+				params:
+					trustedFingerprint !== undefined
+						? { trusted_fingerprint: trustedFingerprint }
+						: undefined,
 				useMailSessionToken: false
 			})
 				.then((response) => {
@@ -237,7 +367,7 @@ const useMailClient = (): MailClient => {
 				})
 				.catch(createResultFromUnknown);
 		},
-		async getMessage(messageId, boxId) {
+		async getMessage(messageId, boxId, markRead = false) {
 			if (!boxId || !messageId) return MissingRequiredParam();
 
 			if (isTauri) {
@@ -245,7 +375,12 @@ const useMailClient = (): MailClient => {
 
 				if (!token) return NotLoggedIn();
 
-				return invoke("get_message", { token, boxId, messageId })
+				return invoke("get_message", {
+					token,
+					boxId,
+					messageId,
+					markRead
+				})
 					.then((data: unknown) => {
 						const output = MessageModel.safeParse(data);
 
@@ -254,7 +389,9 @@ const useMailClient = (): MailClient => {
 					.catch(parseError);
 			}
 
-			return fetch(`/mail/boxes/${boxId}/${messageId}`)
+			return fetch(`/mail/boxes/${boxId}/${messageId}`, {
+				params: { mark_read: markRead.toString() }
+			})
 				.then((response) => {
 					if (!response.ok) {
 						return response;
@@ -265,6 +402,42 @@ const useMailClient = (): MailClient => {
 					return parseZodOutput(output);
 				})
 				.catch(createResultFromUnknown);
+		},
+		async getAttachment(attachmentId, messageId, boxId) {
+			if (!boxId || !messageId || !attachmentId) return MissingRequiredParam();
+
+			if (isTauri) {
+				const token = user?.token;
+
+				if (!token) return NotLoggedIn();
+
+				return invoke("get_attachment", {
+					token,
+					boxId,
+					messageId,
+					attachmentId
+				})
+					.then((data: unknown) => {
+						const output = z.number().array().safeParse(data);
+
+						return parseZodOutput(output);
+					})
+					.catch(parseError);
+			}
+
+			return fetch(`/mail/boxes/${boxId}/${messageId}/${attachmentId}`, {
+				responseType: "bytes"
+			})
+				.then((response) => {
+					if (!response.ok) {
+						return response;
+					}
+
+					const output = z.number().array().safeParse(response.data);
+
+					return parseZodOutput(output);
+				})
+				.catch(createResultFromUnknown);
 		}
 	};
 };
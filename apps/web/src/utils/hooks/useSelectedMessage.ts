@@ -28,6 +28,8 @@ export const useSetSelectedMessage = (): ((id?: string) => void) => {
 
 const defaultMessage: (messageId: string) => Message = (messageId) => ({
 	id: messageId,
+	sender: null,
+	replyTo: [],
 	bcc: [],
 	cc: [],
 	to: [],
@@ -61,8 +63,9 @@ const useSelectedMessage = (): UseSelectedMessage => {
 	const { data, isFetching, error } = useQuery<Message, AppError>(
 		["message", messageId, selectedBox?.id],
 		async () => {
+			// The message is opened by the user, so it has been read.
 			const result = await mailClient
-				.getMessage(messageId, selectedBox?.id)
+				.getMessage(messageId, selectedBox?.id, true)
 				.catch(createResultFromUnknown);
 
 			if (result.ok) return result.data;
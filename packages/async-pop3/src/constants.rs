@@ -12,4 +12,4 @@ pub const ERR: &str = "-ERR";
 pub const DOT: u8 = 0x2e;
 
 pub const END_OF_LINE: [u8; 2] = [CR, LF];
-pub const EOF: [u8; 5] = [CR, LF, DOT, CR, LF];
+pub const TERMINATION: [u8; 3] = [DOT, CR, LF];
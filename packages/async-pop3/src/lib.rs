@@ -1,16 +1,19 @@
 mod constants;
 mod parse;
 mod socket;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 pub mod types;
 mod utils;
 
-use async_native_tls::{TlsConnector, TlsStream};
+use async_native_tls::TlsStream;
+use async_tcp::Transcript;
 use parse::{parse_capabilities, Parser};
 use socket::Socket;
 
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::{TcpStream, ToSocketAddrs},
+    net::TcpStream,
     time::{timeout, Duration},
 };
 use types::{
@@ -36,13 +39,6 @@ pub struct Client<S: AsyncWrite + AsyncRead + Unpin> {
     pub state: ClientState,
 }
 
-fn get_connection_timeout(timeout: Option<Duration>) -> Duration {
-    match timeout {
-        Some(timeout) => timeout,
-        None => Duration::from_secs(60),
-    }
-}
-
 /// Creates a client from a given socket connection.
 async fn create_client_from_socket<S: AsyncRead + AsyncWrite + Unpin>(
     socket: Socket<S>,
@@ -82,43 +78,46 @@ pub async fn new<S: AsyncRead + AsyncWrite + Unpin>(
     stream: S,
     timeout: Option<Duration>,
 ) -> Result<Client<S>> {
-    let socket = Socket::new(stream, timeout);
+    new_with_transcript(stream, timeout, None).await
+}
+
+/// Creates a new pop3 client from an existing stream, recording all of the traffic (including the greeting) in a given transcript.
+pub async fn new_with_transcript<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    timeout: Option<Duration>,
+    transcript: Option<Transcript>,
+) -> Result<Client<S>> {
+    let mut socket = Socket::new(stream, timeout);
+
+    socket.set_transcript(transcript);
 
     create_client_from_socket(socket).await
 }
 
 /// Create a new pop3 client with a tls connection.
-pub async fn connect<A: ToSocketAddrs>(
-    addr: A,
-    domain: &str,
-    tls_connector: &TlsConnector,
-    connection_timeout: Option<Duration>,
+///
+/// The tls options (like custom roots and pinned certificates), proxy and timeout are taken from the given config.
+pub async fn connect<H: AsRef<str>>(
+    host: H,
+    port: u16,
+    config: &async_tcp::Config,
 ) -> Result<Client<TlsStream<TcpStream>>> {
-    let connection_timeout = get_connection_timeout(connection_timeout);
-
-    let tcp_stream = timeout(connection_timeout, TcpStream::connect(addr)).await??;
+    let tls_stream = timeout(*config.timeout(), async_tcp::dial_tls(host, port, config)).await??;
 
-    let tls_stream = tls_connector.connect(domain, tcp_stream).await?;
-
-    let socket = Socket::new(tls_stream, None);
-
-    create_client_from_socket(socket).await
+    new_with_transcript(tls_stream, None, config.transcript().cloned()).await
 }
 
 /// Creates a new pop3 client using a plain connection.
 ///
 /// DO NOT USE in a production environment. Your password will be sent over a plain tcp stream which hackers could intercept.
-pub async fn connect_plain<A: ToSocketAddrs>(
-    addr: A,
-    connection_timeout: Option<Duration>,
+pub async fn connect_plain<H: AsRef<str>>(
+    host: H,
+    port: u16,
+    config: &async_tcp::Config,
 ) -> Result<Client<TcpStream>> {
-    let connection_timeout = get_connection_timeout(connection_timeout);
+    let tcp_stream = timeout(*config.timeout(), async_tcp::dial(host, port, config)).await??;
 
-    let tcp_stream = timeout(connection_timeout, TcpStream::connect(addr)).await??;
-
-    let socket = Socket::new(tcp_stream, None);
-
-    create_client_from_socket(socket).await
+    new_with_transcript(tcp_stream, None, config.transcript().cloned()).await
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
@@ -293,6 +292,8 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
 
         socket.send_command(command.as_bytes(), false).await?;
 
+        self.marked_as_del.push(msg_number);
+
         Ok(())
     }
 
@@ -316,6 +317,8 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
 
         socket.send_command(command, false).await?;
 
+        self.marked_as_del.clear();
+
         Ok(())
     }
 
@@ -351,7 +354,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
 
         let command = create_command("RETR", &arguments)?;
 
-        socket.send_bytes(command.as_bytes()).await?;
+        socket.send_command(command, false).await?;
 
         let mut response: Vec<u8> = Vec::new();
 
@@ -521,6 +524,33 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
         &self.capabilities
     }
 
+    /// The minimum amount of time the server wants between two logins, as advertised by the LOGIN-DELAY capability.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc2449#section-6.5
+    pub fn login_delay(&self) -> Option<Duration> {
+        self.capabilities
+            .iter()
+            .find_map(|capability| match capability {
+                Capability::LoginDelay(delay) => Some(*delay),
+                _ => None,
+            })
+    }
+
+    /// How long the server keeps messages before deleting them by itself, as advertised by the EXPIRE capability.
+    ///
+    /// Returns `None` if the server does not advertise the capability, `Some(None)` if the server never deletes messages
+    /// and `Some(Some(Duration::ZERO))` if messages are deleted as soon as they have been retrieved.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc2449#section-6.7
+    pub fn expire(&self) -> Option<Option<Duration>> {
+        self.capabilities
+            .iter()
+            .find_map(|capability| match capability {
+                Capability::Expire(expire) => Some(*expire),
+                _ => None,
+            })
+    }
+
     /// Fetches a list of capabilities for the currently connected server and returns it.
     pub async fn capa(&mut self) -> Result<Capabilities> {
         let socket = self.get_socket_mut()?;
@@ -138,8 +138,9 @@ pub fn parse_capabilities(response: &str) -> Capabilities {
                         }
                         "RESP-CODES" => Some(Capability::RespCodes),
                         "LOGIN-DELAY" => {
+                            // The delay is specified in seconds.
                             let delay: Duration = match split.next() {
-                                Some(delay) => Duration::from_secs(delay.parse::<u64>().unwrap()),
+                                Some(delay) => Duration::from_secs(delay.parse::<u64>().ok()?),
                                 None => Duration::from_secs(0),
                             };
 
@@ -147,11 +148,14 @@ pub fn parse_capabilities(response: &str) -> Capabilities {
                         }
                         "PIPELINING" => Some(Capability::Pipelining),
                         "EXPIRE" => {
+                            // The retention period is specified in days, or 'NEVER' if the server never deletes messages by itself.
                             let expires: Option<Duration> = match split.next() {
+                                Some("NEVER") | None => None,
                                 Some(expires) => {
-                                    Some(Duration::from_secs(expires.parse::<u64>().unwrap()))
+                                    let days = expires.parse::<u64>().ok()?;
+
+                                    Some(Duration::from_secs(days * 24 * 60 * 60))
                                 }
-                                None => None,
                             };
 
                             Some(Capability::Expire(expires))
@@ -199,4 +203,19 @@ mod test {
 
         assert_eq!(parsed_capabilities, to_match);
     }
+
+    #[test]
+    fn test_parse_expire_capability() {
+        let to_parse = "EXPIRE 0\r\nEXPIRE NEVER\r\nEXPIRE 31 USER\r\nEXPIRE soon\r\n";
+
+        let to_match: Vec<Capability> = vec![
+            Capability::Expire(Some(Duration::from_secs(0))),
+            Capability::Expire(None),
+            Capability::Expire(Some(Duration::from_secs(31 * 24 * 60 * 60))),
+        ];
+
+        let parsed_capabilities = parse_capabilities(to_parse);
+
+        assert_eq!(parsed_capabilities, to_match);
+    }
 }
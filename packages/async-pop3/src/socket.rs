@@ -1,19 +1,20 @@
 use std::time::Duration;
 
+use async_tcp::{Transcript, TranscriptStream};
 use tokio::{
     io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream},
     time::timeout,
 };
 
 use crate::{
-    constants::{DOT, END_OF_LINE, EOF, LF},
+    constants::{DOT, END_OF_LINE, LF, TERMINATION},
     parse::{parse_server_response, parse_utf8_bytes},
     types::{Error, ErrorKind, Result},
 };
 
 pub struct Socket<T: AsyncRead + AsyncWrite + Unpin> {
     timeout: Duration,
-    stream: BufStream<T>,
+    stream: BufStream<TranscriptStream<T>>,
 }
 
 impl<T: AsyncRead + AsyncWrite + Unpin> Socket<T> {
@@ -22,10 +23,17 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Socket<T> {
     pub fn new(stream: T, timeout: Option<Duration>) -> Socket<T> {
         Self {
             timeout: timeout.unwrap_or(Self::DEFAULT_RESPONSE_TIMEOUT),
-            stream: BufStream::new(stream),
+            stream: BufStream::new(TranscriptStream::new(stream, None)),
         }
     }
 
+    /// Records the traffic over this socket in the given transcript.
+    pub fn set_transcript(&mut self, transcript: Option<Transcript>) -> &mut Self {
+        self.stream.get_mut().set_transcript(transcript);
+
+        self
+    }
+
     /// Send a command to the server and read the response into a string.
     pub async fn send_command<C: AsRef<[u8]>>(
         &mut self,
@@ -41,31 +49,21 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Socket<T> {
     pub async fn read_response(&mut self, multi_line_response: bool) -> Result<String> {
         let mut response: Vec<u8> = Vec::new();
 
-        if multi_line_response {
-            self.read_multi_line(&mut response).await?;
-
-            let line_break_index = response
-                .iter()
-                .position(|item| item == &LF)
-                .unwrap_or(response.len());
+        self.read_line(&mut response).await?;
 
-            let status_response = match response.get(..line_break_index) {
-                Some(bytes) => parse_utf8_bytes(Vec::from(bytes))?,
-                None => String::new(),
-            };
+        let status_response = parse_utf8_bytes(response)?;
 
-            parse_server_response(&status_response)?;
+        // The server only sends the body of a multi line response if the status is positive, so we check it before reading any further.
+        let status_response = parse_server_response(&status_response)?.to_string();
 
-            match response.get(line_break_index..) {
-                Some(bytes) => parse_utf8_bytes(Vec::from(bytes)),
-                None => Ok(String::new()),
-            }
-        } else {
-            self.read_line(&mut response).await?;
+        if multi_line_response {
+            let mut response: Vec<u8> = Vec::new();
 
-            let response = parse_utf8_bytes(response)?;
+            self.read_multi_line(&mut response).await?;
 
-            parse_server_response(&response).map(|response| response.to_string())
+            parse_utf8_bytes(response)
+        } else {
+            Ok(status_response)
         }
     }
 
@@ -76,37 +74,21 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Socket<T> {
         loop {
             let bytes_read = self.read_line(buf).await?;
 
-            if bytes_read < 1 {
-                return Ok(bytes_read);
-            }
-
             total_bytes_read += bytes_read;
 
             let start_of_line = buf.len().saturating_sub(bytes_read);
 
-            // Get the first three characters of the current line
-            match buf.get(start_of_line..start_of_line.saturating_add(3)) {
-                Some(first_three_chars) => {
-                    // If the first character is a DOT and the latter two are not CR and LF, then the line is byte-stuffed and the DOT should be removed
-                    if first_three_chars.first().unwrap() == &DOT {
-                        let latter_two_chars = &first_three_chars[1..];
-
-                        if latter_two_chars != END_OF_LINE {
-                            buf.remove(start_of_line);
-                        }
-                    }
-                }
-                None => {}
-            };
-
-            let last_five_bytes = buf.get(buf.len().saturating_sub(5)..).unwrap();
-
-            if last_five_bytes == EOF {
-                // Remove the last 3 bytes which should be `[DOT, CR, LF]` as they are not part of the message
-                buf.truncate(buf.len().saturating_sub(3));
+            // A line containing only a DOT marks the end of the response and is not part of the message
+            if buf.get(start_of_line..) == Some(&TERMINATION[..]) {
+                buf.truncate(start_of_line);
 
                 return Ok(total_bytes_read);
-            };
+            }
+
+            // If the line starts with a DOT, then the line is byte-stuffed and the DOT should be removed
+            if buf.get(start_of_line) == Some(&DOT) {
+                buf.remove(start_of_line);
+            }
         }
     }
 
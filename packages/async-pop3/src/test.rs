@@ -1,94 +1,95 @@
-use std::env;
+use std::time::Duration;
 
-// use async_native_tls::{TlsConnector, TlsStream};
-use dotenv::dotenv;
-use tokio::net::TcpStream;
+use async_tcp::{Config, Transcript};
+use tokio::io::DuplexStream;
 
-use crate::{types::StatsResponse, ClientState};
+use crate::{
+    test_support::MockServer,
+    types::{Capability, ErrorKind, StatsResponse, UniqueIDResponse},
+    ClientState,
+};
 
 use super::Client;
 
-#[derive(Debug)]
-struct ClientInfo {
-    server: String,
-    port: u16,
-    username: String,
-    password: String,
-}
+const MESSAGE: &str = "Subject: Hello\r\nFrom: sender@example.com\r\n\r\nHello world\r\n.hidden dot\r\nSecond line\r\n";
 
-fn create_client_info() -> ClientInfo {
-    dotenv().ok();
+const SECOND_MESSAGE: &str = "Subject: Second\r\n\r\nBody\r\n";
 
-    ClientInfo {
-        server: env::var("SERVER").unwrap().to_owned(),
-        port: env::var("PORT").unwrap().parse().unwrap(),
-        username: env::var("USERNAME").unwrap().to_owned(),
-        password: env::var("PASSWORD").unwrap().to_owned(),
-    }
-}
+fn create_server() -> MockServer {
+    let mut server = MockServer::new();
 
-async fn create_logged_in_client() -> Client<TcpStream> {
-    let client_info = create_client_info();
-    let server = client_info.server.as_ref();
-    let port = client_info.port;
+    server
+        .add_message("first", MESSAGE)
+        .add_message("second", SECOND_MESSAGE);
 
-    let username = client_info.username;
-    let password = client_info.password;
+    server
+}
 
-    let mut client = super::connect_plain((server, port), None).await.unwrap();
+async fn create_logged_in_client(server: &MockServer) -> Client<DuplexStream> {
+    let mut client = super::new(server.connect(), None).await.unwrap();
 
-    client.login(username, password).await.unwrap();
+    client
+        .login(MockServer::DEFAULT_USERNAME, MockServer::DEFAULT_PASSWORD)
+        .await
+        .unwrap();
 
     client
 }
 
-// async fn create_logged_in_client_tls() -> Client<TlsStream<TcpStream>> {
-//     let client_info = create_client_info();
-//     let server = client_info.server.as_ref();
-//     let port = client_info.port;
-
-//     let username = client_info.username;
-//     let password = client_info.password;
-
-//     let tls = TlsConnector::new();
+#[tokio::test]
+async fn connect() {
+    let mut server = MockServer::new();
 
-//     let mut client = super::connect((server, port), server, &tls, None)
-//         .await
-//         .unwrap();
+    server.set_greeting("POP3 GreenMail Server v1.6.12 ready");
 
-//     client.login(username, password).await.unwrap();
+    let addr = server.listen().await.unwrap();
 
-//     client
-// }
+    let transcript = Transcript::new();
 
-#[tokio::test]
-async fn connect() {
-    let client_info = create_client_info();
+    let mut config = Config::default();
 
-    let server = client_info.server.as_ref();
-    let port = client_info.port;
+    config.set_transcript(Some(transcript.clone()));
 
-    let mut client = super::connect_plain((server, port), None).await.unwrap();
+    let mut client = super::connect_plain(addr.ip().to_string(), addr.port(), &config)
+        .await
+        .unwrap();
 
     let greeting = client.greeting().unwrap();
 
     assert_eq!(greeting, "POP3 GreenMail Server v1.6.12 ready");
 
+    // The options in the config are used for the connection.
+    assert!(transcript
+        .export()
+        .contains("S: +OK POP3 GreenMail Server v1.6.12 ready"));
+
     client.quit().await.unwrap()
 }
 
 #[tokio::test]
 async fn login() {
-    let mut client = create_logged_in_client().await;
+    let server = create_server();
+
+    let mut client = create_logged_in_client(&server).await;
 
     assert_eq!(client.get_state(), &ClientState::Transaction);
 
     client.quit().await.unwrap();
+
+    let mut client = super::new(server.connect(), None).await.unwrap();
+
+    let result = client.login(MockServer::DEFAULT_USERNAME, "wrong").await;
+
+    assert!(matches!(result.unwrap_err().kind(), ErrorKind::ServerError));
+
+    assert_eq!(client.get_state(), &ClientState::Authentication);
 }
 
 #[tokio::test]
 async fn noop() {
-    let mut client = create_logged_in_client().await;
+    let server = create_server();
+
+    let mut client = create_logged_in_client(&server).await;
 
     assert_eq!(client.noop().await.unwrap(), ());
 
@@ -97,83 +98,218 @@ async fn noop() {
 
 #[tokio::test]
 async fn stat() {
-    let mut client = create_logged_in_client().await;
+    let server = create_server();
+
+    let mut client = create_logged_in_client(&server).await;
 
     let stats = client.stat().await.unwrap();
 
-    assert_eq!(stats, (0, 0));
+    assert_eq!(stats, (2, (MESSAGE.len() + SECOND_MESSAGE.len()) as u64));
 
     client.quit().await.unwrap();
 }
 
 #[tokio::test]
 async fn list() {
-    let mut client = create_logged_in_client().await;
-
-    // let list = client.list(Some(4)).unwrap();
+    let server = create_server();
 
-    // match list {
-    //     Right(list_item) => {
-    //         println!("{}", list_item.0);
-    //     }
-    //     _ => {}
-    // };
+    let mut client = create_logged_in_client(&server).await;
 
     let list = client.list(None).await.unwrap();
 
     match list {
         StatsResponse::StatsList(list) => {
-            assert_eq!(list, Vec::new());
+            assert_eq!(
+                list,
+                vec![(1, MESSAGE.len() as u64), (2, SECOND_MESSAGE.len() as u64)]
+            );
         }
-        _ => {}
+        _ => unreachable!(),
+    };
+
+    match client.list(Some(2)).await.unwrap() {
+        StatsResponse::Stats(stats) => assert_eq!(stats, (2, SECOND_MESSAGE.len() as u64)),
+        _ => unreachable!(),
     };
 
     client.quit().await.unwrap();
 }
 
-// #[test]
-// fn retr() {
-//     let mut client = create_logged_in_client();
+#[tokio::test]
+async fn empty_list() {
+    let server = MockServer::new();
 
-//     let bytes = client.retr(1).unwrap();
+    let mut client = create_logged_in_client(&server).await;
 
-//     println!("{}", String::from_utf8(bytes).unwrap());
+    match client.list(None).await.unwrap() {
+        StatsResponse::StatsList(list) => assert_eq!(list, Vec::new()),
+        _ => unreachable!(),
+    };
 
-//     client.quit().unwrap();
-// }
+    client.quit().await.unwrap();
+}
 
-// #[test]
-// fn top() {
-//     let mut client = create_logged_in_client();
+#[tokio::test]
+async fn retr() {
+    let server = create_server();
+
+    let mut client = create_logged_in_client(&server).await;
+
+    let bytes = client.retr(1).await.unwrap();
+
+    assert_eq!(String::from_utf8(bytes).unwrap(), MESSAGE);
+
+    client.quit().await.unwrap();
+}
+
+#[tokio::test]
+async fn transcript() {
+    let server = create_server();
+
+    let transcript = Transcript::new();
+
+    let mut client = super::new_with_transcript(server.connect(), None, Some(transcript.clone()))
+        .await
+        .unwrap();
+
+    client
+        .login(MockServer::DEFAULT_USERNAME, MockServer::DEFAULT_PASSWORD)
+        .await
+        .unwrap();
+
+    client.retr(1).await.unwrap();
+
+    let export = transcript.export();
 
-//     let bytes = client.top(1, 0).unwrap();
+    assert!(export.contains("C: PASS <redacted>"));
+    assert!(export.contains("C: RETR 1"));
+    assert!(!export.contains(&format!("PASS {}", MockServer::DEFAULT_PASSWORD)));
+    assert!(!export.contains("Hello world"));
+
+    client.quit().await.unwrap();
+}
 
-//     println!("{}", String::from_utf8(bytes).unwrap());
+#[tokio::test]
+async fn top() {
+    let server = create_server();
 
-//     client.quit().unwrap();
-// }
+    let mut client = create_logged_in_client(&server).await;
 
-// #[test]
-// fn uidl() {
-//     let mut client = create_logged_in_client();
+    let bytes = client.top(1, 1).await.unwrap();
 
-//     let uidl = client.uidl(Some(1)).unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        "Subject: Hello\r\nFrom: sender@example.com\r\n\r\nHello world\r\n"
+    );
 
-//     match uidl {
-//         Right(unique_id) => {
-//             println!("{}", unique_id.1);
-//         }
-//         _ => {}
-//     };
+    client.quit().await.unwrap();
+}
 
-//     let uidl = client.uidl(None).unwrap();
+#[tokio::test]
+async fn uidl() {
+    let server = create_server();
 
-//     match uidl {
-//         Left(list) => {
-//             println!("{}", list.len());
-//         }
-//         _ => {}
-//     };
+    let mut client = create_logged_in_client(&server).await;
 
-//     client.quit().unwrap();
-// }
+    match client.uidl(Some(1)).await.unwrap() {
+        UniqueIDResponse::UniqueID(unique_id) => {
+            assert_eq!(unique_id, (1, String::from("first")))
+        }
+        _ => unreachable!(),
+    };
+
+    match client.uidl(None).await.unwrap() {
+        UniqueIDResponse::UniqueIDList(list) => assert_eq!(
+            list,
+            vec![(1, String::from("first")), (2, String::from("second"))]
+        ),
+        _ => unreachable!(),
+    };
+
+    client.quit().await.unwrap();
+}
+
+#[tokio::test]
+async fn dele() {
+    let server = create_server();
+
+    let mut client = create_logged_in_client(&server).await;
+
+    client.dele(1).await.unwrap();
+
+    assert!(client.is_deleted(&1));
+
+    assert!(matches!(
+        client.retr(1).await.unwrap_err().kind(),
+        ErrorKind::MessageIsDeleted
+    ));
+
+    client.rset().await.unwrap();
+
+    assert!(!client.is_deleted(&1));
+
+    client.dele(2).await.unwrap();
+
+    client.quit().await.unwrap();
+
+    let maildrop = server.maildrop();
+
+    assert_eq!(maildrop.len(), 1);
+    assert_eq!(maildrop[0].unique_id(), "first");
+}
+
+#[tokio::test]
+async fn capabilities() {
+    let mut server = create_server();
+
+    server.set_capabilities(vec!["USER", "UIDL", "LOGIN-DELAY 900", "EXPIRE 0"]);
+
+    let client = create_logged_in_client(&server).await;
+
+    assert!(!client.capabilities().contains(&Capability::Top));
+
+    assert_eq!(client.login_delay(), Some(Duration::from_secs(900)));
+
+    assert_eq!(client.expire(), Some(Some(Duration::ZERO)));
+}
+
+#[tokio::test]
+async fn server_error() {
+    let mut server = create_server();
+
+    server.fail_command("UIDL", "Temporarily unavailable");
+
+    let mut client = create_logged_in_client(&server).await;
+
+    let error = client.uidl(None).await.err().unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::ServerError));
+
+    assert_eq!(error.message(), "Server error: Temporarily unavailable");
+
+    // The connection should still be usable after an error.
+    assert_eq!(client.stat().await.unwrap().0, 2);
+
+    client.quit().await.unwrap();
+}
+
+#[tokio::test]
+async fn latency() {
+    let mut server = create_server();
+
+    server.set_latency(Duration::from_millis(50));
+
+    // The server responds slower than the client is willing to wait.
+    let error = super::new(server.connect(), Some(Duration::from_millis(10)))
+        .await
+        .err()
+        .unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::InvalidResponse));
+
+    server.set_latency(Duration::from_millis(10));
+
+    let mut client = create_logged_in_client(&server).await;
+
+    client.quit().await.unwrap();
+}
@@ -0,0 +1,503 @@
+//! A configurable in-memory Pop3 server that can be used to test code that talks to a Pop3 server,
+//! without needing a real mail account.
+//!
+//! Only available with the `test-support` feature enabled.
+//!
+//! # Examples
+//! ```rust,ignore
+//! let mut server = MockServer::new();
+//!
+//! server.add_message("unique-1", "Subject: Hello\r\n\r\nWorld\r\n");
+//!
+//! let mut client = async_pop3::new(server.connect(), None).await?;
+//!
+//! client.login("user", "password").await?;
+//! ```
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream},
+    net::TcpListener,
+    time::sleep,
+};
+
+use crate::types::Result;
+
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A message in the mock server's maildrop.
+pub struct MockMessage {
+    unique_id: String,
+    body: Vec<u8>,
+}
+
+impl MockMessage {
+    pub fn new<U: Into<String>, B: Into<Vec<u8>>>(unique_id: U, body: B) -> Self {
+        Self {
+            unique_id: unique_id.into(),
+            body: body.into(),
+        }
+    }
+
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MockConfig {
+    greeting: String,
+    capabilities: Vec<String>,
+    username: String,
+    password: String,
+    errors: HashMap<String, String>,
+    latency: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+/// A scripted Pop3 server that serves a fixed maildrop.
+///
+/// Messages that are deleted during a session are removed from the maildrop once the client quits,
+/// so a test can inspect the maildrop afterwards using `MockServer::maildrop`.
+pub struct MockServer {
+    config: MockConfig,
+    maildrop: Arc<Mutex<Vec<MockMessage>>>,
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockServer {
+    pub const DEFAULT_USERNAME: &'static str = "user";
+    pub const DEFAULT_PASSWORD: &'static str = "password";
+
+    /// Creates a server with an empty maildrop that accepts the default username and password.
+    pub fn new() -> Self {
+        Self {
+            config: MockConfig {
+                greeting: String::from("POP3 mock server ready"),
+                capabilities: vec![
+                    String::from("USER"),
+                    String::from("TOP"),
+                    String::from("UIDL"),
+                ],
+                username: String::from(Self::DEFAULT_USERNAME),
+                password: String::from(Self::DEFAULT_PASSWORD),
+                errors: HashMap::new(),
+                latency: None,
+            },
+            maildrop: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The text the server greets new connections with.
+    pub fn set_greeting<G: Into<String>>(&mut self, greeting: G) -> &mut Self {
+        self.config.greeting = greeting.into();
+
+        self
+    }
+
+    /// The lines the server responds with to a CAPA command, e.g. `["USER", "UIDL", "EXPIRE 0"]`.
+    pub fn set_capabilities<C: AsRef<str>>(&mut self, capabilities: Vec<C>) -> &mut Self {
+        self.config.capabilities = capabilities
+            .iter()
+            .map(|capability| capability.as_ref().to_string())
+            .collect();
+
+        self
+    }
+
+    /// The only username and password combination the server accepts.
+    pub fn set_credentials<U: Into<String>, P: Into<String>>(
+        &mut self,
+        username: U,
+        password: P,
+    ) -> &mut Self {
+        self.config.username = username.into();
+        self.config.password = password.into();
+
+        self
+    }
+
+    /// Adds a message to the end of the maildrop.
+    pub fn add_message<U: Into<String>, B: Into<Vec<u8>>>(
+        &mut self,
+        unique_id: U,
+        body: B,
+    ) -> &mut Self {
+        self.maildrop
+            .lock()
+            .unwrap()
+            .push(MockMessage::new(unique_id, body));
+
+        self
+    }
+
+    /// Makes the server respond with `-ERR <message>` whenever the given command is sent.
+    pub fn fail_command<C: AsRef<str>, M: Into<String>>(
+        &mut self,
+        command: C,
+        message: M,
+    ) -> &mut Self {
+        self.config
+            .errors
+            .insert(command.as_ref().to_ascii_uppercase(), message.into());
+
+        self
+    }
+
+    /// Waits the given amount of time before sending every response.
+    pub fn set_latency(&mut self, latency: Duration) -> &mut Self {
+        self.config.latency = Some(latency);
+
+        self
+    }
+
+    /// The messages that are currently in the maildrop.
+    pub fn maildrop(&self) -> Vec<MockMessage> {
+        self.maildrop.lock().unwrap().clone()
+    }
+
+    /// Starts a session on an in-memory stream and returns the client side of it.
+    pub fn connect(&self) -> DuplexStream {
+        let (client, server) = io::duplex(DUPLEX_BUFFER_SIZE);
+
+        let session = MockSession::new(self.config.clone(), self.maildrop.clone());
+
+        tokio::spawn(session.run(server));
+
+        client
+    }
+
+    /// Listens for connections on a random local port and returns its address.
+    pub async fn listen(&self) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+
+        let addr = listener.local_addr()?;
+
+        let config = self.config.clone();
+        let maildrop = self.maildrop.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let session = MockSession::new(config.clone(), maildrop.clone());
+
+                tokio::spawn(session.run(stream));
+            }
+        });
+
+        Ok(addr)
+    }
+}
+
+#[derive(PartialEq)]
+enum MockState {
+    Authorization,
+    Transaction,
+}
+
+struct MockSession {
+    config: MockConfig,
+    maildrop: Arc<Mutex<Vec<MockMessage>>>,
+    state: MockState,
+    username: Option<String>,
+    /// A snapshot of the maildrop, taken when the client logs in.
+    messages: Vec<MockMessage>,
+    deleted: Vec<usize>,
+}
+
+impl MockSession {
+    fn new(config: MockConfig, maildrop: Arc<Mutex<Vec<MockMessage>>>) -> Self {
+        Self {
+            config,
+            maildrop,
+            state: MockState::Authorization,
+            username: None,
+            messages: Vec::new(),
+            deleted: Vec::new(),
+        }
+    }
+
+    async fn run<S: AsyncRead + AsyncWrite + Unpin>(mut self, stream: S) {
+        let mut stream = BufReader::new(stream);
+
+        let greeting = format!("+OK {}\r\n", self.config.greeting);
+
+        if self
+            .respond(&mut stream, greeting.as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            match stream.read_line(&mut line).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+
+            let (response, quit) = self.handle_command(line.trim_end());
+
+            if self.respond(&mut stream, &response).await.is_err() || quit {
+                return;
+            }
+        }
+    }
+
+    async fn respond<S: AsyncWrite + Unpin>(&self, stream: &mut S, response: &[u8]) -> Result<()> {
+        if let Some(latency) = self.config.latency {
+            sleep(latency).await;
+        }
+
+        stream.write_all(response).await?;
+
+        stream.flush().await?;
+
+        Ok(())
+    }
+
+    /// Handles a single command and returns the response and whether the connection should be closed.
+    fn handle_command(&mut self, line: &str) -> (Vec<u8>, bool) {
+        let mut split = line.split(' ');
+
+        let command = split.next().unwrap_or_default().to_ascii_uppercase();
+
+        let arguments: Vec<&str> = split.collect();
+
+        if let Some(message) = self.config.errors.get(&command) {
+            return (err(message), false);
+        }
+
+        let response = match (&self.state, command.as_str()) {
+            (_, "CAPA") => {
+                let mut response = ok("Capability list follows");
+
+                for capability in &self.config.capabilities {
+                    response.extend_from_slice(capability.as_bytes());
+                    response.extend_from_slice(b"\r\n");
+                }
+
+                response.extend_from_slice(b".\r\n");
+
+                response
+            }
+            (_, "QUIT") => {
+                if self.state == MockState::Transaction {
+                    self.commit_deletions();
+                }
+
+                return (ok("Bye"), true);
+            }
+            (MockState::Authorization, "USER") => match arguments.first() {
+                Some(username) => {
+                    self.username = Some(username.to_string());
+
+                    ok("Send your password")
+                }
+                None => err("Missing username"),
+            },
+            (MockState::Authorization, "PASS") => {
+                let password = arguments.join(" ");
+
+                if self.username.as_ref() == Some(&self.config.username)
+                    && password == self.config.password
+                {
+                    self.messages = self.maildrop.lock().unwrap().clone();
+                    self.state = MockState::Transaction;
+
+                    ok("Logged in")
+                } else {
+                    self.username = None;
+
+                    err("Invalid username or password")
+                }
+            }
+            (MockState::Transaction, "NOOP") => ok(""),
+            (MockState::Transaction, "STAT") => {
+                let (count, size) = self
+                    .visible_messages()
+                    .fold((0, 0), |(count, size), (_, message)| {
+                        (count + 1, size + message.body.len())
+                    });
+
+                ok(&format!("{} {}", count, size))
+            }
+            (MockState::Transaction, "LIST") => {
+                self.scan_listing(arguments.first(), |message| message.body.len().to_string())
+            }
+            (MockState::Transaction, "UIDL") => {
+                self.scan_listing(arguments.first(), |message| message.unique_id.clone())
+            }
+            (MockState::Transaction, "RETR") => match self.get_message(arguments.first()) {
+                Some(message) => {
+                    let mut response = ok("Message follows");
+
+                    response.extend(byte_stuff(&message.body, None));
+
+                    response
+                }
+                None => err("No such message"),
+            },
+            (MockState::Transaction, "TOP") => {
+                let lines = arguments
+                    .get(1)
+                    .and_then(|lines| lines.parse::<usize>().ok());
+
+                match (self.get_message(arguments.first()), lines) {
+                    (Some(message), Some(lines)) => {
+                        let mut response = ok("Top of message follows");
+
+                        response.extend(byte_stuff(&message.body, Some(lines)));
+
+                        response
+                    }
+                    _ => err("No such message"),
+                }
+            }
+            (MockState::Transaction, "DELE") => match self.get_index(arguments.first()) {
+                Some(index) => {
+                    self.deleted.push(index);
+
+                    ok("Message deleted")
+                }
+                None => err("No such message"),
+            },
+            (MockState::Transaction, "RSET") => {
+                self.deleted.clear();
+
+                ok("")
+            }
+            _ => err("Unknown command"),
+        };
+
+        (response, false)
+    }
+
+    /// The messages that have not been marked as deleted, together with their message number.
+    fn visible_messages(&self) -> impl Iterator<Item = (usize, &MockMessage)> {
+        self.messages
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !self.deleted.contains(index))
+            .map(|(index, message)| (index + 1, message))
+    }
+
+    fn get_index(&self, msg_number: Option<&&str>) -> Option<usize> {
+        let index = msg_number?.parse::<usize>().ok()?.checked_sub(1)?;
+
+        if index >= self.messages.len() || self.deleted.contains(&index) {
+            return None;
+        }
+
+        Some(index)
+    }
+
+    fn get_message(&self, msg_number: Option<&&str>) -> Option<&MockMessage> {
+        self.get_index(msg_number)
+            .and_then(|index| self.messages.get(index))
+    }
+
+    fn scan_listing<F: Fn(&MockMessage) -> String>(
+        &self,
+        msg_number: Option<&&str>,
+        value: F,
+    ) -> Vec<u8> {
+        match msg_number {
+            Some(_) => match self.get_index(msg_number) {
+                Some(index) => ok(&format!("{} {}", index + 1, value(&self.messages[index]))),
+                None => err("No such message"),
+            },
+            None => {
+                let mut response = ok("Listing follows");
+
+                for (msg_number, message) in self.visible_messages() {
+                    response.extend_from_slice(
+                        format!("{} {}\r\n", msg_number, value(message)).as_bytes(),
+                    );
+                }
+
+                response.extend_from_slice(b".\r\n");
+
+                response
+            }
+        }
+    }
+
+    fn commit_deletions(&mut self) {
+        let deleted: Vec<&str> = self
+            .deleted
+            .iter()
+            .filter_map(|index| self.messages.get(*index))
+            .map(|message| message.unique_id.as_str())
+            .collect();
+
+        self.maildrop
+            .lock()
+            .unwrap()
+            .retain(|message| !deleted.contains(&message.unique_id.as_str()));
+    }
+}
+
+fn ok(message: &str) -> Vec<u8> {
+    format!("+OK {}\r\n", message).into_bytes()
+}
+
+fn err(message: &str) -> Vec<u8> {
+    format!("-ERR {}\r\n", message).into_bytes()
+}
+
+/// Formats a message as a multi line response body, optionally only including the headers and the given amount of body lines.
+fn byte_stuff(body: &[u8], body_lines: Option<usize>) -> Vec<u8> {
+    let mut response = Vec::with_capacity(body.len() + 5);
+
+    let mut in_body = false;
+    let mut lines_in_body = 0;
+
+    for line in body.split_inclusive(|byte| byte == &b'\n') {
+        if in_body {
+            if let Some(max_lines) = body_lines {
+                if lines_in_body >= max_lines {
+                    break;
+                }
+            }
+
+            lines_in_body += 1;
+        }
+
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+
+        if content.is_empty() {
+            in_body = true;
+        }
+
+        if content.first() == Some(&b'.') {
+            response.push(b'.');
+        }
+
+        response.extend_from_slice(content);
+        response.extend_from_slice(b"\r\n");
+    }
+
+    response.extend_from_slice(b".\r\n");
+
+    response
+}
@@ -1,11 +1,14 @@
 use std::{error, fmt};
 
 use async_native_tls::Error as TlsError;
+use async_tcp::types::Error as TcpError;
 use tokio::{io::Error as IoError, time::error::Elapsed as TimeoutError};
 
 #[derive(Debug)]
 pub enum ErrorKind {
     Tls(TlsError),
+    /// Failed to set up the connection to the server, like when its certificate is not trusted or the proxy refused it.
+    Tcp(Box<TcpError>),
     Io(IoError),
     Timeout(TimeoutError),
     Connect,
@@ -82,6 +85,14 @@ impl From<TlsError> for Error {
     }
 }
 
+impl From<TcpError> for Error {
+    fn from(tcp_error: TcpError) -> Self {
+        let message = tcp_error.to_string();
+
+        Self::new(ErrorKind::Tcp(Box::new(tcp_error)), message)
+    }
+}
+
 impl From<IoError> for Error {
     fn from(io_error: IoError) -> Self {
         Self::new(ErrorKind::Io(io_error), "Error with connection to server")
@@ -0,0 +1,210 @@
+use async_native_tls::Certificate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x509_parser::parse_x509_certificate;
+
+use crate::types::{Error, ErrorKind, Result};
+
+const CHANGE_CIPHER_SPEC_RECORD: u8 = 20;
+const HANDSHAKE_RECORD: u8 = 22;
+const CERTIFICATE_MESSAGE: u8 = 11;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// The details of a certificate presented by a server, which can be shown to a user so they can decide whether to trust it.
+pub struct CertificateInfo {
+    subject: String,
+    issuer: String,
+    /// Unix timestamp (in seconds) from which the certificate is valid.
+    not_before: i64,
+    /// Unix timestamp (in seconds) until which the certificate is valid.
+    not_after: i64,
+    /// The SHA-256 fingerprint of the certificate, formatted as lowercase hex.
+    fingerprint: String,
+}
+
+impl CertificateInfo {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (_, certificate) = parse_x509_certificate(der).map_err(|error| {
+            Error::new(
+                ErrorKind::InvalidCertificate,
+                format!("Failed to parse certificate: {}", error),
+            )
+        })?;
+
+        let validity = certificate.validity();
+
+        Ok(Self {
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            not_before: validity.not_before.timestamp(),
+            not_after: validity.not_after.timestamp(),
+            fingerprint: fingerprint_der(der),
+        })
+    }
+
+    pub fn from_certificate(certificate: &Certificate) -> Result<Self> {
+        let der = certificate.to_der()?;
+
+        Self::from_der(&der)
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn not_before(&self) -> &i64 {
+        &self.not_before
+    }
+
+    pub fn not_after(&self) -> &i64 {
+        &self.not_after
+    }
+
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// Whether the certificate is signed by itself instead of a certificate authority.
+    pub fn is_self_signed(&self) -> bool {
+        self.subject == self.issuer
+    }
+}
+
+/// The SHA-256 fingerprint of a certificate, formatted as lowercase hex.
+pub fn fingerprint(certificate: &Certificate) -> Result<String> {
+    let der = certificate.to_der()?;
+
+    Ok(fingerprint_der(&der))
+}
+
+fn fingerprint_der(der: &[u8]) -> String {
+    let digest = Sha256::digest(der);
+
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn read_u24(bytes: &[u8]) -> usize {
+    ((bytes[0] as usize) << 16) | ((bytes[1] as usize) << 8) | bytes[2] as usize
+}
+
+/// Finds the certificates in DER format that a server presented, given the raw bytes it sent during a TLS handshake.
+///
+/// The certificates are in the order the server sent them, which starts with its own certificate.
+/// This only works up to TLS 1.2, since TLS 1.3 encrypts the certificate message.
+pub(crate) fn presented_certificates(received: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut handshake = Vec::new();
+
+    let mut records = received;
+
+    // A handshake message can be split over multiple records, so we join them first.
+    while records.len() >= 5 {
+        let length = u16::from_be_bytes([records[3], records[4]]) as usize;
+
+        let payload = match records.get(5..5 + length) {
+            Some(payload) => payload,
+            None => break,
+        };
+
+        match records[0] {
+            HANDSHAKE_RECORD => handshake.extend_from_slice(payload),
+            // Everything the server sends after this is encrypted.
+            CHANGE_CIPHER_SPEC_RECORD => break,
+            _ => {}
+        }
+
+        records = &records[5 + length..];
+    }
+
+    let mut messages = handshake.as_slice();
+
+    while messages.len() >= 4 {
+        let length = read_u24(&messages[1..4]);
+
+        let body = messages.get(4..4 + length)?;
+
+        if messages[0] == CERTIFICATE_MESSAGE {
+            let length = read_u24(body.get(0..3)?);
+
+            let mut list = body.get(3..3 + length)?;
+
+            let mut certificates = Vec::new();
+
+            while !list.is_empty() {
+                let length = read_u24(list.get(0..3)?);
+
+                certificates.push(list.get(3..3 + length)?.to_vec());
+
+                list = &list[3 + length..];
+            }
+
+            return Some(certificates);
+        }
+
+        messages = &messages[4 + length..];
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use async_native_tls::Certificate;
+
+    use super::{presented_certificates, CertificateInfo};
+
+    const CERTIFICATE: &str = include_str!("test_data/localhost.crt");
+
+    #[test]
+    fn presented_chain() {
+        let certificates = [b"leaf".to_vec(), b"authority".to_vec()];
+
+        let mut list = Vec::new();
+
+        for certificate in &certificates {
+            list.extend_from_slice(&[0, 0, certificate.len() as u8]);
+            list.extend_from_slice(certificate);
+        }
+
+        let mut message = vec![11, 0, 0, list.len() as u8 + 3, 0, 0, list.len() as u8];
+
+        message.extend_from_slice(&list);
+
+        // A server hello, followed by the certificate message that is split over two records.
+        let mut received = vec![22, 3, 3, 0, 6, 2, 0, 0, 2, 3, 3];
+
+        let (first, second) = message.split_at(5);
+
+        for part in [first, second] {
+            received.extend_from_slice(&[22, 3, 3, 0, part.len() as u8]);
+            received.extend_from_slice(part);
+        }
+
+        assert_eq!(
+            presented_certificates(&received),
+            Some(certificates.to_vec())
+        );
+
+        // After the server switched to encryption, nothing can be read anymore.
+        assert_eq!(presented_certificates(&[20, 3, 3, 0, 1, 1]), None);
+    }
+
+    #[test]
+    fn certificate_info() {
+        let certificate = Certificate::from_pem(CERTIFICATE.as_bytes()).unwrap();
+
+        let info = CertificateInfo::from_certificate(&certificate).unwrap();
+
+        assert_eq!(info.subject(), "CN=localhost, O=Dust-Mail");
+        assert!(info.is_self_signed());
+        assert!(info.not_before() < info.not_after());
+        assert_eq!(
+            info.fingerprint(),
+            "d6dfa87f464f54a8c10ef07fbe75780bdc5b8ea95a8e7a8db315afe5b976bda7"
+        );
+    }
+}
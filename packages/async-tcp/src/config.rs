@@ -1,9 +1,18 @@
 use tokio::time::Duration;
 
+use crate::{proxy::ProxyConfig, tls::TlsConfig, transcript::Transcript};
+
+/// The delay recommended by https://www.rfc-editor.org/rfc/rfc8305#section-5, in milliseconds.
+const DEFAULT_CONNECTION_ATTEMPT_DELAY: u64 = 250;
+
 #[derive(Clone)]
 pub struct Config {
     timeout: Duration,
     error_on_timeout: bool,
+    tls: TlsConfig,
+    proxy: Option<ProxyConfig>,
+    connection_attempt_delay: Duration,
+    transcript: Option<Transcript>,
 }
 
 impl Config {
@@ -11,6 +20,10 @@ impl Config {
         Self {
             timeout,
             error_on_timeout,
+            tls: TlsConfig::default(),
+            proxy: None,
+            connection_attempt_delay: Duration::from_millis(DEFAULT_CONNECTION_ATTEMPT_DELAY),
+            transcript: None,
         }
     }
 
@@ -25,6 +38,49 @@ impl Config {
     pub fn error_on_timeout(&self) -> bool {
         self.error_on_timeout
     }
+
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    pub fn set_tls(&mut self, tls: TlsConfig) -> &mut Self {
+        self.tls = tls;
+
+        self
+    }
+
+    /// The proxy that connections should be tunneled through, if any.
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    pub fn set_proxy(&mut self, proxy: Option<ProxyConfig>) -> &mut Self {
+        self.proxy = proxy;
+
+        self
+    }
+
+    /// How long we wait for a connection to one of the addresses of a server before we also start connecting to the next one.
+    pub fn connection_attempt_delay(&self) -> &Duration {
+        &self.connection_attempt_delay
+    }
+
+    pub fn set_connection_attempt_delay(&mut self, delay: Duration) -> &mut Self {
+        self.connection_attempt_delay = delay;
+
+        self
+    }
+
+    /// The transcript that the traffic of connections using this config is recorded in, if any.
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    pub fn set_transcript(&mut self, transcript: Option<Transcript>) -> &mut Self {
+        self.transcript = transcript;
+
+        self
+    }
 }
 
 impl Default for Config {
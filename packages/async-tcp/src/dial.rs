@@ -0,0 +1,195 @@
+use std::{future::Future, io, net::SocketAddr};
+
+use tokio::{
+    net::{lookup_host, TcpStream},
+    task::JoinSet,
+    time::{timeout, Duration},
+};
+
+use crate::{
+    config::Config,
+    types::{Error, ErrorKind, Result},
+};
+
+/// Opens a tcp connection to a remote server at a given host and port.
+///
+/// If the config specifies a proxy, the connection is tunneled through it. Otherwise all of the addresses the host resolves to are raced against each other.
+pub async fn dial<H: AsRef<str>>(host: H, port: u16, config: &Config) -> Result<TcpStream> {
+    match config.proxy() {
+        Some(proxy) => proxy.connect(host, port).await,
+        None => {
+            let addrs = lookup_host((host.as_ref(), port)).await?.collect();
+
+            race(
+                sort_addresses(addrs),
+                *config.connection_attempt_delay(),
+                TcpStream::connect,
+            )
+            .await
+        }
+    }
+}
+
+/// Connects to the first address that accepts a connection, according to https://www.rfc-editor.org/rfc/rfc8305.
+///
+/// Every address gets a head start of the given delay before a connection to the next address is attempted, unless the connection fails before that.
+async fn race<F, C>(addrs: Vec<SocketAddr>, delay: Duration, connect: F) -> Result<TcpStream>
+where
+    F: Fn(SocketAddr) -> C,
+    C: Future<Output = io::Result<TcpStream>> + Send + 'static,
+{
+    let mut addrs = addrs.into_iter();
+
+    // Dropping the set aborts the attempts that are still running once we have a connection.
+    let mut attempts = JoinSet::new();
+
+    let mut last_error = None;
+
+    loop {
+        if let Some(addr) = addrs.next() {
+            attempts.spawn(connect(addr));
+        }
+
+        let finished = if addrs.len() > 0 {
+            match timeout(delay, attempts.join_next()).await {
+                Ok(finished) => finished,
+                // Our current attempts are taking too long, so we start another one.
+                Err(_) => continue,
+            }
+        } else {
+            attempts.join_next().await
+        };
+
+        match finished {
+            Some(Ok(Ok(stream))) => return Ok(stream),
+            Some(Ok(Err(error))) => last_error = Some(error),
+            Some(Err(_)) => {}
+            // All of the attempts failed and there are no addresses left.
+            None => break,
+        }
+    }
+
+    match last_error {
+        Some(error) => Err(error.into()),
+        None => Err(Error::new(
+            ErrorKind::Io(io::ErrorKind::NotFound.into()),
+            "The host did not resolve to any addresses",
+        )),
+    }
+}
+
+/// Orders the addresses so that the address families alternate, starting with IPv6.
+fn sort_addresses(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (mut ipv6, mut ipv4): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        addrs.into_iter().partition(|addr| addr.is_ipv6());
+
+    ipv6.reverse();
+    ipv4.reverse();
+
+    let mut sorted = Vec::new();
+
+    while let Some(addr) = ipv6.pop() {
+        sorted.push(addr);
+
+        if let Some(addr) = ipv4.pop() {
+            sorted.push(addr);
+        }
+    }
+
+    while let Some(addr) = ipv4.pop() {
+        sorted.push(addr);
+    }
+
+    sorted
+}
+
+#[cfg(test)]
+mod test {
+    use std::{future::pending, net::SocketAddr};
+
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        time::{timeout, Duration, Instant},
+    };
+
+    use crate::types::ErrorKind;
+
+    use super::{race, sort_addresses};
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn sort() {
+        let sorted = sort_addresses(vec![
+            addr("10.0.0.1:993"),
+            addr("10.0.0.2:993"),
+            addr("10.0.0.3:993"),
+            addr("[::1]:993"),
+            addr("[::2]:993"),
+        ]);
+
+        assert_eq!(
+            sorted,
+            vec![
+                addr("[::1]:993"),
+                addr("10.0.0.1:993"),
+                addr("[::2]:993"),
+                addr("10.0.0.2:993"),
+                addr("10.0.0.3:993"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn race_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let working = listener.local_addr().unwrap();
+
+        // An address that never responds, like a host with a broken IPv6 setup.
+        let unresponsive = addr("[::2]:993");
+
+        // A port that nothing is listening on, so connecting to it fails immediately.
+        let refused = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+            listener.local_addr().unwrap()
+        };
+
+        let connect = |addr: SocketAddr| async move {
+            if addr == unresponsive {
+                pending::<()>().await;
+            }
+
+            TcpStream::connect(addr).await
+        };
+
+        let start = Instant::now();
+
+        let stream = timeout(
+            Duration::from_secs(5),
+            race(
+                vec![unresponsive, refused, working],
+                Duration::from_millis(50),
+                connect,
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(stream.peer_addr().unwrap(), working);
+
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let error = race(vec![refused], Duration::from_millis(50), connect)
+            .await
+            .err()
+            .unwrap();
+
+        assert!(matches!(error.kind(), ErrorKind::Io(_)));
+    }
+}
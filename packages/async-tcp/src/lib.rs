@@ -1,62 +1,92 @@
-use async_native_tls::{TlsConnector, TlsStream};
+use std::net::SocketAddr;
+
+use async_native_tls::TlsStream;
 pub use config::Config;
 use constants::{CR, LF};
 use tokio::{
     io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream},
-    net::{TcpStream, ToSocketAddrs},
+    net::TcpStream,
 };
 
+mod certificate;
 mod config;
 mod constants;
+mod dial;
+mod proxy;
+mod response;
+mod tls;
+mod transcript;
 pub mod types;
 
+pub use certificate::{fingerprint, CertificateInfo};
+pub use dial::dial;
+pub use proxy::{ProxyConfig, ProxyCredentials, ProxyType};
+pub use response::{ImapResponse, SmtpReply};
+pub use tls::{dial_tls, TlsConfig, TlsVersion};
+pub use transcript::{Direction, Transcript, TranscriptEntry, TranscriptStream};
+
 use types::{Error, ErrorKind, Result};
 
 pub struct TcpClient<S: AsyncWrite + AsyncRead + Unpin> {
-    stream: BufStream<S>,
+    stream: BufStream<TranscriptStream<S>>,
     config: Config,
 }
 
 pub fn new<T: AsyncWrite + AsyncRead + Unpin>(stream: T, config: Config) -> TcpClient<T> {
+    let stream = TranscriptStream::new(stream, config.transcript().cloned());
+
     TcpClient {
         stream: BufStream::new(stream),
         config,
     }
 }
 
-/// Connects to a remote server in a secure manner using a given address and hostname, with a timeout duration.
-pub async fn connect<A: ToSocketAddrs, H: AsRef<str>>(
-    addr: A,
+/// Connects to a remote server in a secure manner using a given hostname and port, with a timeout duration.
+pub async fn connect<H: AsRef<str>>(
     host: H,
+    port: u16,
     config: Option<Config>,
 ) -> Result<TcpClient<TlsStream<TcpStream>>> {
     let config = config.unwrap_or_default();
 
-    let tcp = tokio::time::timeout(config.timeout().clone(), TcpStream::connect(addr)).await??;
-
-    let tls = TlsConnector::new();
-
-    let stream = tls.connect(host.as_ref(), tcp).await?;
+    let stream =
+        tokio::time::timeout(config.timeout().clone(), dial_tls(host, port, &config)).await??;
 
     let client = new(stream, config);
 
     Ok(client)
 }
 
-/// Connects to a remote server given address with a timeout duration.
-pub async fn connect_plain<A: ToSocketAddrs>(
-    addr: A,
+/// Connects to a remote server given hostname and port with a timeout duration.
+pub async fn connect_plain<H: AsRef<str>>(
+    host: H,
+    port: u16,
     config: Option<Config>,
 ) -> Result<TcpClient<TcpStream>> {
     let config = config.unwrap_or_default();
 
-    let stream = tokio::time::timeout(config.timeout().clone(), TcpStream::connect(addr)).await??;
+    let stream =
+        tokio::time::timeout(config.timeout().clone(), dial(host, port, &config)).await??;
 
     let client = new(stream, config);
 
     Ok(client)
 }
 
+impl TcpClient<TcpStream> {
+    /// The address of the server we are connected to, or of the proxy server if the connection is tunneled through one.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.stream.get_ref().get_ref().peer_addr()?)
+    }
+}
+
+impl TcpClient<TlsStream<TcpStream>> {
+    /// The address of the server we are connected to, or of the proxy server if the connection is tunneled through one.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.stream.get_ref().get_ref().get_ref().peer_addr()?)
+    }
+}
+
 impl<S: AsyncWrite + AsyncRead + Unpin> TcpClient<S> {
     /// Creates a new client using a given stream and timeout duration.
 
@@ -69,10 +99,10 @@ impl<S: AsyncWrite + AsyncRead + Unpin> TcpClient<S> {
         Ok(response)
     }
 
-    /// Write some bytes to the socket, end it off with a LF CR and send it to the remote server.
+    /// Write some bytes to the socket, end it off with a CRLF and send it to the remote server.
     pub async fn send_bytes<B: AsRef<[u8]>>(&mut self, bytes: B) -> Result<()> {
         self.stream.write_all(bytes.as_ref()).await?;
-        self.stream.write_all(&[LF, CR]).await?;
+        self.stream.write_all(&[CR, LF]).await?;
         self.stream.flush().await?;
 
         Ok(())
@@ -103,4 +133,19 @@ impl<S: AsyncWrite + AsyncRead + Unpin> TcpClient<S> {
 
         Ok(())
     }
+
+    /// Upgrades the connection to a secure one, after the server agreed to it with a response to a command like `STARTTLS`.
+    ///
+    /// Anything the server sent before the upgrade that was not read yet is discarded, because it could have been injected by an attacker.
+    pub async fn starttls<H: AsRef<str>>(self, host: H) -> Result<TcpClient<TlsStream<S>>> {
+        let stream = self.stream.into_inner().into_inner();
+
+        let stream = tokio::time::timeout(
+            *self.config.timeout(),
+            self.config.tls().secure(host, stream),
+        )
+        .await??;
+
+        Ok(new(stream, self.config))
+    }
 }
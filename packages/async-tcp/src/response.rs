@@ -0,0 +1,287 @@
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{
+    types::{Error, ErrorKind, Result},
+    TcpClient,
+};
+
+/// A response to an IMAP command, consisting of the untagged lines the server sent and the tagged line that completed the command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImapResponse {
+    untagged: Vec<String>,
+    /// The status of the completion, `OK`, `NO` or `BAD`.
+    status: String,
+    /// The text after the status of the completion.
+    text: String,
+}
+
+impl ImapResponse {
+    /// The untagged lines (starting with `*`) the server sent before completing the command, without their line endings.
+    pub fn untagged(&self) -> &Vec<String> {
+        &self.untagged
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status.eq_ignore_ascii_case("OK")
+    }
+
+    /// The capabilities the server listed in an untagged `CAPABILITY` response, in uppercase.
+    pub fn capabilities(&self) -> Vec<String> {
+        self.untagged
+            .iter()
+            .filter_map(|line| {
+                let mut words = line.split(' ');
+
+                match (words.next(), words.next()) {
+                    (Some("*"), Some(name)) if name.eq_ignore_ascii_case("CAPABILITY") => {
+                        Some(words.map(|capability| capability.to_ascii_uppercase()))
+                    }
+                    _ => None,
+                }
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+/// A reply from an SMTP server, which can span multiple lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtpReply {
+    code: u16,
+    /// The text of every line of the reply, without the code.
+    lines: Vec<String>,
+}
+
+impl SmtpReply {
+    pub fn code(&self) -> &u16 {
+        &self.code
+    }
+
+    pub fn lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    /// Whether the code indicates the command was completed successfully.
+    pub fn is_positive(&self) -> bool {
+        (200..400).contains(&self.code)
+    }
+}
+
+fn invalid_response<S: Into<String>>(message: S) -> Error {
+    Error::new(ErrorKind::InvalidResponse, message)
+}
+
+/// Strips the line ending of a line.
+fn trim_line(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+impl<S: AsyncWrite + AsyncRead + Unpin> TcpClient<S> {
+    /// Reads lines until the server completes the command with the given tag.
+    ///
+    /// Literals are not supported, so this should only be used for commands that return simple responses, like `CAPABILITY`.
+    pub async fn read_imap_response<T: AsRef<str>>(&mut self, tag: T) -> Result<ImapResponse> {
+        let tag = tag.as_ref();
+
+        let mut untagged = Vec::new();
+
+        loop {
+            let response = self.read_response().await?;
+
+            let line = trim_line(&response);
+
+            let completion = line
+                .strip_prefix(tag)
+                .and_then(|rest| rest.strip_prefix(' '));
+
+            match completion {
+                Some(completion) => {
+                    let (status, text) = completion.split_once(' ').unwrap_or((completion, ""));
+
+                    return Ok(ImapResponse {
+                        untagged,
+                        status: status.to_ascii_uppercase(),
+                        text: text.to_string(),
+                    });
+                }
+                // Continuation requests and other tags are not something we expect, but they are not ours to handle either.
+                None => untagged.push(line.to_string()),
+            }
+        }
+    }
+
+    /// Reads a complete SMTP reply, following the continuation lines (`250-...`) until the last line (`250 ...`).
+    pub async fn read_smtp_reply(&mut self) -> Result<SmtpReply> {
+        let mut code = None;
+
+        let mut lines = Vec::new();
+
+        loop {
+            let response = self.read_response().await?;
+
+            let line = trim_line(&response);
+
+            let line_code: u16 = line
+                .get(..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| {
+                    invalid_response(format!("Invalid SMTP reply from server: '{}'", line))
+                })?;
+
+            if *code.get_or_insert(line_code) != line_code {
+                return Err(invalid_response(
+                    "Server changed the reply code in the middle of a multi line reply",
+                ));
+            }
+
+            let is_last = !line[3..].starts_with('-');
+
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+
+            if is_last {
+                return Ok(SmtpReply {
+                    code: line_code,
+                    lines,
+                });
+            }
+        }
+    }
+
+    /// Reads the body of a multi line POP response until the terminating dot, removing the byte stuffing from the lines.
+    ///
+    /// The status line of the response should be read with `read_response` first.
+    pub async fn read_pop_multi_line(&mut self) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+
+        loop {
+            let response = self.read_response().await?;
+
+            let line = trim_line(&response);
+
+            if line == "." {
+                return Ok(lines);
+            }
+
+            let line = line.strip_prefix('.').unwrap_or(line);
+
+            lines.push(line.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use crate::{types::ErrorKind, Config, TcpClient};
+
+    /// Creates a client that reads the given data from the server.
+    async fn connect(data: &str) -> (TcpClient<DuplexStream>, DuplexStream) {
+        let (client, mut server) = duplex(4096);
+
+        server.write_all(data.as_bytes()).await.unwrap();
+
+        (crate::new(client, Config::default()), server)
+    }
+
+    #[tokio::test]
+    async fn imap() {
+        let (mut client, _server) = connect(
+            "* CAPABILITY IMAP4rev1 IDLE\r\n* CAPABILITY AUTH=PLAIN\r\n* OK not ours\r\nA0001 OK CAPABILITY completed\r\n",
+        )
+        .await;
+
+        let response = client.read_imap_response("A0001").await.unwrap();
+
+        assert!(response.is_ok());
+        assert_eq!(response.text(), "CAPABILITY completed");
+        assert_eq!(response.untagged().len(), 3);
+        assert_eq!(
+            response.capabilities(),
+            vec!["IMAP4REV1", "IDLE", "AUTH=PLAIN"]
+        );
+
+        let (mut client, _server) = connect("A00010 OK\r\nA0001 BAD unknown command\r\n").await;
+
+        let response = client.read_imap_response("A0001").await.unwrap();
+
+        assert!(!response.is_ok());
+        assert_eq!(response.status(), "BAD");
+        assert_eq!(response.untagged(), &vec![String::from("A00010 OK")]);
+    }
+
+    #[tokio::test]
+    async fn smtp() {
+        let (mut client, _server) = connect(
+            "220 mail.example.com ESMTP\r\n250-mail.example.com\r\n250-PIPELINING\r\n250 STARTTLS\r\n",
+        )
+        .await;
+
+        let greeting = client.read_smtp_reply().await.unwrap();
+
+        assert_eq!(greeting.code(), &220);
+        assert_eq!(
+            greeting.lines(),
+            &vec![String::from("mail.example.com ESMTP")]
+        );
+
+        let reply = client.read_smtp_reply().await.unwrap();
+
+        assert!(reply.is_positive());
+        assert_eq!(
+            reply.lines(),
+            &vec![
+                String::from("mail.example.com"),
+                String::from("PIPELINING"),
+                String::from("STARTTLS")
+            ]
+        );
+
+        let (mut client, _server) = connect("250-first\r\n354 second\r\n").await;
+
+        let error = client.read_smtp_reply().await.err().unwrap();
+
+        assert!(matches!(error.kind(), ErrorKind::InvalidResponse));
+
+        let (mut client, _server) = connect("* OK IMAP4rev1\r\n").await;
+
+        let error = client.read_smtp_reply().await.err().unwrap();
+
+        assert!(matches!(error.kind(), ErrorKind::InvalidResponse));
+    }
+
+    #[tokio::test]
+    async fn pop() {
+        let (mut client, _server) =
+            connect("+OK\r\nUSER\r\n..stuffed\r\nUIDL\r\n.\r\nnext\r\n").await;
+
+        assert_eq!(client.read_response().await.unwrap(), "+OK\r\n");
+
+        let lines = client.read_pop_multi_line().await.unwrap();
+
+        assert_eq!(lines, vec!["USER", ".stuffed", "UIDL"]);
+
+        assert_eq!(client.read_response().await.unwrap(), "next\r\n");
+    }
+
+    #[tokio::test]
+    async fn line_ending() {
+        let (mut client, mut server) = connect("").await;
+
+        client.send_bytes("A0001 CAPABILITY").await.unwrap();
+
+        let mut buf = [0; 18];
+
+        server.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, b"A0001 CAPABILITY\r\n");
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_native_tls::{Certificate, Protocol, TlsConnector, TlsStream};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+
+use crate::{
+    certificate::{presented_certificates, CertificateInfo},
+    config::Config,
+    dial::dial,
+    types::{Error, ErrorKind, Result},
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    Tls12,
+}
+
+impl From<TlsVersion> for Protocol {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls10 => Protocol::Tlsv10,
+            TlsVersion::Tls11 => Protocol::Tlsv11,
+            TlsVersion::Tls12 => Protocol::Tlsv12,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+/// Options that specify how a secure connection to a server should be set up and which certificates should be trusted.
+pub struct TlsConfig {
+    /// Extra root certificates in PEM format, for servers that use a private certificate authority.
+    root_certificates: Vec<String>,
+    /// SHA-256 fingerprints of server certificates that should be trusted even if they cannot be verified, e.g. self-signed certificates.
+    pinned_fingerprints: Vec<String>,
+    min_version: Option<TlsVersion>,
+    /// The server name that is sent to and verified against the server, instead of the host we connect to.
+    server_name: Option<String>,
+    /// Accept any certificate, which makes the connection vulnerable to man in the middle attacks.
+    insecure: bool,
+}
+
+impl TlsConfig {
+    /// Trust a root certificate in PEM format.
+    pub fn add_root_certificate<C: Into<String>>(&mut self, pem: C) -> &mut Self {
+        self.root_certificates.push(pem.into());
+
+        self
+    }
+
+    /// Trust a server certificate with the given SHA-256 fingerprint.
+    ///
+    /// When at least one fingerprint is pinned, only certificates with a pinned fingerprint are accepted.
+    pub fn add_pinned_fingerprint<F: AsRef<str>>(&mut self, fingerprint: F) -> &mut Self {
+        self.pinned_fingerprints
+            .push(normalize_fingerprint(fingerprint.as_ref()));
+
+        self
+    }
+
+    pub fn set_min_version(&mut self, version: Option<TlsVersion>) -> &mut Self {
+        self.min_version = version;
+
+        self
+    }
+
+    pub fn set_server_name<S: Into<String>>(&mut self, server_name: Option<S>) -> &mut Self {
+        self.server_name = server_name.map(|server_name| server_name.into());
+
+        self
+    }
+
+    /// Accept any certificate the server presents. Only use this if you know what you are doing.
+    pub fn set_insecure(&mut self, insecure: bool) -> &mut Self {
+        self.insecure = insecure;
+
+        self
+    }
+
+    pub fn root_certificates(&self) -> &Vec<String> {
+        &self.root_certificates
+    }
+
+    pub fn pinned_fingerprints(&self) -> &Vec<String> {
+        &self.pinned_fingerprints
+    }
+
+    pub fn min_version(&self) -> Option<&TlsVersion> {
+        self.min_version.as_ref()
+    }
+
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    pub fn insecure(&self) -> &bool {
+        &self.insecure
+    }
+
+    /// Creates a tls connector that follows this config.
+    ///
+    /// Pinned fingerprints are not checked by the connector itself, use `secure` to also verify those.
+    pub fn connector(&self) -> Result<TlsConnector> {
+        let mut connector = TlsConnector::new();
+
+        for pem in &self.root_certificates {
+            let certificate = Certificate::from_pem(pem.as_bytes())?;
+
+            connector = connector.add_root_certificate(certificate);
+        }
+
+        if let Some(version) = self.min_version {
+            connector = connector.min_protocol_version(Some(version.into()));
+        }
+
+        // A pinned certificate is trusted because of its fingerprint, so it does not have to be signed by a trusted authority.
+        if self.insecure || !self.pinned_fingerprints.is_empty() {
+            connector = connector
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+
+        Ok(connector)
+    }
+
+    /// Upgrades a given stream to a secure connection with a server at a given host.
+    pub async fn secure<S: AsyncRead + AsyncWrite + Unpin, H: AsRef<str>>(
+        &self,
+        host: H,
+        stream: S,
+    ) -> Result<TlsStream<S>> {
+        let connector = self.connector()?;
+
+        let server_name = self.server_name().unwrap_or(host.as_ref());
+
+        let stream = connector.connect(server_name, stream).await?;
+
+        self.verify_pinned(&stream)?;
+
+        Ok(stream)
+    }
+
+    /// Checks whether the certificate of the server matches one of the pinned fingerprints, if there are any.
+    fn verify_pinned<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: &TlsStream<S>,
+    ) -> Result<()> {
+        if self.insecure || self.pinned_fingerprints.is_empty() {
+            return Ok(());
+        }
+
+        let certificate = match stream.peer_certificate()? {
+            Some(certificate) => CertificateInfo::from_certificate(&certificate)?,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::CertificateMismatch,
+                    "The server did not present a certificate",
+                ))
+            }
+        };
+
+        let is_pinned = self
+            .pinned_fingerprints
+            .iter()
+            .any(|pinned| normalize_fingerprint(pinned) == certificate.fingerprint());
+
+        if is_pinned {
+            Ok(())
+        } else {
+            Err(untrusted_certificate_error(vec![certificate]))
+        }
+    }
+}
+
+/// Connects to a server at a given host and port and secures the connection, following the given config.
+///
+/// If the server presents a certificate that is not trusted, an `UntrustedCertificate` error is returned containing the details of the certificates it presented,
+/// so they can be shown to the user and the certificate of the server can be pinned if they decide to trust it.
+pub async fn dial_tls<H: AsRef<str>>(
+    host: H,
+    port: u16,
+    config: &Config,
+) -> Result<TlsStream<TcpStream>> {
+    let tls = config.tls();
+
+    let tcp = dial(host.as_ref(), port, config).await?;
+
+    match tls.secure(host.as_ref(), tcp).await {
+        Err(error)
+            if !tls.insecure
+                && matches!(
+                    error.kind(),
+                    ErrorKind::Tls(_) | ErrorKind::UntrustedCertificate(_)
+                ) =>
+        {
+            // If we can connect without verifying the certificate, the certificate is the reason the handshake failed.
+            match inspect(host.as_ref(), port, config).await {
+                Ok(certificates) => Err(untrusted_certificate_error(certificates)),
+                Err(_) => Err(error),
+            }
+        }
+        result => result,
+    }
+}
+
+/// A stream that keeps a copy of everything that is read from it.
+struct RecordingStream<S> {
+    stream: S,
+    received: Vec<u8>,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for RecordingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        let filled = buf.filled().len();
+
+        let poll = Pin::new(&mut this.stream).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = poll {
+            this.received.extend_from_slice(&buf.filled()[filled..]);
+        }
+
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for RecordingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// Fetches the certificates a server presents without verifying them, starting with its own certificate.
+async fn inspect(host: &str, port: u16, config: &Config) -> Result<Vec<CertificateInfo>> {
+    let mut tls = config.tls().clone();
+
+    tls.pinned_fingerprints.clear();
+    tls.insecure = true;
+
+    // Servers that only support TLS 1.3 don't let us read the certificates they sent, but we still know their own certificate.
+    match inspect_chain(host, port, config, &tls).await {
+        Ok(certificates) if !certificates.is_empty() => return Ok(certificates),
+        _ => {}
+    }
+
+    let tcp = dial(host, port, config).await?;
+
+    let stream = tls.secure(host, tcp).await?;
+
+    match stream.peer_certificate()? {
+        Some(certificate) => Ok(vec![CertificateInfo::from_certificate(&certificate)?]),
+        None => Err(Error::new(
+            ErrorKind::CertificateMismatch,
+            "The server did not present a certificate",
+        )),
+    }
+}
+
+/// Fetches the whole chain of certificates a server presents, by reading them from the handshake.
+async fn inspect_chain(
+    host: &str,
+    port: u16,
+    config: &Config,
+    tls: &TlsConfig,
+) -> Result<Vec<CertificateInfo>> {
+    // TLS 1.3 encrypts the certificates, so we ask for an older version.
+    let connector = tls
+        .connector()?
+        .max_protocol_version(Some(Protocol::Tlsv12));
+
+    let tcp = dial(host, port, config).await?;
+
+    let stream = RecordingStream {
+        stream: tcp,
+        received: Vec::new(),
+    };
+
+    let server_name = tls.server_name().unwrap_or(host);
+
+    let stream = connector.connect(server_name, stream).await?;
+
+    presented_certificates(&stream.get_ref().received)
+        .unwrap_or_default()
+        .iter()
+        .map(|der| CertificateInfo::from_der(der))
+        .collect()
+}
+
+fn untrusted_certificate_error(certificates: Vec<CertificateInfo>) -> Error {
+    let message = match certificates.first() {
+        Some(certificate) => format!(
+            "The certificate of the server ({}, issued by {}) is not trusted",
+            certificate.fingerprint(),
+            certificate.issuer()
+        ),
+        None => String::from("The certificate of the server is not trusted"),
+    };
+
+    Error::new(ErrorKind::UntrustedCertificate(certificates), message)
+}
+
+/// Fingerprints are often displayed as uppercase hex separated by colons, so we strip those to be able to compare them.
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|char| char.is_ascii_hexdigit())
+        .map(|char| char.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use async_native_tls::{Identity, TlsAcceptor};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use crate::{types::ErrorKind, Config};
+
+    use super::{dial_tls, normalize_fingerprint, TlsConfig, TlsVersion};
+
+    const CERTIFICATE: &str = include_str!("test_data/localhost.crt");
+    const CHAIN: &str = include_str!("test_data/localhost-chain.crt");
+    const KEY: &str = include_str!("test_data/localhost.key");
+
+    /// Starts a server that presents the given certificates and accepts any number of connections.
+    async fn listen(certificates: &str) -> u16 {
+        let identity = Identity::from_pkcs8(certificates.as_bytes(), KEY.as_bytes()).unwrap();
+
+        let acceptor = TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = acceptor.accept(stream).await;
+            }
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn trust_on_first_use() {
+        let port = listen(CERTIFICATE).await;
+
+        let mut config = Config::default();
+
+        let error = dial_tls("localhost", port, &config).await.err().unwrap();
+
+        let fingerprint = match error.kind() {
+            ErrorKind::UntrustedCertificate(certificates) => {
+                assert_eq!(certificates.len(), 1);
+                assert_eq!(certificates[0].subject(), "CN=localhost, O=Dust-Mail");
+
+                certificates[0].fingerprint().to_string()
+            }
+            _ => unreachable!(),
+        };
+
+        let mut tls = TlsConfig::default();
+
+        tls.add_pinned_fingerprint(&fingerprint);
+
+        config.set_tls(tls);
+
+        dial_tls("localhost", port, &config).await.unwrap();
+
+        let mut tls = TlsConfig::default();
+
+        tls.add_pinned_fingerprint("00:11:22:33");
+
+        config.set_tls(tls);
+
+        let error = dial_tls("localhost", port, &config).await.err().unwrap();
+
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::UntrustedCertificate(certificates) if certificates[0].fingerprint() == fingerprint
+        ));
+    }
+
+    #[tokio::test]
+    async fn presented_chain() {
+        let port = listen(CHAIN).await;
+
+        let error = dial_tls("localhost", port, &Config::default())
+            .await
+            .err()
+            .unwrap();
+
+        let certificates = match error.kind() {
+            ErrorKind::UntrustedCertificate(certificates) => certificates,
+            _ => unreachable!(),
+        };
+
+        let subjects: Vec<&str> = certificates
+            .iter()
+            .map(|certificate| certificate.subject())
+            .collect();
+
+        assert_eq!(
+            subjects,
+            vec![
+                "CN=localhost, O=Dust-Mail",
+                "CN=Dust-Mail Test CA, O=Dust-Mail"
+            ]
+        );
+        assert_eq!(certificates[0].issuer(), certificates[1].subject());
+    }
+
+    #[tokio::test]
+    async fn starttls() {
+        let identity = Identity::from_pkcs8(CERTIFICATE.as_bytes(), KEY.as_bytes()).unwrap();
+
+        let acceptor = TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+
+            let mut stream = BufReader::new(stream);
+
+            stream.write_all(b"220 ready\r\n").await.unwrap();
+
+            let mut command = String::new();
+
+            stream.read_line(&mut command).await.unwrap();
+
+            assert_eq!(command, "STARTTLS\r\n");
+
+            // This line should be discarded by the client, as it was sent before the upgrade.
+            stream
+                .write_all(b"220 go ahead\r\n250 injected\r\n")
+                .await
+                .unwrap();
+
+            let mut stream = acceptor.accept(stream.into_inner()).await.unwrap();
+
+            stream.write_all(b"250 secure\r\n").await.unwrap();
+        });
+
+        let mut tls = TlsConfig::default();
+
+        tls.add_root_certificate(CERTIFICATE);
+
+        let mut config = Config::default();
+
+        config.set_tls(tls);
+
+        let mut client = crate::connect_plain("localhost", port, Some(config))
+            .await
+            .unwrap();
+
+        assert_eq!(client.read_response().await.unwrap(), "220 ready\r\n");
+
+        assert_eq!(
+            client.send_command("STARTTLS").await.unwrap(),
+            "220 go ahead\r\n"
+        );
+
+        let mut client = client.starttls("localhost").await.unwrap();
+
+        assert_eq!(client.read_response().await.unwrap(), "250 secure\r\n");
+    }
+
+    #[test]
+    fn fingerprint_format() {
+        assert_eq!(
+            normalize_fingerprint("AB:cd:0F:12"),
+            normalize_fingerprint("abcd0f12")
+        );
+
+        let mut config = TlsConfig::default();
+
+        config.add_pinned_fingerprint("AB:CD");
+
+        assert_eq!(config.pinned_fingerprints(), &vec![String::from("abcd")]);
+    }
+
+    #[test]
+    fn deserialize() {
+        let config: TlsConfig = serde_json::from_str(
+            r#"{"pinnedFingerprints": ["abcd"], "minVersion": "tls12", "insecure": false}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.min_version(), Some(&TlsVersion::Tls12));
+        assert_eq!(config.pinned_fingerprints(), &vec![String::from("abcd")]);
+        assert!(config.root_certificates().is_empty());
+
+        let config: TlsConfig = serde_json::from_str("{}").unwrap();
+
+        assert_eq!(config, TlsConfig::default());
+    }
+}
@@ -0,0 +1,570 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug, Write},
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The maximum amount of lines a transcript keeps, the oldest lines are dropped once it is full.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Lines longer than this are cut off in the middle, so a single huge response can not fill up the memory.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// The amount of bytes at the end of a line that are kept when it is cut off, as that is where an IMAP literal is announced.
+const LINE_TAIL_LENGTH: usize = 64;
+
+const TRUNCATED: &[u8] = b"...";
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// A single line that was sent to or received from a server.
+pub struct TranscriptEntry {
+    /// Unix timestamp (in milliseconds) of the moment the line was completed.
+    timestamp: u128,
+    direction: Direction,
+    line: String,
+}
+
+impl TranscriptEntry {
+    pub fn timestamp(&self) -> &u128 {
+        &self.timestamp
+    }
+
+    pub fn direction(&self) -> &Direction {
+        &self.direction
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    entries: VecDeque<TranscriptEntry>,
+    /// The incomplete line we are currently sending.
+    sent: Vec<u8>,
+    /// The incomplete line we are currently receiving.
+    received: Vec<u8>,
+    /// The amount of bytes left in a literal we are sending, which are not recorded.
+    sent_literal: usize,
+    /// The amount of bytes left in a literal we are receiving, which are not recorded.
+    received_literal: usize,
+    /// Whether the client is in the middle of a SASL exchange, in which every line it sends contains credentials.
+    authenticating: bool,
+    /// Whether the next positive response from the server is followed by a message body.
+    expecting_body: bool,
+    /// The amount of lines of the message body we are receiving, if we are receiving one.
+    body_lines: Option<usize>,
+}
+
+impl Recorder {
+    fn push(&mut self, direction: Direction, line: String) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+
+        self.entries.push_back(TranscriptEntry {
+            timestamp,
+            direction,
+            line,
+        });
+    }
+
+    fn record_sent(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.sent_literal > 0 {
+                let skipped = self.sent_literal.min(bytes.len());
+
+                self.sent_literal -= skipped;
+                bytes = &bytes[skipped..];
+
+                continue;
+            }
+
+            if let Some(line) = take_line(&mut self.sent, &mut bytes) {
+                self.sent_line(line);
+            }
+        }
+    }
+
+    fn record_received(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.received_literal > 0 {
+                let skipped = self.received_literal.min(bytes.len());
+
+                self.received_literal -= skipped;
+                bytes = &bytes[skipped..];
+
+                continue;
+            }
+
+            if let Some(line) = take_line(&mut self.received, &mut bytes) {
+                self.received_line(line);
+            }
+        }
+    }
+
+    fn sent_line(&mut self, line: String) {
+        if self.authenticating {
+            self.push(Direction::Sent, REDACTED.to_string());
+
+            return;
+        }
+
+        if let Some(size) = literal_size(&line) {
+            self.sent_literal = size;
+        }
+
+        let line = match redact_command(&line) {
+            Some((redacted, command)) => {
+                match command.as_str() {
+                    "AUTHENTICATE" | "AUTH" => self.authenticating = true,
+                    "RETR" | "TOP" => self.expecting_body = true,
+                    _ => {}
+                };
+
+                redacted
+            }
+            None => line,
+        };
+
+        self.push(Direction::Sent, line);
+    }
+
+    fn received_line(&mut self, line: String) {
+        if let Some(lines) = self.body_lines.as_mut() {
+            if line == "." {
+                let lines = *lines;
+
+                self.body_lines = None;
+
+                self.push(Direction::Received, format!("<{} lines redacted>", lines));
+                self.push(Direction::Received, line);
+            } else {
+                *lines += 1;
+            }
+
+            return;
+        }
+
+        // A continuation request is the only response that keeps a SASL exchange going.
+        if self.authenticating && !is_continuation(&line) {
+            self.authenticating = false;
+        }
+
+        if self.expecting_body {
+            self.expecting_body = false;
+
+            if line.starts_with("+OK") {
+                self.body_lines = Some(0);
+            }
+        }
+
+        let line = match literal_size(&line) {
+            Some(size) => {
+                self.received_literal = size;
+
+                format!("{} <{} bytes redacted>", line, size)
+            }
+            None => line,
+        };
+
+        self.push(Direction::Received, line);
+    }
+}
+
+/// Moves bytes into the buffer until it contains a full line, which is then returned without its line ending.
+fn take_line(buffer: &mut Vec<u8>, bytes: &mut &[u8]) -> Option<String> {
+    let (taken, complete) = match bytes.iter().position(|byte| *byte == b'\n') {
+        Some(index) => (index + 1, true),
+        None => (bytes.len(), false),
+    };
+
+    buffer.extend_from_slice(&bytes[..taken]);
+
+    *bytes = &bytes[taken..];
+
+    if buffer.len() > MAX_LINE_LENGTH {
+        let cut = MAX_LINE_LENGTH - LINE_TAIL_LENGTH;
+        let tail = buffer.len() - LINE_TAIL_LENGTH;
+
+        buffer.splice(cut..tail, TRUNCATED.iter().copied());
+    }
+
+    if !complete {
+        return None;
+    }
+
+    let line = String::from_utf8_lossy(buffer)
+        .trim_end_matches(['\r', '\n'])
+        .to_string();
+
+    buffer.clear();
+
+    Some(line)
+}
+
+/// Whether a line is a SASL continuation request, `+` for IMAP and Pop or `334` for SMTP.
+fn is_continuation(line: &str) -> bool {
+    ["+", "334"]
+        .iter()
+        .any(|prefix| line == *prefix || line.starts_with(&format!("{} ", prefix)))
+}
+
+/// The size of the IMAP literal (`{10}` or `{10+}`) that follows a given line, if there is one.
+fn literal_size(line: &str) -> Option<usize> {
+    let literal = line.strip_suffix('}')?;
+
+    let start = literal.rfind('{')?;
+
+    literal[start + 1..].trim_end_matches('+').parse().ok()
+}
+
+/// Redacts the credentials from a command, returning the redacted command and the name of the command if it was recognized.
+///
+/// IMAP commands are prefixed with a tag, so the command can be either the first or the second word of the line.
+fn redact_command(line: &str) -> Option<(String, String)> {
+    let words: Vec<&str> = line.split(' ').collect();
+
+    for index in 0..words.len().min(2) {
+        let command = words[index].to_ascii_uppercase();
+
+        let kept = match command.as_str() {
+            "PASS" | "LOGIN" | "APOP" => index + 1,
+            // The mechanism is useful for debugging, but the initial response is not.
+            "AUTHENTICATE" | "AUTH" => index + 2,
+            "RETR" | "TOP" => return Some((line.to_string(), command)),
+            _ => continue,
+        };
+
+        let mut redacted = words[..kept.min(words.len())].join(" ");
+
+        if words.len() > kept {
+            redacted.push(' ');
+            redacted.push_str(REDACTED);
+        }
+
+        return Some((redacted, command));
+    }
+
+    None
+}
+
+#[derive(Clone, Default)]
+/// A record of the lines that were sent to and received from a server, with credentials and message contents redacted,
+/// which can be attached to bug reports.
+///
+/// Cloning a transcript gives another handle to the same record.
+pub struct Transcript {
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn recorder(&self) -> std::sync::MutexGuard<'_, Recorder> {
+        // A panic while recording does not leave the recorder in an unusable state, so we can ignore the poison.
+        self.recorder
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn record_sent(&self, bytes: &[u8]) {
+        self.recorder().record_sent(bytes)
+    }
+
+    pub fn record_received(&self, bytes: &[u8]) {
+        self.recorder().record_received(bytes)
+    }
+
+    pub fn entries(&self) -> Vec<TranscriptEntry> {
+        self.recorder().entries.iter().cloned().collect()
+    }
+
+    /// Exports the transcript as plain text, one line per entry, prefixed with the timestamp and `C:` or `S:` for lines sent by the client or server.
+    pub fn export(&self) -> String {
+        let mut export = String::new();
+
+        for entry in self.recorder().entries.iter() {
+            let prefix = match entry.direction {
+                Direction::Sent => "C",
+                Direction::Received => "S",
+            };
+
+            let _ = writeln!(export, "[{}] {}: {}", entry.timestamp, prefix, entry.line);
+        }
+
+        export
+    }
+}
+
+impl Debug for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transcript")
+            .field("entries", &self.recorder().entries.len())
+            .finish()
+    }
+}
+
+/// A stream that records everything that is written to and read from it in a transcript, if one is given.
+#[derive(Debug)]
+pub struct TranscriptStream<S> {
+    inner: S,
+    transcript: Option<Transcript>,
+}
+
+impl<S> TranscriptStream<S> {
+    pub fn new(inner: S, transcript: Option<Transcript>) -> Self {
+        Self { inner, transcript }
+    }
+
+    pub fn set_transcript(&mut self, transcript: Option<Transcript>) -> &mut Self {
+        self.transcript = transcript;
+
+        self
+    }
+
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TranscriptStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        let filled = buf.filled().len();
+
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+
+        if let (Poll::Ready(Ok(())), Some(transcript)) = (&result, &this.transcript) {
+            transcript.record_received(&buf.filled()[filled..]);
+        }
+
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TranscriptStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+
+        if let (Poll::Ready(Ok(written)), Some(transcript)) = (&result, &this.transcript) {
+            transcript.record_sent(&buf[..*written]);
+        }
+
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::{Direction, Transcript, TranscriptStream};
+
+    fn lines(transcript: &Transcript) -> Vec<(Direction, String)> {
+        transcript
+            .entries()
+            .into_iter()
+            .map(|entry| (*entry.direction(), entry.line().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn redact_imap() {
+        let transcript = Transcript::new();
+
+        transcript.record_received(b"* OK IMAP4rev1 ready\r\n");
+        transcript.record_sent(b"A1 LOGIN user@example.com hunter2\r\n");
+        transcript.record_received(b"A1 OK LOGIN completed\r\n");
+        transcript.record_sent(b"A2 AUTHENTICATE PLAIN\r\n");
+        transcript.record_received(b"+ \r\n");
+        transcript.record_sent(b"AHVzZXIAaHVudGVyMg==\r\n");
+        transcript.record_received(b"A2 OK\r\n");
+        transcript.record_sent(b"A3 FETCH 1 BODY[]\r\n");
+        // The literal is split across reads, like it would be on a real connection.
+        transcript.record_received(b"* 1 FETCH (BODY[] {11}\r\nHello");
+        transcript.record_received(b" world)\r\nA3 OK\r\n");
+
+        assert_eq!(
+            lines(&transcript),
+            vec![
+                (Direction::Received, "* OK IMAP4rev1 ready".into()),
+                (Direction::Sent, "A1 LOGIN <redacted>".into()),
+                (Direction::Received, "A1 OK LOGIN completed".into()),
+                (Direction::Sent, "A2 AUTHENTICATE PLAIN".into()),
+                (Direction::Received, "+ ".into()),
+                (Direction::Sent, "<redacted>".into()),
+                (Direction::Received, "A2 OK".into()),
+                (Direction::Sent, "A3 FETCH 1 BODY[]".into()),
+                (
+                    Direction::Received,
+                    "* 1 FETCH (BODY[] {11} <11 bytes redacted>".into()
+                ),
+                (Direction::Received, ")".into()),
+                (Direction::Received, "A3 OK".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn redact_long_literal() {
+        let transcript = Transcript::new();
+
+        let flags = "\\Flagged ".repeat(1000);
+
+        transcript.record_sent(b"A1 FETCH 1 (FLAGS BODY[])\r\n");
+        // The response line is longer than the maximum line length and arrives in multiple reads.
+        transcript.record_received(b"* 1 FETCH (FLAGS (");
+        transcript.record_received(flags.as_bytes());
+        transcript.record_received(b") BODY[] {22}\r\nSubject: Hi\r\n\r\nHello\r\n)\r\n");
+        transcript.record_received(b"A1 OK\r\n");
+
+        let lines = lines(&transcript);
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].1.len() < flags.len());
+        assert!(lines[1].1.ends_with(") BODY[] {22} <22 bytes redacted>"));
+        assert_eq!(lines[2], (Direction::Received, ")".into()));
+        assert_eq!(lines[3], (Direction::Received, "A1 OK".into()));
+    }
+
+    #[test]
+    fn redact_pop() {
+        let transcript = Transcript::new();
+
+        transcript.record_sent(b"USER user@example.com\r\n");
+        transcript.record_received(b"+OK\r\n");
+        transcript.record_sent(b"PASS hunter2\r\n");
+        transcript.record_received(b"+OK\r\n");
+        transcript.record_sent(b"RETR 1\r\n");
+        transcript.record_received(b"+OK 12 octets\r\nSubject: Hi\r\n\r\n..Hello\r\n.\r\n");
+        transcript.record_sent(b"QUIT\r\n");
+
+        assert_eq!(
+            lines(&transcript),
+            vec![
+                (Direction::Sent, "USER user@example.com".into()),
+                (Direction::Received, "+OK".into()),
+                (Direction::Sent, "PASS <redacted>".into()),
+                (Direction::Received, "+OK".into()),
+                (Direction::Sent, "RETR 1".into()),
+                (Direction::Received, "+OK 12 octets".into()),
+                (Direction::Received, "<3 lines redacted>".into()),
+                (Direction::Received, ".".into()),
+                (Direction::Sent, "QUIT".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn redact_smtp() {
+        let transcript = Transcript::new();
+
+        transcript.record_received(b"250 AUTH LOGIN\r\n");
+        transcript.record_sent(b"AUTH LOGIN\r\n");
+        transcript.record_received(b"334 VXNlcm5hbWU6\r\n");
+        transcript.record_sent(b"dXNlckBleGFtcGxlLmNvbQ==\r\n");
+        transcript.record_received(b"334 UGFzc3dvcmQ6\r\n");
+        transcript.record_sent(b"aHVudGVyMg==\r\n");
+        transcript.record_received(b"235 2.7.0 Authentication successful\r\n");
+        transcript.record_sent(b"MAIL FROM:<user@example.com>\r\n");
+
+        assert_eq!(
+            lines(&transcript),
+            vec![
+                (Direction::Received, "250 AUTH LOGIN".into()),
+                (Direction::Sent, "AUTH LOGIN".into()),
+                (Direction::Received, "334 VXNlcm5hbWU6".into()),
+                (Direction::Sent, "<redacted>".into()),
+                (Direction::Received, "334 UGFzc3dvcmQ6".into()),
+                (Direction::Sent, "<redacted>".into()),
+                (
+                    Direction::Received,
+                    "235 2.7.0 Authentication successful".into()
+                ),
+                (Direction::Sent, "MAIL FROM:<user@example.com>".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        let (client, mut server) = duplex(1024);
+
+        let transcript = Transcript::new();
+
+        let mut stream = TranscriptStream::new(client, Some(transcript.clone()));
+
+        stream.write_all(b"a1 login user pass\r\n").await.unwrap();
+
+        server.write_all(b"a1 OK\r\n").await.unwrap();
+
+        let mut buf = [0; 7];
+
+        stream.read_exact(&mut buf).await.unwrap();
+
+        let export = transcript.export();
+
+        let lines: Vec<&str> = export.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] C: a1 login <redacted>"));
+        assert!(lines[1].ends_with("] S: a1 OK"));
+    }
+}
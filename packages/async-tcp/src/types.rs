@@ -4,6 +4,8 @@ use tokio::{io::Error as IoError, time::error::Elapsed as TimeoutError};
 
 use async_native_tls::Error as TlsError;
 
+use crate::certificate::CertificateInfo;
+
 #[derive(Debug)]
 pub struct Error {
     message: String,
@@ -78,6 +80,18 @@ pub enum ErrorKind {
     Tls(TlsError),
     ParseString(ParseStringError),
     EmptyResponse,
+    /// The server sent a response that does not follow the protocol we expected.
+    InvalidResponse,
+    /// The server did not present a certificate that could be pinned.
+    CertificateMismatch,
+    /// The certificate of the server could not be verified and is not pinned.
+    ///
+    /// Contains the certificates the server presented, starting with its own certificate.
+    UntrustedCertificate(Vec<CertificateInfo>),
+    /// Failed to parse a certificate.
+    InvalidCertificate,
+    /// The proxy server refused or failed to set up a connection to the remote server.
+    Proxy,
 }
 
 pub type Result<T> = result::Result<T, Error>;
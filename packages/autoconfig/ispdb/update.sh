@@ -0,0 +1,42 @@
+#!/bin/sh
+# Regenerates snapshot.xml.gz from Thunderbird's ISPDB.
+#
+# By default the whole ISPDB is bundled, taken from the repository that hosts it. Pass a file with one provider per line,
+# like providers.txt, to only bundle those providers, fetched from the autoconfig server instead.
+set -eu
+
+if [ $# -gt 0 ]; then
+	providers="$(cd "$(dirname "$1")" && pwd)/$(basename "$1")"
+fi
+
+cd "$(dirname "$0")"
+
+ISPDB_ARCHIVE="${ISPDB_ARCHIVE:-https://github.com/thunderbird/autoconfig/archive/refs/heads/master.tar.gz}"
+ISPDB_URL="${ISPDB_URL:-https://autoconfig.thunderbird.net/v1.1}"
+
+output=$(mktemp)
+workdir=$(mktemp -d)
+trap 'rm -rf "$output" "$workdir"' EXIT
+
+if [ $# -eq 0 ]; then
+	curl --fail --silent --show-error --location "$ISPDB_ARCHIVE" | tar -xz -C "$workdir"
+
+	# Sorted, so the same archive always results in the same snapshot.
+	find "$workdir" -path '*/ispdb/*' -type f | LC_ALL=C sort | while read -r file; do
+		cat "$file" >>"$output"
+		echo >>"$output"
+	done
+else
+	grep -v -e '^#' -e '^[[:space:]]*$' "$providers" | while read -r provider; do
+		curl --fail --silent --show-error "$ISPDB_URL/$provider" >>"$output"
+		echo >>"$output"
+	done
+fi
+
+if [ ! -s "$output" ]; then
+	echo "No configs were found, the snapshot was not changed" >&2
+	exit 1
+fi
+
+# Leave out the name and timestamp, so the snapshot only changes when its contents do.
+gzip -9 -n -c "$output" >snapshot.xml.gz
@@ -0,0 +1,629 @@
+//! A client for Microsoft's POX Autodiscover protocol, which is used by Exchange and Office 365 instead of Thunderbird's autoconfig.
+//!
+//! See https://learn.microsoft.com/en-us/exchange/client-developer/exchange-web-services/autodiscover-for-exchange
+use dust_mail_utils::parse_addr_spec;
+use futures::future::join_all;
+use serde::Deserialize;
+
+use crate::{
+    dns::{sort_srv_records, Resolver},
+    http::Client,
+    types::{
+        config::{
+            AuthenticationType, Config, EmailProvider, EmailProviderProperty, SecurityType, Server,
+            ServerProperty, ServerType, Source, Transport,
+        },
+        Error, ErrorKind, Result,
+    },
+    utils::escape,
+    DowngradePolicy,
+};
+
+const REQUEST_SCHEMA: &str =
+    "http://schemas.microsoft.com/exchange/autodiscover/outlook/requestschema/2006";
+const RESPONSE_SCHEMA: &str =
+    "http://schemas.microsoft.com/exchange/autodiscover/outlook/responseschema/2006a";
+
+/// How many times a server may redirect us to another address or url before we give up, to prevent redirect loops.
+const MAX_REDIRECTS: usize = 10;
+
+const AUTODISCOVER_PATH: &str = "autodiscover/autodiscover.xml";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Autodiscover {
+    response: Response,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Response {
+    user: Option<User>,
+    account: Option<Account>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct User {
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResponseError {
+    error_code: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Account {
+    action: Option<String>,
+    redirect_addr: Option<String>,
+    redirect_url: Option<String>,
+    #[serde(default)]
+    protocol: Vec<Protocol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Protocol {
+    r#type: String,
+    server: Option<String>,
+    port: Option<u16>,
+    login_name: Option<String>,
+    #[serde(rename = "SSL")]
+    ssl: Option<String>,
+    encryption: Option<String>,
+    #[serde(rename = "SPA")]
+    spa: Option<String>,
+    ews_url: Option<String>,
+    #[serde(rename = "OWAUrl")]
+    owa_url: Option<String>,
+}
+
+/// What an Autodiscover server told us to do.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Settings(Config),
+    /// Start over with another email address.
+    RedirectAddr(String),
+    /// Post the same request to another url.
+    RedirectUrl(String),
+}
+
+fn create_request(email_address: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<Autodiscover xmlns="{}">
+  <Request>
+    <EMailAddress>{}</EMailAddress>
+    <AcceptableResponseSchema>{}</AcceptableResponseSchema>
+  </Request>
+</Autodiscover>"#,
+        REQUEST_SCHEMA,
+        escape(email_address),
+        RESPONSE_SCHEMA
+    )
+}
+
+/// The domain of an email address in the ASCII form, so it can be used in urls and DNS queries.
+fn domain_of(email_address: &str) -> Result<String> {
+    let address = parse_addr_spec(email_address)?;
+
+    Ok(address.ascii_domain().to_string())
+}
+
+fn invalid_response<S: Into<String>>(message: S) -> Error {
+    Error::new(ErrorKind::InvalidResponse, message)
+}
+
+/// Parses an Autodiscover response for a given domain.
+fn parse_response(domain: &str, response: &str) -> Result<Outcome> {
+    let autodiscover: Autodiscover = serde_xml_rs::from_str(response)
+        .map_err(|error| Error::new(ErrorKind::Parse, error.to_string()))?;
+
+    let response = autodiscover.response;
+
+    if let Some(error) = response.error {
+        return Err(invalid_response(format!(
+            "Autodiscover server returned error {}: {}",
+            error.error_code.unwrap_or_default(),
+            error.message.unwrap_or_default()
+        )));
+    }
+
+    let account = response
+        .account
+        .ok_or_else(|| invalid_response("Autodiscover response does not contain an account"))?;
+
+    match account.action.as_deref() {
+        Some("redirectAddr") => account
+            .redirect_addr
+            .map(Outcome::RedirectAddr)
+            .ok_or_else(|| invalid_response("Autodiscover redirect is missing an address")),
+        Some("redirectUrl") => account
+            .redirect_url
+            .map(Outcome::RedirectUrl)
+            .ok_or_else(|| invalid_response("Autodiscover redirect is missing a url")),
+        _ => {
+            let display_name = response.user.and_then(|user| user.display_name);
+
+            Ok(Outcome::Settings(map_to_config(
+                domain,
+                display_name,
+                account.protocol,
+            )))
+        }
+    }
+}
+
+/// Maps the protocols from an Autodiscover response to the same structure an autoconfig file has.
+fn map_to_config(domain: &str, display_name: Option<String>, protocols: Vec<Protocol>) -> Config {
+    let mut properties = vec![EmailProviderProperty::Domain(domain.to_string())];
+
+    if let Some(display_name) = display_name {
+        properties.push(EmailProviderProperty::DisplayName(display_name));
+    }
+
+    for protocol in protocols {
+        let server_type = match protocol.r#type.to_ascii_uppercase().as_str() {
+            "IMAP" => ServerType::Imap,
+            "POP3" => ServerType::Pop3,
+            "SMTP" => ServerType::Smtp,
+            // Exchange over RPC, either directly (EXCH) or over http (EXPR).
+            "EXCH" | "EXPR" => ServerType::Exchange,
+            _ => continue,
+        };
+
+        let hostname = match protocol.server {
+            Some(server) => server,
+            None => continue,
+        };
+
+        let mut server_properties = vec![ServerProperty::Hostname(hostname)];
+
+        let is_exchange = server_type == ServerType::Exchange;
+
+        // Exchange is only reachable over https.
+        let port = if is_exchange {
+            Some(443)
+        } else {
+            protocol.port
+        };
+
+        if let Some(port) = port {
+            server_properties.push(ServerProperty::Port(port));
+        }
+
+        let ssl = !matches!(protocol.ssl.as_deref(), Some(ssl) if ssl.eq_ignore_ascii_case("off"));
+
+        // The encryption element overrides the ssl element: SSL means a secure connection from the start, TLS means STARTTLS.
+        let security = match protocol.encryption.as_deref() {
+            _ if is_exchange => SecurityType::Tls,
+            Some(encryption) if encryption.eq_ignore_ascii_case("none") => SecurityType::Plain,
+            Some(encryption) if encryption.eq_ignore_ascii_case("tls") => SecurityType::Starttls,
+            Some(encryption) if encryption.eq_ignore_ascii_case("ssl") => SecurityType::Tls,
+            _ if ssl => SecurityType::Tls,
+            _ => SecurityType::Plain,
+        };
+
+        server_properties.push(ServerProperty::SocketType(security));
+
+        if let Some(login_name) = protocol.login_name {
+            server_properties.push(ServerProperty::Username(login_name));
+        }
+
+        if !is_exchange {
+            let spa =
+                matches!(protocol.spa.as_deref(), Some(spa) if spa.eq_ignore_ascii_case("on"));
+
+            server_properties.push(ServerProperty::Authentication(if spa {
+                AuthenticationType::PasswordEncrypted
+            } else {
+                AuthenticationType::PasswordCleartext
+            }));
+        }
+
+        if let Some(ews_url) = protocol.ews_url {
+            server_properties.push(ServerProperty::EwsURL(ews_url));
+        }
+
+        if let Some(owa_url) = protocol.owa_url {
+            server_properties.push(ServerProperty::OwaURL(owa_url));
+        }
+
+        let server = Server::new(server_type, server_properties);
+
+        properties.push(match server.server_type() {
+            ServerType::Smtp => EmailProviderProperty::OutgoingServer(server),
+            _ => EmailProviderProperty::IncomingServer(server),
+        });
+    }
+
+    let mut config = Config::new(EmailProvider::new(domain, properties), None);
+
+    // Autodiscover requests are only ever sent over https.
+    config.set_origin(Source::Autodiscover, Some(Transport::Https));
+
+    config
+}
+
+/// Posts the Autodiscover request to a given url, following url redirects.
+async fn request_url(client: &Client, url: String, email_address: &str) -> Result<Outcome> {
+    let domain = domain_of(email_address)?;
+
+    let mut url = url;
+
+    for _ in 0..MAX_REDIRECTS {
+        let response = client.post_xml(&url, create_request(email_address)).await?;
+
+        match parse_response(&domain, &response)? {
+            Outcome::RedirectUrl(redirect_url) => {
+                // Credentials may be sent to the url later, so we never follow a redirect to an insecure url.
+                if !redirect_url.starts_with("https://") {
+                    return Err(invalid_response(
+                        "Autodiscover server redirected to an insecure url",
+                    ));
+                }
+
+                url = redirect_url;
+            }
+            outcome => return Ok(outcome),
+        }
+    }
+
+    Err(invalid_response("Autodiscover server redirected too often"))
+}
+
+/// Tries all of the given urls at the same time, returning the outcome of the first url (in order) that succeeded.
+async fn request_urls(client: &Client, urls: Vec<String>, email_address: &str) -> Option<Outcome> {
+    let requests = urls
+        .into_iter()
+        .map(|url| request_url(client, url, email_address));
+
+    join_all(requests)
+        .await
+        .into_iter()
+        .find_map(|result| result.ok())
+}
+
+/// Finds the Autodiscover urls for a domain, in the order Microsoft recommends they are tried.
+///
+/// Returns the outcome together with where it was found, because the lookups that are not authenticated by https are only done if the policy allows it.
+async fn find_outcome(
+    client: &Client,
+    resolver: &dyn Resolver,
+    email_address: &str,
+    policy: DowngradePolicy,
+) -> Result<Option<(Outcome, Source, Transport)>> {
+    let domain = domain_of(email_address)?;
+
+    let urls = vec![
+        format!("https://{}/{}", domain, AUTODISCOVER_PATH),
+        format!("https://autodiscover.{}/{}", domain, AUTODISCOVER_PATH),
+    ];
+
+    if let Some(outcome) = request_urls(client, urls, email_address).await {
+        return Ok(Some((outcome, Source::Autodiscover, Transport::Https)));
+    }
+
+    if policy != DowngradePolicy::AllowHttp {
+        return Ok(None);
+    }
+
+    // The plain http endpoint is only used to find out where the real endpoint is, we never send the request to it.
+    let redirect = client
+        .redirect_location(format!(
+            "http://autodiscover.{}/{}",
+            domain, AUTODISCOVER_PATH
+        ))
+        .await
+        .ok()
+        .flatten()
+        .filter(|location| location.starts_with("https://"));
+
+    if let Some(url) = redirect {
+        if let Ok(outcome) = request_url(client, url, email_address).await {
+            // The location itself came over plain http, so the config is as trustworthy as one that was fetched over http.
+            return Ok(Some((
+                outcome,
+                Source::AutodiscoverRedirect,
+                Transport::Http,
+            )));
+        }
+    }
+
+    // Not being able to look up the record just means we can't use it, just like a missing record.
+    let records = resolver
+        .srv(&format!("_autodiscover._tcp.{}", domain))
+        .await
+        .unwrap_or_default();
+
+    let urls = sort_srv_records(records)
+        .into_iter()
+        .map(|record| match record.port() {
+            443 => format!("https://{}/{}", record.target(), AUTODISCOVER_PATH),
+            port => format!("https://{}:{}/{}", record.target(), port, AUTODISCOVER_PATH),
+        })
+        .collect();
+
+    Ok(request_urls(client, urls, email_address)
+        .await
+        .map(|outcome| (outcome, Source::AutodiscoverSrv, Transport::Https)))
+}
+
+/// Given an email address, try to find the config of its mail servers using Autodiscover.
+///
+/// The `autodiscover.<domain>` and `<domain>/autodiscover` endpoints are tried first. If the policy allows it, they are followed by the http redirect method
+/// and the `_autodiscover._tcp` SRV record, as described in https://learn.microsoft.com/en-us/exchange/client-developer/exchange-web-services/autodiscover-for-exchange.
+pub async fn from_addr(
+    email_address: &str,
+    resolver: &dyn Resolver,
+    policy: DowngradePolicy,
+) -> Result<Option<Config>> {
+    let client = Client::without_redirects()?;
+
+    let mut email_address = email_address.to_string();
+
+    // A config is only as trustworthy as the least trustworthy lookup that led to it.
+    let mut origin = (Source::Autodiscover, Transport::Https);
+
+    for _ in 0..MAX_REDIRECTS {
+        let (outcome, source, transport) =
+            match find_outcome(&client, resolver, &email_address, policy).await? {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+
+        if source != Source::Autodiscover {
+            origin = (source, transport);
+        }
+
+        match outcome {
+            Outcome::Settings(mut config) => {
+                config.set_origin(origin.0, Some(origin.1));
+
+                return Ok(Some(config));
+            }
+            Outcome::RedirectAddr(redirect_addr) => email_address = redirect_addr,
+            Outcome::RedirectUrl(_) => unreachable!(),
+        }
+    }
+
+    Err(invalid_response("Autodiscover server redirected too often"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use futures::future::{BoxFuture, FutureExt};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{
+        dns::{MxRecord, Resolver, SrvRecord},
+        http::Client,
+        types::{
+            config::{SecurityType, ServerType},
+            Result,
+        },
+        DowngradePolicy,
+    };
+
+    use super::{create_request, find_outcome, parse_response, request_url, Outcome};
+
+    /// A resolver without any records that remembers which names were looked up.
+    #[derive(Default)]
+    struct RecordingResolver {
+        names: Mutex<Vec<String>>,
+    }
+
+    impl Resolver for RecordingResolver {
+        fn srv<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<SrvRecord>>> {
+            self.names.lock().unwrap().push(name.to_string());
+
+            async { Ok(Vec::new()) }.boxed()
+        }
+
+        fn mx<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<MxRecord>>> {
+            self.names.lock().unwrap().push(name.to_string());
+
+            async { Ok(Vec::new()) }.boxed()
+        }
+    }
+
+    /// Starts an http server that answers every request with the given xml body, returning its url.
+    async fn serve(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+
+                let mut buf = [0; 1024];
+
+                // The request body ends with the closing tag of the request.
+                while !request.ends_with(b"</Autodiscover>") {
+                    let read = stream.read(&mut buf).await.unwrap();
+
+                    if read == 0 {
+                        break;
+                    }
+
+                    request.extend_from_slice(&buf[..read]);
+                }
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        format!("http://{}/autodiscover/autodiscover.xml", addr)
+    }
+
+    const SETTINGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Autodiscover xmlns="http://schemas.microsoft.com/exchange/autodiscover/responseschema/2006">
+  <Response xmlns="http://schemas.microsoft.com/exchange/autodiscover/outlook/responseschema/2006a">
+    <User>
+      <DisplayName>Jane Doe</DisplayName>
+    </User>
+    <Account>
+      <AccountType>email</AccountType>
+      <Action>settings</Action>
+      <Protocol>
+        <Type>EXCH</Type>
+        <Server>outlook.office365.com</Server>
+        <EwsUrl>https://outlook.office365.com/EWS/Exchange.asmx</EwsUrl>
+      </Protocol>
+      <Protocol>
+        <Type>IMAP</Type>
+        <Server>outlook.office365.com</Server>
+        <Port>993</Port>
+        <LoginName>jane@contoso.com</LoginName>
+        <SSL>on</SSL>
+        <SPA>off</SPA>
+      </Protocol>
+      <Protocol>
+        <Type>SMTP</Type>
+        <Server>smtp.office365.com</Server>
+        <Port>587</Port>
+        <Encryption>TLS</Encryption>
+      </Protocol>
+    </Account>
+  </Response>
+</Autodiscover>"#;
+
+    #[test]
+    fn settings() {
+        let config = match parse_response("contoso.com", SETTINGS).unwrap() {
+            Outcome::Settings(config) => config,
+            outcome => panic!("Expected settings, got {:?}", outcome),
+        };
+
+        let provider = config.email_provider();
+
+        assert_eq!(provider.id(), "contoso.com");
+        assert_eq!(provider.display_name(), Some("Jane Doe"));
+
+        let incoming = provider.incoming_servers();
+
+        assert_eq!(incoming.len(), 2);
+
+        assert_eq!(incoming[0].server_type(), &ServerType::Exchange);
+        assert_eq!(incoming[0].port(), Some(&443));
+
+        assert_eq!(incoming[1].server_type(), &ServerType::Imap);
+        assert_eq!(incoming[1].hostname(), Some("outlook.office365.com"));
+        assert_eq!(incoming[1].security_type(), Some(&SecurityType::Tls));
+        assert_eq!(incoming[1].username(), Some("jane@contoso.com"));
+
+        let outgoing = provider.outgoing_servers();
+
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].port(), Some(&587));
+        assert_eq!(outgoing[0].security_type(), Some(&SecurityType::Starttls));
+    }
+
+    #[test]
+    fn redirect() {
+        let response = r#"<Autodiscover xmlns="http://schemas.microsoft.com/exchange/autodiscover/responseschema/2006">
+  <Response xmlns="http://schemas.microsoft.com/exchange/autodiscover/outlook/responseschema/2006a">
+    <Account>
+      <Action>redirectAddr</Action>
+      <RedirectAddr>jane@contoso.onmicrosoft.com</RedirectAddr>
+    </Account>
+  </Response>
+</Autodiscover>"#;
+
+        assert_eq!(
+            parse_response("contoso.com", response).unwrap(),
+            Outcome::RedirectAddr(String::from("jane@contoso.onmicrosoft.com"))
+        );
+    }
+
+    #[test]
+    fn error() {
+        let response = r#"<Autodiscover xmlns="http://schemas.microsoft.com/exchange/autodiscover/responseschema/2006">
+  <Response>
+    <Error Time="16:01:02.0123456" Id="1234567890">
+      <ErrorCode>600</ErrorCode>
+      <Message>Invalid Request</Message>
+    </Error>
+  </Response>
+</Autodiscover>"#;
+
+        assert!(parse_response("contoso.com", response).is_err());
+    }
+
+    #[test]
+    fn request_body() {
+        let request = create_request("jane&co@contoso.com");
+
+        assert!(request.contains("<EMailAddress>jane&amp;co@contoso.com</EMailAddress>"));
+    }
+
+    #[tokio::test]
+    async fn request() {
+        let client = Client::without_redirects().unwrap();
+
+        let url = serve(SETTINGS).await;
+
+        let outcome = request_url(&client, url, "jane@contoso.com").await.unwrap();
+
+        assert!(matches!(outcome, Outcome::Settings(_)));
+
+        let url = serve(
+            r#"<Autodiscover><Response><Account><Action>redirectUrl</Action><RedirectUrl>http://insecure.contoso.com/autodiscover/autodiscover.xml</RedirectUrl></Account></Response></Autodiscover>"#,
+        )
+        .await;
+
+        assert!(request_url(&client, url, "jane@contoso.com").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn downgrade_policy() {
+        let client = Client::without_redirects().unwrap();
+
+        // The .invalid domain never resolves, so none of the https endpoints can be found.
+        let resolver = RecordingResolver::default();
+
+        let outcome = find_outcome(
+            &client,
+            &resolver,
+            "jane@contoso.invalid",
+            DowngradePolicy::Never,
+        )
+        .await
+        .unwrap();
+
+        assert!(outcome.is_none());
+        assert!(resolver.names.lock().unwrap().is_empty());
+
+        let outcome = find_outcome(
+            &client,
+            &resolver,
+            "jane@contoso.invalid",
+            DowngradePolicy::AllowHttp,
+        )
+        .await
+        .unwrap();
+
+        assert!(outcome.is_none());
+        assert_eq!(
+            *resolver.names.lock().unwrap(),
+            vec![String::from("_autodiscover._tcp.contoso.invalid")]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::types::config::{
+    AuthenticationType, Config, EmailProvider, EmailProviderProperty, SecurityType, Server,
+    ServerProperty, ServerType,
+};
+
+/// Builds a config for an email provider, e.g. to serve it to mail clients using `Config::to_xml`.
+pub struct ConfigBuilder {
+    id: String,
+    domains: Vec<String>,
+    display_name: Option<String>,
+    display_short_name: Option<String>,
+    incoming_servers: Vec<Server>,
+    outgoing_servers: Vec<Server>,
+}
+
+impl ConfigBuilder {
+    pub fn new<I: Into<String>>(id: I) -> Self {
+        Self {
+            id: id.into(),
+            domains: Vec::new(),
+            display_name: None,
+            display_short_name: None,
+            incoming_servers: Vec::new(),
+            outgoing_servers: Vec::new(),
+        }
+    }
+
+    /// Add a domain that the email provider uses in their email addresses.
+    pub fn domain<S: Into<String>>(&mut self, domain: S) -> &mut Self {
+        self.domains.push(domain.into());
+
+        self
+    }
+
+    pub fn display_name<S: Into<String>>(&mut self, display_name: S) -> &mut Self {
+        self.display_name = Some(display_name.into());
+
+        self
+    }
+
+    pub fn display_short_name<S: Into<String>>(&mut self, display_short_name: S) -> &mut Self {
+        self.display_short_name = Some(display_short_name.into());
+
+        self
+    }
+
+    /// Add an incoming server, the ones that are added first are preferred.
+    pub fn incoming_server(&mut self, server: Server) -> &mut Self {
+        self.incoming_servers.push(server);
+
+        self
+    }
+
+    /// Add an outgoing server, the ones that are added first are preferred.
+    pub fn outgoing_server(&mut self, server: Server) -> &mut Self {
+        self.outgoing_servers.push(server);
+
+        self
+    }
+
+    pub fn build(self) -> Config {
+        let mut properties: Vec<EmailProviderProperty> = self
+            .domains
+            .into_iter()
+            .map(EmailProviderProperty::Domain)
+            .collect();
+
+        if let Some(display_name) = self.display_name {
+            properties.push(EmailProviderProperty::DisplayName(display_name));
+        }
+
+        if let Some(display_short_name) = self.display_short_name {
+            properties.push(EmailProviderProperty::DisplayShortName(display_short_name));
+        }
+
+        properties.extend(
+            self.incoming_servers
+                .into_iter()
+                .map(EmailProviderProperty::IncomingServer),
+        );
+
+        properties.extend(
+            self.outgoing_servers
+                .into_iter()
+                .map(EmailProviderProperty::OutgoingServer),
+        );
+
+        Config::new(EmailProvider::new(self.id, properties), None)
+    }
+}
+
+/// Builds a mail server for a config.
+pub struct ServerBuilder {
+    server_type: ServerType,
+    hostname: String,
+    port: u16,
+    security: SecurityType,
+    username: Option<String>,
+    authentication: Vec<AuthenticationType>,
+}
+
+impl ServerBuilder {
+    pub fn new<H: Into<String>>(
+        server_type: ServerType,
+        hostname: H,
+        port: u16,
+        security: SecurityType,
+    ) -> Self {
+        Self {
+            server_type,
+            hostname: hostname.into(),
+            port,
+            security,
+            username: None,
+            authentication: Vec::new(),
+        }
+    }
+
+    /// The username to login with, which can contain placeholders like `%EMAILADDRESS%`.
+    pub fn username<S: Into<String>>(&mut self, username: S) -> &mut Self {
+        self.username = Some(username.into());
+
+        self
+    }
+
+    /// Add a supported authentication type, the ones that are added first are preferred.
+    pub fn authentication(&mut self, authentication: AuthenticationType) -> &mut Self {
+        self.authentication.push(authentication);
+
+        self
+    }
+
+    pub fn build(self) -> Server {
+        let mut properties = vec![
+            ServerProperty::Hostname(self.hostname),
+            ServerProperty::Port(self.port),
+            ServerProperty::SocketType(self.security),
+        ];
+
+        if let Some(username) = self.username {
+            properties.push(ServerProperty::Username(username));
+        }
+
+        properties.extend(
+            self.authentication
+                .into_iter()
+                .map(ServerProperty::Authentication),
+        );
+
+        Server::new(self.server_type, properties)
+    }
+}
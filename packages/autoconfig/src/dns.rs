@@ -0,0 +1,201 @@
+use futures::future::BoxFuture;
+use trust_dns_resolver::{
+    error::{ResolveError, ResolveErrorKind},
+    TokioAsyncResolver,
+};
+
+use crate::types::{Error, ErrorKind, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A service record as described in https://www.rfc-editor.org/rfc/rfc2782.
+pub struct SrvRecord {
+    target: String,
+    port: u16,
+    priority: u16,
+    weight: u16,
+}
+
+impl SrvRecord {
+    pub fn new<T: Into<String>>(target: T, port: u16, priority: u16, weight: u16) -> Self {
+        Self {
+            target: target.into(),
+            port,
+            priority,
+            weight,
+        }
+    }
+
+    /// The host that provides the service, without a trailing dot.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn port(&self) -> &u16 {
+        &self.port
+    }
+
+    /// Records with a lower priority should be tried first.
+    pub fn priority(&self) -> &u16 {
+        &self.priority
+    }
+
+    /// Records with the same priority and a higher weight should be tried first.
+    pub fn weight(&self) -> &u16 {
+        &self.weight
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A mail exchange record, which points to a server that accepts mail for a domain.
+pub struct MxRecord {
+    exchange: String,
+    preference: u16,
+}
+
+impl MxRecord {
+    pub fn new<E: Into<String>>(exchange: E, preference: u16) -> Self {
+        Self {
+            exchange: exchange.into(),
+            preference,
+        }
+    }
+
+    /// The host that accepts mail, without a trailing dot.
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+
+    /// Records with a lower preference should be tried first.
+    pub fn preference(&self) -> &u16 {
+        &self.preference
+    }
+}
+
+/// Looks up the DNS records that are used to discover mail servers.
+///
+/// The system resolver is used by default, but it can be replaced to point the lookups at a local stub, for example in tests.
+pub trait Resolver: Send + Sync {
+    /// Looks up the SRV records for a given name, which is empty if there are none.
+    fn srv<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<SrvRecord>>>;
+
+    /// Looks up the MX records for a given name, which is empty if there are none.
+    fn mx<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<MxRecord>>>;
+}
+
+/// A resolver that uses the DNS configuration of the operating system.
+pub struct SystemResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl SystemResolver {
+    pub fn new() -> Result<Self> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf().map_err(dns_error)?;
+
+        Ok(Self { resolver })
+    }
+}
+
+impl Resolver for SystemResolver {
+    fn srv<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<SrvRecord>>> {
+        Box::pin(async move {
+            let lookup = match self.resolver.srv_lookup(name).await {
+                Ok(lookup) => lookup,
+                Err(error) => return empty_if_not_found(error),
+            };
+
+            let records = lookup
+                .iter()
+                .map(|srv| {
+                    SrvRecord::new(
+                        trim_name(&srv.target().to_utf8()),
+                        srv.port(),
+                        srv.priority(),
+                        srv.weight(),
+                    )
+                })
+                .collect();
+
+            Ok(records)
+        })
+    }
+
+    fn mx<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<MxRecord>>> {
+        Box::pin(async move {
+            let lookup = match self.resolver.mx_lookup(name).await {
+                Ok(lookup) => lookup,
+                Err(error) => return empty_if_not_found(error),
+            };
+
+            let mut records: Vec<MxRecord> = lookup
+                .iter()
+                .map(|mx| MxRecord::new(trim_name(&mx.exchange().to_utf8()), mx.preference()))
+                .collect();
+
+            records.sort_by_key(|record| record.preference);
+
+            Ok(records)
+        })
+    }
+}
+
+fn dns_error(error: ResolveError) -> Error {
+    Error::new(
+        ErrorKind::Dns,
+        format!("Failed to look up DNS records: {}", error),
+    )
+}
+
+/// A name that does not have any records of the type we are looking for is not an error for our purposes.
+fn empty_if_not_found<T>(error: ResolveError) -> Result<Vec<T>> {
+    match error.kind() {
+        ResolveErrorKind::NoRecordsFound { .. } => Ok(Vec::new()),
+        _ => Err(dns_error(error)),
+    }
+}
+
+/// Names in DNS answers are fully qualified and end with a dot, which we don't need.
+fn trim_name(name: &str) -> String {
+    name.trim_end_matches('.').to_string()
+}
+
+/// Orders the records in which they should be tried, lowest priority first and heaviest weight first within a priority.
+///
+/// RFC 2782 picks records with the same priority at random proportional to their weight, but we want the result to be deterministic.
+/// Records with a target of "." are removed, as they mean the service is explicitly not available.
+pub fn sort_srv_records(mut records: Vec<SrvRecord>) -> Vec<SrvRecord> {
+    records.retain(|record| !record.target.is_empty() && record.target != ".");
+
+    records.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then_with(|| b.weight.cmp(&a.weight))
+    });
+
+    records
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sort_srv_records, SrvRecord};
+
+    #[test]
+    fn sort() {
+        let sorted = sort_srv_records(vec![
+            SrvRecord::new("backup.example.com", 993, 20, 0),
+            SrvRecord::new(".", 993, 0, 0),
+            SrvRecord::new("light.example.com", 993, 10, 10),
+            SrvRecord::new("heavy.example.com", 993, 10, 90),
+        ]);
+
+        let targets: Vec<&str> = sorted.iter().map(|record| record.target()).collect();
+
+        assert_eq!(
+            targets,
+            vec![
+                "heavy.example.com",
+                "light.example.com",
+                "backup.example.com"
+            ]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::time::Duration;
 use crate::types::{Error, ErrorKind, Result};
 
 use futures::future::join_all;
-use reqwest::{redirect, Client as HttpClient};
+use reqwest::{header, redirect, Client as HttpClient, ClientBuilder, Response};
 
 pub struct Client {
     client: HttpClient,
@@ -16,12 +16,25 @@ impl Client {
     const XML_CONTENT_TYPE: (&str, &str) = ("application/xml", "text/xml");
 
     pub fn new() -> Result<Self> {
-        let redirect_policy = redirect::Policy::limited(Self::MAX_REDIRECTS);
+        Self::build(HttpClient::builder().redirect(redirect::Policy::limited(Self::MAX_REDIRECTS)))
+    }
+
+    /// Creates a client that refuses to use plain http, which includes following a redirect to an http url.
+    pub fn https_only() -> Result<Self> {
+        Self::build(
+            HttpClient::builder()
+                .redirect(redirect::Policy::limited(Self::MAX_REDIRECTS))
+                .https_only(true),
+        )
+    }
+
+    /// Creates a client that does not follow redirects, so they can be checked before they are followed.
+    pub fn without_redirects() -> Result<Self> {
+        Self::build(HttpClient::builder().redirect(redirect::Policy::none()))
+    }
 
-        let client = HttpClient::builder()
-            .timeout(Self::TIMEOUT)
-            .redirect(redirect_policy)
-            .build()?;
+    fn build(builder: ClientBuilder) -> Result<Self> {
+        let client = builder.timeout(Self::TIMEOUT).build()?;
 
         Ok(Self { client })
     }
@@ -30,6 +43,40 @@ impl Client {
     async fn get_xml<S: Into<String>>(&self, uri: S) -> Result<String> {
         let response = self.client.get(uri.into()).send().await?;
 
+        Self::read_xml(response).await
+    }
+
+    /// Posts an XML body to a given url and returns the XML response (if there is one)
+    pub async fn post_xml<S: Into<String>>(&self, uri: S, body: String) -> Result<String> {
+        let response = self
+            .client
+            .post(uri.into())
+            .header(header::CONTENT_TYPE, Self::XML_CONTENT_TYPE.1)
+            .body(body)
+            .send()
+            .await?;
+
+        Self::read_xml(response).await
+    }
+
+    /// Fetches a given url and returns where it redirects to, if it does.
+    pub async fn redirect_location<S: Into<String>>(&self, uri: S) -> Result<Option<String>> {
+        let response = self.client.get(uri.into()).send().await?;
+
+        if !response.status().is_redirection() {
+            return Ok(None);
+        }
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map(|location| location.to_string());
+
+        Ok(location)
+    }
+
+    async fn read_xml(response: Response) -> Result<String> {
         // Get the Content-Type header, error if it doesn't exist
         let content_type = match response.headers().get("content-type") {
             Some(header) => header.to_str().map_err(|_| {
@@ -76,19 +123,104 @@ impl Client {
         };
     }
 
-    pub async fn request_urls(&self, urls: Vec<String>) -> Option<String> {
-        let request_futures = urls.into_iter().map(|url| self.get_xml(url));
+    /// Fetches all of the given urls at the same time and returns the successful responses, in the same order as the urls.
+    ///
+    /// The urls should be ranked by preference, so the result does not depend on which server happened to respond first.
+    pub async fn request_urls<K>(&self, urls: Vec<(K, String)>) -> Vec<(K, String)> {
+        let (keys, urls): (Vec<K>, Vec<String>) = urls.into_iter().unzip();
+
+        let results = join_all(urls.into_iter().map(|url| self.get_xml(url))).await;
+
+        keys.into_iter()
+            .zip(results)
+            .filter_map(|(key, result)| result.ok().map(|response| (key, response)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time::sleep,
+    };
+
+    use super::Client;
+
+    /// Starts an http server that answers every request with the given body after a delay, returning its url.
+    async fn serve(content_type: &'static str, body: &'static str, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+
+                let mut buf = [0; 1024];
+
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buf).await.unwrap();
+
+                    if read == 0 {
+                        break;
+                    }
 
-        let results = join_all(request_futures).await;
+                    request.extend_from_slice(&buf[..read]);
+                }
 
-        for result in results {
-            match result {
-                Ok(response) => return Some(response),
-                Err(_) => {}
+                sleep(delay).await;
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                );
+
+                stream.write_all(response.as_bytes()).await.unwrap();
             }
-        }
+        });
+
+        format!("http://{}/mail/config-v1.1.xml", addr)
+    }
+
+    #[tokio::test]
+    async fn ranking() {
+        let client = Client::new().unwrap();
+
+        let urls = vec![
+            (
+                "slow",
+                serve("text/xml", "<slow/>", Duration::from_millis(200)).await,
+            ),
+            ("html", serve("text/html", "<html/>", Duration::ZERO).await),
+            ("fast", serve("text/xml", "<fast/>", Duration::ZERO).await),
+        ];
+
+        let responses = client.request_urls(urls).await;
+
+        assert_eq!(
+            responses,
+            vec![
+                ("slow", String::from("<slow/>")),
+                ("fast", String::from("<fast/>"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn https_only() {
+        let url = serve("text/xml", "<config/>", Duration::ZERO).await;
+
+        let responses = Client::https_only()
+            .unwrap()
+            .request_urls(vec![((), url)])
+            .await;
 
-        // Return nothing if all of the requests failed
-        return None;
+        assert!(responses.is_empty());
     }
 }
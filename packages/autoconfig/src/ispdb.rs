@@ -0,0 +1,269 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use flate2::read::GzDecoder;
+use once_cell::sync::Lazy;
+use tokio::fs;
+
+use crate::{
+    parse,
+    types::{
+        config::{Config, Source},
+        Result,
+    },
+};
+
+/// A compressed snapshot of Thunderbird's ISPDB, so the providers in it can be found without a network request.
+///
+/// The snapshot is regenerated from the whole ISPDB by running `ispdb/update.sh`, or from a subset of it by passing a list of providers like `ispdb/providers.txt`.
+const SNAPSHOT: &[u8] = include_bytes!("../ispdb/snapshot.xml.gz");
+
+const END_TAG: &str = "</clientConfig>";
+
+static SNAPSHOT_CONFIGS: Lazy<Vec<Config>> = Lazy::new(|| parse_snapshot(SNAPSHOT));
+
+static LOCAL_DIR: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+/// The directory that was read and the configs that were parsed from it.
+type DirConfigs = (PathBuf, Vec<Config>);
+
+/// The configs that were parsed from a directory, so it only has to be read once.
+static DIR_CONFIGS: Lazy<RwLock<Option<DirConfigs>>> = Lazy::new(|| RwLock::new(None));
+
+/// Splits a file that contains one or more config files into the separate documents.
+fn split_documents(xml: &str) -> Vec<String> {
+    xml.split(END_TAG)
+        .map(|document| document.trim())
+        .filter(|document| !document.is_empty())
+        .map(|document| format!("{}{}", document, END_TAG))
+        .collect()
+}
+
+/// The snapshot is part of the binary, so a config in it that we can't parse is skipped instead of breaking every lookup.
+fn parse_snapshot(snapshot: &[u8]) -> Vec<Config> {
+    let mut xml = String::new();
+
+    if GzDecoder::new(snapshot).read_to_string(&mut xml).is_err() {
+        return Vec::new();
+    }
+
+    split_documents(&xml)
+        .iter()
+        .filter_map(|document| parse::from_str(document).ok())
+        .map(|mut config| {
+            config.set_origin(Source::BundledIspdb, None);
+
+            config
+        })
+        .collect()
+}
+
+fn serves_domain(config: &Config, domain: &str) -> bool {
+    config
+        .email_provider()
+        .domain()
+        .iter()
+        .any(|provider_domain| provider_domain.eq_ignore_ascii_case(domain))
+}
+
+/// Sets a directory with extra config files, for domains that are not in the ISPDB, like the ones of an organization's own mail server.
+///
+/// Every file in the directory may contain one or more configs and the configs in it take precedence over the bundled snapshot.
+/// The directory is read once, so setting it again is needed to pick up changes to its files.
+pub fn set_local_dir(dir: Option<PathBuf>) {
+    if let Ok(mut local_dir) = LOCAL_DIR.write() {
+        *local_dir = dir;
+    }
+
+    if let Ok(mut dir_configs) = DIR_CONFIGS.write() {
+        *dir_configs = None;
+    }
+}
+
+/// The directory with extra config files, if one was set.
+pub fn local_dir() -> Option<PathBuf> {
+    LOCAL_DIR
+        .read()
+        .ok()
+        .and_then(|local_dir| local_dir.clone())
+}
+
+/// Find the config for a given domain in the bundled ISPDB snapshot.
+pub fn from_snapshot(domain: &str) -> Option<Config> {
+    SNAPSHOT_CONFIGS
+        .iter()
+        .find(|config| serves_domain(config, domain))
+        .cloned()
+}
+
+/// Reads all of the configs in a directory, sorted by file name so the same config wins every time if multiple files define the same domain.
+///
+/// A file or config in it that can't be read is skipped, so a single broken file does not break the lookup of every other domain.
+async fn read_dir(dir: &Path) -> Result<Vec<Config>> {
+    let mut entries = fs::read_dir(dir).await?;
+
+    let mut paths = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+
+    paths.sort();
+
+    let mut configs = Vec::new();
+
+    for path in paths {
+        let xml = match fs::read_to_string(&path).await {
+            Ok(xml) => xml,
+            Err(_) => continue,
+        };
+
+        for document in split_documents(&xml) {
+            if let Ok(mut config) = parse::from_str(&document) {
+                config.set_origin(Source::LocalDir, None);
+
+                configs.push(config);
+            }
+        }
+    }
+
+    Ok(configs)
+}
+
+/// Find the config for a given domain in a directory of config files.
+///
+/// The directory is only read on the first lookup, an error is returned if the directory itself can't be read.
+pub async fn from_dir(dir: &Path, domain: &str) -> Result<Option<Config>> {
+    let cached = DIR_CONFIGS.read().ok().and_then(|dir_configs| {
+        dir_configs
+            .as_ref()
+            .filter(|(cached_dir, _)| cached_dir == dir)
+            .map(|(_, configs)| configs.clone())
+    });
+
+    let configs = match cached {
+        Some(configs) => configs,
+        None => {
+            let configs = read_dir(dir).await?;
+
+            if let Ok(mut dir_configs) = DIR_CONFIGS.write() {
+                *dir_configs = Some((dir.to_path_buf(), configs.clone()));
+            }
+
+            configs
+        }
+    };
+
+    Ok(configs
+        .into_iter()
+        .find(|config| serves_domain(config, domain)))
+}
+
+/// Find the config for a given domain without a network request, first in the local directory if one was set and then in the bundled snapshot.
+///
+/// A local directory that is missing or can't be read is skipped, so a misconfigured directory does not break the lookup of every domain.
+pub async fn from_domain(domain: &str) -> Result<Option<Config>> {
+    if let Some(dir) = local_dir() {
+        if let Ok(Some(config)) = from_dir(&dir, domain).await {
+            return Ok(Some(config));
+        }
+    }
+
+    Ok(from_snapshot(domain))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs};
+
+    use crate::types::config::Source;
+
+    use super::{from_dir, from_domain, from_snapshot, set_local_dir, split_documents};
+
+    #[test]
+    fn snapshot() {
+        let config = from_snapshot("GMAIL.com").unwrap();
+
+        assert_eq!(config.email_provider().id(), "googlemail.com");
+        assert_eq!(config.source(), &Source::BundledIspdb);
+        assert!(config.transport().is_none());
+
+        assert!(from_snapshot("example.com").is_none());
+    }
+
+    #[test]
+    fn split() {
+        let documents = split_documents(
+            "<?xml version=\"1.0\"?>\n<clientConfig></clientConfig>\n\n<clientConfig></clientConfig>\n",
+        );
+
+        assert_eq!(
+            documents,
+            vec![
+                "<?xml version=\"1.0\"?>\n<clientConfig></clientConfig>",
+                "<clientConfig></clientConfig>"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn local_dir() {
+        let dir = env::temp_dir().join(format!("autoconfig-ispdb-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("example.xml"),
+            r#"<?xml version="1.0"?>
+            <clientConfig version="1.1">
+                <emailProvider id="example.com">
+                    <domain>example.com</domain>
+                    <incomingServer type="imap">
+                        <hostname>mail.example.com</hostname>
+                        <port>993</port>
+                        <socketType>SSL</socketType>
+                        <username>%EMAILADDRESS%</username>
+                        <authentication>password-cleartext</authentication>
+                    </incomingServer>
+                </emailProvider>
+            </clientConfig>"#,
+        )
+        .unwrap();
+
+        // A file that is not a config should not break the lookup.
+        fs::write(dir.join("broken.xml"), "<clientConfig>").unwrap();
+
+        let config = from_dir(&dir, "example.com").await.unwrap().unwrap();
+
+        assert_eq!(config.source(), &Source::LocalDir);
+
+        assert_eq!(
+            config.email_provider().incoming_servers()[0].hostname(),
+            Some("mail.example.com")
+        );
+
+        assert!(from_dir(&dir, "example.org").await.unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_local_dir() {
+        let dir = env::temp_dir().join(format!("autoconfig-ispdb-missing-{}", std::process::id()));
+
+        assert!(from_dir(&dir, "gmail.com").await.is_err());
+
+        set_local_dir(Some(dir));
+
+        let config = from_domain("gmail.com").await.unwrap().unwrap();
+
+        set_local_dir(None);
+
+        assert_eq!(config.source(), &Source::BundledIspdb);
+    }
+}
@@ -1,71 +1,153 @@
-use dust_mail_utils::validate_email;
+use dust_mail_utils::parse_addr_spec;
 
+use dns::SystemResolver;
 use http::Client;
-use types::{config::Config, Result};
+use placeholders::Placeholders;
+use types::{
+    config::{Config, Source, Transport},
+    Result,
+};
 
+pub mod autodiscover;
+pub mod builder;
+pub mod dns;
 mod http;
+pub mod ispdb;
 mod parse;
+pub mod placeholders;
+mod serialize;
 pub mod types;
 mod utils;
 
-const AT_SYMBOL: char = '@';
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Whether a config may be fetched over plain http when it could not be found over https.
+///
+/// Anyone on the network path can change a config that is fetched over http and point the users credentials at their own server, so this is not allowed by default.
+/// The same goes for the Autodiscover lookups that are not authenticated by https, the plain http redirect and the `_autodiscover._tcp` SRV record.
+pub enum DowngradePolicy {
+    #[default]
+    Never,
+    AllowHttp,
+}
+
+/// Returns the first response, in the order they were ranked in, that is a valid config.
+fn first_valid_config(responses: Vec<(Source, String)>, transport: Transport) -> Option<Config> {
+    responses.into_iter().find_map(|(source, response)| {
+        let mut config = parse::from_str(&response).ok()?;
+
+        config.set_origin(source, Some(transport.clone()));
+
+        Some(config)
+    })
+}
 
 /// Given an email providers domain, try to connect to autoconfig servers for that provider and return the config.
+///
+/// The local config files and the bundled ISPDB snapshot are checked first, so no request is made for the providers we already know.
 pub async fn from_domain<D: AsRef<str>>(domain: D) -> Result<Option<Config>> {
-    let client = Client::new()?;
+    from_domain_with_policy(domain, DowngradePolicy::default()).await
+}
 
+/// Given an email providers domain, try to find its config like `from_domain`, falling back to plain http if the given policy allows it.
+pub async fn from_domain_with_policy<D: AsRef<str>>(
+    domain: D,
+    policy: DowngradePolicy,
+) -> Result<Option<Config>> {
+    let domain = domain.as_ref();
+
+    if let Some(config) = ispdb::from_domain(domain).await? {
+        return Ok(Some(config));
+    }
+
+    // Ranked by how much we trust them: the provider itself, and if it has not setup autoconfig, the config Mozilla has for it.
     let urls = vec![
-        // Try connect to connect with the users mail server directly
-        format!("http://autoconfig.{}/mail/config-v1.1.xml", domain.as_ref()),
-        // The fallback url
-        format!(
-            "http://{}/.well-known/autoconfig/mail/config-v1.1.xml",
-            domain.as_ref()
+        (
+            Source::AutoconfigSubdomain,
+            format!("https://autoconfig.{}/mail/config-v1.1.xml", domain),
+        ),
+        (
+            Source::WellKnown,
+            format!(
+                "https://{}/.well-known/autoconfig/mail/config-v1.1.xml",
+                domain
+            ),
         ),
-        // If the previous two methods did not work then the email server provider has not setup Thunderbird autoconfig, so we ask Mozilla for their config.
-        format!(
-            "https://autoconfig.thunderbird.net/v1.1/{}",
-            domain.as_ref()
+        (
+            Source::Ispdb,
+            format!("https://autoconfig.thunderbird.net/v1.1/{}", domain),
         ),
     ];
 
-    let config_unparsed: Option<String> = client.request_urls(urls).await;
+    let client = Client::https_only()?;
 
-    match config_unparsed {
-        Some(config_unparsed) => {
-            let config = parse::from_str(&config_unparsed)?;
+    if let Some(config) = first_valid_config(client.request_urls(urls).await, Transport::Https) {
+        return Ok(Some(config));
+    }
 
-            Ok(Some(config))
-        }
-        None => Ok(None),
+    if policy != DowngradePolicy::AllowHttp {
+        return Ok(None);
     }
+
+    let urls = vec![
+        (
+            Source::AutoconfigSubdomain,
+            format!("http://autoconfig.{}/mail/config-v1.1.xml", domain),
+        ),
+        (
+            Source::WellKnown,
+            format!(
+                "http://{}/.well-known/autoconfig/mail/config-v1.1.xml",
+                domain
+            ),
+        ),
+    ];
+
+    let client = Client::new()?;
+
+    Ok(first_valid_config(
+        client.request_urls(urls).await,
+        Transport::Http,
+    ))
 }
 
 /// Given an email address, try to connect to the email providers autoconfig servers and return the config that was found.
+///
+/// If the provider does not support autoconfig, Autodiscover is used to find the config instead, which is what Exchange and Office 365 support.
+/// The placeholders in the config that can be derived from the email address are resolved.
 pub async fn from_addr(email_address: &str) -> Result<Option<Config>> {
-    if !validate_email(email_address) {
-        return Err(types::Error::new(
-            types::ErrorKind::BadInput,
-            "Given email address is invalid",
-        ));
-    };
-
-    let mut split = email_address.split(AT_SYMBOL);
-
-    // Skip the prefix
-    split.next();
-
-    let domain = match split.next() {
-        Some(domain) => domain,
-        None => {
-            return Err(types::Error::new(
-                types::ErrorKind::BadInput,
-                "An email address must specify a domain after the '@' symbol",
-            ))
-        }
-    };
-
-    from_domain(domain).await
+    from_addr_with_policy(email_address, DowngradePolicy::default()).await
+}
+
+/// Given an email address, try to find its config like `from_addr`, falling back to plain http if the given policy allows it.
+pub async fn from_addr_with_policy(
+    email_address: &str,
+    policy: DowngradePolicy,
+) -> Result<Option<Config>> {
+    let address = parse_addr_spec(email_address)?;
+
+    // Autodiscover and the placeholders get the address without comments and with a lowercase domain.
+    let email_address = &address.to_string();
+
+    // The ISPDB and the autoconfig urls use the punycode form of international domains.
+    let domain = address.ascii_domain();
+
+    let placeholders = Placeholders::from_addr(email_address)?;
+
+    if let Some(mut config) = from_domain_with_policy(domain, policy).await? {
+        config.resolve(&placeholders);
+
+        return Ok(Some(config));
+    }
+
+    let resolver = SystemResolver::new()?;
+
+    let mut config = autodiscover::from_addr(email_address, &resolver, policy).await?;
+
+    if let Some(config) = config.as_mut() {
+        config.resolve(&placeholders);
+    }
+
+    Ok(config)
 }
 
 #[cfg(test)]
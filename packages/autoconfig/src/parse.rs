@@ -11,6 +11,10 @@ pub fn from_str(string: &str) -> types::Result<Config> {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use crate::placeholders::Placeholders;
+
     #[test]
     fn test_parser() {
         // From: https://wiki.mozilla.org/Thunderbird:Autoconfiguration:ConfigFileFormat
@@ -62,6 +66,76 @@ mod tests {
             </clientConfig>
         "#;
 
-        super::from_str(mock_config).unwrap();
+        let config = super::from_str(mock_config).unwrap();
+
+        let pop_server = config.email_provider().incoming_servers()[0];
+
+        let pop3_config = pop_server.pop3().unwrap();
+
+        assert_eq!(pop3_config.leave_messages_on_server(), &true);
+        assert_eq!(
+            pop3_config.time_to_leave_messages_on_server(),
+            Some(Duration::from_secs(14 * 24 * 60 * 60))
+        );
+        assert_eq!(
+            pop3_config.check_interval(),
+            Some(Duration::from_secs(15 * 60))
+        );
+    }
+
+    #[test]
+    fn input_fields() {
+        let mock_config = r#"
+            <?xml version="1.0"?>
+            <clientConfig version="1.1">
+                <emailProvider id="example.com">
+                    <domain>example.com</domain>
+
+                    <inputField key="USERNAME" label="Screen name"></inputField>
+                    <inputField key="REGION" label="Region">eu</inputField>
+
+                    <incomingServer type="imap">
+                        <hostname>imap.%REGION%.%EMAILDOMAIN%</hostname>
+                        <port>993</port>
+                        <socketType>SSL</socketType>
+                        <username>%USERNAME%</username>
+                        <authentication>password-cleartext</authentication>
+                    </incomingServer>
+
+                    <outgoingServer type="smtp">
+                        <hostname>smtp.%EMAILDOMAIN%</hostname>
+                        <port>587</port>
+                        <socketType>STARTTLS</socketType>
+                        <username>%EMAILLOCALPART%</username>
+                        <authentication>password-cleartext</authentication>
+                    </outgoingServer>
+                </emailProvider>
+            </clientConfig>
+        "#;
+
+        let mut config = super::from_str(mock_config).unwrap();
+
+        let input_fields = config.email_provider().input_fields();
+
+        assert_eq!(input_fields.len(), 2);
+        assert_eq!(input_fields[0].key(), "USERNAME");
+        assert_eq!(input_fields[0].label(), Some("Screen name"));
+        assert_eq!(input_fields[0].default(), None);
+        assert_eq!(input_fields[1].default(), Some("eu"));
+
+        let placeholders = Placeholders::from_addr("john@example.com").unwrap();
+
+        config.resolve(&placeholders);
+
+        let imap_server = config.email_provider().incoming_servers()[0];
+
+        assert_eq!(imap_server.hostname(), Some("imap.eu.example.com"));
+        // The user did not fill in this field, so it can't be resolved yet.
+        assert_eq!(imap_server.username(), Some("%USERNAME%"));
+
+        let smtp_server = config.email_provider().outgoing_servers()[0];
+
+        assert_eq!(smtp_server.hostname(), Some("smtp.example.com"));
+        assert_eq!(smtp_server.username(), Some("john"));
     }
 }
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use dust_mail_utils::parse_addr_spec;
+
+use crate::types::Result;
+
+const EMAIL_ADDRESS: &str = "EMAILADDRESS";
+const EMAIL_LOCAL_PART: &str = "EMAILLOCALPART";
+const EMAIL_DOMAIN: &str = "EMAILDOMAIN";
+
+#[derive(Debug, Clone, Default)]
+/// The values of the placeholders in a config, like `%EMAILADDRESS%` in a username, as described in https://wiki.mozilla.org/Thunderbird:Autoconfiguration:ConfigFileFormat.
+pub struct Placeholders {
+    values: HashMap<String, String>,
+    /// The domain of the email address in ASCII form, which is used for `%EMAILDOMAIN%` in hostnames and urls.
+    ascii_domain: Option<String>,
+}
+
+impl Placeholders {
+    /// The placeholders that are derived from the users email address.
+    pub fn from_addr(email_address: &str) -> Result<Self> {
+        let address = parse_addr_spec(email_address)?;
+
+        let mut placeholders = Self::default();
+
+        placeholders
+            .set(EMAIL_ADDRESS, address.to_string())
+            .set(EMAIL_LOCAL_PART, address.local_part())
+            .set(EMAIL_DOMAIN, address.domain());
+
+        placeholders.ascii_domain = Some(address.ascii_domain().to_string());
+
+        Ok(placeholders)
+    }
+
+    /// Set the value for a placeholder, e.g. the key of an input field that the user filled in.
+    pub fn set<K: AsRef<str>, V: Into<String>>(&mut self, key: K, value: V) -> &mut Self {
+        self.values
+            .insert(key.as_ref().to_ascii_uppercase(), value.into());
+
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .get(&key.to_ascii_uppercase())
+            .map(|value| value.as_str())
+    }
+
+    /// Replaces the placeholders we know the value of in a given text, the other ones are left as they are.
+    pub fn resolve(&self, text: &str) -> String {
+        self.resolve_with(text, false)
+    }
+
+    /// Like `resolve`, but for a hostname or url, which needs the domain of the email address in ASCII form.
+    pub fn resolve_hostname(&self, text: &str) -> String {
+        self.resolve_with(text, true)
+    }
+
+    fn resolve_with(&self, text: &str, hostname: bool) -> String {
+        let mut resolved = String::new();
+
+        let mut rest = text;
+
+        while let Some(start) = rest.find('%') {
+            resolved.push_str(&rest[..start]);
+
+            let after = &rest[start + 1..];
+
+            let end = match after.find('%') {
+                Some(end) => end,
+                None => {
+                    resolved.push_str(&rest[start..]);
+
+                    return resolved;
+                }
+            };
+
+            let key = &after[..end];
+
+            let value = match self.ascii_domain.as_ref() {
+                Some(ascii_domain) if hostname && key == EMAIL_DOMAIN => Some(ascii_domain),
+                _ => self.values.get(key),
+            };
+
+            match value {
+                Some(value) => {
+                    resolved.push_str(value);
+
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    // The closing percent sign could be the start of the next placeholder.
+                    resolved.push('%');
+                    resolved.push_str(&after[..end]);
+
+                    rest = &after[end..];
+                }
+            }
+        }
+
+        resolved.push_str(rest);
+
+        resolved
+    }
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|char| char.is_ascii_uppercase() || char.is_ascii_digit() || char == '_')
+}
+
+/// Whether a text still contains a placeholder, which means it is not usable as is.
+pub fn has_placeholders(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('%').collect();
+
+    // Only the parts between two percent signs can be a key.
+    parts.len() > 2 && parts[1..parts.len() - 1].iter().any(|part| is_key(part))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{has_placeholders, Placeholders};
+
+    #[test]
+    fn resolve() {
+        let mut placeholders = Placeholders::from_addr("john.doe@example.com").unwrap();
+
+        placeholders.set("username", "jdoe");
+
+        assert_eq!(
+            placeholders.resolve("%EMAILADDRESS%"),
+            "john.doe@example.com"
+        );
+        assert_eq!(
+            placeholders.resolve("imap.%EMAILDOMAIN%"),
+            "imap.example.com"
+        );
+        assert_eq!(placeholders.resolve("%USERNAME%"), "jdoe");
+        assert_eq!(
+            placeholders.resolve("%GRANDMA%%EMAILLOCALPART%"),
+            "%GRANDMA%john.doe"
+        );
+        assert_eq!(
+            placeholders.resolve("100% %EMAILDOMAIN%"),
+            "100% example.com"
+        );
+        assert_eq!(placeholders.resolve("50%"), "50%");
+
+        let placeholders = Placeholders::from_addr("jan@bücher.example").unwrap();
+
+        assert_eq!(
+            placeholders.resolve_hostname("imap.%EMAILDOMAIN%"),
+            "imap.xn--bcher-kva.example"
+        );
+        assert_eq!(
+            placeholders.resolve("%EMAILLOCALPART%@%EMAILDOMAIN%"),
+            "jan@bücher.example"
+        );
+    }
+
+    #[test]
+    fn from_addr() {
+        let placeholders = Placeholders::from_addr(" \"john doe\"@Example.COM (work)").unwrap();
+
+        assert_eq!(
+            placeholders.get("EMAILADDRESS"),
+            Some("\"john doe\"@example.com")
+        );
+        assert_eq!(placeholders.get("EMAILLOCALPART"), Some("john doe"));
+        assert_eq!(placeholders.get("EMAILDOMAIN"), Some("example.com"));
+
+        assert!(Placeholders::from_addr("john.doe").is_err());
+    }
+
+    #[test]
+    fn unresolved() {
+        assert!(has_placeholders("%EMAILADDRESS%"));
+        assert!(has_placeholders("mail.%GRANDMA%.com"));
+        assert!(!has_placeholders("john.doe@example.com"));
+        assert!(!has_placeholders("100% sure, 50% of the time"));
+    }
+}
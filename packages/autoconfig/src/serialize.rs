@@ -0,0 +1,335 @@
+use crate::{
+    types::config::{
+        AuthenticationType, Config, Documentation, EmailProviderProperty, InputField, OAuth2Config,
+        Pop3Config, SecurityType, Server, ServerProperty, ServerType,
+    },
+    utils::escape,
+};
+
+const INDENT: &str = "  ";
+
+/// Writes indented XML, one element per line.
+struct Writer {
+    xml: String,
+    depth: usize,
+}
+
+impl Writer {
+    fn new() -> Self {
+        Self {
+            xml: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            depth: 0,
+        }
+    }
+
+    /// Writes the start of a tag, without closing it, so it can be used for both normal and empty elements.
+    fn start_tag(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.xml.push_str(&INDENT.repeat(self.depth));
+        self.xml.push('<');
+        self.xml.push_str(name);
+
+        for (key, value) in attributes {
+            self.xml
+                .push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+    }
+
+    fn open(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.xml.push_str(">\n");
+
+        self.depth += 1;
+    }
+
+    fn close(&mut self, name: &str) {
+        self.depth -= 1;
+
+        self.xml.push_str(&INDENT.repeat(self.depth));
+        self.xml.push_str(&format!("</{}>\n", name));
+    }
+
+    fn element<T: ToString>(&mut self, name: &str, attributes: &[(&str, &str)], text: T) {
+        self.start_tag(name, attributes);
+        self.xml
+            .push_str(&format!(">{}</{}>\n", escape(&text.to_string()), name));
+    }
+
+    fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.xml.push_str("/>\n");
+    }
+
+    fn finish(self) -> String {
+        self.xml
+    }
+}
+
+fn server_type_name(server_type: &ServerType) -> &'static str {
+    match server_type {
+        ServerType::Exchange => "exchange",
+        ServerType::Imap => "imap",
+        ServerType::Pop3 => "pop3",
+        ServerType::Smtp => "smtp",
+    }
+}
+
+fn security_type_name(security_type: &SecurityType) -> &'static str {
+    match security_type {
+        SecurityType::Plain => "plain",
+        SecurityType::Starttls => "STARTTLS",
+        SecurityType::Tls => "SSL",
+    }
+}
+
+fn authentication_type_name(authentication_type: &AuthenticationType) -> &'static str {
+    match authentication_type {
+        AuthenticationType::PasswordCleartext => "password-cleartext",
+        AuthenticationType::PasswordEncrypted => "password-encrypted",
+        AuthenticationType::Ntlm => "NTLM",
+        AuthenticationType::GssApi => "GSSAPI",
+        AuthenticationType::ClientIPAddress => "client-IP-address",
+        AuthenticationType::TlsClientCert => "TLS-client-cert",
+        AuthenticationType::OAuth2 => "OAuth2",
+        AuthenticationType::None => "None",
+    }
+}
+
+fn write_pop3(writer: &mut Writer, pop3: &Pop3Config) {
+    writer.open("pop3", &[]);
+
+    writer.element(
+        "leaveMessagesOnServer",
+        &[],
+        pop3.leave_messages_on_server(),
+    );
+
+    if let Some(download_on_biff) = pop3.download_on_biff() {
+        writer.element("downloadOnBiff", &[], download_on_biff);
+    }
+
+    if let Some(time) = pop3.time_to_leave_messages_on_server() {
+        writer.element(
+            "daysToLeaveMessagesOnServer",
+            &[],
+            time.as_secs() / (24 * 60 * 60),
+        );
+    }
+
+    if let Some(interval) = pop3.check_interval() {
+        writer.empty(
+            "checkInterval",
+            &[("minutes", &(interval.as_secs() / 60).to_string())],
+        );
+    }
+
+    writer.close("pop3");
+}
+
+fn write_server(writer: &mut Writer, name: &str, server: &Server) {
+    writer.open(name, &[("type", server_type_name(server.server_type()))]);
+
+    for property in server.properties() {
+        match property {
+            ServerProperty::Hostname(hostname) => writer.element("hostname", &[], hostname),
+            ServerProperty::Port(port) => writer.element("port", &[], port),
+            ServerProperty::SocketType(security_type) => {
+                writer.element("socketType", &[], security_type_name(security_type))
+            }
+            ServerProperty::Authentication(authentication_type) => writer.element(
+                "authentication",
+                &[],
+                authentication_type_name(authentication_type),
+            ),
+            ServerProperty::OwaURL(url) => writer.element("owaURL", &[], url),
+            ServerProperty::EwsURL(url) => writer.element("ewsURL", &[], url),
+            ServerProperty::UseGlobalPreferredServer(value) => {
+                writer.element("useGlobalPreferredServer", &[], value)
+            }
+            ServerProperty::Pop3(pop3) => write_pop3(writer, pop3),
+            ServerProperty::Username(username) => writer.element("username", &[], username),
+            ServerProperty::Password(password) => writer.element("password", &[], password),
+        }
+    }
+
+    writer.close(name);
+}
+
+fn write_documentation(writer: &mut Writer, documentation: &Documentation) {
+    writer.open("documentation", &[("url", documentation.url())]);
+
+    for description in documentation.properties() {
+        match description.language() {
+            Some(language) => {
+                writer.element("descr", &[("lang", language)], description.description())
+            }
+            None => writer.element("descr", &[], description.description()),
+        }
+    }
+
+    writer.close("documentation");
+}
+
+fn write_input_field(writer: &mut Writer, input_field: &InputField) {
+    let mut attributes = vec![("key", input_field.key())];
+
+    if let Some(label) = input_field.label() {
+        attributes.push(("label", label));
+    }
+
+    writer.element(
+        "inputField",
+        &attributes,
+        input_field.default().unwrap_or(""),
+    );
+}
+
+fn write_oauth2(writer: &mut Writer, oauth2: &OAuth2Config) {
+    writer.open("oAuth2", &[]);
+
+    writer.element("issuer", &[], oauth2.issuer());
+    writer.element("scope", &[], oauth2.scope().join(" "));
+    writer.element("authURL", &[], oauth2.auth_url());
+    writer.element("tokenURL", &[], oauth2.token_url());
+
+    writer.close("oAuth2");
+}
+
+/// Writes a config in the config-v1.1 format, so it can be served to other mail clients.
+pub fn to_string(config: &Config) -> String {
+    let mut writer = Writer::new();
+
+    writer.open("clientConfig", &[("version", config.version())]);
+
+    let email_provider = config.email_provider();
+
+    writer.open("emailProvider", &[("id", email_provider.id())]);
+
+    for property in email_provider.properties() {
+        match property {
+            EmailProviderProperty::Domain(domain) => writer.element("domain", &[], domain),
+            EmailProviderProperty::DisplayName(display_name) => {
+                writer.element("displayName", &[], display_name)
+            }
+            EmailProviderProperty::DisplayShortName(short_name) => {
+                writer.element("displayShortName", &[], short_name)
+            }
+            EmailProviderProperty::IncomingServer(server) => {
+                write_server(&mut writer, "incomingServer", server)
+            }
+            EmailProviderProperty::OutgoingServer(server) => {
+                write_server(&mut writer, "outgoingServer", server)
+            }
+            EmailProviderProperty::Documentation(documentation) => {
+                write_documentation(&mut writer, documentation)
+            }
+            EmailProviderProperty::InputField(input_field) => {
+                write_input_field(&mut writer, input_field)
+            }
+        }
+    }
+
+    writer.close("emailProvider");
+
+    if let Some(oauth2) = config.oauth2() {
+        write_oauth2(&mut writer, oauth2);
+    }
+
+    writer.close("clientConfig");
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::{ConfigBuilder, ServerBuilder},
+        parse,
+        types::config::{AuthenticationType, SecurityType, ServerType},
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut imap =
+            ServerBuilder::new(ServerType::Imap, "mail.example.com", 993, SecurityType::Tls);
+
+        imap.username("%EMAILADDRESS%")
+            .authentication(AuthenticationType::PasswordCleartext);
+
+        let mut smtp = ServerBuilder::new(
+            ServerType::Smtp,
+            "mail.example.com",
+            587,
+            SecurityType::Starttls,
+        );
+
+        smtp.username("%EMAILADDRESS%")
+            .authentication(AuthenticationType::OAuth2)
+            .authentication(AuthenticationType::PasswordCleartext);
+
+        let mut builder = ConfigBuilder::new("example.com");
+
+        builder
+            .domain("example.com")
+            .domain("example.org")
+            .display_name("Example & Co <Mail>")
+            .display_short_name("Example")
+            .incoming_server(imap.build())
+            .outgoing_server(smtp.build());
+
+        let config = builder.build();
+
+        let xml = config.to_xml();
+
+        assert!(xml.contains("<displayName>Example &amp; Co &lt;Mail&gt;</displayName>"));
+
+        assert_eq!(parse::from_str(&xml).unwrap(), config);
+    }
+
+    #[test]
+    fn round_trip_parsed() {
+        let xml = r#"<?xml version="1.0"?>
+            <clientConfig version="1.1">
+                <emailProvider id="example.com">
+                    <domain>example.com</domain>
+                    <inputField key="USERNAME" label="Screen name"></inputField>
+                    <incomingServer type="pop3">
+                        <hostname>pop.example.com</hostname>
+                        <port>995</port>
+                        <socketType>SSL</socketType>
+                        <username>%USERNAME%</username>
+                        <authentication>password-cleartext</authentication>
+                        <pop3>
+                            <leaveMessagesOnServer>true</leaveMessagesOnServer>
+                            <downloadOnBiff>true</downloadOnBiff>
+                            <daysToLeaveMessagesOnServer>14</daysToLeaveMessagesOnServer>
+                            <checkInterval minutes="15"/>
+                        </pop3>
+                    </incomingServer>
+                    <outgoingServer type="smtp">
+                        <hostname>smtp.example.com</hostname>
+                        <port>465</port>
+                        <socketType>SSL</socketType>
+                        <authentication>GSAPI</authentication>
+                        <useGlobalPreferredServer>true</useGlobalPreferredServer>
+                    </outgoingServer>
+                    <documentation url="http://www.example.com/help/mail/thunderbird">
+                        <descr lang="en">Configure Thunderbird 2.0 for IMAP</descr>
+                    </documentation>
+                </emailProvider>
+                <oAuth2>
+                    <issuer>example.com</issuer>
+                    <scope>mail profile</scope>
+                    <authURL>https://example.com/auth</authURL>
+                    <tokenURL>https://example.com/token</tokenURL>
+                </oAuth2>
+            </clientConfig>"#;
+
+        let config = parse::from_str(xml).unwrap();
+
+        let xml = config.to_xml();
+
+        assert!(xml.contains("<authentication>GSSAPI</authentication>"));
+
+        assert_eq!(parse::from_str(&xml).unwrap(), config);
+    }
+}
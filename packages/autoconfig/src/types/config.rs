@@ -2,7 +2,9 @@ use std::time::Duration;
 
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, PartialEq)]
+use crate::{placeholders::Placeholders, serialize};
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 /// A config that follows Mozilla's specification: https://wiki.mozilla.org/Thunderbird:Autoconfiguration:ConfigFileFormat
 pub struct Config {
@@ -10,9 +12,40 @@ pub struct Config {
     email_provider: EmailProvider,
     #[serde(rename = "oAuth2")]
     oauth2: Option<OAuth2Config>,
+    #[serde(skip)]
+    source: Source,
+    #[serde(skip)]
+    transport: Option<Transport>,
 }
 
 impl Config {
+    pub(crate) fn new(email_provider: EmailProvider, oauth2: Option<OAuth2Config>) -> Self {
+        Self {
+            version: String::from("1.1"),
+            email_provider,
+            oauth2,
+            source: Source::default(),
+            transport: None,
+        }
+    }
+
+    pub(crate) fn set_origin(&mut self, source: Source, transport: Option<Transport>) -> &mut Self {
+        self.source = source;
+        self.transport = transport;
+
+        self
+    }
+
+    /// Where the config was found.
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// How the config was fetched, which is none if it was found without a network request.
+    pub fn transport(&self) -> Option<&Transport> {
+        self.transport.as_ref()
+    }
+
     /// The config version
     pub fn version(&self) -> &str {
         &self.version
@@ -27,9 +60,69 @@ impl Config {
     pub fn oauth2(&self) -> Option<&OAuth2Config> {
         self.oauth2.as_ref()
     }
+
+    /// The config in the config-v1.1 format, so it can be served to other mail clients.
+    pub fn to_xml(&self) -> String {
+        serialize::to_string(self)
+    }
+
+    /// Replace the placeholders in the hostnames, usernames and urls of the config with their values.
+    ///
+    /// Placeholders of input fields that were not given a value are replaced by the input field's default, if it has one.
+    pub fn resolve(&mut self, placeholders: &Placeholders) -> &mut Self {
+        let mut placeholders = placeholders.clone();
+
+        for input_field in self.email_provider.input_fields() {
+            if let Some(default) = input_field.default() {
+                if placeholders.get(input_field.key()).is_none() {
+                    placeholders.set(input_field.key(), default);
+                }
+            }
+        }
+
+        for property in self.email_provider.properties.iter_mut() {
+            match property {
+                EmailProviderProperty::IncomingServer(server)
+                | EmailProviderProperty::OutgoingServer(server) => server.resolve(&placeholders),
+                _ => {}
+            }
+        }
+
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Source {
+    /// The directory of config files that was set using `ispdb::set_local_dir`.
+    LocalDir,
+    /// The ISPDB snapshot that is bundled with this crate.
+    BundledIspdb,
+    /// The `autoconfig.<domain>` subdomain of the provider.
+    AutoconfigSubdomain,
+    /// The `.well-known/autoconfig` path on the provider's domain.
+    WellKnown,
+    /// Mozilla's online ISPDB.
+    Ispdb,
+    /// The provider's Autodiscover service.
+    Autodiscover,
+    /// An Autodiscover service that the plain http `autodiscover.<domain>` endpoint redirected to, which anyone on the network path could have changed.
+    AutodiscoverRedirect,
+    /// An Autodiscover service that the `_autodiscover._tcp` SRV record pointed to, which can be any host and is not authenticated.
+    AutodiscoverSrv,
+    /// The config was parsed directly, so we don't know where it came from.
+    #[default]
+    Unknown,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transport {
+    Https,
+    /// Plain http, so the config could have been changed by anyone on the network path.
+    Http,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct OAuth2Config {
     issuer: String,
@@ -62,7 +155,7 @@ impl OAuth2Config {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct EmailProvider {
     id: String,
     #[serde(rename = "$value")]
@@ -70,6 +163,13 @@ pub struct EmailProvider {
 }
 
 impl EmailProvider {
+    pub(crate) fn new<I: Into<String>>(id: I, properties: Vec<EmailProviderProperty>) -> Self {
+        Self {
+            id: id.into(),
+            properties,
+        }
+    }
+
     /// Just an array containing all of the email providers properties, usefull if you want to get multiple properties in 1 for loop.
     pub fn properties(&self) -> &Vec<EmailProviderProperty> {
         &self.properties
@@ -161,6 +261,20 @@ impl EmailProvider {
         servers
     }
 
+    /// The fields the user has to fill in, so their values can be used for the placeholders in the config.
+    pub fn input_fields(&self) -> Vec<&InputField> {
+        let mut input_fields: Vec<&InputField> = Vec::new();
+
+        for property in &self.properties {
+            match property {
+                EmailProviderProperty::InputField(input_field) => input_fields.push(input_field),
+                _ => {}
+            }
+        }
+
+        input_fields
+    }
+
     /// Documentation on how to setup the email client, provided by the email provider.
     pub fn documentation(&self) -> Option<&Documentation> {
         for property in &self.properties {
@@ -174,7 +288,7 @@ impl EmailProvider {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum EmailProviderProperty {
     Domain(String),
@@ -183,9 +297,35 @@ pub enum EmailProviderProperty {
     IncomingServer(Server),
     OutgoingServer(Server),
     Documentation(Documentation),
+    InputField(InputField),
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct InputField {
+    key: String,
+    label: Option<String>,
+    #[serde(rename = "$value")]
+    default: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+impl InputField {
+    /// The name of the placeholder the value of this field is used for, without the percent signs.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// What the field should be called in a form.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The value to use if the user did not fill in the field.
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Server {
     r#type: ServerType,
     #[serde(rename = "$value")]
@@ -193,6 +333,25 @@ pub struct Server {
 }
 
 impl Server {
+    pub(crate) fn new(server_type: ServerType, properties: Vec<ServerProperty>) -> Self {
+        Self {
+            r#type: server_type,
+            properties,
+        }
+    }
+
+    fn resolve(&mut self, placeholders: &Placeholders) {
+        for property in self.properties.iter_mut() {
+            match property {
+                ServerProperty::Hostname(value)
+                | ServerProperty::OwaURL(value)
+                | ServerProperty::EwsURL(value) => *value = placeholders.resolve_hostname(value),
+                ServerProperty::Username(value) => *value = placeholders.resolve(value),
+                _ => {}
+            }
+        }
+    }
+
     /// Just an array containing all of a mail servers properties, usefull if you want to get multiple properties in 1 for loop.
     pub fn properties(&self) -> &Vec<ServerProperty> {
         &self.properties
@@ -278,9 +437,21 @@ impl Server {
 
         None
     }
+
+    /// Pop specific settings, only present on Pop servers.
+    pub fn pop3(&self) -> Option<&Pop3Config> {
+        for property in &self.properties {
+            match property {
+                ServerProperty::Pop3(pop3_config) => return Some(pop3_config),
+                _ => {}
+            }
+        }
+
+        None
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum ServerProperty {
     Hostname(String),
@@ -296,7 +467,7 @@ pub enum ServerProperty {
     Password(String),
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum SecurityType {
     #[serde(rename = "plain")]
     Plain,
@@ -306,7 +477,7 @@ pub enum SecurityType {
     Tls,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum ServerType {
     Exchange,
@@ -315,7 +486,7 @@ pub enum ServerType {
     Smtp,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum AuthenticationType {
     #[serde(rename = "password-cleartext")]
     PasswordCleartext,
@@ -323,8 +494,9 @@ pub enum AuthenticationType {
     PasswordEncrypted,
     #[serde(rename = "NTLM")]
     Ntlm,
-    #[serde(rename = "GSAPI")]
-    GsApi,
+    // Older configs misspell this as `GSAPI`.
+    #[serde(rename = "GSSAPI", alias = "GSAPI")]
+    GssApi,
     #[serde(rename = "client-IP-address")]
     ClientIPAddress,
     #[serde(rename = "TLS-client-cert")]
@@ -334,7 +506,7 @@ pub enum AuthenticationType {
     None,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Pop3Config {
     leave_messages_on_server: bool,
@@ -376,12 +548,12 @@ impl Pop3Config {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 struct CheckInterval {
     minutes: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Documentation {
     url: String,
     #[serde(rename = "$value")]
@@ -400,7 +572,7 @@ impl Documentation {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct DocumentationDescription {
     lang: Option<String>,
     #[serde(rename = "$value")]
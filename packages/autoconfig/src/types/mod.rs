@@ -2,14 +2,19 @@ pub mod config;
 
 use std::{error, fmt, result};
 
+use dust_mail_utils::types::Error as AddressError;
+
 #[derive(Debug)]
 pub enum ErrorKind {
     Http(reqwest::Error),
+    Io(std::io::Error),
     InvalidResponse,
     Timeout,
     BadInput,
     NotFound,
     Parse,
+    /// Failed to look up a DNS record.
+    Dns,
 }
 
 #[derive(Debug)]
@@ -44,6 +49,21 @@ impl From<reqwest::Error> for Error {
     }
 }
 
+impl From<AddressError> for Error {
+    fn from(address_error: AddressError) -> Self {
+        Self::new(
+            ErrorKind::BadInput,
+            format!("Given email address is invalid: {}", address_error),
+        )
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(io_error: std::io::Error) -> Self {
+        Self::new(ErrorKind::Io(io_error), "Error reading a local file")
+    }
+}
+
 impl error::Error for Error {
     fn description(&self) -> &str {
         &self.message
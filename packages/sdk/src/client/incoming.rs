@@ -1,4 +1,5 @@
 use async_native_tls::TlsStream;
+use async_tcp::{Config as TcpConfig, ProxyConfig, TlsConfig, Transcript, TranscriptStream};
 use async_trait::async_trait;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
@@ -14,9 +15,27 @@ use crate::imap::{self, ImapClient};
 use crate::pop::{self, PopClient};
 
 use crate::types::{
-    Error, ErrorKind, IncomingClientType, MailBox, Message, OAuthCredentials, Preview, Result,
+    Attachment, Error, ErrorKind, IncomingClientType, MailBox, Message, OAuthCredentials, Preview,
+    Result,
 };
 
+/// The size in bytes of the largest attachment that a session downloads, unless it is configured otherwise.
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: u64 = 25 * 1024 * 1024;
+
+/// Errors if an attachment is larger than the largest size a session is allowed to download.
+pub(crate) fn check_attachment_size(attachment: &Attachment, max_size: u64) -> Result<()> {
+    match attachment.size() {
+        Some(size) if *size > max_size => Err(Error::new(
+            ErrorKind::TooLarge,
+            format!(
+                "The attachment is {} bytes, which is larger than the maximum of {} bytes",
+                size, max_size
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
 enum IncomingClientTypeWithClient<S>
 where
     S: AsyncRead + AsyncWrite + Unpin + Debug + Send,
@@ -108,7 +127,27 @@ pub trait IncomingSession {
     async fn messages(&mut self, box_id: &str, start: u32, end: u32) -> Result<Vec<Preview>>;
 
     /// Returns all of the relevant data for a specified message.
-    async fn get_message(&mut self, box_id: &str, msg_id: &str) -> Result<Message>;
+    ///
+    /// The message is only marked as read on the server if `mark_read` is set, so a message can be fetched in advance without the user having read it.
+    ///
+    /// The attachments of the message are only described, their data can be downloaded using `get_attachment`.
+    /// A text or html part that is larger than the maximum attachment size of the session can be cut off at that size, which is marked in the content.
+    async fn get_message(&mut self, box_id: &str, msg_id: &str, mark_read: bool)
+        -> Result<Message>;
+
+    /// Downloads the data of an attachment of a specified message, given the id of the attachment from the message's attachment list.
+    ///
+    /// Returns the description of the attachment along with its data, so it can be served with the right content type and filename.
+    /// Fails if the attachment is larger than the maximum attachment size of the session.
+    async fn get_attachment(
+        &mut self,
+        box_id: &str,
+        msg_id: &str,
+        attachment_id: &str,
+    ) -> Result<(Attachment, Vec<u8>)>;
+
+    /// Sets the size in bytes of the largest attachment that can be downloaded in this session, which can also limit how much of the text of a message is downloaded.
+    fn set_max_attachment_size(&mut self, max_size: u64);
 }
 
 /// A struct used to create a connection to an incoming mail server.
@@ -118,6 +157,8 @@ pub struct IncomingClientBuilder {
     client_type: IncomingClientType,
     server: Option<String>,
     port: Option<u16>,
+    /// The options used to set up the connection to the server, such as tls and proxy settings.
+    config: TcpConfig,
 }
 
 impl IncomingClientBuilder {
@@ -129,6 +170,7 @@ impl IncomingClientBuilder {
             client_type: client_type.clone(),
             port: None,
             server: None,
+            config: TcpConfig::default(),
         }
     }
 
@@ -150,6 +192,29 @@ impl IncomingClientBuilder {
         self
     }
 
+    /// Set the options used to secure the connection to the server.
+    ///
+    /// Only used when connecting with `build()`.
+    pub fn set_tls(&mut self, tls: TlsConfig) -> &mut Self {
+        self.config.set_tls(tls);
+
+        self
+    }
+
+    /// Set a proxy server that the connection to the server should be tunneled through.
+    pub fn set_proxy(&mut self, proxy: Option<ProxyConfig>) -> &mut Self {
+        self.config.set_proxy(proxy);
+
+        self
+    }
+
+    /// Record the traffic with the server in a transcript, which can be exported for bug reports.
+    pub fn set_transcript(&mut self, transcript: Option<Transcript>) -> &mut Self {
+        self.config.set_transcript(transcript);
+
+        self
+    }
+
     /// Internal function used to check if the domain and port have been set and return them if they are set.
     ///
     /// This function will error if either one is not set.
@@ -178,13 +243,13 @@ impl IncomingClientBuilder {
     }
 
     /// Creates a new client over a secure tcp connection.
-    pub async fn build(&self) -> Result<IncomingClient<TlsStream<TcpStream>>> {
+    pub async fn build(&self) -> Result<IncomingClient<TranscriptStream<TlsStream<TcpStream>>>> {
         let client = match self.client_type {
             #[cfg(feature = "imap")]
             IncomingClientType::Imap => {
                 let (server, port) = self.get_connect_config()?;
 
-                let client = imap::connect(server, port.clone()).await?;
+                let client = imap::connect(server, port.clone(), &self.config).await?;
 
                 IncomingClientTypeWithClient::Imap(client)
             }
@@ -192,7 +257,7 @@ impl IncomingClientBuilder {
             IncomingClientType::Pop => {
                 let (server, port) = self.get_connect_config()?;
 
-                let client = pop::connect(server, port.clone()).await?;
+                let client = pop::connect(server, port.clone(), &self.config).await?;
 
                 IncomingClientTypeWithClient::Pop(client)
             }
@@ -204,13 +269,13 @@ impl IncomingClientBuilder {
     /// Creates a new client over a plain tcp connection.
     ///
     /// ### Do not use this in a production environment as it will send your credentials to the server without any encryption!
-    pub async fn build_plain(&self) -> Result<IncomingClient<TcpStream>> {
+    pub async fn build_plain(&self) -> Result<IncomingClient<TranscriptStream<TcpStream>>> {
         let client = match self.client_type {
             #[cfg(feature = "imap")]
             IncomingClientType::Imap => {
                 let (server, port) = self.get_connect_config()?;
 
-                let client = imap::connect_plain(server, port.clone()).await?;
+                let client = imap::connect_plain(server, port.clone(), &self.config).await?;
 
                 IncomingClientTypeWithClient::Imap(client)
             }
@@ -218,7 +283,7 @@ impl IncomingClientBuilder {
             IncomingClientType::Pop => {
                 let (server, port) = self.get_connect_config()?;
 
-                let client = pop::connect_plain(server, port.clone()).await?;
+                let client = pop::connect_plain(server, port.clone(), &self.config).await?;
 
                 IncomingClientTypeWithClient::Pop(client)
             }
@@ -0,0 +1,248 @@
+use charset::Charset;
+use encoding_rs::{ISO_2022_JP, UTF_8, WINDOWS_1252};
+
+/// The escape sequences that switch ISO-2022-JP to one of its Japanese character sets.
+///
+/// Text in ISO-2022-JP is plain 7 bit ASCII otherwise, so it is often sent without a charset or labelled as ASCII.
+const ISO_2022_JP_ESCAPES: [&[u8]; 3] = [b"\x1b$B", b"\x1b$@", b"\x1b(J"];
+
+/// Labels that mail clients use for a charset, but that are not known by the Encoding Standard.
+const CHARSET_ALIASES: [(&str, &str); 5] = [
+    ("cp932", "shift_jis"),
+    ("cp936", "gbk"),
+    ("ms936", "gbk"),
+    ("windows-936", "gbk"),
+    ("cp949", "euc-kr"),
+];
+
+/// The sequence that separates the signature from the rest of the message, it never flows into the next line.
+const SIGNATURE_SEPARATOR: &str = "-- ";
+
+fn is_utf8(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        // Text that was cut off, like the start of a part that is fetched for a snippet, can end in the middle of a character.
+        Err(error) => error.error_len().is_none(),
+    }
+}
+
+fn is_iso_2022_jp(bytes: &[u8]) -> bool {
+    ISO_2022_JP_ESCAPES
+        .iter()
+        .any(|escape| bytes.windows(escape.len()).any(|window| window == *escape))
+}
+
+fn charset_for_label(label: &str) -> Option<Charset> {
+    let label = label.trim();
+
+    let label = CHARSET_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(label))
+        .map(|(_, label)| *label)
+        .unwrap_or(label);
+
+    Charset::for_label_no_replacement(label.as_bytes())
+}
+
+/// Guesses the charset of text that has no charset, or one that cannot be right.
+fn detect_charset(bytes: &[u8]) -> Charset {
+    if is_iso_2022_jp(bytes) {
+        Charset::for_encoding(ISO_2022_JP)
+    } else if is_utf8(bytes) {
+        Charset::for_encoding(UTF_8)
+    } else {
+        // The charset of most older western mail, it can decode any byte.
+        Charset::for_encoding(WINDOWS_1252)
+    }
+}
+
+/// Decodes text in a given charset, falling back to the charset that the text looks like if it is missing, unknown or mislabelled.
+///
+/// ASCII and Latin-1 are decoded as Windows-1252, like the Encoding Standard does, unless the text turns out to be UTF-8 or ISO-2022-JP.
+pub fn decode_charset(bytes: &[u8], label: Option<&str>) -> String {
+    if let Some((charset, bom_length)) = Charset::for_bom(bytes) {
+        let (text, _) = charset.decode_without_bom_handling(&bytes[bom_length..]);
+
+        return text.into_owned();
+    }
+
+    let charset = match label.and_then(charset_for_label) {
+        Some(charset) if charset == Charset::for_encoding(UTF_8) && !is_utf8(bytes) => {
+            detect_charset(bytes)
+        }
+        Some(charset) if charset == Charset::for_encoding(WINDOWS_1252) => detect_charset(bytes),
+        Some(charset) => charset,
+        None => detect_charset(bytes),
+    };
+
+    let (text, _) = charset.decode_without_bom_handling(bytes);
+
+    text.into_owned()
+}
+
+/// Joins the lines of text that was sent with `format=flowed`, as described in RFC 3676, so it can be wrapped to fit the screen it is shown on.
+///
+/// A line that ends with a space continues on the next line, unless the next line is quoted at a different depth.
+pub fn decode_flowed(text: &str, delete_space: bool) -> String {
+    let mut lines: Vec<String> = Vec::new();
+
+    // The quote depth and content of the paragraph that the previous line flowed into.
+    let mut paragraph: Option<(usize, String)> = None;
+
+    let quote_line = |depth: usize, content: String| {
+        if depth == 0 {
+            content
+        } else if content.is_empty() {
+            ">".repeat(depth)
+        } else {
+            format!("{} {}", ">".repeat(depth), content)
+        }
+    };
+
+    for line in text.lines() {
+        let depth = line
+            .chars()
+            .take_while(|character| *character == '>')
+            .count();
+
+        let content = &line[depth..];
+
+        // A space is added to the start of lines that start with a space, a quote or `From `, which has to be removed again.
+        let content = content.strip_prefix(' ').unwrap_or(content);
+
+        if let Some((paragraph_depth, paragraph_content)) = paragraph.take() {
+            if paragraph_depth == depth {
+                paragraph = Some((paragraph_depth, paragraph_content));
+            } else {
+                lines.push(quote_line(paragraph_depth, paragraph_content));
+            }
+        }
+
+        let (_, paragraph_content) = paragraph.get_or_insert_with(|| (depth, String::new()));
+
+        let is_flowed = content.ends_with(' ') && content != SIGNATURE_SEPARATOR;
+
+        if is_flowed && delete_space {
+            paragraph_content.push_str(&content[..content.len() - 1]);
+        } else {
+            paragraph_content.push_str(content);
+        }
+
+        if !is_flowed {
+            if let Some((depth, content)) = paragraph.take() {
+                lines.push(quote_line(depth, content));
+            }
+        }
+    }
+
+    if let Some((depth, content)) = paragraph.take() {
+        lines.push(quote_line(depth, content));
+    }
+
+    let mut decoded = lines.join("\r\n");
+
+    if text.ends_with('\n') {
+        decoded.push_str("\r\n");
+    }
+
+    decoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_charset, decode_flowed};
+
+    #[test]
+    fn charsets() {
+        assert_eq!(decode_charset(b"J\xc3\xb6rg", Some("utf-8")), "J\u{f6}rg");
+        assert_eq!(
+            decode_charset(b"\xd6\xd0\xce\xc4", Some("gb2312")),
+            "\u{4e2d}\u{6587}"
+        );
+        assert_eq!(
+            decode_charset(b"\x81\x30\x81\x30", Some("GB18030")),
+            "\u{80}"
+        );
+        assert_eq!(decode_charset(b"\xb0\xa1", Some("cp936")), "\u{554a}");
+
+        // ISO-2022-JP is recognized by its escape sequences when it is not labelled as such.
+        let japanese = b"\x1b$B$3$s$K$A$O\x1b(B";
+
+        assert_eq!(
+            decode_charset(japanese, Some("iso-2022-jp")),
+            "\u{3053}\u{3093}\u{306b}\u{3061}\u{306f}"
+        );
+        assert_eq!(
+            decode_charset(japanese, None),
+            "\u{3053}\u{3093}\u{306b}\u{3061}\u{306f}"
+        );
+        assert_eq!(
+            decode_charset(japanese, Some("us-ascii")),
+            "\u{3053}\u{3093}\u{306b}\u{3061}\u{306f}"
+        );
+    }
+
+    #[test]
+    fn mislabelled_charsets() {
+        // Windows-1252 quotes labelled as UTF-8, ASCII and Latin-1.
+        let quoted = b"\x93Hello\x94 \x80";
+
+        assert_eq!(
+            decode_charset(quoted, Some("utf-8")),
+            "\u{201c}Hello\u{201d} \u{20ac}"
+        );
+        assert_eq!(
+            decode_charset(quoted, Some("us-ascii")),
+            "\u{201c}Hello\u{201d} \u{20ac}"
+        );
+        assert_eq!(
+            decode_charset(quoted, Some("iso-8859-1")),
+            "\u{201c}Hello\u{201d} \u{20ac}"
+        );
+        assert_eq!(
+            decode_charset(quoted, None),
+            "\u{201c}Hello\u{201d} \u{20ac}"
+        );
+
+        // UTF-8 labelled as Latin-1 or without a charset.
+        assert_eq!(
+            decode_charset(b"J\xc3\xb6rg", Some("iso-8859-1")),
+            "J\u{f6}rg"
+        );
+        assert_eq!(decode_charset(b"J\xc3\xb6rg", None), "J\u{f6}rg");
+        assert_eq!(decode_charset(b"J\xc3\xb6rg", Some("unknown")), "J\u{f6}rg");
+
+        // A character that is cut off at the end is not a reason to doubt the charset.
+        assert_eq!(
+            decode_charset(b"J\xc3\xb6rg \xc3", Some("utf-8")),
+            "J\u{f6}rg \u{fffd}"
+        );
+
+        assert_eq!(
+            decode_charset(b"\xef\xbb\xbfHello", Some("iso-8859-1")),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn flowed() {
+        assert_eq!(
+            decode_flowed(
+                "This is a long \r\nparagraph.\r\nA fixed line\r\n \r\n From the start\r\n-- \r\nJohn\r\n",
+                false
+            ),
+            "This is a long paragraph.\r\nA fixed line\r\n\r\nFrom the start\r\n-- \r\nJohn\r\n"
+        );
+
+        assert_eq!(decode_flowed("Hel \r\nlo", true), "Hello");
+
+        // A change in quote depth ends a paragraph, even if the line before it flowed.
+        assert_eq!(
+            decode_flowed(
+                "> Are you \r\n> coming? \r\n>> Yes \r\nSee you\r\n>\r\n",
+                false
+            ),
+            "> Are you coming? \r\n>> Yes \r\nSee you\r\n>\r\n"
+        );
+    }
+}
@@ -1,85 +1,388 @@
-use std::collections::HashMap;
-
-use dust_mail_utils::validate_email;
-use futures::future::join_all;
-
-use crate::types::{ConnectionSecurity, Error, ErrorKind, Result};
-
+use dust_mail_utils::parse_addr_spec;
+use futures::{
+    channel::mpsc,
+    future,
+    stream::{self, BoxStream, FuturesUnordered},
+    Future, FutureExt, StreamExt,
+};
+
+use crate::{
+    session::PasswordCredentials,
+    types::{ConnectionSecurity, Error, ErrorKind, ProxyConfig, Result},
+};
+
+mod mx;
 mod parse;
+mod progress;
 mod service;
+mod srv;
+#[cfg(test)]
+mod stub;
 mod types;
 
+#[cfg(feature = "autoconfig")]
+use autoconfig::placeholders::Placeholders;
 #[cfg(feature = "autoconfig")]
 use parse::AutoConfigParser;
 
-use types::{AuthenticationType, ConfigType, ServerConfig, ServerConfigType};
+use types::{AuthenticationType, ConfigType, Probe, ServerConfig, ServerConfigType};
+
+pub use autoconfig::{
+    dns::{MxRecord, Resolver, SrvRecord, SystemResolver},
+    ispdb::set_local_dir,
+};
+pub use progress::{DetectSource, Progress};
+pub use types::{Config, InputField, ServerSource};
+
+use self::{
+    progress::Reporter,
+    service::{detect_server_config, verify_credentials},
+    types::Socket,
+};
+
+/// The servers that were found on the sockets, a server that was found on multiple sockets with the same address is only included once.
+fn found_servers(sockets: &[Socket], probes: &[Option<Probe>]) -> Vec<ServerConfig> {
+    let mut servers: Vec<ServerConfig> = Vec::new();
+
+    for (socket, probe) in sockets.iter().zip(probes.iter()) {
+        if let Some(probe) = probe {
+            // The sockets that come first are found in a more trustworthy place, like the SRV records, so those are kept.
+            let is_duplicate = servers.iter().any(|server| {
+                server.r#type() == probe.config_type()
+                    && server.port() == socket.port()
+                    && server.domain().eq_ignore_ascii_case(socket.domain())
+            });
+
+            if !is_duplicate {
+                servers.push(socket.clone().into_server_config(probe.clone()));
+            }
+        }
+    }
 
-pub use types::Config;
+    servers
+}
+
+fn multiserver_config(domain: &str, servers: Vec<ServerConfig>) -> Option<Config> {
+    if servers.is_empty() {
+        return None;
+    }
 
-use self::{service::detect_server_config, types::Socket};
+    let (outgoing, incoming) = servers
+        .into_iter()
+        .partition(|server| server.r#type().is_outgoing());
 
-const AT_SYMBOL: &str = "@";
+    Some(Config::new(
+        ConfigType::new_multiserver(incoming, outgoing),
+        domain,
+        None,
+        Some(domain.to_string()),
+    ))
+}
 
 /// Given an array of sockets (domain name, port and connection security) check which of the sockets have a server of a given mail server type running on them.
-async fn check_sockets(sockets: Vec<Socket>) -> Result<HashMap<ServerConfigType, Socket>> {
-    let mut config_types = HashMap::new();
+///
+/// Every time a server is found, the config with the servers that were found so far is reported, so it can be used before the slower sockets are done.
+/// A socket that could not be checked only causes an error if no server was found on any of the other sockets.
+async fn check_sockets(
+    sockets: &[Socket],
+    domain: &str,
+    proxy: Option<&ProxyConfig>,
+    reporter: &Reporter,
+) -> Result<Option<Config>> {
+    let mut probes: Vec<Option<Probe>> = vec![None; sockets.len()];
+
+    let mut errors = Vec::new();
+
+    // First, we create a set of tasks that we then run all at the same time, handling them in the order they finish.
+    let mut tasks = FuturesUnordered::new();
+
+    for (index, socket) in sockets.iter().enumerate() {
+        reporter.report(Progress::Probing {
+            socket: socket.clone(),
+        });
+
+        tasks.push(detect_server_config(socket, proxy).map(move |result| (index, result)));
+    }
+
+    while let Some((index, result)) = tasks.next().await {
+        let socket = sockets[index].clone();
+
+        match result {
+            Ok(probe) => {
+                reporter.report(Progress::Probed {
+                    socket,
+                    server_type: probe.as_ref().map(|probe| probe.config_type().clone()),
+                    error: None,
+                });
+
+                if probe.is_some() {
+                    probes[index] = probe;
+
+                    if let Some(config) =
+                        multiserver_config(domain, found_servers(sockets, &probes))
+                    {
+                        reporter.report(Progress::Found { config });
+                    }
+                }
+            }
+            Err(error) => {
+                reporter.report(Progress::Probed {
+                    socket,
+                    server_type: None,
+                    error: Some(error.to_string()),
+                });
+
+                errors.push((index, error));
+            }
+        }
+    }
 
-    // First, we create an array of tasks that we then later run all at the same time.
-    let mut tasks = Vec::new();
+    let servers = found_servers(sockets, &probes);
 
-    for socket in sockets.iter() {
-        tasks.push(detect_server_config(socket))
+    if servers.is_empty() {
+        // The error of the socket that comes first is returned, no matter which one finished first.
+        if let Some((_, error)) = errors.into_iter().min_by_key(|(index, _)| *index) {
+            return Err(error);
+        }
     }
 
-    let results = join_all(tasks).await;
+    Ok(multiserver_config(domain, servers))
+}
+
+/// Only keeps the servers that accept the given credentials, or returns an error if none of them do.
+async fn verified_servers(
+    servers: &[ServerConfig],
+    credentials: &PasswordCredentials,
+    proxy: Option<&ProxyConfig>,
+) -> Result<Vec<ServerConfig>> {
+    let mut verified = Vec::new();
 
-    for (result, socket) in results.into_iter().zip(sockets.into_iter()) {
-        if let Some(config_type) = result? {
-            config_types.entry(config_type).or_insert(socket);
+    let mut last_error = None;
+
+    // One at a time, so a server does not see a burst of login attempts that could get the account locked.
+    for server in servers {
+        match verify_credentials(server, credentials, proxy).await {
+            Ok(()) => {
+                let mut server = server.clone();
+
+                server.set_verified(true);
+
+                verified.push(server)
+            }
+            // We can't login to every type of server ourselves, and some servers should not get the password without the user confirming it, so those are kept as they are.
+            Err(error) if matches!(error.kind(), ErrorKind::Unsupported) => {
+                verified.push(server.clone())
+            }
+            Err(error) => last_error = Some(error),
         }
     }
 
-    Ok(config_types)
+    match last_error {
+        Some(error) if verified.is_empty() => Err(error),
+        _ => Ok(verified),
+    }
 }
 
 /// Automatically detect an email providers config for a given email address
-pub async fn from_email(email_address: &str) -> Result<Config> {
-    if !validate_email(email_address) {
-        return Err(Error::new(
-            ErrorKind::ParseAddress,
-            "Given email address is invalid",
-        ));
+///
+/// If a proxy is given, the connections to the mail servers that are checked are tunneled through it.
+pub async fn from_email(email_address: &str, proxy: Option<&ProxyConfig>) -> Result<Config> {
+    let resolver = SystemResolver::new()?;
+
+    from_email_with_resolver(email_address, proxy, &resolver).await
+}
+
+/// Automatically detect an email providers config for a given email address and check that its servers accept the given credentials.
+///
+/// Servers that reject them are left out of the config and if none of the incoming or none of the outgoing servers accept them, an error is returned instead,
+/// so the user can be told their credentials are wrong before a session is created.
+pub async fn from_email_with_credentials(
+    email_address: &str,
+    credentials: &PasswordCredentials,
+    proxy: Option<&ProxyConfig>,
+) -> Result<Config> {
+    let config = from_email(email_address, proxy).await?;
+
+    let (incoming, outgoing) = match config.config_type() {
+        ConfigType::MultiServer { incoming, outgoing } => (
+            verified_servers(incoming, credentials, proxy).await?,
+            verified_servers(outgoing, credentials, proxy).await?,
+        ),
     };
 
-    let mut config: Option<Config> = None;
+    let mut verified_config = Config::new(
+        ConfigType::new_multiserver(incoming, outgoing),
+        config.provider(),
+        config.oauth2().clone(),
+        config.display_name().clone(),
+    );
+
+    verified_config.set_input_fields(config.input_fields().clone());
+
+    Ok(verified_config)
+}
+
+/// Runs a detection that reports its progress and turns it into a stream of those events, ending with the result.
+fn progress_stream<'a, F, Fut>(detection: F) -> BoxStream<'a, Progress>
+where
+    F: FnOnce(Reporter) -> Fut,
+    Fut: Future<Output = Result<Config>> + Send + 'a,
+{
+    let (sender, receiver) = mpsc::unbounded();
+
+    let reporter = Reporter::new(sender);
+
+    let detection = detection(reporter.clone());
+
+    let detection = async move {
+        match detection.await {
+            Ok(config) => reporter.report(Progress::Done { config }),
+            Err(error) => reporter.report(Progress::Failed {
+                message: error.to_string(),
+            }),
+        }
+    };
+
+    // The detection has to be polled for the events to be sent, the stream ends once it is done and every reporter is dropped.
+    stream::select(receiver.map(Some), detection.into_stream().map(|_| None))
+        .filter_map(future::ready)
+        .boxed()
+}
 
-    let mut split = email_address.split(AT_SYMBOL);
+/// Automatically detect an email providers config for a given email address, like `from_email`, but as a stream of events that describe what is happening.
+///
+/// Detecting a config can take a while, so this lets a login screen show which sources and sockets are being checked
+/// and lets the user pick a config that was found before all of the sockets are checked. The last event is either `Progress::Done` or `Progress::Failed`.
+pub fn from_email_with_progress<'a>(
+    email_address: &'a str,
+    proxy: Option<&'a ProxyConfig>,
+) -> BoxStream<'a, Progress> {
+    progress_stream(move |reporter| async move {
+        let resolver = SystemResolver::new()?;
+
+        detect(email_address, proxy, &resolver, &reporter).await
+    })
+}
+
+/// Like `from_email_with_progress`, using a given resolver to look up the DNS records of the domain.
+pub fn from_email_with_resolver_and_progress<'a>(
+    email_address: &'a str,
+    proxy: Option<&'a ProxyConfig>,
+    resolver: &'a dyn Resolver,
+) -> BoxStream<'a, Progress> {
+    progress_stream(move |reporter| async move {
+        detect(email_address, proxy, resolver, &reporter).await
+    })
+}
 
-    // Skip the prefix
-    split.next();
+/// Automatically detect an email providers config for a given email address, using a given resolver to look up the DNS records of the domain.
+pub async fn from_email_with_resolver(
+    email_address: &str,
+    proxy: Option<&ProxyConfig>,
+    resolver: &dyn Resolver,
+) -> Result<Config> {
+    detect(email_address, proxy, resolver, &Reporter::default()).await
+}
+
+async fn detect(
+    email_address: &str,
+    proxy: Option<&ProxyConfig>,
+    resolver: &dyn Resolver,
+    reporter: &Reporter,
+) -> Result<Config> {
+    let address = parse_addr_spec(email_address).map_err(|error| {
+        Error::new(
+            ErrorKind::ParseAddress,
+            format!("Given email address is invalid: {}", error),
+        )
+    })?;
+
+    // Comments and white space are stripped and the domain is normalized, so everything below works with a clean address.
+    let email_address = &address.to_string();
+
+    let mut config: Option<Config> = None;
 
-    let domain = split.next().unwrap().to_string();
+    // DNS and the config sources only know international domains by their punycode form.
+    let domain = address.ascii_domain().to_string();
 
     #[cfg(feature = "autoconfig")]
     {
-        let detected_autoconfig = autoconfig::from_domain(&domain).await?;
+        // The local configs and the bundled ISPDB don't need a network request, so we look there first, by the domain and then by the provider that handles its mail.
+        let mut provider_domains: Vec<String> = Vec::new();
 
-        match detected_autoconfig {
-            Some(detected_autoconfig) => {
-                config = Some(AutoConfigParser::parse(detected_autoconfig)?)
+        reporter.report(Progress::Trying {
+            source: DetectSource::Ispdb {
+                domain: domain.clone(),
+            },
+        });
+
+        let mut detected_autoconfig = autoconfig::ispdb::from_domain(&domain).await?;
+
+        if detected_autoconfig.is_none() {
+            provider_domains = mx::provider_domains(&domain, resolver).await;
+
+            for provider_domain in provider_domains.iter() {
+                reporter.report(Progress::Trying {
+                    source: DetectSource::Ispdb {
+                        domain: provider_domain.clone(),
+                    },
+                });
+
+                detected_autoconfig = autoconfig::ispdb::from_domain(provider_domain).await?;
+
+                if detected_autoconfig.is_some() {
+                    break;
+                }
+            }
+        }
+
+        // This also tries Autodiscover, for Exchange and Office 365 accounts on custom domains.
+        if detected_autoconfig.is_none() {
+            reporter.report(Progress::Trying {
+                source: DetectSource::Autoconfig {
+                    domain: domain.clone(),
+                },
+            });
+
+            detected_autoconfig = autoconfig::from_addr(email_address).await?;
+        }
+
+        // Custom domains are often hosted by a big provider, which we can find by looking at where the domain's mail is delivered.
+        if detected_autoconfig.is_none() {
+            for provider_domain in provider_domains.iter() {
+                reporter.report(Progress::Trying {
+                    source: DetectSource::Autoconfig {
+                        domain: provider_domain.clone(),
+                    },
+                });
+
+                detected_autoconfig = autoconfig::from_domain(provider_domain).await?;
+
+                if detected_autoconfig.is_some() {
+                    break;
+                }
             }
-            None => {}
+        }
+
+        if let Some(mut detected_autoconfig) = detected_autoconfig {
+            detected_autoconfig.resolve(&Placeholders::from_addr(email_address)?);
+
+            config = Some(AutoConfigParser::parse(detected_autoconfig)?)
         }
     }
 
-    // If we didn't find anything using autoconfig, we try the subdomains.
+    // If we didn't find anything using autoconfig, we try the servers the domain advertises and the subdomains.
     if config.is_none() {
         let mail_domain = format!("mail.{}", domain);
 
-        let mut incoming_configs: Vec<ServerConfig> = Vec::new();
-        let mut outgoing_configs: Vec<ServerConfig> = Vec::new();
+        reporter.report(Progress::Trying {
+            source: DetectSource::Srv {
+                domain: domain.clone(),
+            },
+        });
 
-        let mut sockets_to_check: Vec<Socket> = Vec::new();
+        // The advertised servers come first, so they are preferred over the guessed ones if both of them work.
+        let mut sockets_to_check: Vec<Socket> = srv::find_sockets(&domain, resolver).await;
 
         #[cfg(feature = "imap")]
         {
@@ -144,28 +447,7 @@ pub async fn from_email(email_address: &str) -> Result<Config> {
             }
         }
 
-        let results = check_sockets(sockets_to_check).await?;
-
-        for (config_type, socket) in results {
-            let is_outgoing = config_type.is_outgoing();
-
-            let config = socket.into_server_config(config_type);
-
-            if is_outgoing {
-                outgoing_configs.push(config);
-            } else {
-                incoming_configs.push(config);
-            }
-        }
-
-        if incoming_configs.len() > 0 || outgoing_configs.len() > 0 {
-            config = Some(Config::new(
-                ConfigType::new_multiserver(incoming_configs, outgoing_configs),
-                domain.as_str(),
-                None,
-                Some(domain.to_string()),
-            ));
-        }
+        config = check_sockets(&sockets_to_check, &domain, proxy, reporter).await?;
     }
 
     match config {
@@ -177,13 +459,185 @@ pub async fn from_email(email_address: &str) -> Result<Config> {
     }
 }
 
+#[cfg(test)]
 mod test {
+    use autoconfig::dns::MxRecord;
+    use futures::{channel::mpsc, StreamExt};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use crate::{
+        detect::{
+            stub::StubResolver,
+            types::{ConfigType, ServerConfigType, Socket},
+            ServerSource,
+        },
+        types::ConnectionSecurity,
+    };
+
+    use super::{progress::Reporter, DetectSource, Progress};
+
+    /// Starts a server that greets every connection like an IMAP server and answers a CAPABILITY command.
+    async fn imap_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+
+                    let _ = stream.write_all(b"* OK ready\r\n").await;
+
+                    let mut command = String::new();
+
+                    let _ = stream.read_line(&mut command).await;
+
+                    if let Some(tag) = command.strip_suffix(" CAPABILITY\r\n") {
+                        let response = format!("* CAPABILITY IMAP4rev1\r\n{} OK done\r\n", tag);
+
+                        let _ = stream.write_all(response.as_bytes()).await;
+                    }
+                });
+            }
+        });
+
+        port
+    }
+
     #[tokio::test]
     async fn from_email() {
         let email = "mail@samtaen.nl";
 
-        let config = super::from_email(email).await.unwrap();
+        let config = super::from_email(email, None).await.unwrap();
 
         println!("{}", config.to_json().unwrap());
     }
+
+    #[tokio::test]
+    async fn progress() {
+        let mut resolver = StubResolver::default();
+
+        resolver.add_mx("example.com", vec![MxRecord::new("aspmx.l.google.com", 1)]);
+
+        // Gmail is in the bundled ISPDB snapshot, so this does not need a network connection.
+        let events: Vec<Progress> =
+            super::from_email_with_resolver_and_progress("john@example.com", None, &resolver)
+                .collect()
+                .await;
+
+        let sources: Vec<&DetectSource> = events
+            .iter()
+            .filter_map(|event| match event {
+                Progress::Trying { source } => Some(source),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            sources,
+            vec![
+                &DetectSource::Ispdb {
+                    domain: String::from("example.com")
+                },
+                &DetectSource::Ispdb {
+                    domain: String::from("gmail.com")
+                }
+            ]
+        );
+
+        assert!(matches!(
+            events.last(),
+            Some(Progress::Done { config }) if config.provider() == "googlemail.com"
+        ));
+    }
+
+    #[tokio::test]
+    async fn sockets_progress() {
+        let port = imap_server().await;
+
+        let (sender, receiver) = mpsc::unbounded();
+
+        let config = super::check_sockets(
+            &[Socket::new("127.0.0.1", port, ConnectionSecurity::Plain)],
+            "example.com",
+            None,
+            &Reporter::new(sender),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let ConfigType::MultiServer { incoming, outgoing } = config.config_type();
+
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].r#type(), &ServerConfigType::Imap);
+        assert!(outgoing.is_empty());
+
+        let events: Vec<Progress> = receiver.collect().await;
+
+        assert!(matches!(events[0], Progress::Probing { .. }));
+        assert!(matches!(
+            events[1],
+            Progress::Probed {
+                server_type: Some(ServerConfigType::Imap),
+                error: None,
+                ..
+            }
+        ));
+        assert!(matches!(events[2], Progress::Found { .. }));
+        assert_eq!(events.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_sockets() {
+        let port = imap_server().await;
+        let srv_port = imap_server().await;
+
+        // Nothing listens on this port once the listener is dropped, so connecting to it fails.
+        let closed_port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let closed = Socket::new("127.0.0.1", closed_port, ConnectionSecurity::Plain);
+
+        let mut srv = Socket::new("127.0.0.1", srv_port, ConnectionSecurity::Plain);
+
+        srv.set_source(ServerSource::Srv);
+
+        let config = super::check_sockets(
+            &[
+                closed.clone(),
+                Socket::new("127.0.0.1", port, ConnectionSecurity::Plain),
+                srv,
+            ],
+            "example.com",
+            None,
+            &Reporter::default(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let ConfigType::MultiServer { incoming, .. } = config.config_type();
+
+        // The servers that were found are still ranked by how confident we are about them.
+        assert_eq!(incoming.len(), 2);
+        assert_eq!(incoming[0].port(), &srv_port);
+        assert_eq!(incoming[1].port(), &port);
+        assert!(incoming[0].confidence() > incoming[1].confidence());
+
+        // Without any server, the error of the failed socket is returned.
+        assert!(
+            super::check_sockets(&[closed], "example.com", None, &Reporter::default())
+                .await
+                .is_err()
+        );
+    }
 }
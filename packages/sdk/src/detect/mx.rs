@@ -0,0 +1,108 @@
+use autoconfig::dns::Resolver;
+
+/// Known suffixes of mail exchange hosts, mapped to the domain of the provider that runs them.
+///
+/// Providers that host mail for custom domains don't publish an autoconfig file for every one of those domains, but they do for their own.
+const PROVIDERS: [(&str, &str); 10] = [
+    ("aspmx.l.google.com", "gmail.com"),
+    ("googlemail.com", "gmail.com"),
+    ("mail.protection.outlook.com", "office365.com"),
+    ("olc.protection.outlook.com", "outlook.com"),
+    ("yahoodns.net", "yahoo.com"),
+    ("messagingengine.com", "fastmail.com"),
+    ("zoho.com", "zoho.com"),
+    ("zoho.eu", "zoho.eu"),
+    ("mail.icloud.com", "icloud.com"),
+    ("mailbox.org", "mailbox.org"),
+];
+
+/// Checks whether a host is the given domain or one of its subdomains.
+fn is_within(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// The provider domain for a mail exchange host, if it is one we know.
+fn provider_for_host(host: &str) -> Option<&'static str> {
+    let host = host.to_ascii_lowercase();
+
+    PROVIDERS
+        .iter()
+        .find(|(suffix, _)| is_within(&host, suffix))
+        .map(|(_, provider)| *provider)
+}
+
+/// Looks up the MX records of a domain and returns the domains of the providers that handle its mail, most preferred first.
+///
+/// Only hosts of known providers are used. The domain that owns any other host can't be derived from its name without a
+/// list of public suffixes, taking the last two labels would turn `mx.example.co.uk` into `co.uk`.
+/// A failed lookup returns nothing, as this is only a way to find more configs to try.
+pub async fn provider_domains(domain: &str, resolver: &dyn Resolver) -> Vec<String> {
+    let records = resolver.mx(domain).await.unwrap_or_default();
+
+    let domain = domain.to_ascii_lowercase();
+
+    let mut providers: Vec<String> = Vec::new();
+
+    for record in records {
+        let provider = match provider_for_host(record.exchange()) {
+            Some(provider) => provider.to_string(),
+            None => continue,
+        };
+
+        if is_within(&domain, &provider) || providers.contains(&provider) {
+            continue;
+        }
+
+        providers.push(provider);
+    }
+
+    providers
+}
+
+#[cfg(test)]
+mod test {
+    use autoconfig::dns::MxRecord;
+
+    use crate::detect::stub::StubResolver;
+
+    use super::provider_domains;
+
+    #[tokio::test]
+    async fn providers() {
+        let mut resolver = StubResolver::default();
+
+        resolver
+            .add_mx(
+                "example.com",
+                vec![
+                    MxRecord::new("ASPMX.L.GOOGLE.COM", 1),
+                    MxRecord::new("alt1.aspmx.l.google.com", 5),
+                    MxRecord::new("mx.example.com", 10),
+                    MxRecord::new("mx.mailhost.net", 20),
+                ],
+            )
+            .add_mx("example.co.uk", vec![MxRecord::new("mx.example.co.uk", 10)])
+            .add_mx(
+                "contoso.com",
+                vec![MxRecord::new("contoso-com.mail.protection.outlook.com", 0)],
+            )
+            .add_failing("broken.com");
+
+        assert_eq!(
+            provider_domains("example.com", &resolver).await,
+            vec!["gmail.com"]
+        );
+
+        assert!(provider_domains("example.co.uk", &resolver)
+            .await
+            .is_empty());
+
+        assert_eq!(
+            provider_domains("contoso.com", &resolver).await,
+            vec!["office365.com"]
+        );
+
+        assert!(provider_domains("broken.com", &resolver).await.is_empty());
+        assert!(provider_domains("unknown.com", &resolver).await.is_empty());
+    }
+}
@@ -1,17 +1,20 @@
 #[cfg(feature = "autoconfig")]
 use autoconfig::{
     self,
+    placeholders::has_placeholders,
     types::config::{
         AuthenticationType as AutoConfigAuthenticationType, Config as AutoConfig,
-        OAuth2Config as AutoConfigOAuth2Config, SecurityType as AutoConfigSecurityType, Server,
-        ServerType as AutoConfigServerType,
+        OAuth2Config as AutoConfigOAuth2Config, Pop3Config as AutoConfigPop3Config,
+        SecurityType as AutoConfigSecurityType, Server, ServerType as AutoConfigServerType,
+        Source as AutoConfigSource, Transport as AutoConfigTransport,
     },
 };
 
-use crate::types::{ConnectionSecurity, Result};
+use crate::types::{ConnectionSecurity, DownloadOptions, Result};
 
 use super::{
-    types::OAuth2Config, AuthenticationType, Config, ConfigType, ServerConfig, ServerConfigType,
+    types::{InputField, OAuth2Config, ServerSource},
+    AuthenticationType, Config, ConfigType, ServerConfig, ServerConfigType,
 };
 
 #[cfg(feature = "autoconfig")]
@@ -19,7 +22,22 @@ pub struct AutoConfigParser;
 
 #[cfg(feature = "autoconfig")]
 impl AutoConfigParser {
-    fn map_server_to_server_config(server: &Server) -> Option<ServerConfig> {
+    fn server_source(autoconfig: &AutoConfig) -> ServerSource {
+        match autoconfig.source() {
+            AutoConfigSource::LocalDir => ServerSource::LocalConfig,
+            AutoConfigSource::BundledIspdb | AutoConfigSource::Ispdb => ServerSource::Ispdb,
+            AutoConfigSource::Autodiscover => ServerSource::Autodiscover,
+            // These are only found if the user allowed it, and are as trustworthy as the lookup that led to them.
+            AutoConfigSource::AutodiscoverRedirect => ServerSource::HttpAutoconfig,
+            AutoConfigSource::AutodiscoverSrv => ServerSource::Srv,
+            _ => match autoconfig.transport() {
+                Some(AutoConfigTransport::Http) => ServerSource::HttpAutoconfig,
+                _ => ServerSource::Autoconfig,
+            },
+        }
+    }
+
+    fn map_server_to_server_config(server: &Server, source: &ServerSource) -> Option<ServerConfig> {
         {
             let domain: String = server.hostname()?.into();
 
@@ -57,12 +75,34 @@ impl AutoConfigParser {
                 AutoConfigServerType::Exchange => ServerConfigType::Exchange,
             };
 
-            let server_config = ServerConfig::new(server_type, port, domain, security, auth_type);
+            let mut server_config =
+                ServerConfig::new(server_type, port, domain, security, auth_type);
+
+            server_config.set_source(source.clone());
+
+            // A username that still has placeholders in it depends on an input field the user has to fill in first.
+            if let Some(username) = server.username() {
+                if !has_placeholders(username) {
+                    server_config.set_username(username);
+                }
+            }
+
+            if let Some(pop3_config) = server.pop3() {
+                server_config.set_download_options(Self::parse_pop3_config(pop3_config));
+            }
 
             Some(server_config)
         }
     }
 
+    fn parse_pop3_config(config: &AutoConfigPop3Config) -> DownloadOptions {
+        DownloadOptions::new(
+            *config.leave_messages_on_server(),
+            config.time_to_leave_messages_on_server(),
+            config.check_interval(),
+        )
+    }
+
     fn parse_oauth2_config(config: &AutoConfigOAuth2Config) -> OAuth2Config {
         OAuth2Config::new(
             config.token_url().into(),
@@ -71,6 +111,37 @@ impl AutoConfigParser {
         )
     }
 
+    /// The input fields without a default that a server's username still refers to, which the user has to fill in.
+    fn unresolved_input_fields(autoconfig: &AutoConfig) -> Vec<InputField> {
+        let provider = autoconfig.email_provider();
+
+        let usernames: Vec<&str> = provider
+            .incoming_servers()
+            .into_iter()
+            .chain(provider.outgoing_servers())
+            .filter_map(|server| server.username())
+            .collect();
+
+        provider
+            .input_fields()
+            .into_iter()
+            .filter(|input_field| input_field.default().is_none())
+            .filter(|input_field| {
+                let placeholder = format!("%{}%", input_field.key());
+
+                usernames
+                    .iter()
+                    .any(|username| username.contains(&placeholder))
+            })
+            .map(|input_field| {
+                InputField::new(
+                    input_field.key(),
+                    input_field.label().map(|label| label.to_string()),
+                )
+            })
+            .collect()
+    }
+
     pub fn parse(autoconfig: AutoConfig) -> Result<Config> {
         let provider: String = autoconfig.email_provider().id().into();
 
@@ -79,25 +150,29 @@ impl AutoConfigParser {
             .display_name()
             .map(|name| name.to_string());
 
+        let source = Self::server_source(&autoconfig);
+
         let incoming: Vec<ServerConfig> = autoconfig
             .email_provider()
             .incoming_servers()
             .iter()
-            .filter_map(|server| Self::map_server_to_server_config(server))
+            .filter_map(|server| Self::map_server_to_server_config(server, &source))
             .collect();
 
         let outgoing: Vec<ServerConfig> = autoconfig
             .email_provider()
             .outgoing_servers()
             .iter()
-            .filter_map(|server| Self::map_server_to_server_config(server))
+            .filter_map(|server| Self::map_server_to_server_config(server, &source))
             .collect();
 
-        let config_type = ConfigType::MultiServer { incoming, outgoing };
+        let config_type = ConfigType::new_multiserver(incoming, outgoing);
 
         let oauth2_config = autoconfig.oauth2().map(Self::parse_oauth2_config);
 
-        let config = Config::new(config_type, provider, oauth2_config, display_name);
+        let mut config = Config::new(config_type, provider, oauth2_config, display_name);
+
+        config.set_input_fields(Self::unresolved_input_fields(&autoconfig));
 
         Ok(config)
     }
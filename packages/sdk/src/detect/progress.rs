@@ -0,0 +1,62 @@
+use futures::channel::mpsc::UnboundedSender;
+use serde::Serialize;
+
+use super::types::{Config, ServerConfigType, Socket};
+
+/// A place where we look for the config of an email provider.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum DetectSource {
+    /// The local directory with configs and the bundled ISPDB snapshot, which don't need a network request.
+    Ispdb { domain: String },
+    /// The autoconfig files and Autodiscover endpoints of a domain and Thunderbird's online ISPDB.
+    Autoconfig { domain: String },
+    /// The SRV records a domain uses to advertise its mail servers.
+    Srv { domain: String },
+}
+
+/// Something that happened while detecting the config for an email address.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Progress {
+    /// We started looking for a config at a given source.
+    Trying { source: DetectSource },
+    /// We started checking which mail server is running on a socket.
+    Probing { socket: Socket },
+    /// We finished checking a socket, the server type is the kind of mail server we found on it, if any.
+    #[serde(rename_all = "camelCase")]
+    Probed {
+        socket: Socket,
+        server_type: Option<ServerConfigType>,
+        error: Option<String>,
+    },
+    /// We found a usable config, but are still checking other sockets, so a more complete one can follow.
+    ///
+    /// The user can already pick this one if they don't want to wait for the rest.
+    Found { config: Config },
+    /// We are done and found a config, this is always the last event.
+    Done { config: Config },
+    /// We are done, but could not detect a config, this is always the last event.
+    Failed { message: String },
+}
+
+/// Sends progress events to whoever is listening, if anyone is.
+#[derive(Default, Clone)]
+pub struct Reporter {
+    sender: Option<UnboundedSender<Progress>>,
+}
+
+impl Reporter {
+    pub fn new(sender: UnboundedSender<Progress>) -> Self {
+        Self {
+            sender: Some(sender),
+        }
+    }
+
+    pub fn report(&self, progress: Progress) {
+        if let Some(sender) = &self.sender {
+            // The receiver is gone if nobody is interested in the events anymore, which is fine.
+            let _ = sender.unbounded_send(progress);
+        }
+    }
+}
@@ -1,180 +1,658 @@
 use std::time::Duration;
 
-use async_native_tls::TlsConnector;
 use async_tcp::{types::ErrorKind as TcpErrorKind, Config as TcpConfig, TcpClient};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use futures::{future::select_ok, FutureExt};
-use tokio::{
-    io::{AsyncRead, AsyncWrite},
-    net::ToSocketAddrs,
-};
+use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::types::{ConnectionSecurity, Error, ErrorKind, Result};
+use crate::{
+    session::PasswordCredentials,
+    types::{ConnectionSecurity, Error, ErrorKind, ProxyConfig, Result},
+};
 
-use super::types::{ServerConfigType, Socket};
+use super::types::{
+    AuthenticationType, Probe, ServerConfig, ServerConfigType, ServerSource, Socket,
+};
 // TODO: Make all of these functions use a singular socket instead of each creating their own connection.
 
-/// Checks if a given connection is connected to an SMTP supporting server.
+/// The kind of authentication a SASL mechanism uses.
+fn mechanism_auth_type(mechanism: &str) -> AuthenticationType {
+    let mechanism = mechanism.to_ascii_uppercase();
+
+    match mechanism.as_str() {
+        "PLAIN" | "LOGIN" => AuthenticationType::ClearText,
+        "CRAM-MD5" | "DIGEST-MD5" => AuthenticationType::Encrypted,
+        "XOAUTH2" | "OAUTHBEARER" => AuthenticationType::OAuth2,
+        _ if mechanism.starts_with("SCRAM-") => AuthenticationType::Encrypted,
+        _ => AuthenticationType::Unknown,
+    }
+}
+
+/// Adds the kinds of authentication that the given SASL mechanisms use, ignoring the ones we don't know and the ones we already have.
+fn add_mechanisms<'a, I: IntoIterator<Item = &'a str>>(
+    auth_types: &mut Vec<AuthenticationType>,
+    mechanisms: I,
+) {
+    for mechanism in mechanisms {
+        add_auth_type(auth_types, mechanism_auth_type(mechanism));
+    }
+}
+
+fn add_auth_type(auth_types: &mut Vec<AuthenticationType>, auth_type: AuthenticationType) {
+    if auth_type != AuthenticationType::Unknown && !auth_types.contains(&auth_type) {
+        auth_types.push(auth_type);
+    }
+}
+
+fn credentials_rejected<S: Into<String>>(message: S) -> Error {
+    Error::new(ErrorKind::InvalidCredentials, message)
+}
+
+/// Sends an EHLO and returns the extensions the server supports, in uppercase, or `None` if the server did not accept it.
+#[cfg(feature = "smtp")]
+async fn smtp_extensions<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+) -> Result<Option<Vec<String>>> {
+    client.send_bytes("EHLO dust-mail").await?;
+
+    let reply = client.read_smtp_reply().await?;
+
+    if reply.code() != &250 {
+        return Ok(None);
+    }
+
+    // The first line only contains the name of the server.
+    let extensions = reply
+        .lines()
+        .iter()
+        .skip(1)
+        .map(|extension| extension.to_ascii_uppercase())
+        .collect();
+
+    Ok(Some(extensions))
+}
+
+/// The SASL mechanisms the server advertises in its extensions.
+#[cfg(feature = "smtp")]
+fn smtp_mechanisms(extensions: &[String]) -> Vec<&str> {
+    let mut mechanisms = Vec::new();
+
+    for extension in extensions {
+        // Some older servers advertise their mechanisms as `AUTH=PLAIN LOGIN`.
+        let mut words = extension.split([' ', '=']);
+
+        if words.next() == Some("AUTH") {
+            mechanisms.extend(words.filter(|word| !word.is_empty()));
+        }
+    }
+
+    mechanisms
+}
+
+#[cfg(feature = "smtp")]
+fn smtp_auth_types(extensions: &[String]) -> Vec<AuthenticationType> {
+    let mut auth_types = Vec::new();
+
+    add_mechanisms(&mut auth_types, smtp_mechanisms(extensions));
+
+    auth_types
+}
+
+/// Asks the server to upgrade the connection using STARTTLS, returning whether it agreed to it.
+#[cfg(feature = "smtp")]
+async fn smtp_starttls<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+) -> Result<bool> {
+    client.send_bytes("STARTTLS").await?;
+
+    Ok(client.read_smtp_reply().await?.code() == &220)
+}
+
+/// Checks if a given connection is connected to an SMTP supporting server and finds out how we can login to it.
+#[cfg(feature = "smtp")]
+async fn probe_smtp<S: AsyncRead + AsyncWrite + Unpin>(
+    mut client: TcpClient<S>,
+    host: &str,
+) -> Result<Option<Probe>> {
+    // Read greeting, which can span multiple lines
+    let greeting = client.read_smtp_reply().await?;
+
+    if greeting.code() != &220 {
+        client.close().await?;
+
+        return Ok(None);
+    }
+
+    let mentions_smtp = greeting
+        .lines()
+        .iter()
+        .any(|line| line.to_ascii_lowercase().contains("smtp"));
+
+    let extensions = smtp_extensions(&mut client).await?;
+
+    // Not every server mentions smtp in its greeting, but every smtp server should accept a greeting of its own.
+    if !mentions_smtp && extensions.is_none() {
+        client.close().await?;
+
+        return Ok(None);
+    }
+
+    let mut extensions = extensions.unwrap_or_default();
+
+    let starttls = extensions.iter().any(|extension| extension == "STARTTLS");
+
+    // Most servers only advertise their login mechanisms once the connection is secure.
+    if starttls && smtp_starttls(&mut client).await? {
+        let mut client = client.starttls(host).await?;
+
+        extensions = smtp_extensions(&mut client).await?.unwrap_or_default();
+
+        client.close().await?;
+    } else {
+        client.close().await?;
+    }
+
+    Ok(Some(Probe::new(
+        ServerConfigType::Smtp,
+        smtp_auth_types(&extensions),
+        starttls,
+    )))
+}
+
+/// Logs in using the first mechanism the server advertises that we support, PLAIN or LOGIN.
+///
+/// A server that does not advertise either of them returns an unsupported error, because that says nothing about the credentials.
 #[cfg(feature = "smtp")]
-async fn is_smtp<S: AsyncRead + AsyncWrite + Unpin>(mut client: TcpClient<S>) -> Result<bool> {
+async fn login_smtp<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+    credentials: &PasswordCredentials,
+) -> Result<bool> {
+    let extensions = match smtp_extensions(client).await? {
+        Some(extensions) => extensions,
+        None => return Ok(false),
+    };
+
+    let mechanisms = smtp_mechanisms(&extensions);
+
+    if mechanisms.contains(&"PLAIN") {
+        let response = BASE64.encode(format!(
+            "\0{}\0{}",
+            credentials.username(),
+            credentials.password()
+        ));
+
+        client
+            .send_bytes(format!("AUTH PLAIN {}", response))
+            .await?;
+
+        return Ok(client.read_smtp_reply().await?.code() == &235);
+    }
+
+    if mechanisms.contains(&"LOGIN") {
+        client.send_bytes("AUTH LOGIN").await?;
+
+        // The server asks for the username and then for the password.
+        for value in [credentials.username(), credentials.password()] {
+            if client.read_smtp_reply().await?.code() != &334 {
+                return Ok(false);
+            }
+
+            client.send_bytes(BASE64.encode(value)).await?;
+        }
+
+        return Ok(client.read_smtp_reply().await?.code() == &235);
+    }
+
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "The smtp server does not support a login mechanism we can verify credentials with",
+    ))
+}
+
+#[cfg(feature = "smtp")]
+async fn verify_smtp<S: AsyncRead + AsyncWrite + Unpin>(
+    mut client: TcpClient<S>,
+    host: &str,
+    starttls: bool,
+    credentials: &PasswordCredentials,
+) -> Result<bool> {
+    client.read_smtp_reply().await?;
+
+    if starttls {
+        if !smtp_starttls(&mut client).await? {
+            return Ok(false);
+        }
+
+        let mut client = client.starttls(host).await?;
+
+        // Everything we knew about the server before the upgrade is forgotten, so we have to greet it again.
+        return login_smtp(&mut client, credentials).await;
+    }
+
+    login_smtp(&mut client, credentials).await
+}
+
+#[cfg(feature = "imap")]
+async fn imap_capabilities<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+    tag: &str,
+) -> Result<Vec<String>> {
+    client.send_bytes(format!("{} CAPABILITY", tag)).await?;
+
+    Ok(client.read_imap_response(tag).await?.capabilities())
+}
+
+#[cfg(feature = "imap")]
+fn imap_auth_types(capabilities: &[String]) -> Vec<AuthenticationType> {
+    let mut auth_types = Vec::new();
+
+    add_mechanisms(
+        &mut auth_types,
+        capabilities
+            .iter()
+            .filter_map(|capability| capability.strip_prefix("AUTH=")),
+    );
+
+    // The LOGIN command sends the password in clear text and is supported unless the server says it isn't.
+    if !capabilities
+        .iter()
+        .any(|capability| capability == "LOGINDISABLED")
+    {
+        add_auth_type(&mut auth_types, AuthenticationType::ClearText);
+    }
+
+    auth_types
+}
+
+#[cfg(feature = "imap")]
+async fn imap_starttls<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+) -> Result<bool> {
+    client.send_bytes("A0002 STARTTLS").await?;
+
+    Ok(client.read_imap_response("A0002").await?.is_ok())
+}
+
+/// Checks if a given connection is connected to an IMAP supporting server and finds out how we can login to it.
+#[cfg(feature = "imap")]
+async fn probe_imap<S: AsyncRead + AsyncWrite + Unpin>(
+    mut client: TcpClient<S>,
+    host: &str,
+) -> Result<Option<Probe>> {
     // Read greeting
-    let greeting = client.read_response().await?;
+    client.read_response().await?;
+
+    let mut capabilities = imap_capabilities(&mut client, "A0001").await?;
+
+    let is_imap = capabilities
+        .iter()
+        .any(|capability| capability == "IMAP4REV1" || capability == "IMAP4REV2");
+
+    if !is_imap {
+        client.close().await?;
+
+        return Ok(None);
+    }
 
-    client.close().await?;
+    let starttls = capabilities
+        .iter()
+        .any(|capability| capability == "STARTTLS");
 
-    let greeting = greeting.trim().to_ascii_lowercase();
+    // Most servers only advertise their login mechanisms once the connection is secure.
+    if starttls && imap_starttls(&mut client).await? {
+        let mut client = client.starttls(host).await?;
 
-    let is_smtp = greeting.contains("smtp") || greeting.contains("esmtp");
+        capabilities = imap_capabilities(&mut client, "A0003").await?;
 
-    Ok(is_smtp)
+        client.close().await?;
+    } else {
+        client.close().await?;
+    }
+
+    Ok(Some(Probe::new(
+        ServerConfigType::Imap,
+        imap_auth_types(&capabilities),
+        starttls,
+    )))
+}
+
+/// Formats a string as an IMAP quoted string.
+#[cfg(feature = "imap")]
+fn imap_quote(input: &str) -> String {
+    format!("\"{}\"", input.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
-/// Checks if a given connection is connected to an IMAP supporting server.
+/// Logs in using the LOGIN command, unless the server says it should not be used on this connection.
 #[cfg(feature = "imap")]
-async fn is_imap<S: AsyncRead + AsyncWrite + Unpin>(mut client: TcpClient<S>) -> Result<bool> {
+async fn login_imap<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+    credentials: &PasswordCredentials,
+) -> Result<bool> {
+    let capabilities = imap_capabilities(client, "A0003").await?;
+
+    if capabilities
+        .iter()
+        .any(|capability| capability == "LOGINDISABLED")
+    {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "The imap server does not allow the LOGIN command on this connection",
+        ));
+    }
+
+    client
+        .send_bytes(format!(
+            "A0004 LOGIN {} {}",
+            imap_quote(credentials.username()),
+            imap_quote(credentials.password())
+        ))
+        .await?;
+
+    let is_ok = client.read_imap_response("A0004").await?.is_ok();
+
+    if is_ok {
+        client.send_bytes("A0005 LOGOUT").await?;
+    }
+
+    Ok(is_ok)
+}
+
+#[cfg(feature = "imap")]
+async fn verify_imap<S: AsyncRead + AsyncWrite + Unpin>(
+    mut client: TcpClient<S>,
+    host: &str,
+    starttls: bool,
+    credentials: &PasswordCredentials,
+) -> Result<bool> {
     // Read greeting
     client.read_response().await?;
 
-    let response = client.send_command("A0001 CAPABILITY").await?;
+    if starttls {
+        if !imap_starttls(&mut client).await? {
+            return Ok(false);
+        }
+
+        let mut client = client.starttls(host).await?;
+
+        return login_imap(&mut client, credentials).await;
+    }
+
+    login_imap(&mut client, credentials).await
+}
+
+#[cfg(feature = "pop")]
+fn is_pop_ok(response: &str) -> bool {
+    response.starts_with("+OK")
+}
+
+/// Returns the capabilities of the server, in uppercase.
+#[cfg(feature = "pop")]
+async fn pop_capabilities<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+) -> Result<Vec<String>> {
+    client.send_bytes("CAPA").await?;
+
+    // Servers that don't support the CAPA command only support the commands from the original specification, which includes USER and PASS.
+    if !is_pop_ok(&client.read_response().await?) {
+        return Ok(vec![String::from("USER")]);
+    }
+
+    let capabilities = client
+        .read_pop_multi_line()
+        .await?
+        .iter()
+        .map(|capability| capability.to_ascii_uppercase())
+        .collect();
+
+    Ok(capabilities)
+}
+
+#[cfg(feature = "pop")]
+fn pop_auth_types(greeting: &str, capabilities: &[String]) -> Vec<AuthenticationType> {
+    let mut auth_types = Vec::new();
+
+    for capability in capabilities {
+        let mut words = capability.split(' ');
+
+        match words.next() {
+            Some("USER") => add_auth_type(&mut auth_types, AuthenticationType::ClearText),
+            Some("SASL") => add_mechanisms(&mut auth_types, words),
+            _ => {}
+        }
+    }
+
+    // A timestamp in the greeting means the server supports APOP, which does not send the password itself.
+    let has_timestamp = greeting
+        .find('<')
+        .map(|start| greeting[start..].contains('>'))
+        .unwrap_or(false);
+
+    if has_timestamp {
+        add_auth_type(&mut auth_types, AuthenticationType::Encrypted);
+    }
+
+    auth_types
+}
+
+#[cfg(feature = "pop")]
+async fn pop_starttls<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+) -> Result<bool> {
+    client.send_bytes("STLS").await?;
+
+    Ok(is_pop_ok(&client.read_response().await?))
+}
+
+/// Checks if a given connection is connected to a POP supporting server and finds out how we can login to it.
+#[cfg(feature = "pop")]
+async fn probe_pop<S: AsyncRead + AsyncWrite + Unpin>(
+    mut client: TcpClient<S>,
+    host: &str,
+) -> Result<Option<Probe>> {
+    let greeting = client.read_response().await?;
+
+    if !is_pop_ok(&greeting) {
+        client.close().await?;
+
+        return Ok(None);
+    }
+
+    let mut capabilities = pop_capabilities(&mut client).await?;
+
+    let starttls = capabilities.iter().any(|capability| capability == "STLS");
+
+    // Most servers only advertise their login mechanisms once the connection is secure.
+    if starttls && pop_starttls(&mut client).await? {
+        let mut client = client.starttls(host).await?;
+
+        capabilities = pop_capabilities(&mut client).await?;
+
+        client.close().await?;
+    } else {
+        client.close().await?;
+    }
+
+    Ok(Some(Probe::new(
+        ServerConfigType::Pop,
+        pop_auth_types(&greeting, &capabilities),
+        starttls,
+    )))
+}
+
+#[cfg(feature = "pop")]
+async fn login_pop<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut TcpClient<S>,
+    credentials: &PasswordCredentials,
+) -> Result<bool> {
+    client
+        .send_bytes(format!("USER {}", credentials.username()))
+        .await?;
+
+    if !is_pop_ok(&client.read_response().await?) {
+        return Ok(false);
+    }
+
+    client
+        .send_bytes(format!("PASS {}", credentials.password()))
+        .await?;
+
+    let is_ok = is_pop_ok(&client.read_response().await?);
+
+    if is_ok {
+        client.send_bytes("QUIT").await?;
+    }
+
+    Ok(is_ok)
+}
+
+#[cfg(feature = "pop")]
+async fn verify_pop<S: AsyncRead + AsyncWrite + Unpin>(
+    mut client: TcpClient<S>,
+    host: &str,
+    starttls: bool,
+    credentials: &PasswordCredentials,
+) -> Result<bool> {
+    if !is_pop_ok(&client.read_response().await?) {
+        return Ok(false);
+    }
+
+    if starttls {
+        if !pop_starttls(&mut client).await? {
+            return Ok(false);
+        }
 
-    client.close().await?;
+        let mut client = client.starttls(host).await?;
 
-    let response = response.trim().to_ascii_lowercase();
+        return login_pop(&mut client, credentials).await;
+    }
 
-    let is_imap = response
-        .split(' ')
-        .find(|capability| capability == &"imap4rev1")
-        .is_some();
+    login_pop(&mut client, credentials).await
+}
 
-    Ok(is_imap)
+fn not_found<S: Into<String>>(message: S) -> Error {
+    Error::new(ErrorKind::ConfigNotFound, message)
 }
 
 #[cfg(feature = "imap")]
-async fn check_for_imap<T: ToSocketAddrs, S: AsRef<str>>(
+async fn check_for_imap<S: AsRef<str>>(
     security: &ConnectionSecurity,
-    addr: &T,
     domain: S,
+    port: u16,
     tcp_config: &Option<TcpConfig>,
-) -> Result<ServerConfigType> {
-    let is_imap = match security {
+) -> Result<Probe> {
+    let domain = domain.as_ref();
+
+    let probe = match security {
         ConnectionSecurity::Tls => {
-            let connection = async_tcp::connect(&addr, domain, tcp_config.clone()).await?;
+            let connection = async_tcp::connect(domain, port, tcp_config.clone()).await?;
 
-            is_imap(connection).await?
+            probe_imap(connection, domain).await?
         }
         _ => {
-            let connection = async_tcp::connect_plain(&addr, tcp_config.clone()).await?;
+            let connection = async_tcp::connect_plain(domain, port, tcp_config.clone()).await?;
 
-            is_imap(connection).await?
+            probe_imap(connection, domain).await?
         }
     };
 
-    if is_imap {
-        Ok(ServerConfigType::Imap)
-    } else {
-        Err(Error::new(
-            ErrorKind::ConfigNotFound,
-            "Given server is not an imap server",
-        ))
-    }
+    probe.ok_or_else(|| not_found("Given server is not an imap server"))
 }
 
 #[cfg(feature = "smtp")]
-async fn check_for_smtp<T: ToSocketAddrs, S: AsRef<str>>(
+async fn check_for_smtp<S: AsRef<str>>(
     security: &ConnectionSecurity,
-    addr: &T,
     domain: S,
+    port: u16,
     tcp_config: &Option<TcpConfig>,
-) -> Result<ServerConfigType> {
-    let is_smtp = match security {
+) -> Result<Probe> {
+    let domain = domain.as_ref();
+
+    let probe = match security {
         ConnectionSecurity::Tls => {
-            let connection = async_tcp::connect(&addr, domain, tcp_config.clone()).await?;
+            let connection = async_tcp::connect(domain, port, tcp_config.clone()).await?;
 
-            is_smtp(connection).await?
+            probe_smtp(connection, domain).await?
         }
         _ => {
-            let connection = async_tcp::connect_plain(&addr, tcp_config.clone()).await?;
+            let connection = async_tcp::connect_plain(domain, port, tcp_config.clone()).await?;
 
-            is_smtp(connection).await?
+            probe_smtp(connection, domain).await?
         }
     };
 
-    if is_smtp {
-        Ok(ServerConfigType::Smtp)
-    } else {
-        Err(Error::new(
-            ErrorKind::ConfigNotFound,
-            "Given server is not an smtp server",
-        ))
-    }
+    probe.ok_or_else(|| not_found("Given server is not an smtp server"))
 }
 
 #[cfg(feature = "pop")]
-async fn check_for_pop<T: ToSocketAddrs, S: AsRef<str>>(
+async fn check_for_pop<S: AsRef<str>>(
     security: &ConnectionSecurity,
-    addr: &T,
     domain: S,
+    port: u16,
     tcp_config: &Option<TcpConfig>,
-) -> Result<ServerConfigType> {
-    let tcp_config = tcp_config.as_ref().cloned().unwrap_or_default();
+) -> Result<Probe> {
+    let domain = domain.as_ref();
 
-    let connection_timeout = Some(tcp_config.into_timeout());
-
-    // The async-pop package checks the greeting for us when we call the connect function, so we don't have to do it ourselves.
-    let is_pop = match security {
+    let probe = match security {
         ConnectionSecurity::Tls => {
-            let tls = TlsConnector::new();
+            let connection = async_tcp::connect(domain, port, tcp_config.clone()).await?;
 
-            async_pop3::connect(&addr, domain.as_ref(), &tls, connection_timeout)
-                .await
-                .is_ok()
+            probe_pop(connection, domain).await?
+        }
+        _ => {
+            let connection = async_tcp::connect_plain(domain, port, tcp_config.clone()).await?;
+
+            probe_pop(connection, domain).await?
         }
-        _ => async_pop3::connect_plain(&addr, connection_timeout)
-            .await
-            .is_ok(),
     };
 
-    if is_pop {
-        Ok(ServerConfigType::Pop)
-    } else {
-        Err(Error::new(
-            ErrorKind::ConfigNotFound,
-            "Given server is not a pop server",
-        ))
-    }
+    probe.ok_or_else(|| not_found("Given server is not a pop server"))
+}
+
+fn tcp_config(proxy: Option<&ProxyConfig>) -> TcpConfig {
+    let connect_timeout = Duration::from_millis(5 * 1000);
+
+    let mut tcp_config = TcpConfig::new(connect_timeout, true);
+
+    tcp_config.set_proxy(proxy.cloned());
+
+    tcp_config
 }
 
 /// Fetch the service type from a given server address. e.g I have a server at 192.168.0.1:993, this function could tell me that it is an Imap server.
 ///
 /// This function is needed because, for example we can never assume that a service running on port 993 is an Imap server, even though that would be the expected behavior.
-pub async fn detect_server_config(socket: &Socket) -> Result<Option<ServerConfigType>> {
-    let addr = socket.addr();
+///
+/// While we are connected, we also find out which login mechanisms the server supports and whether it supports STARTTLS.
+pub async fn detect_server_config(
+    socket: &Socket,
+    proxy: Option<&ProxyConfig>,
+) -> Result<Option<Probe>> {
     let domain = socket.domain();
+    let port = socket.port().clone();
 
     let security = socket.security();
 
-    let connect_timeout = Duration::from_millis(5 * 1000);
-
-    let tcp_config = Some(TcpConfig::new(connect_timeout, true));
+    let tcp_config = Some(tcp_config(proxy));
 
     let mut checkers = Vec::new();
 
     #[cfg(feature = "imap")]
     {
-        let check_for_imap_future = check_for_imap(security, &addr, domain, &tcp_config);
+        let check_for_imap_future = check_for_imap(security, domain, port, &tcp_config);
 
         checkers.push(check_for_imap_future.boxed());
     }
 
     #[cfg(feature = "pop")]
     {
-        let check_for_smtp_future = check_for_pop(security, &addr, domain, &tcp_config);
+        let check_for_smtp_future = check_for_pop(security, domain, port, &tcp_config);
 
         checkers.push(check_for_smtp_future.boxed());
     }
 
     #[cfg(feature = "smtp")]
     {
-        let check_for_smtp_future = check_for_smtp(security, &addr, domain, &tcp_config);
+        let check_for_smtp_future = check_for_smtp(security, domain, port, &tcp_config);
 
         checkers.push(check_for_smtp_future.boxed());
     }
@@ -182,7 +660,7 @@ pub async fn detect_server_config(socket: &Socket) -> Result<Option<ServerConfig
     let result = select_ok(checkers).await;
 
     match result {
-        Ok((config_type, _remaining)) => Ok(Some(config_type)),
+        Ok((probe, _remaining)) => Ok(Some(probe)),
         Err(err) => match err.kind() {
             ErrorKind::Tcp(tcp_err) => match tcp_err.kind() {
                 TcpErrorKind::Timeout(_) => Ok(None),
@@ -193,15 +671,355 @@ pub async fn detect_server_config(socket: &Socket) -> Result<Option<ServerConfig
     }
 }
 
+/// Checks whether a given server accepts the given credentials, by logging in with them.
+///
+/// The password is never sent to a server without an encrypted connection, or to one that an SRV record pointed outside of the domain
+/// before the user confirmed it, those return an unsupported error instead.
+pub async fn verify_credentials(
+    server: &ServerConfig,
+    credentials: &PasswordCredentials,
+    proxy: Option<&ProxyConfig>,
+) -> Result<()> {
+    if server.is_insecure() {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "Credentials are not verified over a connection that is not encrypted",
+        ));
+    }
+
+    if server.source() == &ServerSource::ExternalSrv {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "Credentials are not verified with a server outside of the domain without the user confirming it",
+        ));
+    }
+
+    let domain = server.domain();
+    let port = *server.port();
+
+    let starttls = matches!(server.security(), ConnectionSecurity::StartTls);
+
+    let tcp_config = Some(tcp_config(proxy));
+
+    let accepted = match server.r#type() {
+        #[cfg(feature = "imap")]
+        ServerConfigType::Imap => match server.security() {
+            ConnectionSecurity::Tls => {
+                let connection = async_tcp::connect(domain, port, tcp_config).await?;
+
+                verify_imap(connection, domain, starttls, credentials).await?
+            }
+            _ => {
+                let connection = async_tcp::connect_plain(domain, port, tcp_config).await?;
+
+                verify_imap(connection, domain, starttls, credentials).await?
+            }
+        },
+        #[cfg(feature = "pop")]
+        ServerConfigType::Pop => match server.security() {
+            ConnectionSecurity::Tls => {
+                let connection = async_tcp::connect(domain, port, tcp_config).await?;
+
+                verify_pop(connection, domain, starttls, credentials).await?
+            }
+            _ => {
+                let connection = async_tcp::connect_plain(domain, port, tcp_config).await?;
+
+                verify_pop(connection, domain, starttls, credentials).await?
+            }
+        },
+        #[cfg(feature = "smtp")]
+        ServerConfigType::Smtp => match server.security() {
+            ConnectionSecurity::Tls => {
+                let connection = async_tcp::connect(domain, port, tcp_config).await?;
+
+                verify_smtp(connection, domain, starttls, credentials).await?
+            }
+            _ => {
+                let connection = async_tcp::connect_plain(domain, port, tcp_config).await?;
+
+                verify_smtp(connection, domain, starttls, credentials).await?
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Verifying credentials is not supported for this server type",
+            ))
+        }
+    };
+
+    if accepted {
+        Ok(())
+    } else {
+        Err(credentials_rejected(format!(
+            "The server at {}:{} rejected the given credentials",
+            domain, port
+        )))
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use async_tcp::{Config as TcpConfig, TcpClient};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
     use crate::{
-        detect::{types::ServerConfigType, Socket},
-        types::ConnectionSecurity,
+        detect::{
+            types::{AuthenticationType, ServerConfigType},
+            Socket,
+        },
+        session::PasswordCredentials,
+        types::{ConnectionSecurity, ErrorKind},
     };
 
     use super::detect_server_config;
 
+    /// Creates a client for a server that sends the given responses, in order.
+    async fn connect(responses: &str) -> (TcpClient<DuplexStream>, DuplexStream) {
+        let (client, mut server) = duplex(4096);
+
+        server.write_all(responses.as_bytes()).await.unwrap();
+
+        (async_tcp::new(client, TcpConfig::default()), server)
+    }
+
+    /// The commands the client sent to the server.
+    async fn sent(mut server: DuplexStream) -> String {
+        let mut commands = String::new();
+
+        server.read_to_string(&mut commands).await.unwrap();
+
+        commands
+    }
+
+    #[cfg(feature = "imap")]
+    #[tokio::test]
+    async fn imap() {
+        let (client, _server) = connect(
+            "* OK ready\r\n* CAPABILITY IMAP4rev1 AUTH=PLAIN AUTH=XOAUTH2 AUTH=SCRAM-SHA-256\r\nA0001 OK done\r\n",
+        )
+        .await;
+
+        let probe = super::probe_imap(client, "localhost")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(probe.config_type(), &ServerConfigType::Imap);
+        assert!(!probe.starttls());
+        assert_eq!(
+            probe.auth_types(),
+            &vec![
+                AuthenticationType::ClearText,
+                AuthenticationType::OAuth2,
+                AuthenticationType::Encrypted
+            ]
+        );
+
+        let (client, _server) =
+            connect("* OK ready\r\n* CAPABILITY IMAP4rev1 LOGINDISABLED\r\nA0001 OK done\r\n")
+                .await;
+
+        let probe = super::probe_imap(client, "localhost")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(probe.auth_types().is_empty());
+
+        let (client, _server) =
+            connect("220 mail.example.com ESMTP\r\nA0001 BAD unknown command\r\n").await;
+
+        assert!(super::probe_imap(client, "localhost")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "smtp")]
+    #[tokio::test]
+    async fn smtp() {
+        let (client, server) = connect(
+            "220 mail.example.com\r\n250-mail.example.com\r\n250-PIPELINING\r\n250-AUTH=PLAIN LOGIN\r\n250 AUTH CRAM-MD5 XOAUTH2\r\n",
+        )
+        .await;
+
+        let probe = super::probe_smtp(client, "localhost")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(probe.config_type(), &ServerConfigType::Smtp);
+        assert!(!probe.starttls());
+        assert_eq!(
+            probe.auth_types(),
+            &vec![
+                AuthenticationType::ClearText,
+                AuthenticationType::Encrypted,
+                AuthenticationType::OAuth2
+            ]
+        );
+
+        assert_eq!(sent(server).await, "EHLO dust-mail\r\n");
+
+        let (client, _server) = connect("* OK IMAP4rev1 ready\r\n").await;
+
+        assert!(super::probe_smtp(client, "localhost").await.is_err());
+    }
+
+    #[cfg(feature = "pop")]
+    #[tokio::test]
+    async fn pop() {
+        let (client, _server) = connect(
+            "+OK ready <1896.697170952@dbc.mtview.ca.us>\r\n+OK\r\nUSER\r\nSASL PLAIN OAUTHBEARER\r\nUIDL\r\n.\r\n",
+        )
+        .await;
+
+        let probe = super::probe_pop(client, "localhost")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(probe.config_type(), &ServerConfigType::Pop);
+        assert_eq!(
+            probe.auth_types(),
+            &vec![
+                AuthenticationType::ClearText,
+                AuthenticationType::OAuth2,
+                AuthenticationType::Encrypted
+            ]
+        );
+
+        // Servers without CAPA only support USER and PASS.
+        let (client, _server) = connect("+OK ready\r\n-ERR unknown command\r\n").await;
+
+        let probe = super::probe_pop(client, "localhost")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(probe.auth_types(), &vec![AuthenticationType::ClearText]);
+    }
+
+    #[cfg(feature = "imap")]
+    #[tokio::test]
+    async fn verify_imap() {
+        let credentials = PasswordCredentials::new("john \"doe\"", "secret");
+
+        let (client, server) = connect(
+            "* OK ready\r\n* CAPABILITY IMAP4rev1\r\nA0003 OK done\r\nA0004 OK logged in\r\n",
+        )
+        .await;
+
+        assert!(super::verify_imap(client, "localhost", false, &credentials)
+            .await
+            .unwrap());
+
+        assert_eq!(
+            sent(server).await,
+            "A0003 CAPABILITY\r\nA0004 LOGIN \"john \\\"doe\\\"\" \"secret\"\r\nA0005 LOGOUT\r\n"
+        );
+
+        let (client, _server) = connect(
+            "* OK ready\r\n* CAPABILITY IMAP4rev1\r\nA0003 OK done\r\nA0004 NO [AUTHENTICATIONFAILED] invalid credentials\r\n",
+        )
+        .await;
+
+        assert!(
+            !super::verify_imap(client, "localhost", false, &credentials)
+                .await
+                .unwrap()
+        );
+
+        // The password is not sent if the server does not allow it.
+        let (client, server) =
+            connect("* OK ready\r\n* CAPABILITY IMAP4rev1 LOGINDISABLED\r\nA0003 OK done\r\n")
+                .await;
+
+        let error = super::verify_imap(client, "localhost", false, &credentials)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.kind(), ErrorKind::Unsupported));
+        assert_eq!(sent(server).await, "A0003 CAPABILITY\r\n");
+    }
+
+    #[cfg(feature = "smtp")]
+    #[tokio::test]
+    async fn verify_smtp() {
+        let credentials = PasswordCredentials::new("john", "secret");
+
+        let (client, server) = connect(
+            "220 mail.example.com\r\n250-mail.example.com\r\n250 AUTH PLAIN\r\n235 accepted\r\n",
+        )
+        .await;
+
+        assert!(super::verify_smtp(client, "localhost", false, &credentials)
+            .await
+            .unwrap());
+
+        assert_eq!(
+            sent(server).await,
+            "EHLO dust-mail\r\nAUTH PLAIN AGpvaG4Ac2VjcmV0\r\n"
+        );
+
+        let (client, _server) = connect(
+            "220 mail.example.com\r\n250-mail.example.com\r\n250 AUTH PLAIN\r\n535 rejected\r\n",
+        )
+        .await;
+
+        assert!(
+            !super::verify_smtp(client, "localhost", false, &credentials)
+                .await
+                .unwrap()
+        );
+
+        let (client, server) = connect(
+            "220 mail.example.com\r\n250-mail.example.com\r\n250 AUTH LOGIN\r\n334 VXNlcm5hbWU6\r\n334 UGFzc3dvcmQ6\r\n235 accepted\r\n",
+        )
+        .await;
+
+        assert!(super::verify_smtp(client, "localhost", false, &credentials)
+            .await
+            .unwrap());
+
+        assert_eq!(
+            sent(server).await,
+            "EHLO dust-mail\r\nAUTH LOGIN\r\nam9obg==\r\nc2VjcmV0\r\n"
+        );
+
+        // A server without a mechanism we support can't tell us anything about the credentials.
+        let (client, _server) =
+            connect("220 mail.example.com\r\n250-mail.example.com\r\n250 AUTH CRAM-MD5\r\n").await;
+
+        let error = super::verify_smtp(client, "localhost", false, &credentials)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.kind(), ErrorKind::Unsupported));
+    }
+
+    #[cfg(feature = "pop")]
+    #[tokio::test]
+    async fn verify_pop() {
+        let credentials = PasswordCredentials::new("john", "secret");
+
+        let (client, server) = connect("+OK ready\r\n+OK\r\n+OK logged in\r\n").await;
+
+        assert!(super::verify_pop(client, "localhost", false, &credentials)
+            .await
+            .unwrap());
+
+        assert_eq!(sent(server).await, "USER john\r\nPASS secret\r\nQUIT\r\n");
+
+        let (client, _server) = connect("+OK ready\r\n+OK\r\n-ERR invalid password\r\n").await;
+
+        assert!(!super::verify_pop(client, "localhost", false, &credentials)
+            .await
+            .unwrap());
+    }
+
     #[tokio::test]
     async fn client_type() {
         let domain = "mail.samtaen.nl";
@@ -212,16 +1030,24 @@ mod test {
         #[cfg(feature = "imap")]
         {
             assert_eq!(
-                detect_server_config(&Socket::new(domain, imap_port, ConnectionSecurity::Tls))
-                    .await
-                    .unwrap(),
+                detect_server_config(
+                    &Socket::new(domain, imap_port, ConnectionSecurity::Tls),
+                    None
+                )
+                .await
+                .unwrap()
+                .map(|probe| probe.config_type().clone()),
                 Some(ServerConfigType::Imap),
             );
 
             assert_ne!(
-                detect_server_config(&Socket::new(domain, pop_port, ConnectionSecurity::Tls))
-                    .await
-                    .unwrap(),
+                detect_server_config(
+                    &Socket::new(domain, pop_port, ConnectionSecurity::Tls),
+                    None
+                )
+                .await
+                .unwrap()
+                .map(|probe| probe.config_type().clone()),
                 Some(ServerConfigType::Imap),
             );
         }
@@ -229,37 +1055,49 @@ mod test {
         #[cfg(feature = "pop")]
         {
             assert_eq!(
-                detect_server_config(&Socket::new(domain, pop_port, ConnectionSecurity::Tls))
-                    .await
-                    .unwrap(),
+                detect_server_config(
+                    &Socket::new(domain, pop_port, ConnectionSecurity::Tls),
+                    None
+                )
+                .await
+                .unwrap()
+                .map(|probe| probe.config_type().clone()),
                 Some(ServerConfigType::Pop),
             );
 
             assert_ne!(
-                detect_server_config(&Socket::new(domain, imap_port, ConnectionSecurity::Tls))
-                    .await
-                    .unwrap(),
+                detect_server_config(
+                    &Socket::new(domain, imap_port, ConnectionSecurity::Tls),
+                    None
+                )
+                .await
+                .unwrap()
+                .map(|probe| probe.config_type().clone()),
                 Some(ServerConfigType::Pop),
             );
         }
 
         #[cfg(feature = "smtp")]
         {
-            assert_eq!(
-                detect_server_config(&Socket::new(
-                    domain,
-                    smtp_port,
-                    ConnectionSecurity::StartTls
-                ))
-                .await
-                .unwrap(),
-                Some(ServerConfigType::Smtp),
-            );
+            let probe = detect_server_config(
+                &Socket::new(domain, smtp_port, ConnectionSecurity::StartTls),
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+            assert_eq!(probe.config_type(), &ServerConfigType::Smtp);
+            assert!(probe.starttls());
 
             assert_ne!(
-                detect_server_config(&Socket::new(domain, imap_port, ConnectionSecurity::Tls))
-                    .await
-                    .unwrap(),
+                detect_server_config(
+                    &Socket::new(domain, imap_port, ConnectionSecurity::Tls),
+                    None
+                )
+                .await
+                .unwrap()
+                .map(|probe| probe.config_type().clone()),
                 Some(ServerConfigType::Smtp),
             );
         }
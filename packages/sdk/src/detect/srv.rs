@@ -0,0 +1,139 @@
+use autoconfig::dns::{sort_srv_records, Resolver};
+use futures::future::join_all;
+
+use crate::types::ConnectionSecurity;
+
+use super::types::{ServerSource, Socket};
+
+/// The services that can be used to find mail servers, as described in https://www.rfc-editor.org/rfc/rfc6186 and https://www.rfc-editor.org/rfc/rfc8314.
+///
+/// The services that use a secure connection from the start are listed first, because RFC 8314 says they should be preferred.
+fn services() -> Vec<(&'static str, ConnectionSecurity)> {
+    let mut services = Vec::new();
+
+    #[cfg(feature = "imap")]
+    {
+        services.push(("_imaps._tcp", ConnectionSecurity::Tls));
+        services.push(("_imap._tcp", ConnectionSecurity::StartTls));
+    }
+
+    #[cfg(feature = "pop")]
+    {
+        services.push(("_pop3s._tcp", ConnectionSecurity::Tls));
+        services.push(("_pop3._tcp", ConnectionSecurity::StartTls));
+    }
+
+    #[cfg(feature = "smtp")]
+    {
+        services.push(("_submissions._tcp", ConnectionSecurity::Tls));
+        services.push(("_submission._tcp", ConnectionSecurity::StartTls));
+    }
+
+    services
+}
+
+/// Whether a host is the domain itself or one of its subdomains.
+fn in_domain(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Looks up the mail servers a domain advertises using SRV records, in the order they should be tried.
+///
+/// Services that can't be looked up are skipped, as they are only used to improve on the servers we find by probing.
+/// The DNS response is not authenticated, so as RFC 6186 section 6 says, a target outside of the domain is not trusted like the other ones.
+pub async fn find_sockets(domain: &str, resolver: &dyn Resolver) -> Vec<Socket> {
+    let services = services();
+
+    let names: Vec<String> = services
+        .iter()
+        .map(|(service, _)| format!("{}.{}", service, domain))
+        .collect();
+
+    let lookups = join_all(names.iter().map(|name| resolver.srv(name))).await;
+
+    let mut sockets = Vec::new();
+
+    for ((_, security), records) in services.into_iter().zip(lookups) {
+        for record in sort_srv_records(records.unwrap_or_default()) {
+            let mut socket = Socket::new(record.target(), *record.port(), security.clone());
+
+            socket.set_source(if in_domain(record.target(), domain) {
+                ServerSource::Srv
+            } else {
+                ServerSource::ExternalSrv
+            });
+
+            sockets.push(socket);
+        }
+    }
+
+    sockets
+}
+
+#[cfg(test)]
+mod test {
+    use autoconfig::dns::SrvRecord;
+
+    use crate::{
+        detect::{stub::StubResolver, types::ServerSource},
+        types::ConnectionSecurity,
+    };
+
+    use super::find_sockets;
+
+    #[cfg(feature = "imap")]
+    #[tokio::test]
+    async fn srv_sockets() {
+        let mut resolver = StubResolver::default();
+
+        resolver
+            .add_srv(
+                "_imap._tcp.example.com",
+                vec![SrvRecord::new("imap.example.com", 143, 0, 1)],
+            )
+            .add_srv(
+                "_imaps._tcp.example.com",
+                vec![
+                    SrvRecord::new("backup.example.com", 993, 10, 0),
+                    SrvRecord::new("mail.example.com", 993, 0, 1),
+                    SrvRecord::new("mail.attacker.net.", 993, 20, 0),
+                    // Only a subdomain counts, not a domain that happens to end the same way.
+                    SrvRecord::new("mail.notexample.com", 993, 30, 0),
+                ],
+            )
+            // A target of "." means the service is not available.
+            .add_srv(
+                "_submissions._tcp.example.com",
+                vec![SrvRecord::new(".", 0, 0, 0)],
+            )
+            .add_failing("_pop3._tcp.example.com");
+
+        let sockets = find_sockets("example.com", &resolver).await;
+
+        let sockets: Vec<(&str, u16, bool, &ServerSource)> = sockets
+            .iter()
+            .map(|socket| {
+                (
+                    socket.domain(),
+                    *socket.port(),
+                    matches!(socket.security(), ConnectionSecurity::Tls),
+                    socket.source(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            sockets,
+            vec![
+                ("mail.example.com", 993, true, &ServerSource::Srv),
+                ("backup.example.com", 993, true, &ServerSource::Srv),
+                ("mail.attacker.net.", 993, true, &ServerSource::ExternalSrv),
+                ("mail.notexample.com", 993, true, &ServerSource::ExternalSrv),
+                ("imap.example.com", 143, false, &ServerSource::Srv),
+            ]
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use autoconfig::{
+    dns::{MxRecord, Resolver, SrvRecord},
+    types::{Error, ErrorKind, Result},
+};
+use futures::future::BoxFuture;
+
+/// A resolver that answers from a fixed set of records, so detection can be tested without a network.
+#[derive(Default)]
+pub struct StubResolver {
+    srv: HashMap<String, Vec<SrvRecord>>,
+    mx: HashMap<String, Vec<MxRecord>>,
+    /// Names for which the lookup fails, like it would when the DNS server is not reachable.
+    failing: Vec<String>,
+}
+
+impl StubResolver {
+    pub fn add_srv<N: Into<String>>(&mut self, name: N, records: Vec<SrvRecord>) -> &mut Self {
+        self.srv.insert(name.into(), records);
+
+        self
+    }
+
+    pub fn add_mx<N: Into<String>>(&mut self, name: N, records: Vec<MxRecord>) -> &mut Self {
+        self.mx.insert(name.into(), records);
+
+        self
+    }
+
+    pub fn add_failing<N: Into<String>>(&mut self, name: N) -> &mut Self {
+        self.failing.push(name.into());
+
+        self
+    }
+
+    fn lookup<T: Clone>(&self, records: &HashMap<String, Vec<T>>, name: &str) -> Result<Vec<T>> {
+        if self.failing.iter().any(|failing| failing == name) {
+            return Err(Error::new(ErrorKind::Dns, "Server failure"));
+        }
+
+        Ok(records.get(name).cloned().unwrap_or_default())
+    }
+}
+
+impl Resolver for StubResolver {
+    fn srv<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<SrvRecord>>> {
+        Box::pin(async move { self.lookup(&self.srv, name) })
+    }
+
+    fn mx<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<MxRecord>>> {
+        Box::pin(async move { self.lookup(&self.mx, name) })
+    }
+}
@@ -1,9 +1,10 @@
+use std::cmp::Reverse;
+
 use serde::Serialize;
-use tokio::net::ToSocketAddrs;
 
 use crate::{
     parse::to_json,
-    types::{ConnectionSecurity, Result},
+    types::{ConnectionSecurity, DownloadOptions, Result},
 };
 
 #[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash)]
@@ -23,6 +24,42 @@ impl ServerConfigType {
     }
 }
 
+/// Where we found the details of a server, which says something about how much we can trust them.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Default)]
+pub enum ServerSource {
+    /// A config file that the operator of this app provided.
+    LocalConfig,
+    /// Thunderbird's database of email providers, either the bundled snapshot or the online version.
+    Ispdb,
+    /// A config file the email provider served over https.
+    Autoconfig,
+    /// The Autodiscover service of the email provider.
+    Autodiscover,
+    /// A config file the email provider served over plain http, so it could have been changed by anyone on the network path.
+    HttpAutoconfig,
+    /// The SRV records of the domain.
+    Srv,
+    /// An SRV record of the domain that points at a host outside of it, which anyone who can spoof the DNS response could have added.
+    ExternalSrv,
+    /// We found the server by trying common hostnames and ports.
+    #[default]
+    Guess,
+}
+
+impl ServerSource {
+    fn confidence(&self) -> i16 {
+        match self {
+            Self::LocalConfig => 90,
+            Self::Ispdb => 80,
+            Self::Autoconfig | Self::Autodiscover => 70,
+            Self::Srv => 60,
+            Self::HttpAutoconfig => 40,
+            Self::Guess => 30,
+            Self::ExternalSrv => 20,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerConfig {
@@ -31,6 +68,15 @@ pub struct ServerConfig {
     domain: String,
     security: ConnectionSecurity,
     auth_type: Vec<AuthenticationType>,
+    download_options: Option<DownloadOptions>,
+    username: Option<String>,
+    source: ServerSource,
+    /// Whether the server accepted the credentials of the user.
+    verified: bool,
+    /// How sure we are that this is the right server to use, from 0 to 100.
+    confidence: u8,
+    /// Whether the connection to the server is not encrypted, so the password and messages can be read by anyone on the network path.
+    insecure: bool,
 }
 
 impl ServerConfig {
@@ -41,13 +87,62 @@ impl ServerConfig {
         security: ConnectionSecurity,
         auth_type: Vec<AuthenticationType>,
     ) -> Self {
-        Self {
+        let insecure = matches!(security, ConnectionSecurity::Plain);
+
+        let mut server_config = Self {
             r#type,
             port,
             domain: domain.into(),
             security,
             auth_type,
-        }
+            download_options: None,
+            username: None,
+            source: ServerSource::default(),
+            verified: false,
+            confidence: 0,
+            insecure,
+        };
+
+        server_config.update_confidence();
+
+        server_config
+    }
+
+    fn update_confidence(&mut self) {
+        let security = match self.security {
+            ConnectionSecurity::Tls => 10,
+            ConnectionSecurity::StartTls => 5,
+            ConnectionSecurity::Plain => -20,
+        };
+
+        let verified = if self.verified { 10 } else { 0 };
+
+        self.confidence = (self.source.confidence() + security + verified).clamp(0, 100) as u8;
+    }
+
+    /// Set where we found the details of this server.
+    pub fn set_source(&mut self, source: ServerSource) -> &mut Self {
+        self.source = source;
+
+        self.update_confidence();
+
+        self
+    }
+
+    /// Set whether the server accepted the credentials of the user.
+    pub fn set_verified(&mut self, verified: bool) -> &mut Self {
+        self.verified = verified;
+
+        self.update_confidence();
+
+        self
+    }
+
+    /// Set the settings that specify how messages should be downloaded from this server, only applicable to Pop servers.
+    pub fn set_download_options(&mut self, download_options: DownloadOptions) -> &mut Self {
+        self.download_options = Some(download_options);
+
+        self
     }
 
     pub fn r#type(&self) -> &ServerConfigType {
@@ -69,9 +164,44 @@ impl ServerConfig {
     pub fn auth_type(&self) -> &Vec<AuthenticationType> {
         &self.auth_type
     }
+
+    /// How messages should be downloaded from this server, if the server specified it.
+    pub fn download_options(&self) -> Option<&DownloadOptions> {
+        self.download_options.as_ref()
+    }
+
+    /// Set the username that should be used to login to this server.
+    pub fn set_username<U: Into<String>>(&mut self, username: U) -> &mut Self {
+        self.username = Some(username.into());
+
+        self
+    }
+
+    /// The username that should be used to login to this server, if it is known.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    pub fn source(&self) -> &ServerSource {
+        &self.source
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.verified
+    }
+
+    /// How sure we are that this is the right server to use, from 0 to 100, based on where we found it, how secure the connection is and whether the user's credentials were accepted.
+    pub fn confidence(&self) -> &u8 {
+        &self.confidence
+    }
+
+    /// Whether the connection to the server is not encrypted, so the password and messages can be read by anyone on the network path.
+    pub fn is_insecure(&self) -> bool {
+        self.insecure
+    }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
 pub enum AuthenticationType {
     ClearText,
     Encrypted,
@@ -111,6 +241,33 @@ impl OAuth2Config {
     }
 }
 
+/// A value the user has to fill in before the servers can be used, like a username that is not their email address.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InputField {
+    key: String,
+    label: Option<String>,
+}
+
+impl InputField {
+    pub fn new<K: Into<String>>(key: K, label: Option<String>) -> Self {
+        Self {
+            key: key.into(),
+            label,
+        }
+    }
+
+    /// The name of the placeholder the value is used for, like `USERNAME` in `%USERNAME%`.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// What the field should be called in a form.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum ConfigType {
@@ -120,8 +277,20 @@ pub enum ConfigType {
     },
 }
 
+/// Sorts the servers so the ones we are most confident about come first, servers with the same confidence keep their order.
+fn sort_by_confidence(servers: &mut [ServerConfig]) {
+    servers.sort_by_key(|server| Reverse(server.confidence));
+}
+
 impl ConfigType {
-    pub fn new_multiserver(incoming: Vec<ServerConfig>, outgoing: Vec<ServerConfig>) -> Self {
+    /// Creates a config with multiple servers, which are sorted by how confident we are about them.
+    pub fn new_multiserver(
+        mut incoming: Vec<ServerConfig>,
+        mut outgoing: Vec<ServerConfig>,
+    ) -> Self {
+        sort_by_confidence(&mut incoming);
+        sort_by_confidence(&mut outgoing);
+
         ConfigType::MultiServer { incoming, outgoing }
     }
 }
@@ -133,6 +302,7 @@ pub struct Config {
     provider: String,
     oauth2: Option<OAuth2Config>,
     display_name: Option<String>,
+    input_fields: Vec<InputField>,
 }
 
 impl Config {
@@ -147,9 +317,16 @@ impl Config {
             oauth2: oauth2_config,
             provider: provider.into(),
             r#type,
+            input_fields: Vec::new(),
         }
     }
 
+    pub fn set_input_fields(&mut self, input_fields: Vec<InputField>) -> &mut Self {
+        self.input_fields = input_fields;
+
+        self
+    }
+
     pub fn oauth2(&self) -> &Option<OAuth2Config> {
         &self.oauth2
     }
@@ -169,15 +346,58 @@ impl Config {
         &self.display_name
     }
 
+    /// The values the user still has to fill in, the servers that depend on them are returned without a username.
+    pub fn input_fields(&self) -> &Vec<InputField> {
+        &self.input_fields
+    }
+
     pub fn to_json(&self) -> Result<String> {
         to_json(self)
     }
 }
 
+/// What we found out about a server by talking to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    config_type: ServerConfigType,
+    auth_types: Vec<AuthenticationType>,
+    starttls: bool,
+}
+
+impl Probe {
+    pub fn new(
+        config_type: ServerConfigType,
+        auth_types: Vec<AuthenticationType>,
+        starttls: bool,
+    ) -> Self {
+        Self {
+            config_type,
+            auth_types,
+            starttls,
+        }
+    }
+
+    pub fn config_type(&self) -> &ServerConfigType {
+        &self.config_type
+    }
+
+    /// The ways the server lets us login, based on the mechanisms it advertised.
+    pub fn auth_types(&self) -> &Vec<AuthenticationType> {
+        &self.auth_types
+    }
+
+    /// Whether the server supports upgrading a plain connection to a secure one.
+    pub fn starttls(&self) -> bool {
+        self.starttls
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
 pub struct Socket {
     domain: String,
     port: u16,
     security: ConnectionSecurity,
+    source: ServerSource,
 }
 
 impl Socket {
@@ -186,32 +406,123 @@ impl Socket {
             domain: domain.into(),
             port,
             security,
+            source: ServerSource::Guess,
         }
     }
 
+    /// Set where we found this socket, by default we assume it is a guess.
+    pub fn set_source(&mut self, source: ServerSource) -> &mut Self {
+        self.source = source;
+
+        self
+    }
+
     pub fn from_tuple<S: AsRef<str>>(tuple: (S, u16, ConnectionSecurity)) -> Self {
         Self::new(tuple.0.as_ref(), tuple.1, tuple.2)
     }
 
-    pub fn addr(&self) -> impl ToSocketAddrs + '_ {
-        (self.domain.as_ref(), self.port.clone())
+    pub fn port(&self) -> &u16 {
+        &self.port
     }
 
     pub fn domain(&self) -> &str {
         &self.domain
     }
 
-    pub fn into_server_config(self, config_type: ServerConfigType) -> ServerConfig {
-        ServerConfig::new(
-            config_type,
+    pub fn into_server_config(self, probe: Probe) -> ServerConfig {
+        // The ports that are expected to use STARTTLS are connected to in plain text, so it is up to the server whether it is actually supported.
+        let security = match self.security {
+            ConnectionSecurity::Tls => ConnectionSecurity::Tls,
+            _ if probe.starttls => ConnectionSecurity::StartTls,
+            _ => ConnectionSecurity::Plain,
+        };
+
+        let auth_types = if probe.auth_types.is_empty() {
+            vec![AuthenticationType::Unknown]
+        } else {
+            probe.auth_types
+        };
+
+        let mut server_config = ServerConfig::new(
+            probe.config_type,
             self.port,
             self.domain,
-            self.security,
-            vec![AuthenticationType::Encrypted],
-        )
+            security,
+            auth_types,
+        );
+
+        server_config.set_source(self.source);
+
+        server_config
+    }
+
+    pub fn source(&self) -> &ServerSource {
+        &self.source
     }
 
     pub fn security(&self) -> &ConnectionSecurity {
         &self.security
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::types::ConnectionSecurity;
+
+    use super::{ConfigType, ServerConfig, ServerConfigType, ServerSource};
+
+    #[test]
+    fn confidence() {
+        let mut guessed_plain = ServerConfig::new(
+            ServerConfigType::Imap,
+            143,
+            "mail.example.com",
+            ConnectionSecurity::Plain,
+            Vec::new(),
+        );
+
+        let guessed_tls = ServerConfig::new(
+            ServerConfigType::Imap,
+            993,
+            "mail.example.com",
+            ConnectionSecurity::Tls,
+            Vec::new(),
+        );
+
+        let mut advertised = ServerConfig::new(
+            ServerConfigType::Imap,
+            993,
+            "imap.example.com",
+            ConnectionSecurity::Tls,
+            Vec::new(),
+        );
+
+        advertised.set_source(ServerSource::Srv);
+
+        assert!(guessed_plain.is_insecure());
+        assert!(!guessed_tls.is_insecure());
+
+        let ConfigType::MultiServer { incoming, .. } = ConfigType::new_multiserver(
+            vec![guessed_plain.clone(), guessed_tls, advertised],
+            Vec::new(),
+        );
+
+        let ranked: Vec<(&str, &u16, &u8)> = incoming
+            .iter()
+            .map(|server| (server.domain(), server.port(), server.confidence()))
+            .collect();
+
+        assert_eq!(
+            ranked,
+            vec![
+                ("imap.example.com", &993, &70),
+                ("mail.example.com", &993, &40),
+                ("mail.example.com", &143, &10)
+            ]
+        );
+
+        guessed_plain.set_verified(true);
+
+        assert_eq!(guessed_plain.confidence(), &20);
+    }
+}
@@ -0,0 +1,1621 @@
+//! A scripted in-memory IMAP4rev1 server, used to test the imap client without a network connection.
+//!
+//! It supports the subset of the protocol that the client uses: LOGIN, CAPABILITY, LIST, SELECT, CLOSE, CREATE, DELETE,
+//! RENAME, FETCH, STORE, SEARCH (and their UID variants), IDLE, NOOP and LOGOUT.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::DateTime;
+use dust_mail_utils::{parse_address_list, Address, Mailbox};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream},
+    sync::watch,
+};
+
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The hierarchy delimiter that is reported for every mailbox.
+const DELIMITER: &str = ".";
+
+const DEFAULT_INTERNAL_DATE: &str = "01-Jan-2023 00:00:00 +0000";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockMessage {
+    uid: u32,
+    flags: Vec<String>,
+    body: Vec<u8>,
+}
+
+impl MockMessage {
+    pub fn flags(&self) -> &Vec<String> {
+        &self.flags
+    }
+
+    fn has_flag(&self, flag: &str) -> bool {
+        self.flags
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(flag))
+    }
+
+    fn add_flag(&mut self, flag: &str) {
+        if !self.has_flag(flag) {
+            self.flags.push(flag.to_string());
+        }
+    }
+
+    fn remove_flag(&mut self, flag: &str) {
+        self.flags
+            .retain(|existing| !existing.eq_ignore_ascii_case(flag));
+    }
+
+    /// The header part of the message, including the empty line that separates it from the body.
+    fn header(&self) -> &[u8] {
+        split_header(&self.body).0
+    }
+
+    fn text(&self) -> &[u8] {
+        split_header(&self.body).1
+    }
+
+    /// The unfolded headers of the message, with lowercase keys.
+    fn headers(&self) -> HashMap<String, String> {
+        parse_header_block(self.header())
+    }
+
+    /// The MIME structure of the message.
+    fn part(&self) -> MockPart<'_> {
+        MockPart::new(&self.body)
+    }
+
+    fn internal_date(&self) -> String {
+        self.headers()
+            .get("date")
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+            .map(|date| date.format("%d-%b-%Y %H:%M:%S %z").to_string())
+            .unwrap_or_else(|| String::from(DEFAULT_INTERNAL_DATE))
+    }
+
+    fn envelope(&self) -> String {
+        envelope(&self.headers())
+    }
+
+    /// The data of a body section, like `1.2`, `TEXT` or `HEADER.FIELDS (SUBJECT)`.
+    fn section(&self, section: &str) -> Option<Vec<u8>> {
+        let tokens: Vec<&str> = section.split('.').collect();
+
+        let path: Vec<usize> = tokens
+            .iter()
+            .map_while(|token| token.parse().ok())
+            .collect();
+
+        let specifier = tokens[path.len()..].join(".");
+
+        let root = self.part();
+
+        let part = root.find(&path)?;
+
+        // The header and text specifiers refer to the message itself or to an encapsulated message.
+        let message = match &part.kind {
+            _ if path.is_empty() => part,
+            MockPartKind::Message(message) => message,
+            _ => part,
+        };
+
+        let data = match specifier.as_str() {
+            "" if path.is_empty() => self.body.as_slice(),
+            "" => part.body,
+            "MIME" if !path.is_empty() => part.header,
+            "HEADER" => message.header,
+            "TEXT" => message.body,
+            specifier => {
+                let (fields, not) = match specifier.strip_prefix("HEADER.FIELDS.NOT") {
+                    Some(fields) => (fields, true),
+                    None => (specifier.strip_prefix("HEADER.FIELDS")?, false),
+                };
+
+                return Some(header_fields(message.header, fields, not));
+            }
+        };
+
+        Some(data.to_vec())
+    }
+}
+
+/// A part of a MIME message, the way the server sees a message when it answers a BODYSTRUCTURE or BODY[section] fetch.
+struct MockPart<'a> {
+    header: &'a [u8],
+    body: &'a [u8],
+    kind: MockPartKind<'a>,
+}
+
+enum MockPartKind<'a> {
+    Single,
+    Multipart(Vec<MockPart<'a>>),
+    /// An encapsulated message, like a forwarded email.
+    Message(Box<MockPart<'a>>),
+}
+
+impl<'a> MockPart<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let (header, body) = split_header(data);
+
+        let (content_type, params) = content_type(&parse_header_block(header));
+
+        let parts: Vec<MockPart> = match param(&params, "boundary") {
+            Some(boundary) if content_type.starts_with("multipart/") => {
+                split_multipart(body, boundary)
+                    .into_iter()
+                    .map(MockPart::new)
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        let kind = if !parts.is_empty() {
+            MockPartKind::Multipart(parts)
+        } else if content_type == "message/rfc822" {
+            MockPartKind::Message(Box::new(MockPart::new(body)))
+        } else {
+            MockPartKind::Single
+        };
+
+        Self { header, body, kind }
+    }
+
+    fn headers(&self) -> HashMap<String, String> {
+        parse_header_block(self.header)
+    }
+
+    /// The part with a given number in this part, a part that is not a multipart only has a part 1, which is the part itself.
+    fn child(&self, number: usize) -> Option<&MockPart<'a>> {
+        match &self.kind {
+            MockPartKind::Multipart(parts) => parts.get(number.checked_sub(1)?),
+            MockPartKind::Message(message) => message.child(number),
+            MockPartKind::Single if number == 1 => Some(self),
+            MockPartKind::Single => None,
+        }
+    }
+
+    /// Finds a part by its part numbers, like `[1, 2]` for the second part of the first part.
+    fn find(&self, path: &[usize]) -> Option<&MockPart<'a>> {
+        path.iter()
+            .try_fold(self, |part, number| part.child(*number))
+    }
+
+    /// Formats the part as a BODYSTRUCTURE, including the extension data.
+    fn body_structure(&self) -> String {
+        let headers = self.headers();
+
+        let (content_type, params) = content_type(&headers);
+
+        let (media_type, subtype) = content_type
+            .split_once('/')
+            .unwrap_or(("application", "octet-stream"));
+
+        if let MockPartKind::Multipart(parts) = &self.kind {
+            let parts: String = parts.iter().map(MockPart::body_structure).collect();
+
+            return format!(
+                "({} {} {} NIL NIL NIL)",
+                parts,
+                quote(&subtype.to_ascii_uppercase()),
+                param_list(&params)
+            );
+        }
+
+        let header = |key: &str| nstring(headers.get(key).map(|value| value.as_str()));
+
+        let encoding = headers
+            .get("content-transfer-encoding")
+            .map(|encoding| encoding.to_ascii_uppercase())
+            .unwrap_or_else(|| String::from("7BIT"));
+
+        let fields = format!(
+            "{} {} {} {} {} {} {}",
+            quote(&media_type.to_ascii_uppercase()),
+            quote(&subtype.to_ascii_uppercase()),
+            param_list(&params),
+            header("content-id"),
+            header("content-description"),
+            quote(&encoding),
+            self.body.len()
+        );
+
+        let disposition = match headers.get("content-disposition") {
+            Some(value) => {
+                let (disposition, params) = parse_header_value(value);
+
+                format!(
+                    "({} {})",
+                    quote(&disposition.to_ascii_uppercase()),
+                    param_list(&params)
+                )
+            }
+            None => String::from("NIL"),
+        };
+
+        let extension = format!("NIL {} NIL NIL", disposition);
+
+        let lines = count_lines(self.body);
+
+        match &self.kind {
+            MockPartKind::Message(message) => format!(
+                "({} {} {} {} {})",
+                fields,
+                envelope(&message.headers()),
+                message.body_structure(),
+                lines,
+                extension
+            ),
+            _ if media_type == "text" => format!("({} {} {})", fields, lines, extension),
+            _ => format!("({} {})", fields, extension),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockMailbox {
+    name: String,
+    attributes: Vec<String>,
+    messages: Vec<MockMessage>,
+    uid_next: u32,
+}
+
+impl MockMailbox {
+    fn new<N: Into<String>>(name: N, attributes: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            attributes,
+            messages: Vec::new(),
+            uid_next: 1,
+        }
+    }
+
+    pub fn get_message(&self, uid: u32) -> Option<&MockMessage> {
+        self.messages.iter().find(|message| message.uid == uid)
+    }
+
+    fn selectable(&self) -> bool {
+        !self
+            .attributes
+            .iter()
+            .any(|attribute| attribute.eq_ignore_ascii_case("\\Noselect"))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MockConfig {
+    greeting: String,
+    errors: HashMap<String, String>,
+}
+
+/// A scripted IMAP server with a set of fixture mailboxes.
+///
+/// Every connection shares the same mailboxes, so changes made by a client can be inspected using `MockImapServer::mailbox`.
+pub struct MockImapServer {
+    config: MockConfig,
+    mailboxes: Arc<Mutex<Vec<MockMailbox>>>,
+    changes: watch::Sender<u64>,
+}
+
+impl Default for MockImapServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockImapServer {
+    pub const DEFAULT_USERNAME: &'static str = "user";
+    pub const DEFAULT_PASSWORD: &'static str = "password";
+
+    /// Creates a server with an empty INBOX that accepts the default username and password.
+    pub fn new() -> Self {
+        let (changes, _) = watch::channel(0);
+
+        Self {
+            config: MockConfig {
+                greeting: String::from("* OK IMAP4rev1 mock server ready"),
+                errors: HashMap::new(),
+            },
+            mailboxes: Arc::new(Mutex::new(vec![MockMailbox::new("INBOX", Vec::new())])),
+            changes,
+        }
+    }
+
+    /// The untagged response that is sent when a client connects, like `* BYE Too many connections`.
+    ///
+    /// The connection is closed right after a `BYE` greeting.
+    pub fn set_greeting<G: Into<String>>(&mut self, greeting: G) -> &mut Self {
+        self.config.greeting = greeting.into();
+
+        self
+    }
+
+    /// Adds a mailbox with the given attributes, e.g. `\Noselect`.
+    pub fn add_mailbox<N: Into<String>>(&mut self, name: N, attributes: &[&str]) -> &mut Self {
+        let attributes = attributes
+            .iter()
+            .map(|attribute| attribute.to_string())
+            .collect();
+
+        self.mailboxes
+            .lock()
+            .unwrap()
+            .push(MockMailbox::new(name, attributes));
+
+        self
+    }
+
+    /// Appends a message with a given uid and flags to a mailbox, notifying any idling clients.
+    ///
+    /// Panics if the mailbox does not exist.
+    pub fn add_message<B: Into<Vec<u8>>>(
+        &mut self,
+        mailbox: &str,
+        uid: u32,
+        flags: &[&str],
+        body: B,
+    ) -> &mut Self {
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+
+        let mailbox = mailboxes
+            .iter_mut()
+            .find(|existing| existing.name == mailbox)
+            .expect("Mailbox does not exist");
+
+        mailbox.messages.push(MockMessage {
+            uid,
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+            body: body.into(),
+        });
+
+        mailbox.uid_next = mailbox.uid_next.max(uid.saturating_add(1));
+
+        drop(mailboxes);
+
+        self.changes.send_modify(|version| *version += 1);
+
+        self
+    }
+
+    /// Makes the server respond with `<tag> NO <message>` whenever the given command is sent.
+    pub fn fail_command<C: AsRef<str>, M: Into<String>>(
+        &mut self,
+        command: C,
+        message: M,
+    ) -> &mut Self {
+        self.config
+            .errors
+            .insert(command.as_ref().to_ascii_uppercase(), message.into());
+
+        self
+    }
+
+    /// A snapshot of the mailbox with the given name.
+    pub fn mailbox(&self, name: &str) -> Option<MockMailbox> {
+        self.mailboxes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|mailbox| mailbox.name == name)
+            .cloned()
+    }
+
+    /// Starts a session on an in-memory stream and returns the client side of it.
+    pub fn connect(&self) -> DuplexStream {
+        let (client, server) = io::duplex(DUPLEX_BUFFER_SIZE);
+
+        let session = MockSession {
+            config: self.config.clone(),
+            mailboxes: self.mailboxes.clone(),
+            changes: self.changes.subscribe(),
+            authenticated: false,
+            selected: None,
+            known_exists: 0,
+        };
+
+        tokio::spawn(session.run(server));
+
+        client
+    }
+}
+
+struct MockSession {
+    config: MockConfig,
+    mailboxes: Arc<Mutex<Vec<MockMailbox>>>,
+    changes: watch::Receiver<u64>,
+    authenticated: bool,
+    /// The name of the selected mailbox.
+    selected: Option<String>,
+    /// The message count we last reported to the client for the selected mailbox.
+    known_exists: usize,
+}
+
+/// What should happen after a command has been handled.
+enum Next {
+    Continue,
+    Idle(String),
+    Logout,
+}
+
+impl MockSession {
+    async fn run<S: AsyncRead + AsyncWrite + Unpin>(mut self, stream: S) {
+        let mut stream = BufReader::new(stream);
+
+        let greeting = format!("{}\r\n", self.config.greeting);
+
+        if self
+            .respond(&mut stream, greeting.as_bytes())
+            .await
+            .is_err()
+            || self.config.greeting.starts_with("* BYE")
+        {
+            return;
+        }
+
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            match stream.read_line(&mut line).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+
+            let mut response = Vec::new();
+
+            let next = self.handle_line(line.trim_end(), &mut response);
+
+            if self.respond(&mut stream, &response).await.is_err() {
+                return;
+            }
+
+            match next {
+                Next::Continue => {}
+                Next::Logout => return,
+                Next::Idle(tag) => {
+                    if self.idle(&mut stream, &tag).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn respond<S: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut S,
+        response: &[u8],
+    ) -> io::Result<()> {
+        stream.write_all(response).await?;
+
+        stream.flush().await
+    }
+
+    /// Reports new messages while the client is idling, until it sends DONE.
+    async fn idle<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut BufReader<S>,
+        tag: &str,
+    ) -> io::Result<()> {
+        let mut line = String::new();
+
+        let mut changes_open = true;
+
+        loop {
+            let mut response = Vec::new();
+
+            self.report_exists(&mut response);
+
+            if !response.is_empty() {
+                self.respond(stream, &response).await?;
+            }
+
+            tokio::select! {
+                read = stream.read_line(&mut line) => {
+                    if read? == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+
+                    let response = if line.trim().eq_ignore_ascii_case("DONE") {
+                        format!("{} OK IDLE terminated\r\n", tag)
+                    } else {
+                        format!("{} BAD Expected DONE\r\n", tag)
+                    };
+
+                    return self.respond(stream, response.as_bytes()).await;
+                }
+                changed = self.changes.changed(), if changes_open => {
+                    changes_open = changed.is_ok();
+                }
+            }
+        }
+    }
+
+    /// Writes an EXISTS response if the amount of messages in the selected mailbox has changed.
+    fn report_exists(&mut self, response: &mut Vec<u8>) {
+        let exists = match self.selected.as_ref() {
+            Some(name) => self
+                .mailboxes
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|mailbox| &mailbox.name == name)
+                .map(|mailbox| mailbox.messages.len()),
+            None => None,
+        };
+
+        if let Some(exists) = exists {
+            if exists != self.known_exists {
+                self.known_exists = exists;
+
+                response.extend_from_slice(format!("* {} EXISTS\r\n", exists).as_bytes());
+            }
+        }
+    }
+
+    fn handle_line(&mut self, line: &str, response: &mut Vec<u8>) -> Next {
+        let (tag, rest) = match line.split_once(' ') {
+            Some(split) => split,
+            None => {
+                response.extend_from_slice(b"* BAD Missing command\r\n");
+
+                return Next::Continue;
+            }
+        };
+
+        let mut arguments = tokenize(rest);
+
+        if arguments.is_empty() {
+            tagged(response, tag, "BAD", "Missing command");
+
+            return Next::Continue;
+        }
+
+        let mut command = arguments.remove(0).to_ascii_uppercase();
+
+        let uid = command == "UID";
+
+        if uid {
+            if arguments.is_empty() {
+                tagged(response, tag, "BAD", "Missing command");
+
+                return Next::Continue;
+            }
+
+            command = arguments.remove(0).to_ascii_uppercase();
+        }
+
+        let error_key = if uid {
+            format!("UID {}", command)
+        } else {
+            command.clone()
+        };
+
+        if let Some(message) = self.config.errors.get(&error_key) {
+            tagged(response, tag, "NO", message);
+
+            return Next::Continue;
+        }
+
+        let result = match command.as_str() {
+            "CAPABILITY" => {
+                response.extend_from_slice(b"* CAPABILITY IMAP4rev1 IDLE\r\n");
+
+                Ok(String::from("CAPABILITY completed"))
+            }
+            "NOOP" => {
+                self.report_exists(response);
+
+                Ok(String::from("NOOP completed"))
+            }
+            "LOGOUT" => {
+                response.extend_from_slice(b"* BYE Logging out\r\n");
+
+                tagged(response, tag, "OK", "LOGOUT completed");
+
+                return Next::Logout;
+            }
+            "LOGIN" => self.login(&arguments),
+            _ if !self.authenticated => Err(("BAD", String::from("Not authenticated"))),
+            "LIST" => self.list(&arguments, response),
+            "SELECT" => self.select(&arguments, response),
+            "CREATE" => self.create(&arguments),
+            "DELETE" => self.delete(&arguments),
+            "RENAME" => self.rename(&arguments),
+            "IDLE" => {
+                response.extend_from_slice(b"+ idling\r\n");
+
+                return Next::Idle(tag.to_string());
+            }
+            _ if self.selected.is_none() => Err(("BAD", String::from("No mailbox selected"))),
+            "CLOSE" => self.close(),
+            "FETCH" => self.fetch(&arguments, uid, response),
+            "STORE" => self.store(&arguments, uid, response),
+            "SEARCH" => self.search(&arguments, uid, response),
+            _ => Err(("BAD", format!("Unknown command {}", command))),
+        };
+
+        match result {
+            Ok(message) => tagged(response, tag, "OK", &message),
+            Err((status, message)) => tagged(response, tag, status, &message),
+        }
+
+        Next::Continue
+    }
+
+    fn login(&mut self, arguments: &[String]) -> CommandResult {
+        if self.authenticated {
+            return Err(("BAD", String::from("Already authenticated")));
+        }
+
+        match arguments {
+            [username, password] => {
+                if unquote(username) == MockImapServer::DEFAULT_USERNAME
+                    && unquote(password) == MockImapServer::DEFAULT_PASSWORD
+                {
+                    self.authenticated = true;
+
+                    Ok(String::from("LOGIN completed"))
+                } else {
+                    Err(("NO", String::from("Invalid credentials")))
+                }
+            }
+            _ => Err(("BAD", String::from("Expected a username and password"))),
+        }
+    }
+
+    fn list(&self, arguments: &[String], response: &mut Vec<u8>) -> CommandResult {
+        let pattern = match arguments {
+            [_, pattern] => unquote(pattern),
+            _ => return Err(("BAD", String::from("Expected a reference and a pattern"))),
+        };
+
+        for mailbox in self.mailboxes.lock().unwrap().iter() {
+            let matches = match pattern.as_str() {
+                "*" => true,
+                "%" => !mailbox.name.contains(DELIMITER),
+                pattern => mailbox.name == pattern,
+            };
+
+            if matches {
+                response.extend_from_slice(
+                    format!(
+                        "* LIST ({}) {} {}\r\n",
+                        mailbox.attributes.join(" "),
+                        quote(DELIMITER),
+                        astring(&mailbox.name)
+                    )
+                    .as_bytes(),
+                );
+            }
+        }
+
+        Ok(String::from("LIST completed"))
+    }
+
+    fn select(&mut self, arguments: &[String], response: &mut Vec<u8>) -> CommandResult {
+        let name = match arguments {
+            [name] => unquote(name),
+            _ => return Err(("BAD", String::from("Expected a mailbox"))),
+        };
+
+        // A failed select deselects the current mailbox.
+        self.selected = None;
+
+        let mailboxes = self.mailboxes.lock().unwrap();
+
+        let mailbox = find_mailbox(&mailboxes, &name)?;
+
+        if !mailbox.selectable() {
+            return Err(("NO", String::from("Mailbox is not selectable")));
+        }
+
+        response.extend_from_slice(b"* FLAGS (\\Answered \\Flagged \\Deleted \\Seen \\Draft)\r\n");
+        response.extend_from_slice(
+            format!("* {} EXISTS\r\n* 0 RECENT\r\n", mailbox.messages.len()).as_bytes(),
+        );
+
+        if let Some(first_unseen) = mailbox
+            .messages
+            .iter()
+            .position(|message| !message.has_flag("\\Seen"))
+        {
+            response.extend_from_slice(
+                format!(
+                    "* OK [UNSEEN {}] First unseen message\r\n",
+                    first_unseen + 1
+                )
+                .as_bytes(),
+            );
+        }
+
+        response.extend_from_slice(b"* OK [UIDVALIDITY 1] UIDs valid\r\n");
+        response.extend_from_slice(
+            format!("* OK [UIDNEXT {}] Predicted next UID\r\n", mailbox.uid_next).as_bytes(),
+        );
+
+        let known_exists = mailbox.messages.len();
+
+        drop(mailboxes);
+
+        self.known_exists = known_exists;
+
+        self.selected = Some(name);
+
+        Ok(String::from("[READ-WRITE] SELECT completed"))
+    }
+
+    fn create(&self, arguments: &[String]) -> CommandResult {
+        let name = match arguments {
+            [name] => unquote(name),
+            _ => return Err(("BAD", String::from("Expected a mailbox"))),
+        };
+
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+
+        if mailboxes.iter().any(|mailbox| mailbox.name == name) {
+            return Err(("NO", String::from("Mailbox already exists")));
+        }
+
+        mailboxes.push(MockMailbox::new(name, Vec::new()));
+
+        Ok(String::from("CREATE completed"))
+    }
+
+    fn delete(&self, arguments: &[String]) -> CommandResult {
+        let name = match arguments {
+            [name] => unquote(name),
+            _ => return Err(("BAD", String::from("Expected a mailbox"))),
+        };
+
+        if name.eq_ignore_ascii_case("INBOX") {
+            return Err(("NO", String::from("Cannot delete INBOX")));
+        }
+
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+
+        find_mailbox(&mailboxes, &name)?;
+
+        mailboxes.retain(|mailbox| mailbox.name != name);
+
+        Ok(String::from("DELETE completed"))
+    }
+
+    fn rename(&self, arguments: &[String]) -> CommandResult {
+        let (name, new_name) = match arguments {
+            [name, new_name] => (unquote(name), unquote(new_name)),
+            _ => return Err(("BAD", String::from("Expected a mailbox and a new name"))),
+        };
+
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+
+        if mailboxes.iter().any(|mailbox| mailbox.name == new_name) {
+            return Err(("NO", String::from("Mailbox already exists")));
+        }
+
+        let mailbox = mailboxes
+            .iter_mut()
+            .find(|mailbox| mailbox.name == name)
+            .ok_or(("NO", String::from("Mailbox does not exist")))?;
+
+        mailbox.name = new_name;
+
+        Ok(String::from("RENAME completed"))
+    }
+
+    fn close(&mut self) -> CommandResult {
+        let name = self.selected.take().unwrap();
+
+        self.with_selected_mailbox(&name, |mailbox| {
+            mailbox
+                .messages
+                .retain(|message| !message.has_flag("\\Deleted"))
+        })?;
+
+        Ok(String::from("CLOSE completed"))
+    }
+
+    fn fetch(&mut self, arguments: &[String], uid: bool, response: &mut Vec<u8>) -> CommandResult {
+        let (sequence_set, items) = match arguments {
+            [sequence_set, items] => (sequence_set, fetch_items(items)),
+            _ => return Err(("BAD", String::from("Expected a sequence set and items"))),
+        };
+
+        let name = self.selected.clone().unwrap();
+
+        let data = self.with_selected_mailbox(&name, |mailbox| {
+            let msg_numbers = resolve_sequence_set(mailbox, sequence_set, uid)?;
+
+            let mut data = Vec::new();
+
+            for msg_number in msg_numbers {
+                let message = &mut mailbox.messages[msg_number - 1];
+
+                let marks_seen = items.iter().any(|item| {
+                    matches!(item.as_str(), "RFC822" | "RFC822.TEXT")
+                        || matches!(parse_section_item(item), Some(item) if !item.peek)
+                });
+
+                if marks_seen {
+                    message.add_flag("\\Seen");
+                }
+
+                let mut attributes: Vec<Vec<u8>> = Vec::new();
+
+                if uid && !items.iter().any(|item| item == "UID") {
+                    attributes.push(format!("UID {}", message.uid).into_bytes());
+                }
+
+                for item in items.iter() {
+                    if let Some(item) = parse_section_item(item) {
+                        let data = message.section(&item.section).ok_or_else(|| {
+                            ("BAD", format!("Unsupported body section {}", item.section))
+                        })?;
+
+                        let attribute = match item.partial {
+                            Some((origin, count)) => {
+                                let start = origin.min(data.len());
+                                let end = origin.saturating_add(count).min(data.len());
+
+                                literal(
+                                    &format!("BODY[{}]<{}>", item.section, origin),
+                                    &data[start..end],
+                                )
+                            }
+                            None => literal(&format!("BODY[{}]", item.section), &data),
+                        };
+
+                        attributes.push(attribute);
+
+                        continue;
+                    }
+
+                    let attribute = match item.as_str() {
+                        "UID" => format!("UID {}", message.uid).into_bytes(),
+                        "FLAGS" => format!("FLAGS ({})", message.flags.join(" ")).into_bytes(),
+                        "INTERNALDATE" => {
+                            format!("INTERNALDATE {}", quote(&message.internal_date())).into_bytes()
+                        }
+                        "RFC822.SIZE" => format!("RFC822.SIZE {}", message.body.len()).into_bytes(),
+                        "ENVELOPE" => format!("ENVELOPE {}", message.envelope()).into_bytes(),
+                        "RFC822" => literal("RFC822", &message.body),
+                        "RFC822.HEADER" => literal("RFC822.HEADER", message.header()),
+                        "RFC822.TEXT" => literal("RFC822.TEXT", message.text()),
+                        "BODYSTRUCTURE" => {
+                            format!("BODYSTRUCTURE {}", message.part().body_structure())
+                                .into_bytes()
+                        }
+                        item => return Err(("BAD", format!("Unsupported fetch item {}", item))),
+                    };
+
+                    attributes.push(attribute);
+                }
+
+                data.push((msg_number, attributes.join(&b' ')));
+            }
+
+            Ok(data)
+        })??;
+
+        for (msg_number, attributes) in data {
+            response.extend_from_slice(format!("* {} FETCH (", msg_number).as_bytes());
+            response.extend_from_slice(&attributes);
+            response.extend_from_slice(b")\r\n");
+        }
+
+        Ok(String::from("FETCH completed"))
+    }
+
+    fn store(&mut self, arguments: &[String], uid: bool, response: &mut Vec<u8>) -> CommandResult {
+        let (sequence_set, action, flags) = match arguments {
+            [sequence_set, action, flags] => (
+                sequence_set,
+                action.to_ascii_uppercase(),
+                tokenize(strip_parens(flags)),
+            ),
+            _ => {
+                return Err((
+                    "BAD",
+                    String::from("Expected a sequence set, action and flags"),
+                ))
+            }
+        };
+
+        let name = self.selected.clone().unwrap();
+
+        let silent = action.ends_with(".SILENT");
+
+        let action = action.trim_end_matches(".SILENT").to_string();
+
+        let data = self.with_selected_mailbox(&name, |mailbox| {
+            let msg_numbers = resolve_sequence_set(mailbox, sequence_set, uid)?;
+
+            let mut data = Vec::new();
+
+            for msg_number in msg_numbers {
+                let message = &mut mailbox.messages[msg_number - 1];
+
+                match action.as_str() {
+                    "FLAGS" => message.flags = flags.clone(),
+                    "+FLAGS" => flags.iter().for_each(|flag| message.add_flag(flag)),
+                    "-FLAGS" => flags.iter().for_each(|flag| message.remove_flag(flag)),
+                    action => return Err(("BAD", format!("Unknown store action {}", action))),
+                }
+
+                let uid_attribute = if uid {
+                    format!("UID {} ", message.uid)
+                } else {
+                    String::new()
+                };
+
+                data.push(format!(
+                    "* {} FETCH ({}FLAGS ({}))\r\n",
+                    msg_number,
+                    uid_attribute,
+                    message.flags.join(" ")
+                ));
+            }
+
+            Ok(data)
+        })??;
+
+        if !silent {
+            for line in data {
+                response.extend_from_slice(line.as_bytes());
+            }
+        }
+
+        Ok(String::from("STORE completed"))
+    }
+
+    fn search(&mut self, arguments: &[String], uid: bool, response: &mut Vec<u8>) -> CommandResult {
+        let name = self.selected.clone().unwrap();
+
+        let found = self.with_selected_mailbox(&name, |mailbox| {
+            let mut found = Vec::new();
+
+            for (index, message) in mailbox.messages.iter().enumerate() {
+                let mut matches = true;
+
+                let mut criteria = arguments.iter();
+
+                while let Some(criterion) = criteria.next() {
+                    let criterion_matches = match criterion.to_ascii_uppercase().as_str() {
+                        "ALL" => true,
+                        "SEEN" => message.has_flag("\\Seen"),
+                        "UNSEEN" => !message.has_flag("\\Seen"),
+                        "FLAGGED" => message.has_flag("\\Flagged"),
+                        "UNFLAGGED" => !message.has_flag("\\Flagged"),
+                        "DELETED" => message.has_flag("\\Deleted"),
+                        "UNDELETED" => !message.has_flag("\\Deleted"),
+                        "ANSWERED" => message.has_flag("\\Answered"),
+                        "UNANSWERED" => !message.has_flag("\\Answered"),
+                        "UID" => match criteria.next() {
+                            Some(uids) => {
+                                let msg_numbers = resolve_sequence_set(mailbox, uids, true)?;
+
+                                msg_numbers.contains(&(index + 1))
+                            }
+                            None => return Err(("BAD", String::from("Expected a uid set"))),
+                        },
+                        criterion => {
+                            return Err(("BAD", format!("Unsupported search key {}", criterion)))
+                        }
+                    };
+
+                    matches = matches && criterion_matches;
+                }
+
+                if matches {
+                    found.push(if uid { message.uid } else { index as u32 + 1 });
+                }
+            }
+
+            Ok(found)
+        })??;
+
+        let found: Vec<String> = found.iter().map(|id| id.to_string()).collect();
+
+        if found.is_empty() {
+            response.extend_from_slice(b"* SEARCH\r\n");
+        } else {
+            response.extend_from_slice(format!("* SEARCH {}\r\n", found.join(" ")).as_bytes());
+        }
+
+        Ok(String::from("SEARCH completed"))
+    }
+
+    fn with_selected_mailbox<T, F: FnOnce(&mut MockMailbox) -> T>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> std::result::Result<T, (&'static str, String)> {
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+
+        match mailboxes.iter_mut().find(|mailbox| mailbox.name == name) {
+            Some(mailbox) => Ok(f(mailbox)),
+            None => Err(("NO", String::from("Selected mailbox no longer exists"))),
+        }
+    }
+}
+
+/// The text of a successful tagged response, or the status and text of a failed one.
+type CommandResult = std::result::Result<String, (&'static str, String)>;
+
+fn tagged(response: &mut Vec<u8>, tag: &str, status: &str, message: &str) {
+    response.extend_from_slice(format!("{} {} {}\r\n", tag, status, message).as_bytes());
+}
+
+fn find_mailbox<'a>(
+    mailboxes: &'a [MockMailbox],
+    name: &str,
+) -> std::result::Result<&'a MockMailbox, (&'static str, String)> {
+    mailboxes
+        .iter()
+        .find(|mailbox| mailbox.name == name)
+        .ok_or(("NO", String::from("Mailbox does not exist")))
+}
+
+/// Turns a sequence set like `1:3,5,7:*` into a list of message numbers that exist in the mailbox.
+fn resolve_sequence_set(
+    mailbox: &MockMailbox,
+    sequence_set: &str,
+    uid: bool,
+) -> std::result::Result<Vec<usize>, (&'static str, String)> {
+    let ids: Vec<u32> = if uid {
+        mailbox.messages.iter().map(|message| message.uid).collect()
+    } else {
+        (1..=mailbox.messages.len() as u32).collect()
+    };
+
+    let largest = ids.iter().max().cloned().unwrap_or(0);
+
+    let parse = |id: &str| -> std::result::Result<u32, (&'static str, String)> {
+        if id == "*" {
+            return Ok(largest);
+        }
+
+        id.parse()
+            .map_err(|_| ("BAD", format!("Invalid sequence set {}", sequence_set)))
+    };
+
+    let mut msg_numbers = Vec::new();
+
+    for part in sequence_set.split(',') {
+        let (start, end) = match part.split_once(':') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(part)?, parse(part)?),
+        };
+
+        let (start, end) = (start.min(end), start.max(end));
+
+        for (index, id) in ids.iter().enumerate() {
+            if id >= &start && id <= &end && !msg_numbers.contains(&(index + 1)) {
+                msg_numbers.push(index + 1);
+            }
+        }
+    }
+
+    // A sequence number that does not exist is an error, a uid that does not exist is simply ignored.
+    if !uid && msg_numbers.is_empty() {
+        return Err(("BAD", String::from("Invalid message sequence number")));
+    }
+
+    msg_numbers.sort();
+
+    Ok(msg_numbers)
+}
+
+/// Expands the fetch macros and returns the uppercase fetch items.
+fn fetch_items(items: &str) -> Vec<String> {
+    let items: Vec<String> = tokenize(strip_parens(items))
+        .into_iter()
+        .map(|item| item.to_ascii_uppercase())
+        .collect();
+
+    items
+        .into_iter()
+        .flat_map(|item| match item.as_str() {
+            "ALL" => vec!["FLAGS", "INTERNALDATE", "RFC822.SIZE", "ENVELOPE"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            "FAST" => vec!["FLAGS", "INTERNALDATE", "RFC822.SIZE"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            "FULL" => vec!["FLAGS", "INTERNALDATE", "RFC822.SIZE", "ENVELOPE", "BODY"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            _ => vec![item],
+        })
+        .collect()
+}
+
+/// Splits the arguments of a command, keeping quoted strings, parenthesized lists and bracketed sections together.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    let mut current = String::new();
+
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for char in input.chars() {
+        if in_quotes {
+            current.push(char);
+
+            if escaped {
+                escaped = false;
+            } else if char == '\\' {
+                escaped = true;
+            } else if char == '"' {
+                in_quotes = false;
+            }
+
+            continue;
+        }
+
+        match char {
+            '"' => {
+                in_quotes = true;
+                current.push(char);
+            }
+            '(' | '[' => {
+                depth += 1;
+                current.push(char);
+            }
+            ')' | ']' => {
+                depth -= 1;
+                current.push(char);
+            }
+            ' ' if depth == 0 => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            char => current.push(char),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn strip_parens(input: &str) -> &str {
+    input
+        .strip_prefix('(')
+        .and_then(|input| input.strip_suffix(')'))
+        .unwrap_or(input)
+}
+
+/// Removes the quotes and escapes from a quoted string, or returns an atom as is.
+fn unquote(input: &str) -> String {
+    match input
+        .strip_prefix('"')
+        .and_then(|input| input.strip_suffix('"'))
+    {
+        Some(quoted) => {
+            let mut unquoted = String::with_capacity(quoted.len());
+
+            let mut escaped = false;
+
+            for char in quoted.chars() {
+                if char == '\\' && !escaped {
+                    escaped = true;
+
+                    continue;
+                }
+
+                escaped = false;
+
+                unquoted.push(char);
+            }
+
+            unquoted
+        }
+        None => input.to_string(),
+    }
+}
+
+fn quote(input: &str) -> String {
+    format!("\"{}\"", input.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Formats a string as a quoted string if possible, otherwise as a literal.
+fn astring(input: &str) -> String {
+    if input.is_ascii() && !input.contains(['\r', '\n']) {
+        quote(input)
+    } else {
+        format!("{{{}}}\r\n{}", input.len(), input)
+    }
+}
+
+fn nstring(input: Option<&str>) -> String {
+    match input {
+        Some(input) => astring(input),
+        None => String::from("NIL"),
+    }
+}
+
+fn literal(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut literal = format!("{} {{{}}}\r\n", name, data.len()).into_bytes();
+
+    literal.extend_from_slice(data);
+
+    literal
+}
+
+/// Splits a message or a body part into its header, including the empty line that ends it, and its body.
+fn split_header(data: &[u8]) -> (&[u8], &[u8]) {
+    // A part without any header fields starts with the empty line.
+    if data.starts_with(b"\r\n") {
+        return data.split_at(2);
+    }
+
+    match find(data, b"\r\n\r\n") {
+        Some(index) => data.split_at(index + 4),
+        None => (data, &[]),
+    }
+}
+
+/// The unfolded fields of a header, with lowercase keys.
+fn parse_header_block(header: &[u8]) -> HashMap<String, String> {
+    let header = String::from_utf8_lossy(header);
+
+    let mut headers: HashMap<String, String> = HashMap::new();
+
+    let mut last_key: Option<String> = None;
+
+    for line in header.split("\r\n") {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(value) = last_key.as_ref().and_then(|key| headers.get_mut(key)) {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            let key = key.trim().to_ascii_lowercase();
+
+            headers
+                .entry(key.clone())
+                .or_insert_with(|| value.trim().to_string());
+
+            last_key = Some(key);
+        }
+    }
+
+    headers
+}
+
+/// Only keeps the given fields of a header, or all other fields if `not` is set, like a `HEADER.FIELDS (SUBJECT)` fetch.
+fn header_fields(header: &[u8], fields: &str, not: bool) -> Vec<u8> {
+    let fields: Vec<String> = tokenize(strip_parens(fields.trim()))
+        .iter()
+        .map(|field| unquote(field).to_ascii_uppercase())
+        .collect();
+
+    let header = String::from_utf8_lossy(header);
+
+    let mut filtered = String::new();
+
+    let mut included = false;
+
+    for line in header.split("\r\n").filter(|line| !line.is_empty()) {
+        // A folded line belongs to the field before it.
+        if !line.starts_with(' ') && !line.starts_with('\t') {
+            let key = line.split(':').next().unwrap_or_default();
+
+            included = fields.contains(&key.trim().to_ascii_uppercase()) != not;
+        }
+
+        if included {
+            filtered.push_str(line);
+            filtered.push_str("\r\n");
+        }
+    }
+
+    filtered.push_str("\r\n");
+
+    filtered.into_bytes()
+}
+
+/// Parses a header value with parameters, like `text/plain; charset="utf-8"`, into its lowercase value and its parameters.
+fn parse_header_value(value: &str) -> (String, Vec<(String, String)>) {
+    let mut items = value.split(';');
+
+    let value = items.next().unwrap_or_default().trim().to_ascii_lowercase();
+
+    let params = items
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), unquote(value.trim())))
+        .collect();
+
+    (value, params)
+}
+
+fn content_type(headers: &HashMap<String, String>) -> (String, Vec<(String, String)>) {
+    match headers.get("content-type") {
+        Some(value) => parse_header_value(value),
+        None => (
+            String::from("text/plain"),
+            vec![(String::from("charset"), String::from("us-ascii"))],
+        ),
+    }
+}
+
+fn param<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(param, _)| param == key)
+        .map(|(_, value)| value.as_str())
+}
+
+fn param_list(params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return String::from("NIL");
+    }
+
+    let params: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{} {}", quote(&key.to_ascii_uppercase()), quote(value)))
+        .collect();
+
+    format!("({})", params.join(" "))
+}
+
+/// Splits the body of a multipart into its parts, the line break before a delimiter belongs to the delimiter.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+
+    let mut parts = Vec::new();
+
+    let mut start: Option<usize> = None;
+
+    let mut offset = 0;
+
+    while offset < body.len() {
+        let line_end = find(&body[offset..], b"\r\n")
+            .map(|index| offset + index)
+            .unwrap_or(body.len());
+
+        let line = &body[offset..line_end];
+
+        if line.starts_with(delimiter.as_bytes()) {
+            if let Some(start) = start {
+                parts.push(&body[start..offset.saturating_sub(2).max(start)]);
+            }
+
+            // The close delimiter ends the multipart, anything after it is ignored.
+            if line[delimiter.len()..].starts_with(b"--") {
+                break;
+            }
+
+            start = Some((line_end + 2).min(body.len()));
+        }
+
+        offset = line_end + 2;
+    }
+
+    parts
+}
+
+fn count_lines(body: &[u8]) -> usize {
+    let lines = body.windows(2).filter(|window| window == b"\r\n").count();
+
+    if body.is_empty() || body.ends_with(b"\r\n") {
+        lines
+    } else {
+        lines + 1
+    }
+}
+
+/// A `BODY[<section>]<<partial>>` fetch item, like `BODY.PEEK[1.2]<0.256>`.
+struct SectionItem {
+    peek: bool,
+    section: String,
+    partial: Option<(usize, usize)>,
+}
+
+fn parse_section_item(item: &str) -> Option<SectionItem> {
+    let (peek, rest) = match item.strip_prefix("BODY.PEEK[") {
+        Some(rest) => (true, rest),
+        None => (false, item.strip_prefix("BODY[")?),
+    };
+
+    let (section, rest) = rest.split_once(']')?;
+
+    let partial = match rest {
+        "" => None,
+        rest => {
+            let (origin, count) = rest.strip_prefix('<')?.strip_suffix('>')?.split_once('.')?;
+
+            Some((origin.parse().ok()?, count.parse().ok()?))
+        }
+    };
+
+    Some(SectionItem {
+        peek,
+        section: section.to_string(),
+        partial,
+    })
+}
+
+/// Formats the headers of a message as an envelope.
+fn envelope(headers: &HashMap<String, String>) -> String {
+    let header = |key: &str| nstring(headers.get(key).map(|value| value.as_str()));
+
+    let from = headers.get("from").map(|from| address_list(from));
+
+    let address = |key: &str| match headers.get(key) {
+        Some(value) => address_list(value),
+        None => String::from("NIL"),
+    };
+
+    // The sender and reply-to fields default to the from field.
+    let default_to_from = |key: &str| match headers.get(key) {
+        Some(value) => address_list(value),
+        None => from.clone().unwrap_or_else(|| String::from("NIL")),
+    };
+
+    format!(
+        "({} {} {} {} {} {} {} {} {} {})",
+        header("date"),
+        header("subject"),
+        address("from"),
+        default_to_from("sender"),
+        default_to_from("reply-to"),
+        address("to"),
+        address("cc"),
+        address("bcc"),
+        header("in-reply-to"),
+        header("message-id"),
+    )
+}
+
+fn envelope_address(mailbox: &Mailbox) -> String {
+    let address = mailbox.address();
+
+    format!(
+        "({} NIL {} {})",
+        nstring(mailbox.name()),
+        astring(address.local_part()),
+        astring(address.domain())
+    )
+}
+
+/// Formats an address header as an envelope address list, a group is wrapped in entries that mark its start and end.
+fn address_list(header: &str) -> String {
+    let addresses: Vec<String> = parse_address_list(header)
+        .unwrap_or_default()
+        .iter()
+        .map(|address| match address {
+            Address::Mailbox(mailbox) => envelope_address(mailbox),
+            Address::Group { name, members } => {
+                let members: String = members.iter().map(envelope_address).collect();
+
+                format!(
+                    "(NIL NIL {} NIL){}(NIL NIL NIL NIL)",
+                    astring(name),
+                    members
+                )
+            }
+        })
+        .collect();
+
+    if addresses.is_empty() {
+        String::from("NIL")
+    } else {
+        format!("({})", addresses.join(""))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    use super::{tokenize, MockImapServer, MockMessage};
+
+    const MULTIPART_MESSAGE: &str = "Subject: Report\r\nContent-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n--outer\r\nContent-Type: multipart/alternative; boundary=\"inner\"\r\n\r\n--inner\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nHello world\r\n--inner\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>Hello world</p>\r\n--inner--\r\n--outer\r\nContent-Type: application/pdf; name=\"report.pdf\"\r\nContent-Disposition: attachment; filename=\"report.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--outer--\r\n";
+
+    #[test]
+    fn tokenize_arguments() {
+        assert_eq!(
+            tokenize("1:3 (FLAGS BODY.PEEK[HEADER.FIELDS (FROM TO)]) \"a \\\" b\""),
+            vec![
+                "1:3",
+                "(FLAGS BODY.PEEK[HEADER.FIELDS (FROM TO)])",
+                "\"a \\\" b\""
+            ]
+        );
+    }
+
+    #[test]
+    fn body_sections() {
+        let message = MockMessage {
+            uid: 1,
+            flags: Vec::new(),
+            body: MULTIPART_MESSAGE.as_bytes().to_vec(),
+        };
+
+        assert_eq!(
+            message.part().body_structure(),
+            "(((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 11 1 NIL NIL NIL NIL)(\"TEXT\" \"HTML\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 18 1 NIL NIL NIL NIL) \"ALTERNATIVE\" (\"BOUNDARY\" \"inner\") NIL NIL NIL)(\"APPLICATION\" \"PDF\" (\"NAME\" \"report.pdf\") NIL NIL \"BASE64\" 8 NIL (\"ATTACHMENT\" (\"FILENAME\" \"report.pdf\")) NIL NIL) \"MIXED\" (\"BOUNDARY\" \"outer\") NIL NIL NIL)"
+        );
+
+        assert_eq!(message.section("1.1").unwrap(), b"Hello world");
+        assert_eq!(message.section("1.2").unwrap(), b"<p>Hello world</p>");
+        assert_eq!(message.section("2").unwrap(), b"JVBERi0=");
+        assert_eq!(
+            message.section("HEADER.FIELDS (SUBJECT)").unwrap(),
+            b"Subject: Report\r\n\r\n"
+        );
+        assert!(message.section("3").is_none());
+
+        let message = MockMessage {
+            uid: 2,
+            flags: Vec::new(),
+            body: b"Subject: Plain\r\n\r\nHello\r\n".to_vec(),
+        };
+
+        // A message that is not a multipart only has a part 1.
+        assert_eq!(message.section("1").unwrap(), b"Hello\r\n");
+        assert!(message.section("1.1").is_some());
+        assert!(message.section("2").is_none());
+    }
+
+    #[tokio::test]
+    async fn idle() {
+        let mut server = MockImapServer::new();
+
+        let mut stream = BufReader::new(server.connect());
+
+        let mut line = String::new();
+
+        for command in ["A1 LOGIN user password\r\n", "A2 SELECT INBOX\r\n"] {
+            stream.write_all(command.as_bytes()).await.unwrap();
+        }
+
+        stream.write_all(b"A3 IDLE\r\n").await.unwrap();
+
+        while line != "+ idling\r\n" {
+            line.clear();
+
+            stream.read_line(&mut line).await.unwrap();
+        }
+
+        server.add_message("INBOX", 1, &[], "Subject: New\r\n\r\nNew\r\n");
+
+        line.clear();
+
+        stream.read_line(&mut line).await.unwrap();
+
+        assert_eq!(line, "* 1 EXISTS\r\n");
+
+        stream.write_all(b"DONE\r\n").await.unwrap();
+
+        line.clear();
+
+        stream.read_line(&mut line).await.unwrap();
+
+        assert_eq!(line, "A3 OK IDLE terminated\r\n");
+    }
+}
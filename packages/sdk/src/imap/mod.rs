@@ -1,9 +1,13 @@
+#[cfg(test)]
+mod mock;
 mod parse;
-// use std::collections::HashMap;
+use std::collections::HashMap;
 use std::fmt::Debug;
 
+use async_imap::imap_proto::types::{Response, Status};
 use async_imap::types::Fetch as ImapFetch;
-use async_native_tls::{TlsConnector, TlsStream};
+use async_native_tls::TlsStream;
+use async_tcp::{Config as TcpConfig, TranscriptStream};
 use async_trait::async_trait;
 use futures::StreamExt;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -11,13 +15,23 @@ use tokio::net::TcpStream;
 use tokio::time::Duration;
 
 use crate::cache::{Cache, Refresher};
-use crate::client::incoming::IncomingSession;
+use crate::client::incoming::{
+    check_attachment_size, IncomingSession, DEFAULT_MAX_ATTACHMENT_SIZE,
+};
 use crate::types::{
-    Error, ErrorKind, MailBox, MailBoxList, Message, OAuthCredentials, Preview, Result,
+    Attachment, Error, ErrorKind, MailBox, MailBoxList, Message, OAuthCredentials, Preview, Result,
 };
 
-const QUERY_PREVIEW: &str = "(FLAGS INTERNALDATE RFC822.SIZE ENVELOPE UID)";
-const QUERY_FULL_MESSAGE: &str = "(FLAGS INTERNALDATE RFC822.SIZE ENVELOPE RFC822 UID)";
+use self::parse::TextPart;
+
+const QUERY_PREVIEW: &str = "(FLAGS INTERNALDATE RFC822.SIZE ENVELOPE UID BODYSTRUCTURE BODY.PEEK[HEADER.FIELDS (IMPORTANCE X-PRIORITY PRIORITY)])";
+/// Everything about a message except for the data of its parts, which are fetched separately once it is known which ones are needed.
+const QUERY_MESSAGE_STRUCTURE: &str =
+    "(FLAGS INTERNALDATE RFC822.SIZE ENVELOPE UID BODYSTRUCTURE BODY.PEEK[HEADER])";
+const QUERY_BODYSTRUCTURE: &str = "(UID BODYSTRUCTURE)";
+
+/// The amount of bytes of a message's text that is fetched to create a snippet from.
+const SNIPPET_FETCH_SIZE: u32 = 2048;
 
 // const STATUS_ITEMS: &str = "(MESSAGES UNSEEN)";
 
@@ -54,32 +68,81 @@ pub struct ImapClient<S: AsyncRead + AsyncWrite + Unpin + Debug + Send> {
 pub struct ImapSession<S: AsyncWrite + AsyncRead + Unpin + Debug + Send + Sync> {
     session: async_imap::Session<S>,
     box_list: Cache<MailBoxList>,
-    /// The currently selected box' id.
-    selected_box: Option<String>,
+    /// The currently selected box, including its message counts.
+    selected_box: Option<MailBox>,
+    max_attachment_size: u64,
+}
+
+/// Creates a new imap client from an existing stream, reading the greeting of the server first.
+///
+/// A server can refuse the connection with a `BYE` greeting, or skip the login with a `PREAUTH` greeting.
+/// We always log in, so the latter is reported as unsupported.
+pub async fn new<S: AsyncRead + AsyncWrite + Unpin + Debug + Send>(
+    stream: S,
+) -> Result<ImapClient<S>> {
+    let mut client = async_imap::Client::new(stream);
+
+    let greeting = match client.read_response().await {
+        Some(greeting) => greeting?,
+        None => {
+            return Err(Error::new(
+                ErrorKind::MailServer,
+                "The imap server closed the connection without a greeting",
+            ))
+        }
+    };
+
+    match greeting.parsed() {
+        Response::Data {
+            status: Status::Ok, ..
+        } => Ok(ImapClient { client }),
+        Response::Data {
+            status: Status::PreAuth,
+            ..
+        } => Err(Error::new(
+            ErrorKind::Unsupported,
+            "The imap server authenticated the connection before logging in, which is not supported",
+        )),
+        Response::Data {
+            status: Status::Bye,
+            information,
+            ..
+        } => Err(Error::new(
+            ErrorKind::MailServer,
+            format!(
+                "The imap server refused the connection: {}",
+                information.as_deref().unwrap_or("no reason given")
+            ),
+        )),
+        _ => Err(Error::new(
+            ErrorKind::MailServer,
+            "The imap server did not send a valid greeting",
+        )),
+    }
 }
 
 pub async fn connect<S: AsRef<str>, P: Into<u16>>(
     server: S,
     port: P,
-) -> Result<ImapClient<TlsStream<TcpStream>>> {
-    let tls = TlsConnector::new();
+    config: &TcpConfig,
+) -> Result<ImapClient<TranscriptStream<TlsStream<TcpStream>>>> {
+    let stream = async_tcp::dial_tls(server, port.into(), config).await?;
 
-    let client = async_imap::connect((server.as_ref(), port.into()), server.as_ref(), tls).await?;
+    let stream = TranscriptStream::new(stream, config.transcript().cloned());
 
-    let imap_client = ImapClient { client };
-
-    Ok(imap_client)
+    new(stream).await
 }
 
 pub async fn connect_plain<S: AsRef<str>, P: Into<u16>>(
     server: S,
     port: P,
-) -> Result<ImapClient<TcpStream>> {
-    let stream = TcpStream::connect((server.as_ref(), port.into())).await?;
+    config: &TcpConfig,
+) -> Result<ImapClient<TranscriptStream<TcpStream>>> {
+    let stream = async_tcp::dial(server, port.into(), config).await?;
 
-    let client = async_imap::Client::new(stream);
+    let stream = TranscriptStream::new(stream, config.transcript().cloned());
 
-    Ok(ImapClient { client })
+    new(stream).await
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> ImapClient<S> {
@@ -90,6 +153,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> ImapClient<S> {
             session,
             box_list: box_list_cache,
             selected_box: None,
+            max_attachment_size: DEFAULT_MAX_ATTACHMENT_SIZE,
         }
     }
 
@@ -144,20 +208,82 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> ImapSession<S> {
     }
 
     /// Given an array of fetches that is expected to have length 1, return that one fetch and error if it has more or less than 1 items.
-    fn get_item_from_fetch_else_err<'a>(fetched: &'a Vec<ImapFetch>) -> Result<&'a ImapFetch> {
-        if fetched.len() == 0 {
-            return Err(Error::new(
+    fn get_item_from_fetch_else_err(fetched: Vec<ImapFetch>) -> Result<ImapFetch> {
+        match fetched.into_iter().next() {
+            Some(item) => Ok(item),
+            None => Err(Error::new(
                 ErrorKind::UnexpectedBehavior,
                 "Could not find a message with that id",
-            ));
+            )),
         }
+    }
 
-        match fetched.first() {
-            Some(item) => Ok(item),
-            None => {
-                unreachable!()
+    /// Fetches the given items of a single message in the selected box using its uid.
+    async fn uid_fetch_message(&mut self, msg_id: &str, query: &str) -> Result<ImapFetch> {
+        let msg_uid: u32 = msg_id.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::ParseString,
+                "Failed to parse imap message uid to u32",
+            )
+        })?;
+
+        let session = self.get_session_mut();
+
+        let mut fetch_stream = session.uid_fetch(msg_id, query).await?;
+
+        let mut fetched = Vec::new();
+
+        while let Some(fetch) = fetch_stream.next().await {
+            let fetch = fetch?;
+
+            // Only add the fetches that match our uid
+            if fetch.uid == Some(msg_uid) {
+                fetched.push(fetch);
             }
         }
+
+        Self::get_item_from_fetch_else_err(fetched)
+    }
+
+    /// Fetches the start of the text parts of the given messages and adds the snippets that are created from them to their previews.
+    ///
+    /// The text parts are grouped by their section, so all of the messages that have their text in the same part are fetched at once.
+    async fn fetch_snippets(
+        &mut self,
+        previews: &mut [Preview],
+        text_parts: HashMap<String, Vec<(u32, usize, TextPart)>>,
+    ) -> Result<()> {
+        let session = self.get_session_mut();
+
+        for (section, parts) in text_parts {
+            let uids: Vec<String> = parts.iter().map(|(uid, _, _)| uid.to_string()).collect();
+
+            let query = format!("(UID BODY.PEEK[{}]<0.{}>)", section, SNIPPET_FETCH_SIZE);
+
+            let mut fetch_stream = session.uid_fetch(uids.join(","), query).await?;
+
+            let mut fetched = HashMap::new();
+
+            while let Some(fetch) = fetch_stream.next().await {
+                let fetch = fetch?;
+
+                if let Some(uid) = fetch.uid {
+                    fetched.insert(uid, fetch);
+                }
+            }
+
+            for (uid, index, part) in parts {
+                let snippet = fetched
+                    .get(&uid)
+                    .and_then(|fetch| parse::fetch_to_snippet(fetch, &part));
+
+                if let Some(snippet) = snippet {
+                    previews[index].set_snippet(snippet);
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// This function will check if a box with a given box id is actually selectable, throwing an error if it is not.
@@ -188,28 +314,40 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> ImapSession<S> {
         let box_is_selected_already = self.selected_box.is_some();
 
         // If there is no box selected yet or the box we have selected is not the box when want to select, we have to request the server.
-        if !box_is_selected_already || self.selected_box.as_ref().unwrap() != box_id {
+        if !box_is_selected_already || self.selected_box.as_ref().unwrap().id() != box_id {
+            let box_list = self.get_mail_box_list().await?;
+
+            let mut found_box = match box_list.get_box(box_id) {
+                Some(found_box) => found_box.clone(),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::MailBoxNotFound,
+                        "Could not find a mailbox with that id",
+                    ))
+                }
+            };
+
             let session = self.get_session_mut();
 
             // If there is already a box selected we must close it first
             if box_is_selected_already {
                 session.close().await?;
+
+                self.selected_box = None;
             }
 
-            session.select(&box_id).await?;
+            let session = self.get_session_mut();
 
-            self.selected_box = Some(String::from(box_id))
-        };
+            let imap_counts = session.select(&box_id).await?;
 
-        let box_list = self.get_mail_box_list().await?;
+            found_box.create_counts(imap_counts);
 
-        if let Some(found_box) = box_list.get_box(&box_id) {
-            Ok(found_box)
-        } else {
-            Err(Error::new(
-                ErrorKind::MailBoxNotFound,
-                "Could not find a mailbox with that id",
-            ))
+            self.selected_box = Some(found_box);
+        };
+
+        match self.selected_box.as_ref() {
+            Some(selected_box) => Ok(selected_box),
+            None => unreachable!(),
         }
     }
 }
@@ -231,7 +369,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> IncomingSession fo
     }
 
     async fn get(&mut self, box_id: &str) -> Result<&MailBox> {
-        let box_id = box_id.trim().to_ascii_lowercase();
+        let box_id = box_id.trim();
 
         let box_list = self.get_mail_box_list().await?;
 
@@ -277,9 +415,14 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> IncomingSession fo
             None => new_name.to_string(),
         };
 
+        // A mailbox can only be closed if one is selected.
+        let box_is_selected = self.selected_box.take().is_some();
+
         let session = self.get_session_mut();
 
-        session.close().await?;
+        if box_is_selected {
+            session.close().await?;
+        }
 
         session.rename(box_id, &new_name).await?;
 
@@ -326,159 +469,560 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync> IncomingSession fo
 
             let mut previews: Vec<Preview> = Vec::new();
 
+            let mut text_parts: HashMap<String, Vec<(u32, usize, TextPart)>> = HashMap::new();
+
             while let Some(fetch) = preview_stream.next().await {
                 let fetch = fetch?;
 
                 let preview = parse::fetch_to_preview(&fetch)?;
 
+                if let (Some(uid), Some(part)) = (fetch.uid, parse::fetch_to_text_part(&fetch)) {
+                    text_parts
+                        .entry(part.section())
+                        .or_default()
+                        .push((uid, previews.len(), part));
+                }
+
                 previews.push(preview);
             }
 
+            drop(preview_stream);
+
+            self.fetch_snippets(&mut previews, text_parts).await?;
+
             Ok(previews)
         } else {
             Ok(Vec::new())
         }
     }
 
-    async fn get_message(&mut self, box_id: &str, msg_id: &str) -> Result<Message> {
+    async fn get_message(
+        &mut self,
+        box_id: &str,
+        msg_id: &str,
+        mark_read: bool,
+    ) -> Result<Message> {
         self.box_is_selectable_else_err(box_id).await?;
 
         self.select(box_id).await?;
 
-        let session = self.get_session_mut();
+        // The parts are fetched with peek, so the message only becomes read when asked to. This is done before its flags are fetched, so they include it.
+        if mark_read {
+            let session = self.get_session_mut();
 
-        let mut fetch_stream = session.uid_fetch(msg_id, QUERY_FULL_MESSAGE).await?;
+            let updates = session.uid_store(msg_id, "+FLAGS.SILENT (\\Seen)").await?;
 
-        let mut fetched = Vec::new();
+            updates.collect::<Vec<_>>().await;
+        }
 
-        while let Some(fetch) = fetch_stream.next().await {
-            let fetch = fetch?;
+        let fetch = self
+            .uid_fetch_message(msg_id, QUERY_MESSAGE_STRUCTURE)
+            .await?;
+
+        let structure = parse::fetch_to_structure(&fetch);
+
+        // Only the parts that are shown are downloaded, the attachments are fetched when they are requested.
+        // A part that is larger than the maximum size is only downloaded up to that size.
+        let sections: Vec<String> = structure
+            .content_parts()
+            .map(|part| {
+                if part.is_larger_than(self.max_attachment_size) {
+                    format!(
+                        "BODY.PEEK[{}]<0.{}>",
+                        part.section(),
+                        self.max_attachment_size
+                    )
+                } else {
+                    format!("BODY.PEEK[{}]", part.section())
+                }
+            })
+            .collect();
 
-            let uid = match &fetch.uid {
-                Some(uid) => uid,
-                // Only returns None when the UID parameter is not specified.
-                None => unreachable!(),
-            };
+        let content_fetch = if sections.is_empty() {
+            None
+        } else {
+            let query = format!("(UID {})", sections.join(" "));
 
-            let msg_id: u32 = msg_id.parse().map_err(|_| {
-                Error::new(
-                    ErrorKind::ParseString,
-                    "Failed to parse imap message uid to u32",
-                )
-            })?;
+            Some(self.uid_fetch_message(msg_id, &query).await?)
+        };
 
-            // Only add the fetches that match our uid
-            if uid == &msg_id {
-                fetched.push(fetch);
+        parse::fetch_to_message(
+            &fetch,
+            content_fetch.as_ref(),
+            structure,
+            self.max_attachment_size,
+        )
+    }
+
+    async fn get_attachment(
+        &mut self,
+        box_id: &str,
+        msg_id: &str,
+        attachment_id: &str,
+    ) -> Result<(Attachment, Vec<u8>)> {
+        self.box_is_selectable_else_err(box_id).await?;
+
+        self.select(box_id).await?;
+
+        let fetch = self.uid_fetch_message(msg_id, QUERY_BODYSTRUCTURE).await?;
+
+        let (attachment, encoding) = match parse::fetch_to_attachment(&fetch, attachment_id) {
+            Some(attachment) => attachment,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedBehavior,
+                    format!("Could not find an attachment with id '{}'", attachment_id),
+                ))
             }
-        }
+        };
+
+        check_attachment_size(&attachment, self.max_attachment_size)?;
+
+        let query = format!("(UID BODY.PEEK[{}])", attachment.id());
 
-        let fetch = Self::get_item_from_fetch_else_err(&fetched)?;
+        let fetch = self.uid_fetch_message(msg_id, &query).await?;
+
+        let data = parse::fetch_to_attachment_data(&fetch, &attachment, &encoding)?;
+
+        Ok((attachment, data))
+    }
 
-        parse::fetch_to_message(fetch).await
+    fn set_max_attachment_size(&mut self, max_size: u64) {
+        self.max_attachment_size = max_size;
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use async_native_tls::TlsStream;
+    use futures::StreamExt;
+    use tokio::io::DuplexStream;
 
-    use tokio::net::TcpStream;
+    use super::{mock::MockImapServer, ImapSession};
 
-    use super::ImapSession;
+    use crate::{
+        client::incoming::IncomingSession,
+        types::{AddressListEntry, ErrorKind, Flag, Importance},
+    };
 
-    use crate::client::incoming::IncomingSession;
+    const MESSAGE: &str = "Date: Sat, 11 Mar 2023 12:00:00 +0000\r\nSubject: Hello\r\nFrom: \"John Doe\" <john@example.com>\r\nTo: jane@example.com\r\nContent-Type: text/plain\r\n\r\nHello\r\n";
 
-    use dotenv::dotenv;
+    fn create_test_server() -> MockImapServer {
+        let mut server = MockImapServer::new();
 
-    use std::env;
+        server
+            .add_mailbox("Web", &[])
+            .add_mailbox("Archive", &["\\Noselect"]);
 
-    async fn create_test_session() -> ImapSession<TlsStream<TcpStream>> {
-        dotenv().ok();
+        server
+            .add_message(
+                "INBOX",
+                1111,
+                &["\\Seen"],
+                "Subject: First\r\n\r\nFirst\r\n",
+            )
+            .add_message("INBOX", 1112, &[], "Subject: Second\r\n\r\nSecond\r\n")
+            .add_message("INBOX", 1113, &[], MESSAGE);
 
-        let username = env::var("IMAP_USERNAME").unwrap();
-        let password = env::var("IMAP_PASSWORD").unwrap();
-
-        let server = env::var("IMAP_SERVER").unwrap();
-        let port: u16 = 993;
+        server
+    }
 
-        let client = super::connect(server, port).await.unwrap();
+    async fn create_test_session(server: &MockImapServer) -> ImapSession<DuplexStream> {
+        let client = super::new(server.connect()).await.unwrap();
 
-        let session = client.login(&username, &password).await.unwrap();
+        let session = client
+            .login(
+                MockImapServer::DEFAULT_USERNAME,
+                MockImapServer::DEFAULT_PASSWORD,
+            )
+            .await
+            .unwrap();
 
         session
     }
 
     #[tokio::test]
     async fn login() {
-        let mut session = create_test_session().await;
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
 
         session.logout().await.unwrap();
+
+        let client = super::new(server.connect()).await.unwrap();
+
+        let result = client
+            .login(MockImapServer::DEFAULT_USERNAME, "wrong")
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn greeting() {
+        let mut server = create_test_server();
+
+        server.set_greeting("* BYE Too many connections");
+
+        let error = super::new(server.connect()).await.err().unwrap();
+
+        assert!(matches!(error.kind(), ErrorKind::MailServer));
+        assert!(error.to_string().contains("Too many connections"));
+
+        server.set_greeting("* PREAUTH Logged in as user");
+
+        let error = super::new(server.connect()).await.err().unwrap();
+
+        assert!(matches!(error.kind(), ErrorKind::Unsupported));
     }
 
     #[tokio::test]
     async fn get_mailbox() {
-        let mut session = create_test_session().await;
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
+
+        let mailbox = session.get("Web").await.unwrap();
 
-        let box_name = "Web";
+        assert_eq!(mailbox.id(), "Web");
+        assert!(mailbox.selectable());
 
-        let mailbox = session.get(box_name).await.unwrap();
+        let mailbox = session.get("Archive").await.unwrap();
 
-        println!("{:?}", mailbox);
+        assert!(!mailbox.selectable());
+
+        assert!(session.get("Missing").await.is_err());
+
+        session.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_box_list() {
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
+
+        let box_list = session.box_list().await.unwrap();
+
+        let mut ids: Vec<&str> = box_list.iter().map(|mailbox| mailbox.id()).collect();
+
+        ids.sort();
+
+        assert_eq!(ids, vec!["Archive", "INBOX", "Web"]);
 
         session.logout().await.unwrap();
     }
 
     #[tokio::test]
     async fn get_messages() {
-        let mut session = create_test_session().await;
+        let server = create_test_server();
 
-        let box_name = "INBOX";
+        let mut session = create_test_session(&server).await;
 
-        let messages = session.messages(box_name, 0, 10).await.unwrap();
+        let messages = session.messages("INBOX", 0, 10).await.unwrap();
 
-        for preview in messages.into_iter() {
-            println!("{}", preview.sent().unwrap());
-        }
+        let ids: Vec<&str> = messages.iter().map(|preview| preview.id()).collect();
+
+        assert_eq!(ids, vec!["1111", "1112", "1113"]);
+
+        assert!(messages[0]
+            .flags()
+            .iter()
+            .any(|flag| matches!(flag, Flag::Read)));
+
+        let preview = &messages[2];
+
+        assert_eq!(preview.subject(), Some("Hello"));
+        assert_eq!(preview.sent(), Some(&1678536000));
+        assert_eq!(preview.from()[0].address(), "john@example.com");
+        assert_eq!(preview.from()[0].name(), Some("John Doe"));
+
+        // Only the last two messages
+        let messages = session.messages("INBOX", 0, 2).await.unwrap();
+
+        assert_eq!(messages.len(), 2);
+
+        // Switching to another mailbox closes the current one.
+        let messages = session.messages("Web", 0, 10).await.unwrap();
+
+        assert!(messages.is_empty());
+
+        assert!(session.messages("Archive", 0, 10).await.is_err());
 
         session.logout().await.unwrap();
     }
 
     #[tokio::test]
-    async fn get_box_list() {
-        let mut session = create_test_session().await;
+    async fn message_previews() {
+        let mut server = create_test_server();
 
-        let box_list = session.box_list().await.unwrap();
+        server.add_message(
+            "INBOX",
+            1114,
+            &[],
+            "Subject: Report\r\nFrom: john@example.com\r\nTo: jane@example.com\r\nCc: team@example.com\r\nMessage-ID: <report@example.com>\r\nIn-Reply-To: <question@example.com>\r\nX-Priority: 1 (Highest)\r\nContent-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n--outer\r\nContent-Type: multipart/alternative; boundary=\"inner\"\r\n\r\n--inner\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>The report is attached</p>\r\n--inner\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\nThe report is=\r\n attached\r\n\r\n> Where is the report?\r\n--inner--\r\n--outer\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"report.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--outer--\r\n",
+        );
 
-        for mailbox in box_list {
-            println!("{}", mailbox.id());
-        }
+        let mut session = create_test_session(&server).await;
+
+        let messages = session.messages("INBOX", 0, 10).await.unwrap();
+
+        let preview = &messages[1];
+
+        assert_eq!(preview.snippet(), Some("Second"));
+        assert_eq!(preview.size(), Some(&27));
+        assert!(!preview.has_attachments());
+        assert_eq!(preview.importance(), &Importance::Normal);
+
+        let preview = &messages[3];
+
+        // The plain text is preferred over the html, even though it comes after it.
+        assert_eq!(preview.snippet(), Some("The report is attached"));
+        assert!(preview.has_attachments());
+        assert_eq!(preview.importance(), &Importance::High);
+        assert_eq!(preview.message_id(), Some("report@example.com"));
+        assert_eq!(preview.in_reply_to(), Some("question@example.com"));
+        assert_eq!(preview.to()[0].addresses()[0].address(), "jane@example.com");
+        assert_eq!(preview.cc()[0].addresses()[0].address(), "team@example.com");
 
         session.logout().await.unwrap();
+
+        // Creating the snippets does not mark the messages as read.
+        let mailbox = server.mailbox("INBOX").unwrap();
+
+        assert!(mailbox.get_message(1114).unwrap().flags().is_empty());
     }
 
     #[tokio::test]
     async fn get_message() {
-        let mut session = create_test_session().await;
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
+
+        let message = session.get_message("INBOX", "1113", false).await.unwrap();
+
+        assert_eq!(message.id(), "1113");
+        assert_eq!(message.subject(), Some("Hello"));
+        assert_eq!(message.to()[0].addresses()[0].address(), "jane@example.com");
+        assert!(message.content().text().unwrap().contains("Hello"));
+        assert!(message.flags().is_empty());
+
+        // Fetching the message without marking it as read leaves its flags untouched.
+        let is_seen = |server: &MockImapServer| {
+            server
+                .mailbox("INBOX")
+                .unwrap()
+                .get_message(1113)
+                .unwrap()
+                .flags()
+                .contains(&String::from("\\Seen"))
+        };
+
+        assert!(!is_seen(&server));
 
-        let msg_id = "1113";
-        let box_id = "INBOX";
+        let message = session.get_message("INBOX", "1113", true).await.unwrap();
 
-        let message = session.get_message(box_id, msg_id).await.unwrap();
+        assert!(message
+            .flags()
+            .iter()
+            .any(|flag| matches!(flag, Flag::Read)));
+        assert!(is_seen(&server));
 
-        println!("{}", message.content().text().unwrap());
+        assert!(session.get_message("INBOX", "2000", false).await.is_err());
 
         session.logout().await.unwrap();
     }
 
     #[tokio::test]
-    async fn rename_box() {
-        let mut session = create_test_session().await;
+    async fn message_attachments() {
+        let mut server = create_test_server();
+
+        server.add_message(
+            "INBOX",
+            1114,
+            &[],
+            "Subject: Report\r\nContent-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n--outer\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nThe report is attached\r\n--outer\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"report.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--outer--\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        let message = session.get_message("INBOX", "1114", false).await.unwrap();
+
+        assert!(message.content().text().unwrap().contains("report"));
+        assert!(message.content().html().is_none());
+
+        let attachment = &message.attachments()[0];
+
+        assert_eq!(message.attachments().len(), 1);
+        assert_eq!(attachment.id(), "2");
+        assert_eq!(attachment.filename(), Some("report.pdf"));
+        assert_eq!(attachment.content_type(), "application/pdf");
+        assert_eq!(attachment.size(), Some(&8));
+
+        let (attachment, data) = session.get_attachment("INBOX", "1114", "2").await.unwrap();
+
+        assert_eq!(attachment.filename(), Some("report.pdf"));
+        assert_eq!(data, b"%PDF-");
+
+        assert!(session.get_attachment("INBOX", "1114", "3").await.is_err());
+
+        session.set_max_attachment_size(4);
+
+        let error = session
+            .get_attachment("INBOX", "1114", "2")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.kind(), ErrorKind::TooLarge));
+
+        session.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn truncated_message() {
+        let mut server = create_test_server();
+
+        server.add_message(
+            "INBOX",
+            1114,
+            &[],
+            "Subject: Long\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\nSGVsbG8gd29ybGQ=\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        let message = session.get_message("INBOX", "1114", false).await.unwrap();
+
+        assert_eq!(message.content().text(), Some("Hello world"));
+        assert!(!message.content().is_truncated());
+
+        // Only the start of the part is fetched, which ends in the middle of a base64 group.
+        session.set_max_attachment_size(10);
+
+        let message = session.get_message("INBOX", "1114", false).await.unwrap();
+
+        assert_eq!(message.content().text(), Some("Hello "));
+        assert!(message.content().is_truncated());
+
+        session.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn message_addresses() {
+        let mut server = create_test_server();
+
+        server.add_message(
+            "INBOX",
+            1114,
+            &[],
+            "Subject: Addresses\r\nFrom: =?utf-8?q?Doe=2C_J=C3=B6rg?= <jorg@Example.com>\r\nSender: list@example.com\r\nTo: Friends: jane@example.com, \"Doe, John\" <john@example.com>;\r\nCc: undisclosed-recipients:;\r\n\r\nHello\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        let message = session.get_message("INBOX", "1114", false).await.unwrap();
+
+        assert_eq!(message.from()[0].name(), Some("Doe, Jörg"));
+        assert_eq!(message.from()[0].address(), "jorg@example.com");
+
+        assert_eq!(message.sender().unwrap().address(), "list@example.com");
+
+        // The envelope of the mock server, like a real one, uses the author when there is no reply to header.
+        assert!(message.reply_to().is_empty());
+
+        match &message.to()[0] {
+            AddressListEntry::Group(group) => {
+                assert_eq!(group.name(), "Friends");
+                assert_eq!(group.members().len(), 2);
+                assert_eq!(
+                    group.members()[1].full(),
+                    "\"Doe, John\" <john@example.com>"
+                );
+            }
+            AddressListEntry::Address(_) => panic!("Expected a group"),
+        }
+
+        match &message.cc()[0] {
+            AddressListEntry::Group(group) => assert!(group.members().is_empty()),
+            AddressListEntry::Address(_) => panic!("Expected a group"),
+        }
+
+        server.add_message(
+            "INBOX",
+            1115,
+            &[],
+            "Subject: Authors\r\nFrom: jane@example.com, John <john@example.com>\r\n\r\nHello\r\n",
+        );
+
+        // The envelope uses all of the authors as the sender, which should not show up as a separate sender.
+        let message = session.get_message("INBOX", "1115", false).await.unwrap();
+
+        assert_eq!(message.from().len(), 2);
+        assert!(message.sender().is_none());
+        assert!(message.reply_to().is_empty());
+
+        session.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn manage_boxes() {
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
+
+        session.create("Drafts").await.unwrap();
+
+        assert!(server.mailbox("Drafts").is_some());
+
+        session.delete("Drafts").await.unwrap();
+
+        assert!(server.mailbox("Drafts").is_none());
+
+        session.rename("Web", "Delivery").await.unwrap();
+
+        assert!(server.mailbox("Web").is_none());
+        assert!(server.mailbox("Delivery").is_some());
+
+        session.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn store_and_search() {
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
+
+        session.session.select("INBOX").await.unwrap();
+
+        let updates: Vec<_> = session
+            .session
+            .store("1", "+FLAGS (\\Flagged)")
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(updates.len(), 1);
+
+        let unseen = session.session.search("UNSEEN").await.unwrap();
+
+        assert_eq!(unseen, [2, 3].into_iter().collect());
+
+        let flagged = session.session.uid_search("FLAGGED").await.unwrap();
+
+        assert_eq!(flagged, [1111].into_iter().collect());
+
+        session.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn server_error() {
+        let mut server = create_test_server();
+
+        server.fail_command("SELECT", "Server busy");
 
-        let new_box_name = "Delivery";
-        let box_id = "Test";
+        let mut session = create_test_session(&server).await;
 
-        session.rename(box_id, new_box_name).await.unwrap();
+        assert!(session.messages("INBOX", 0, 10).await.is_err());
 
         session.logout().await.unwrap();
     }
@@ -1,16 +1,86 @@
 use std::{borrow::Cow, collections::HashMap};
 
-use async_imap::types::{
-    Fetch,
-    Flag as ImapFlag,
-    //  Mailbox as ImapCounts,
+use async_imap::{
+    imap_proto::types::{
+        Address as ImapAddress, BodyContentCommon, BodyContentSinglePart, BodyParams,
+        BodyStructure, ContentEncoding, Envelope, SectionPath,
+    },
+    types::{
+        Fetch,
+        Flag as ImapFlag,
+        //  Mailbox as ImapCounts,
+    },
 };
+use dust_mail_utils::decode_encoded_words;
 
 use crate::{
-    parse::{parse_headers, parse_rfc822},
-    types::{Address, Content, Error, ErrorKind, Flag, Headers, Message, Preview, Result},
+    parse::{
+        create_content, decode_part, parse_headers, parse_importance, parse_message_id,
+        parse_partial_text_part, parse_snippet, parse_text_part,
+    },
+    types::{
+        Address, AddressListEntry, Addresses, Attachment, Error, ErrorKind, Flag, Group, Headers,
+        Message, Preview, Result,
+    },
 };
 
+/// The part of a message that holds its text, the start of which is fetched to create a snippet for the message's preview.
+pub struct TextPart {
+    path: Vec<u32>,
+    content_type: String,
+    encoding: String,
+    size: u64,
+}
+
+impl TextPart {
+    /// The section that is used to fetch the part, like `1.2`.
+    pub fn section(&self) -> String {
+        path_to_section(&self.path)
+    }
+
+    /// Whether the encoded part is larger than a given size in bytes, in which case only the start of it is fetched.
+    pub fn is_larger_than(&self, max_size: u64) -> bool {
+        self.size > max_size
+    }
+}
+
+/// The parts of a message as described by its BODYSTRUCTURE, split into the ones that make up its content and its attachments.
+#[derive(Default)]
+pub struct MessageStructure {
+    text: Option<TextPart>,
+    html: Option<TextPart>,
+    attachments: Vec<Attachment>,
+}
+
+impl MessageStructure {
+    /// The parts that have to be fetched to show the message.
+    pub fn content_parts(&self) -> impl Iterator<Item = &TextPart> {
+        self.text.iter().chain(self.html.iter())
+    }
+}
+
+fn path_to_section(path: &[u32]) -> String {
+    let numbers: Vec<String> = path.iter().map(|number| number.to_string()).collect();
+
+    numbers.join(".")
+}
+
+fn section_to_path(section: &str) -> Option<Vec<u32>> {
+    section
+        .split('.')
+        .map(|number| number.parse().ok().filter(|number| *number > 0))
+        .collect()
+}
+
+/// A message that is not a multipart only has a part 1, which is its body.
+fn part_path(path: &[u32]) -> Vec<u32> {
+    if path.is_empty() {
+        vec![1]
+    } else {
+        path.to_vec()
+    }
+}
+
 fn bytes_to_string<'a>(bytes: &Option<Cow<'a, [u8]>>) -> Option<String> {
     match bytes {
         Some(bytes) => Some(std::str::from_utf8(&bytes).ok()?.to_string()),
@@ -18,14 +88,63 @@ fn bytes_to_string<'a>(bytes: &Option<Cow<'a, [u8]>>) -> Option<String> {
     }
 }
 
-fn address_to_string(
-    mailbox: &Option<Cow<'_, [u8]>>,
-    host: &Option<Cow<'_, [u8]>>,
-) -> Option<String> {
-    let mailbox_string = bytes_to_string(mailbox)?;
-    let host_string = bytes_to_string(host)?;
+fn envelope_address(address: &ImapAddress, mailbox: String, host: String) -> Address {
+    let name = bytes_to_string(&address.name).map(|name| decode_encoded_words(&name));
+
+    Address::from_parts(name, mailbox, host)
+}
+
+/// Converts an address list from an envelope, in which a group starts with an entry without a host that holds the name of the group and ends with an entry without a mailbox.
+fn envelope_address_list(addresses: Option<&Vec<ImapAddress>>) -> Vec<AddressListEntry> {
+    let mut entries = Vec::new();
+
+    let mut group: Option<(String, Vec<Address>)> = None;
+
+    for address in addresses.into_iter().flatten() {
+        match (
+            bytes_to_string(&address.mailbox),
+            bytes_to_string(&address.host),
+        ) {
+            (Some(mailbox), Some(host)) => {
+                let address = envelope_address(address, mailbox, host);
+
+                match group.as_mut() {
+                    Some((_, members)) => members.push(address),
+                    None => entries.push(AddressListEntry::Address(address)),
+                }
+            }
+            (Some(name), None) => group = Some((decode_encoded_words(&name), Vec::new())),
+            (None, _) => {
+                if let Some((name, members)) = group.take() {
+                    entries.push(AddressListEntry::Group(Group::new(name, members)))
+                }
+            }
+        }
+    }
+
+    entries
+}
+
+/// Converts a mailbox list from an envelope, the members of a group are included as separate addresses.
+fn envelope_mailbox_list(addresses: Option<&Vec<ImapAddress>>) -> Vec<Address> {
+    envelope_address_list(addresses)
+        .iter()
+        .flat_map(AddressListEntry::addresses)
+        .cloned()
+        .collect()
+}
 
-    Some(format!("{}@{}", mailbox_string, host_string))
+fn envelope_addresses(envelope: &Envelope) -> Addresses {
+    Addresses::new(
+        envelope_mailbox_list(envelope.from.as_ref()),
+        envelope_mailbox_list(envelope.sender.as_ref())
+            .into_iter()
+            .next(),
+        envelope_address_list(envelope.reply_to.as_ref()),
+        envelope_address_list(envelope.to.as_ref()),
+        envelope_address_list(envelope.cc.as_ref()),
+        envelope_address_list(envelope.bcc.as_ref()),
+    )
 }
 
 fn parse_uid(uid: Option<u32>) -> Result<String> {
@@ -56,6 +175,239 @@ fn imap_flags_to_flags<'a, I: Iterator<Item = ImapFlag<'a>>>(imap_flag: I) -> Ve
         .collect()
 }
 
+fn content_type_to_string(common: &BodyContentCommon) -> String {
+    let mut content_type = format!("{}/{}", common.ty.ty, common.ty.subtype);
+
+    for (key, value) in common.ty.params.iter().flatten() {
+        content_type.push_str(&format!("; {}=\"{}\"", key, value));
+    }
+
+    content_type
+}
+
+fn encoding_to_string(encoding: &ContentEncoding) -> String {
+    let encoding = match encoding {
+        ContentEncoding::SevenBit => "7bit",
+        ContentEncoding::EightBit => "8bit",
+        ContentEncoding::Binary => "binary",
+        ContentEncoding::Base64 => "base64",
+        ContentEncoding::QuotedPrintable => "quoted-printable",
+        ContentEncoding::Other(encoding) => encoding.as_ref(),
+    };
+
+    encoding.to_string()
+}
+
+fn is_attachment(common: &BodyContentCommon, other: &BodyContentSinglePart) -> bool {
+    match &common.disposition {
+        Some(disposition) => disposition.ty.eq_ignore_ascii_case("attachment"),
+        // Without a disposition, parts that are not text are attachments, unless the html refers to them by their content id.
+        None => !common.ty.ty.eq_ignore_ascii_case("text") && other.id.is_none(),
+    }
+}
+
+fn has_attachments(structure: &BodyStructure) -> bool {
+    match structure {
+        BodyStructure::Basic { common, other, .. } | BodyStructure::Text { common, other, .. } => {
+            is_attachment(common, other)
+        }
+        // An encapsulated message, like a forwarded email, is an attachment as well.
+        BodyStructure::Message { .. } => true,
+        BodyStructure::Multipart { bodies, .. } => bodies.iter().any(has_attachments),
+    }
+}
+
+/// Finds the first text part with a given subtype that is not an attachment, without looking inside of encapsulated messages.
+fn find_text_part(structure: &BodyStructure, path: &[u32], subtype: &str) -> Option<TextPart> {
+    match structure {
+        BodyStructure::Text { common, other, .. }
+            if common.ty.subtype.eq_ignore_ascii_case(subtype) && !is_attachment(common, other) =>
+        {
+            Some(TextPart {
+                path: part_path(path),
+                content_type: content_type_to_string(common),
+                encoding: encoding_to_string(&other.transfer_encoding),
+                size: other.octets.into(),
+            })
+        }
+        BodyStructure::Multipart { bodies, .. } => {
+            bodies.iter().zip(1..).find_map(|(body, number)| {
+                let mut path = path.to_vec();
+
+                path.push(number);
+
+                find_text_part(body, &path, subtype)
+            })
+        }
+        _ => None,
+    }
+}
+
+fn body_param<'a>(params: &'a BodyParams, key: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .flatten()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.as_ref())
+}
+
+/// The fields of a part that is not a multipart, which is the kind of part that can be downloaded as an attachment.
+fn single_part<'a>(
+    structure: &'a BodyStructure,
+) -> Option<(&'a BodyContentCommon<'a>, &'a BodyContentSinglePart<'a>)> {
+    match structure {
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => Some((common, other)),
+        BodyStructure::Multipart { .. } => None,
+    }
+}
+
+/// Finds the part with the given part numbers, like `[1, 2]` for the second part of the first part.
+fn find_part<'a>(structure: &'a BodyStructure<'a>, path: &[u32]) -> Option<&'a BodyStructure<'a>> {
+    let (number, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Some(structure),
+    };
+
+    match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            find_part(bodies.get(*number as usize - 1)?, rest)
+        }
+        // The parts of an encapsulated message are numbered like the parts of the message itself.
+        BodyStructure::Message { body, .. } => find_part(body, path),
+        _ if *number == 1 => find_part(structure, rest),
+        _ => None,
+    }
+}
+
+fn create_attachment(
+    common: &BodyContentCommon,
+    other: &BodyContentSinglePart,
+    path: &[u32],
+) -> Attachment {
+    let content_type = format!("{}/{}", common.ty.ty, common.ty.subtype).to_ascii_lowercase();
+
+    let mut attachment = Attachment::new(path_to_section(path), content_type);
+
+    attachment.set_size(other.octets.into());
+
+    let filename = common
+        .disposition
+        .as_ref()
+        .and_then(|disposition| body_param(&disposition.params, "filename"))
+        .or_else(|| body_param(&common.ty.params, "name"));
+
+    if let Some(filename) = filename {
+        attachment.set_filename(decode_encoded_words(filename));
+    }
+
+    if let Some(content_id) = other.id.as_ref().and_then(|id| parse_message_id(id)) {
+        attachment.set_content_id(content_id);
+    }
+
+    attachment
+}
+
+/// Describes every part that is not a multipart and not one of the given content parts as an attachment.
+fn find_attachments(
+    structure: &BodyStructure,
+    path: &[u32],
+    content: &[&TextPart],
+    attachments: &mut Vec<Attachment>,
+) {
+    if let BodyStructure::Multipart { bodies, .. } = structure {
+        for (body, number) in bodies.iter().zip(1..) {
+            let mut path = path.to_vec();
+
+            path.push(number);
+
+            find_attachments(body, &path, content, attachments);
+        }
+
+        return;
+    }
+
+    let path = part_path(path);
+
+    if content.iter().any(|part| part.path == path) {
+        return;
+    }
+
+    if let Some((common, other)) = single_part(structure) {
+        attachments.push(create_attachment(common, other, &path));
+    }
+}
+
+/// Splits a message into the parts that are needed to show it and its attachments, using its BODYSTRUCTURE.
+pub fn fetch_to_structure(fetch: &Fetch) -> MessageStructure {
+    let structure = match fetch.bodystructure() {
+        Some(structure) => structure,
+        None => return MessageStructure::default(),
+    };
+
+    let text = find_text_part(structure, &[], "plain");
+    let html = find_text_part(structure, &[], "html");
+
+    let mut attachments = Vec::new();
+
+    let content: Vec<&TextPart> = text.iter().chain(html.iter()).collect();
+
+    find_attachments(structure, &[], &content, &mut attachments);
+
+    MessageStructure {
+        text,
+        html,
+        attachments,
+    }
+}
+
+/// Finds an attachment by its id in the BODYSTRUCTURE of a message, along with the transfer encoding of its data.
+pub fn fetch_to_attachment(fetch: &Fetch, id: &str) -> Option<(Attachment, String)> {
+    let path = section_to_path(id)?;
+
+    let (common, other) = single_part(find_part(fetch.bodystructure()?, &path)?)?;
+
+    Some((
+        create_attachment(common, other, &path),
+        encoding_to_string(&other.transfer_encoding),
+    ))
+}
+
+/// Decodes the data of an attachment that was fetched with its section.
+pub fn fetch_to_attachment_data(
+    fetch: &Fetch,
+    attachment: &Attachment,
+    encoding: &str,
+) -> Result<Vec<u8>> {
+    let path = section_to_path(attachment.id()).unwrap_or_default();
+
+    match fetch.section(&SectionPath::Part(path, None)) {
+        Some(body) => decode_part(body, encoding),
+        None => Err(Error::new(
+            ErrorKind::UnexpectedBehavior,
+            format!(
+                "The server did not return the data of attachment '{}'",
+                attachment.id()
+            ),
+        )),
+    }
+}
+
+/// Finds the part of a message that a snippet can be created from, preferring plain text over html.
+pub fn fetch_to_text_part(fetch: &Fetch) -> Option<TextPart> {
+    let structure = fetch.bodystructure()?;
+
+    find_text_part(structure, &[], "plain").or_else(|| find_text_part(structure, &[], "html"))
+}
+
+/// Creates a snippet from a partial fetch of a message's text part.
+pub fn fetch_to_snippet(fetch: &Fetch, part: &TextPart) -> Option<String> {
+    let body = fetch.section(&SectionPath::Part(part.path.clone(), None))?;
+
+    parse_snippet(body, &part.content_type, &part.encoding)
+}
+
 pub fn fetch_to_preview(fetch: &Fetch) -> Result<Preview> {
     let id = match parse_uid(fetch.uid) {
         Ok(uid) => uid,
@@ -79,109 +431,118 @@ pub fn fetch_to_preview(fetch: &Fetch) -> Result<Preview> {
         None => None,
     };
 
-    let from = match envelope.from.as_ref() {
-        Some(from) => from
-            .iter()
-            .map(|address| {
-                let name = bytes_to_string(&address.name);
-                let address = address_to_string(&address.mailbox, &address.host);
-
-                Address::new(name, address)
-            })
-            .collect(),
-        None => Vec::new(),
-    };
+    let addresses = envelope_addresses(envelope);
 
     let subject = bytes_to_string(&envelope.subject);
 
-    let preview = Preview::new(from, flags, id, sent, subject);
-
-    Ok(preview)
-}
+    let mut preview = Preview::new(addresses, flags, id, sent, subject);
 
-pub async fn fetch_to_message(fetch: &Fetch) -> Result<Message> {
-    let envelope = fetch.envelope().unwrap();
+    if let Some(size) = fetch.size {
+        preview.set_size(size.into());
+    }
 
-    let flags = imap_flags_to_flags(fetch.flags());
+    if let Some(structure) = fetch.bodystructure() {
+        preview.set_has_attachments(has_attachments(structure));
+    }
 
-    let sent = match fetch.internal_date() {
-        Some(date) => Some(date.timestamp()),
-        None => None,
-    };
+    if let Some(message_id) =
+        bytes_to_string(&envelope.message_id).and_then(|id| parse_message_id(&id))
+    {
+        preview.set_message_id(message_id);
+    }
 
-    let from = match envelope.from.as_ref() {
-        Some(from) => from
-            .iter()
-            .map(|address| {
-                let name = bytes_to_string(&address.name);
-                let address = address_to_string(&address.mailbox, &address.host);
+    if let Some(in_reply_to) =
+        bytes_to_string(&envelope.in_reply_to).and_then(|id| parse_message_id(&id))
+    {
+        preview.set_in_reply_to(in_reply_to);
+    }
 
-                Address::new(name, address)
-            })
-            .collect(),
-        None => Vec::new(),
-    };
+    // Only the header fields that hold the importance of the message are fetched.
+    if let Some(header) = fetch.header() {
+        preview.set_importance(parse_importance(&parse_headers(header)?));
+    }
 
-    let to = match envelope.to.as_ref() {
-        Some(to) => to
-            .iter()
-            .map(|address| {
-                let name = bytes_to_string(&address.name);
-                let address = address_to_string(&address.mailbox, &address.host);
+    Ok(preview)
+}
 
-                Address::new(name, address)
-            })
-            .collect(),
-        None => Vec::new(),
+/// The text of a content part, of which only the start was fetched if it is larger than the maximum size.
+fn fetch_to_text(
+    fetch: Option<&Fetch>,
+    part: Option<&TextPart>,
+    max_size: u64,
+) -> Result<Option<String>> {
+    let (fetch, part) = match (fetch, part) {
+        (Some(fetch), Some(part)) => (fetch, part),
+        _ => return Ok(None),
     };
 
-    let cc = match envelope.cc.as_ref() {
-        Some(cc) => cc
-            .iter()
-            .map(|address| {
-                let name = bytes_to_string(&address.name);
-                let address = address_to_string(&address.mailbox, &address.host);
+    match fetch.section(&SectionPath::Part(part.path.clone(), None)) {
+        Some(body) if part.is_larger_than(max_size) => Ok(Some(parse_partial_text_part(
+            body,
+            &part.content_type,
+            &part.encoding,
+        )?)),
+        Some(body) => Ok(Some(parse_text_part(
+            body,
+            &part.content_type,
+            &part.encoding,
+        )?)),
+        None => Ok(None),
+    }
+}
 
-                Address::new(name, address)
-            })
-            .collect(),
-        None => Vec::new(),
+/// Creates a message from a fetch of its envelope, headers and structure and a fetch of the parts that make up its content.
+///
+/// Content parts that are larger than the maximum content size are expected to be fetched up to that size, the content is then marked as truncated.
+pub fn fetch_to_message(
+    fetch: &Fetch,
+    content_fetch: Option<&Fetch>,
+    structure: MessageStructure,
+    max_content_size: u64,
+) -> Result<Message> {
+    let id = parse_uid(fetch.uid)?;
+
+    let envelope = match fetch.envelope() {
+        Some(envelope) => envelope,
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidMessage,
+                format!("Message with id '{}' does not contain an envelope", id),
+            ))
+        }
     };
 
-    let bcc = match envelope.bcc.as_ref() {
-        Some(bcc) => bcc
-            .iter()
-            .map(|address| {
-                let name = bytes_to_string(&address.name);
-                let address = address_to_string(&address.mailbox, &address.host);
+    let flags = imap_flags_to_flags(fetch.flags());
 
-                Address::new(name, address)
-            })
-            .collect(),
-        None => Vec::new(),
+    let sent = match fetch.internal_date() {
+        Some(date) => Some(date.timestamp()),
+        None => None,
     };
 
+    let addresses = envelope_addresses(envelope);
+
     let subject = bytes_to_string(&envelope.subject);
 
-    let id = match parse_uid(fetch.uid) {
-        Ok(uid) => uid,
-        Err(err) => return Err(err),
+    let headers: Headers = match fetch.header() {
+        Some(header) => parse_headers(header)?,
+        None => HashMap::new(),
     };
 
-    let (content, headers): (Content, Headers) = match fetch.body() {
-        Some(body) => {
-            let content = parse_rfc822(body).await?;
-            let headers = parse_headers(body)?;
+    let text = fetch_to_text(content_fetch, structure.text.as_ref(), max_content_size)?;
+    let html = fetch_to_text(content_fetch, structure.html.as_ref(), max_content_size)?;
 
-            (content, headers)
-        }
-        None => (Content::new(None, None), HashMap::new()),
-    };
+    let mut content = create_content(text.as_deref(), html.as_deref());
 
-    let message = Message::new(
-        from, to, cc, bcc, headers, flags, id, sent, subject, content,
+    content.set_truncated(
+        structure
+            .content_parts()
+            .any(|part| part.is_larger_than(max_content_size)),
     );
 
+    let mut message = Message::new(addresses, headers, flags, id, sent, subject, content);
+
+    message.set_attachments(structure.attachments);
+
     Ok(message)
 }
 
@@ -1,5 +1,6 @@
 mod cache;
 mod client;
+mod decode;
 mod parse;
 
 #[cfg(feature = "detect")]
@@ -9,7 +10,7 @@ pub mod detect;
 mod imap;
 
 #[cfg(feature = "pop")]
-mod pop;
+pub mod pop;
 
 pub mod types;
 
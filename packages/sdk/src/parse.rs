@@ -1,9 +1,12 @@
 use std::collections::HashMap;
 
-use mailparse::parse_mail;
+use mailparse::{parse_mail, DispositionType, MailHeaderMap, ParsedMail};
 use serde::Serialize;
 
-use crate::types::{self, Content, Headers};
+use crate::decode;
+use crate::types::{
+    self, Address, AddressListEntry, Addresses, Attachment, Content, Headers, Importance,
+};
 
 const ALLOWED_HTML_TAGS: [&str; 71] = [
     "address",
@@ -79,6 +82,12 @@ const ALLOWED_HTML_TAGS: [&str; 71] = [
     "center",
 ];
 
+/// The elements whose content is left out when html is turned into a snippet, quoted replies are left out like they are in plain text.
+const HIDDEN_HTML_ELEMENTS: [&str; 5] = ["head", "title", "style", "script", "blockquote"];
+
+/// The maximum amount of characters in a snippet.
+const SNIPPET_LENGTH: usize = 200;
+
 const GENERIC_HTML_ATTRIBUTES: [&str; 12] = [
     "style",
     "width",
@@ -104,44 +113,66 @@ fn sanitize_html(dirty: &str) -> String {
     clean
 }
 
-fn sanitize_text(dirty: &str) -> String {
-    ammonia::clean_text(dirty)
+/// Creates the content of a message from its text and html, sanitizing the html so it is safe to render.
+///
+/// The text is left as is, it is meant to be shown as plain text and escaping it would show the escaped characters.
+pub fn create_content(text: Option<&str>, html: Option<&str>) -> Content {
+    Content::new(text.map(String::from), html.map(sanitize_html))
+}
+
+/// Finds a parameter of the content type of a part, like its charset, regardless of the case of its name.
+fn content_type_param<'a>(part: &'a ParsedMail, name: &str) -> Option<&'a str> {
+    part.ctype
+        .params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim_matches('"'))
+}
+
+/// Decodes the text of a part, in its charset or the one it looks like when that is missing or wrong, and joins the lines of `format=flowed` text.
+fn decode_text_part(part: &ParsedMail) -> types::Result<String> {
+    let body = part.get_body_raw()?;
+
+    let text = decode::decode_charset(&body, content_type_param(part, "charset"));
+
+    let is_flowed = part.ctype.mimetype.eq_ignore_ascii_case("text/plain")
+        && content_type_param(part, "format")
+            .map_or(false, |format| format.eq_ignore_ascii_case("flowed"));
+
+    if is_flowed {
+        let delete_space = content_type_param(part, "delsp").map_or(false, |delete_space| {
+            delete_space.eq_ignore_ascii_case("yes")
+        });
+
+        Ok(decode::decode_flowed(&text, delete_space))
+    } else {
+        Ok(text)
+    }
 }
 
 /// Parse an RFC 822 body to an appropriate and useful struct.
+///
+/// The first text and html parts that are not attachments are used, like in [`parse_attachments`].
 pub async fn parse_rfc822(body: &[u8]) -> types::Result<Content> {
     let parsed = parse_mail(body)?;
 
     let mut text: Option<String> = None;
     let mut html: Option<String> = None;
 
-    for part in parsed.parts() {
-        let headers = part.get_headers();
-
-        for header in headers {
-            let key = header.get_key_ref().trim().to_ascii_lowercase();
-
-            if key == "content-type" {
-                let value = header.get_value().trim().to_ascii_lowercase();
-
-                let body = Some(part.get_body()?);
+    for part in parsed
+        .parts()
+        .filter(|part| part.subparts.is_empty() && !is_attachment(part))
+    {
+        let mimetype = part.ctype.mimetype.to_ascii_lowercase();
 
-                if value.starts_with("text/plain") {
-                    text = match body {
-                        Some(body_data) => Some(sanitize_text(&body_data)),
-                        None => None,
-                    }
-                } else if value.starts_with("text/html") {
-                    html = match body {
-                        Some(body_data) => Some(sanitize_html(&body_data)),
-                        None => None,
-                    }
-                }
-            }
+        if mimetype == "text/plain" && text.is_none() {
+            text = Some(decode_text_part(part)?);
+        } else if mimetype == "text/html" && html.is_none() {
+            html = Some(decode_text_part(part)?);
         }
     }
 
-    Ok(Content::new(text, html))
+    Ok(create_content(text.as_deref(), html.as_deref()))
 }
 
 pub fn parse_headers(response: &[u8]) -> types::Result<Headers> {
@@ -158,6 +189,493 @@ pub fn parse_headers(response: &[u8]) -> types::Result<Headers> {
     Ok(headers)
 }
 
+/// Parses an address list header like `To`, an entry that can not be parsed is skipped instead of failing the whole list.
+pub fn parse_address_list(value: &str) -> Vec<AddressListEntry> {
+    match dust_mail_utils::parse_address_list(value) {
+        Ok(list) => list.iter().map(AddressListEntry::from).collect(),
+        Err(_) => value
+            .split(',')
+            .filter_map(|entry| dust_mail_utils::parse_address_list(entry).ok())
+            .flatten()
+            .map(|address| AddressListEntry::from(&address))
+            .collect(),
+    }
+}
+
+/// Parses a mailbox list header like `From`, the members of a group are included as separate addresses.
+pub fn parse_mailbox_list(value: &str) -> Vec<Address> {
+    parse_address_list(value)
+        .iter()
+        .flat_map(AddressListEntry::addresses)
+        .cloned()
+        .collect()
+}
+
+/// Parses the address headers of an RFC 822 message.
+///
+/// The raw header values are used, because a decoded display name can contain commas and other characters that have a meaning in an address list.
+pub fn parse_addresses(response: &[u8]) -> types::Result<Addresses> {
+    let (parsed, _) = mailparse::parse_headers(response)?;
+
+    let mut from = Vec::new();
+    let mut sender = None;
+    let mut reply_to = Vec::new();
+    let mut to = Vec::new();
+    let mut cc = Vec::new();
+    let mut bcc = Vec::new();
+
+    for header in parsed.iter() {
+        let value = String::from_utf8_lossy(header.get_value_raw());
+
+        match header.get_key_ref().trim().to_ascii_lowercase().as_str() {
+            "from" => from = parse_mailbox_list(&value),
+            "sender" => sender = parse_mailbox_list(&value).into_iter().next(),
+            "reply-to" => reply_to = parse_address_list(&value),
+            "to" => to = parse_address_list(&value),
+            "cc" => cc = parse_address_list(&value),
+            "bcc" => bcc = parse_address_list(&value),
+            _ => {}
+        }
+    }
+
+    Ok(Addresses::new(from, sender, reply_to, to, cc, bcc))
+}
+
+/// Finds a header value without regard to the case of its key, because not every client uses the same spelling, like `Message-ID` and `Message-Id`.
+pub fn header_value<'a>(headers: &'a Headers, key: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parses the first message id in a header like `Message-ID` or `In-Reply-To`, without its angle brackets.
+pub fn parse_message_id(value: &str) -> Option<String> {
+    let id = match value.find('<') {
+        Some(start) => {
+            let id = &value[start + 1..];
+
+            &id[..id.find('>')?]
+        }
+        // Some clients leave out the angle brackets.
+        None => value.split_whitespace().next()?,
+    };
+
+    let id = id.trim();
+
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.to_string())
+    }
+}
+
+/// Parses the importance of a message from the different headers that clients use to mark it.
+pub fn parse_importance(headers: &Headers) -> Importance {
+    if let Some(importance) = header_value(headers, "Importance") {
+        match importance.trim().to_ascii_lowercase().as_str() {
+            "high" => return Importance::High,
+            "low" => return Importance::Low,
+            _ => {}
+        }
+    }
+
+    // The value is a number from 1 (highest) to 5 (lowest), often followed by a description like `1 (Highest)`.
+    if let Some(priority) = header_value(headers, "X-Priority") {
+        match priority.trim().chars().next() {
+            Some('1') | Some('2') => return Importance::High,
+            Some('4') | Some('5') => return Importance::Low,
+            _ => {}
+        }
+    }
+
+    if let Some(priority) = header_value(headers, "Priority") {
+        match priority.trim().to_ascii_lowercase().as_str() {
+            "urgent" => return Importance::High,
+            "non-urgent" => return Importance::Low,
+            _ => {}
+        }
+    }
+
+    Importance::Normal
+}
+
+fn decode_html_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        entity => {
+            let number = entity.strip_prefix('#')?;
+
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+
+            char::from_u32(code)
+        }
+    }
+}
+
+fn push_decoded_html_entities(output: &mut String, text: &str) {
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+
+        rest = &rest[start..];
+
+        let decoded = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((end, decode_html_entity(&rest[1..end])?)));
+
+        match decoded {
+            Some((end, character)) => {
+                output.push(character);
+
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+}
+
+/// Turns html into text for a snippet, by removing the tags and the content of the elements that are not shown and decoding the entities.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_decoded_html_entities(&mut text, &rest[..start]);
+
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            // The html was cut off in the middle of a tag.
+            None => return text,
+        };
+
+        let tag = &rest[start + 1..end];
+
+        rest = &rest[end + 1..];
+
+        // Tags separate words, like `<td>` or `<br>`.
+        text.push(' ');
+
+        if tag.starts_with('/') {
+            continue;
+        }
+
+        let name = tag
+            .split(|character: char| character.is_whitespace() || character == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if HIDDEN_HTML_ELEMENTS.contains(&name.as_str()) {
+            let closing_tag = format!("</{}", name);
+
+            rest = match rest.to_ascii_lowercase().find(&closing_tag) {
+                Some(index) => &rest[index..],
+                None => "",
+            };
+        }
+    }
+
+    push_decoded_html_entities(&mut text, rest);
+
+    text
+}
+
+/// Creates a short snippet from the text of a message, for use in a preview.
+///
+/// Quoted replies and the signature are left out and the white space is collapsed.
+pub fn create_snippet(text: &str) -> Option<String> {
+    let mut snippet = String::new();
+
+    for line in text.lines() {
+        // The signature separator is a line with two dashes and a space.
+        if line == "-- " {
+            break;
+        }
+
+        if line.trim_start().starts_with('>') {
+            continue;
+        }
+
+        for word in line.split_whitespace() {
+            if !snippet.is_empty() {
+                snippet.push(' ');
+            }
+
+            snippet.push_str(word);
+        }
+
+        if snippet.chars().count() >= SNIPPET_LENGTH {
+            break;
+        }
+    }
+
+    let snippet: String = snippet.chars().take(SNIPPET_LENGTH).collect();
+
+    // The text can end with a broken character if it was cut off in the middle of one.
+    let snippet = snippet
+        .trim_end_matches(char::REPLACEMENT_CHARACTER)
+        .trim_end();
+
+    if snippet.is_empty() {
+        None
+    } else {
+        Some(snippet.to_string())
+    }
+}
+
+/// Wraps the body of a single part of a message, like one that was fetched separately, in its headers so it can be decoded like a complete part.
+fn create_mime_part(body: &[u8], content_type: &str, encoding: &str) -> Vec<u8> {
+    let mut part = format!(
+        "Content-Type: {}\r\nContent-Transfer-Encoding: {}\r\n\r\n",
+        content_type, encoding
+    )
+    .into_bytes();
+
+    part.extend_from_slice(body);
+
+    part
+}
+
+/// Decodes the text of a single body part given its content type and transfer encoding.
+pub fn parse_text_part(body: &[u8], content_type: &str, encoding: &str) -> types::Result<String> {
+    let part = create_mime_part(body, content_type, encoding);
+
+    let parsed = parse_mail(&part)?;
+
+    decode_text_part(&parsed)
+}
+
+/// Decodes the text of a body part like `parse_text_part`, for a part that was cut off at any byte, like the ones that are fetched with a partial IMAP fetch.
+pub fn parse_partial_text_part(
+    body: &[u8],
+    content_type: &str,
+    encoding: &str,
+) -> types::Result<String> {
+    parse_text_part(&trim_partial_body(body, encoding), content_type, encoding)
+}
+
+/// Leaves out the incomplete base64 group at the end of a body part that was cut off, so the rest of it can still be decoded.
+fn trim_partial_body(body: &[u8], encoding: &str) -> Vec<u8> {
+    if encoding.eq_ignore_ascii_case("base64") {
+        let mut body: Vec<u8> = body
+            .iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .copied()
+            .collect();
+
+        // Base64 is decoded in groups of four characters, so an incomplete group at the end is left out.
+        body.truncate(body.len() - body.len() % 4);
+
+        body
+    } else {
+        body.to_vec()
+    }
+}
+
+/// Removes the transfer encoding, like base64, from the data of a single body part.
+pub fn decode_part(body: &[u8], encoding: &str) -> types::Result<Vec<u8>> {
+    let part = create_mime_part(body, "application/octet-stream", encoding);
+
+    let parsed = parse_mail(&part)?;
+
+    Ok(parsed.get_body_raw()?)
+}
+
+/// Creates a snippet from the start of a body part, which can be cut off at any byte, like the ones that are fetched with a partial IMAP fetch.
+pub fn parse_snippet(body: &[u8], content_type: &str, encoding: &str) -> Option<String> {
+    let body = trim_partial_body(body, encoding);
+
+    let part = create_mime_part(&body, content_type, encoding);
+
+    let parsed = parse_mail(&part).ok()?;
+
+    let text = decode_text_part(&parsed).ok()?;
+
+    if parsed.ctype.mimetype.eq_ignore_ascii_case("text/html") {
+        create_snippet(&html_to_text(&text))
+    } else {
+        create_snippet(&text)
+    }
+}
+
+fn is_attachment(part: &ParsedMail) -> bool {
+    match part.get_content_disposition().disposition {
+        DispositionType::Attachment => true,
+        DispositionType::Inline => false,
+        // Without a disposition, parts that are not text are attachments, unless the html refers to them by their content id.
+        _ => {
+            !part
+                .ctype
+                .mimetype
+                .to_ascii_lowercase()
+                .starts_with("text/")
+                && part.headers.get_first_value("Content-ID").is_none()
+        }
+    }
+}
+
+/// Parses the snippet and whether there are attachments from the start of an RFC 822 message, for use in a preview.
+///
+/// A multipart/mixed message is assumed to have attachments, because the attachments themselves can be past the end of the part that was retrieved.
+pub fn parse_preview_content(message: &[u8]) -> types::Result<(Option<String>, bool)> {
+    let parsed = parse_mail(message)?;
+
+    let mut has_attachments = parsed
+        .ctype
+        .mimetype
+        .eq_ignore_ascii_case("multipart/mixed");
+
+    let mut text: Option<String> = None;
+    let mut html: Option<String> = None;
+
+    for part in parsed.parts().filter(|part| part.subparts.is_empty()) {
+        if is_attachment(part) {
+            has_attachments = true;
+
+            continue;
+        }
+
+        let mimetype = part.ctype.mimetype.to_ascii_lowercase();
+
+        if mimetype == "text/plain" && text.is_none() {
+            text = decode_text_part(part).ok();
+        } else if mimetype == "text/html" && html.is_none() {
+            html = decode_text_part(part).ok();
+        }
+    }
+
+    let snippet = match (text, html) {
+        (Some(text), _) => create_snippet(&text),
+        (None, Some(html)) => create_snippet(&html_to_text(&html)),
+        (None, None) => None,
+    };
+
+    Ok((snippet, has_attachments))
+}
+
+/// Collects the parts of a message that have no parts of their own, along with their part numbers.
+///
+/// The parts are numbered like the sections of an IMAP fetch, so a message that is not a multipart only has a part 1.
+fn leaf_parts<'a>(
+    part: &'a ParsedMail<'a>,
+    path: Vec<u32>,
+    parts: &mut Vec<(String, &'a ParsedMail<'a>)>,
+) {
+    if part.subparts.is_empty() {
+        let path = if path.is_empty() { vec![1] } else { path };
+
+        let numbers: Vec<String> = path.iter().map(|number| number.to_string()).collect();
+
+        parts.push((numbers.join("."), part));
+
+        return;
+    }
+
+    for (subpart, number) in part.subparts.iter().zip(1..) {
+        let mut path = path.clone();
+
+        path.push(number);
+
+        leaf_parts(subpart, path, parts);
+    }
+}
+
+fn part_to_attachment(id: String, part: &ParsedMail) -> types::Result<Attachment> {
+    let mut attachment = Attachment::new(id, part.ctype.mimetype.to_ascii_lowercase());
+
+    let (_, body_start) = mailparse::parse_headers(part.raw_bytes)?;
+
+    attachment.set_size((part.raw_bytes.len() - body_start) as u64);
+
+    let disposition = part.get_content_disposition();
+
+    if let Some(filename) = disposition
+        .params
+        .get("filename")
+        .or_else(|| part.ctype.params.get("name"))
+    {
+        attachment.set_filename(filename.as_str());
+    }
+
+    if let Some(content_id) = part
+        .headers
+        .get_first_value("Content-ID")
+        .and_then(|id| parse_message_id(&id))
+    {
+        attachment.set_content_id(content_id);
+    }
+
+    Ok(attachment)
+}
+
+/// Describes the parts of a full RFC 822 message that are not shown as its content.
+///
+/// The first plain text and html parts that are not attachments make up the content, every other part is an attachment.
+pub fn parse_attachments(message: &[u8]) -> types::Result<Vec<Attachment>> {
+    let parsed = parse_mail(message)?;
+
+    let mut parts = Vec::new();
+
+    leaf_parts(&parsed, Vec::new(), &mut parts);
+
+    let mut has_text = false;
+    let mut has_html = false;
+
+    let mut attachments = Vec::new();
+
+    for (id, part) in parts {
+        if !is_attachment(part) {
+            let mimetype = part.ctype.mimetype.to_ascii_lowercase();
+
+            if mimetype == "text/plain" && !has_text {
+                has_text = true;
+
+                continue;
+            } else if mimetype == "text/html" && !has_html {
+                has_html = true;
+
+                continue;
+            }
+        }
+
+        attachments.push(part_to_attachment(id, part)?);
+    }
+
+    Ok(attachments)
+}
+
+/// Returns the decoded data of the part with the given id in a full RFC 822 message.
+pub fn parse_attachment_data(message: &[u8], id: &str) -> types::Result<Option<Vec<u8>>> {
+    let parsed = parse_mail(message)?;
+
+    let mut parts = Vec::new();
+
+    leaf_parts(&parsed, Vec::new(), &mut parts);
+
+    match parts.into_iter().find(|(part_id, _)| part_id == id) {
+        Some((_, part)) => Ok(Some(part.get_body_raw()?)),
+        None => Ok(None),
+    }
+}
+
 pub fn to_json<T: ?Sized + Serialize>(value: &T) -> types::Result<String> {
     serde_json::to_string(value).map_err(|e| {
         types::Error::new(
@@ -166,3 +684,153 @@ pub fn to_json<T: ?Sized + Serialize>(value: &T) -> types::Result<String> {
         )
     })
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::types::Importance;
+
+    use super::{
+        create_snippet, decode_part, parse_address_list, parse_attachment_data, parse_attachments,
+        parse_importance, parse_mailbox_list, parse_message_id, parse_snippet, parse_text_part,
+    };
+
+    #[test]
+    fn address_list() {
+        let list = parse_address_list("john@example.com, not an address, Jane <jane@example.com>");
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].addresses()[0].name(), Some("Jane"));
+
+        let from = parse_mailbox_list("Friends: john@example.com, jane@example.com;");
+
+        assert_eq!(from.len(), 2);
+        assert_eq!(from[1].address(), "jane@example.com");
+    }
+
+    #[test]
+    fn snippet() {
+        assert_eq!(
+            create_snippet(
+                "Hi Jane,\r\n\r\nSee you  tomorrow!\r\n\r\n> Are you coming?\r\n-- \r\nJohn"
+            ),
+            Some(String::from("Hi Jane, See you tomorrow!"))
+        );
+        assert_eq!(create_snippet("> Only a quote\r\n"), None);
+
+        // A partial fetch can cut off base64 in the middle of a group and text in the middle of a character.
+        assert_eq!(
+            parse_snippet(b"SGVsbG8gd29ybGQh\r\nIEE", "text/plain", "base64"),
+            Some(String::from("Hello world!"))
+        );
+        assert_eq!(
+            parse_snippet(
+                b"J=C3=B6rg =C3",
+                "text/plain; charset=utf-8",
+                "quoted-printable"
+            ),
+            Some(String::from("J\u{f6}rg"))
+        );
+        assert_eq!(
+            parse_snippet(
+                b"<html><head><style>p { color: red; }</style></head><body><p>Hello&nbsp;&amp; welcome</p><blockquote>Quoted</blockquote><p>Bye",
+                "text/html",
+                "7bit"
+            ),
+            Some(String::from("Hello & welcome Bye"))
+        );
+    }
+
+    #[test]
+    fn thread_headers() {
+        assert_eq!(
+            parse_message_id(" <1234@example.com>"),
+            Some(String::from("1234@example.com"))
+        );
+        assert_eq!(
+            parse_message_id("1234@example.com"),
+            Some(String::from("1234@example.com"))
+        );
+        assert_eq!(parse_message_id("<>"), None);
+
+        let mut headers = HashMap::new();
+
+        assert_eq!(parse_importance(&headers), Importance::Normal);
+
+        headers.insert(String::from("X-Priority"), String::from("1 (Highest)"));
+
+        assert_eq!(parse_importance(&headers), Importance::High);
+
+        headers.insert(String::from("importance"), String::from("Low"));
+
+        assert_eq!(parse_importance(&headers), Importance::Low);
+    }
+
+    #[test]
+    fn attachments() {
+        let message = b"Content-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n--outer\r\nContent-Type: multipart/related; boundary=\"inner\"\r\n\r\n--inner\r\nContent-Type: text/html\r\n\r\n<img src=\"cid:logo@example.com\">\r\n--inner\r\nContent-Type: image/png\r\nContent-ID: <logo@example.com>\r\nContent-Transfer-Encoding: base64\r\n\r\niVBORw0=\r\n--inner--\r\n--outer\r\nContent-Type: application/pdf; name=\"report.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--outer--\r\n";
+
+        let attachments = parse_attachments(message).unwrap();
+
+        assert_eq!(attachments.len(), 2);
+
+        assert_eq!(attachments[0].id(), "1.2");
+        assert_eq!(attachments[0].content_type(), "image/png");
+        assert_eq!(attachments[0].content_id(), Some("logo@example.com"));
+
+        assert_eq!(attachments[1].id(), "2");
+        assert_eq!(attachments[1].filename(), Some("report.pdf"));
+
+        assert_eq!(
+            parse_attachment_data(message, "2").unwrap(),
+            Some(b"%PDF-".to_vec())
+        );
+        assert_eq!(parse_attachment_data(message, "3").unwrap(), None);
+
+        // A single part that was fetched on its own.
+        assert_eq!(decode_part(b"JVBE\r\nRi0=", "base64").unwrap(), b"%PDF-");
+        assert_eq!(
+            parse_text_part(
+                b"J=C3=B6rg",
+                "text/plain; charset=utf-8",
+                "quoted-printable"
+            )
+            .unwrap(),
+            "J\u{f6}rg"
+        );
+    }
+
+    #[test]
+    fn text_parts() {
+        // Plain text is not escaped, it is never rendered as html.
+        assert_eq!(
+            parse_text_part(b"if a < b && c > d", "text/plain", "7bit").unwrap(),
+            "if a < b && c > d"
+        );
+
+        assert_eq!(
+            parse_text_part(
+                b"A paragraph that \r\nwas wrapped.\r\n> Quoted \r\n> text\r\n",
+                "text/plain; charset=\"utf-8\"; format=\"flowed\"",
+                "7bit"
+            )
+            .unwrap(),
+            "A paragraph that was wrapped.\r\n> Quoted text\r\n"
+        );
+
+        assert_eq!(
+            parse_text_part(
+                b"=93Quoted=94",
+                "text/plain; charset=utf-8",
+                "quoted-printable"
+            )
+            .unwrap(),
+            "\u{201c}Quoted\u{201d}"
+        );
+        assert_eq!(
+            parse_text_part(b"\x1b$B$3$s$K$A$O\x1b(B", "text/plain", "7bit").unwrap(),
+            "\u{3053}\u{3093}\u{306b}\u{3061}\u{306f}"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use async_pop3::types::{Capability, UniqueID, UniqueIDResponse};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncWrite},
+};
+
+use crate::{
+    parse::to_json,
+    types::{DownloadOptions, Error, ErrorKind, Result},
+};
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// The persistent state of a Pop mailbox download, used to remember which messages have already been downloaded.
+pub struct UidlState {
+    /// A map from a message's unique id to the time (in seconds since epoch) it was first downloaded.
+    seen: HashMap<String, i64>,
+    /// The last time (in seconds since epoch) the server was checked for new messages.
+    last_check: Option<i64>,
+    /// The login delay in seconds that the server advertised the last time it was checked.
+    login_delay: Option<u64>,
+}
+
+impl UidlState {
+    /// Reads the state from a given file, returning an empty state if the file does not exist yet.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+
+        let state_json = fs::read_to_string(path).await?;
+
+        serde_json::from_str(&state_json).map_err(|e| {
+            Error::new(
+                ErrorKind::SerializeJSON,
+                format!("Failed to parse pop download state: {}", e),
+            )
+        })
+    }
+
+    /// Writes the state to a given file so it can be loaded later.
+    pub async fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let state_json = to_json(self)?;
+
+        fs::write(path, state_json).await?;
+
+        Ok(())
+    }
+
+    /// Whether the message with the given unique id has already been downloaded.
+    pub fn is_seen<S: AsRef<str>>(&self, unique_id: S) -> bool {
+        self.seen.contains_key(unique_id.as_ref())
+    }
+
+    /// The last time (in seconds since epoch) the server was checked for new messages.
+    pub fn last_check(&self) -> Option<&i64> {
+        self.last_check.as_ref()
+    }
+}
+
+/// A message that was downloaded from a Pop server.
+pub struct DownloadedMessage {
+    unique_id: String,
+    body: Vec<u8>,
+}
+
+impl DownloadedMessage {
+    /// The unique id the server assigned to this message.
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    /// The full RFC 822 message.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// What needs to happen on the server during a single download.
+struct DownloadPlan {
+    download: Vec<UniqueID>,
+    delete: Vec<u32>,
+}
+
+/// Downloads new messages from a Pop server, optionally leaving them on the server for a configured amount of time.
+///
+/// Pop has no way of marking messages as read, so the unique ids of the downloaded messages are kept in a `UidlState`,
+/// which should be persisted between sessions to avoid downloading the same messages twice.
+pub struct PopDownloader {
+    options: DownloadOptions,
+    state: UidlState,
+}
+
+impl PopDownloader {
+    pub fn new(options: DownloadOptions, state: UidlState) -> Self {
+        Self { options, state }
+    }
+
+    pub fn options(&self) -> &DownloadOptions {
+        &self.options
+    }
+
+    pub fn state(&self) -> &UidlState {
+        &self.state
+    }
+
+    pub fn into_state(self) -> UidlState {
+        self.state
+    }
+
+    /// The minimum amount of time between two checks, which is the largest of the configured check interval and the login delay requested by the server.
+    fn min_check_interval(&self) -> Duration {
+        let check_interval = self.options.check_interval().unwrap_or_default();
+
+        let login_delay = Duration::from_secs(self.state.login_delay.unwrap_or(0));
+
+        check_interval.max(login_delay)
+    }
+
+    /// How long we have to wait before the server may be checked for new messages again.
+    pub fn time_until_next_check(&self) -> Duration {
+        self.time_until_next_check_at(Utc::now().timestamp())
+    }
+
+    fn time_until_next_check_at(&self, now: i64) -> Duration {
+        let last_check = match self.state.last_check {
+            Some(last_check) => last_check,
+            None => return Duration::ZERO,
+        };
+
+        let elapsed = Duration::from_secs(now.saturating_sub(last_check).max(0) as u64);
+
+        self.min_check_interval().saturating_sub(elapsed)
+    }
+
+    /// Whether enough time has passed since the last check to check the server for new messages again.
+    pub fn is_due(&self) -> bool {
+        self.time_until_next_check().is_zero()
+    }
+
+    /// Decide which messages should be downloaded and which should be deleted, given the unique ids of the messages that are currently on the server.
+    fn plan(
+        &self,
+        unique_ids: &[UniqueID],
+        expire: Option<Option<Duration>>,
+        now: i64,
+    ) -> DownloadPlan {
+        let download: Vec<UniqueID> = unique_ids
+            .iter()
+            .filter(|(_, unique_id)| !self.state.is_seen(unique_id))
+            .cloned()
+            .collect();
+
+        // If the server expires messages as soon as they are retrieved, it will delete them for us.
+        let server_deletes_on_retrieval = matches!(expire, Some(Some(expire)) if expire.is_zero());
+
+        if server_deletes_on_retrieval {
+            return DownloadPlan {
+                download,
+                delete: Vec::new(),
+            };
+        }
+
+        let delete = unique_ids
+            .iter()
+            .filter(|(_, unique_id)| {
+                if !self.options.leave_messages_on_server() {
+                    return true;
+                }
+
+                match self.options.time_to_leave_messages_on_server() {
+                    Some(retention) => {
+                        // Messages that are downloaded during this check are first seen now.
+                        let first_seen = self.state.seen.get(unique_id).cloned().unwrap_or(now);
+
+                        let age = now.saturating_sub(first_seen).max(0) as u64;
+
+                        Duration::from_secs(age) >= retention
+                    }
+                    None => false,
+                }
+            })
+            .map(|(msg_number, _)| *msg_number)
+            .collect();
+
+        DownloadPlan { download, delete }
+    }
+
+    /// Download all of the messages that have not been downloaded before and delete the messages
+    /// that should no longer be left on the server.
+    ///
+    /// Pop only removes the messages that were deleted once the session is ended, so this always ends the session with `QUIT`
+    /// and only updates the state after the server has accepted it.
+    ///
+    /// Returns no messages if the server was checked too recently, see `time_until_next_check`.
+    pub async fn download<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        client: &mut async_pop3::Client<S>,
+    ) -> Result<Vec<DownloadedMessage>> {
+        if !self.is_due() {
+            client.quit().await?;
+
+            return Ok(Vec::new());
+        }
+
+        if !client.has_capability(vec![Capability::Uidl]) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "The pop server does not support unique message ids, which are needed to keep track of downloaded messages",
+            ));
+        }
+
+        let unique_ids = match client.uidl(None).await? {
+            UniqueIDResponse::UniqueIDList(unique_ids) => unique_ids,
+            // We gave the function a 'None' so it should never return this
+            UniqueIDResponse::UniqueID(_) => unreachable!(),
+        };
+
+        let now = Utc::now().timestamp();
+
+        let plan = self.plan(&unique_ids, client.expire(), now);
+
+        let mut downloaded = Vec::with_capacity(plan.download.len());
+
+        for (msg_number, unique_id) in plan.download {
+            let body = client.retr(msg_number).await?;
+
+            downloaded.push(DownloadedMessage { unique_id, body });
+        }
+
+        for msg_number in plan.delete {
+            client.dele(msg_number).await?;
+        }
+
+        // The capabilities are forgotten when the session ends.
+        let login_delay = client.login_delay().map(|delay| delay.as_secs());
+
+        client.quit().await?;
+
+        for message in &downloaded {
+            self.state.seen.insert(message.unique_id.clone(), now);
+        }
+
+        // Forget about the messages that are no longer on the server, they can never be downloaded again.
+        self.state
+            .seen
+            .retain(|seen_id, _| unique_ids.iter().any(|(_, unique_id)| unique_id == seen_id));
+
+        self.state.last_check = Some(now);
+        self.state.login_delay = login_delay;
+
+        Ok(downloaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::types::DownloadOptions;
+
+    use super::{DownloadPlan, PopDownloader, UidlState};
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn unique_ids() -> Vec<(u32, String)> {
+        vec![
+            (1, String::from("old")),
+            (2, String::from("recent")),
+            (3, String::from("new")),
+        ]
+    }
+
+    fn state(now: i64) -> UidlState {
+        let mut state = UidlState::default();
+
+        state.seen.insert(String::from("old"), now - 10 * DAY);
+        state.seen.insert(String::from("recent"), now - DAY);
+
+        state
+    }
+
+    #[test]
+    fn plan_leave_on_server() {
+        let now = 1_000 * DAY;
+
+        let options = DownloadOptions::new(true, None, None);
+
+        let downloader = PopDownloader::new(options, state(now));
+
+        assert_eq!(
+            downloader.plan(&unique_ids(), None, now),
+            DownloadPlan {
+                download: vec![(3, String::from("new"))],
+                delete: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn plan_retention() {
+        let now = 1_000 * DAY;
+
+        let options = DownloadOptions::new(true, Some(Duration::from_secs(7 * DAY as u64)), None);
+
+        let downloader = PopDownloader::new(options, state(now));
+
+        assert_eq!(
+            downloader.plan(&unique_ids(), None, now),
+            DownloadPlan {
+                download: vec![(3, String::from("new"))],
+                delete: vec![1],
+            }
+        );
+    }
+
+    #[test]
+    fn plan_delete_after_download() {
+        let now = 1_000 * DAY;
+
+        let options = DownloadOptions::new(false, None, None);
+
+        let downloader = PopDownloader::new(options, state(now));
+
+        assert_eq!(
+            downloader.plan(&unique_ids(), None, now),
+            DownloadPlan {
+                download: vec![(3, String::from("new"))],
+                delete: vec![1, 2, 3],
+            }
+        );
+
+        // The server deletes the messages by itself.
+        assert_eq!(
+            downloader.plan(&unique_ids(), Some(Some(Duration::ZERO)), now),
+            DownloadPlan {
+                download: vec![(3, String::from("new"))],
+                delete: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn next_check() {
+        let now = 1_000 * DAY;
+
+        let options = DownloadOptions::new(true, None, Some(Duration::from_secs(15 * 60)));
+
+        let mut state = UidlState::default();
+
+        let downloader = PopDownloader::new(options.clone(), state.clone());
+
+        assert!(downloader.time_until_next_check_at(now).is_zero());
+
+        state.last_check = Some(now - 5 * 60);
+
+        let downloader = PopDownloader::new(options.clone(), state.clone());
+
+        assert_eq!(
+            downloader.time_until_next_check_at(now),
+            Duration::from_secs(10 * 60)
+        );
+
+        // The login delay requested by the server takes precedence if it is longer.
+        state.login_delay = Some(60 * 60);
+
+        let downloader = PopDownloader::new(options, state);
+
+        assert_eq!(
+            downloader.time_until_next_check_at(now),
+            Duration::from_secs(55 * 60)
+        );
+    }
+}
@@ -1,9 +1,11 @@
+mod download;
 mod parse;
 
 use std::collections::HashMap;
 
-use async_native_tls::{TlsConnector, TlsStream};
-use async_pop3::types::UniqueIDResponse;
+use async_native_tls::TlsStream;
+use async_pop3::types::{StatsResponse, UniqueIDResponse};
+use async_tcp::{Config as TcpConfig, TranscriptStream};
 use async_trait::async_trait;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
@@ -11,17 +13,22 @@ use tokio::{
 };
 
 use crate::{
-    client::incoming::IncomingSession,
-    parse::{parse_headers, parse_rfc822},
-    types::{Counts, Error, ErrorKind, Flag, MailBox, Message, Preview, Result},
+    client::incoming::{check_attachment_size, IncomingSession, DEFAULT_MAX_ATTACHMENT_SIZE},
+    parse::{
+        parse_addresses, parse_attachment_data, parse_attachments, parse_headers, parse_rfc822,
+    },
+    types::{Attachment, Counts, Error, ErrorKind, Flag, MailBox, Message, Preview, Result},
 };
 
-use parse::parse_address;
+use self::parse::{parse_preview, parse_preview_from_headers};
 
-use self::parse::parse_preview_from_headers;
+pub use download::{DownloadedMessage, PopDownloader, UidlState};
 
 const MAILBOX_DEFAULT_NAME: &str = "Inbox";
 
+/// The amount of lines of a message's body that are retrieved to create its preview.
+const PREVIEW_LINES: u32 = 50;
+
 pub struct PopClient<S: AsyncRead + AsyncWrite + Unpin> {
     session: async_pop3::Client<S>,
 }
@@ -30,16 +37,26 @@ pub struct PopSession<S: AsyncRead + AsyncWrite + Unpin> {
     session: async_pop3::Client<S>,
     current_mailbox: Vec<MailBox>,
     unique_id_map: HashMap<String, u32>,
+    max_attachment_size: u64,
+}
+
+/// Creates a new pop client from an existing stream.
+pub async fn new<S: AsyncRead + AsyncWrite + Unpin>(stream: S) -> Result<PopClient<S>> {
+    let session = async_pop3::new(stream, None).await?;
+
+    Ok(PopClient { session })
 }
 
 pub async fn connect<S: AsRef<str>, P: Into<u16>>(
     server: S,
     port: P,
-) -> Result<PopClient<TlsStream<TcpStream>>> {
-    let tls = TlsConnector::new();
+    config: &TcpConfig,
+) -> Result<PopClient<TranscriptStream<TlsStream<TcpStream>>>> {
+    let stream = async_tcp::dial_tls(server, port.into(), config).await?;
+
+    let stream = TranscriptStream::new(stream, config.transcript().cloned());
 
-    let session =
-        async_pop3::connect((server.as_ref(), port.into()), server.as_ref(), &tls, None).await?;
+    let session = async_pop3::new(stream, None).await?;
 
     Ok(PopClient { session })
 }
@@ -47,8 +64,13 @@ pub async fn connect<S: AsRef<str>, P: Into<u16>>(
 pub async fn connect_plain<S: AsRef<str>, P: Into<u16>>(
     server: S,
     port: P,
-) -> Result<PopClient<TcpStream>> {
-    let session = async_pop3::connect_plain((server.as_ref(), port.into()), None).await?;
+    config: &TcpConfig,
+) -> Result<PopClient<TranscriptStream<TcpStream>>> {
+    let stream = async_tcp::dial(server, port.into(), config).await?;
+
+    let stream = TranscriptStream::new(stream, config.transcript().cloned());
+
+    let session = async_pop3::new(stream, None).await?;
 
     Ok(PopClient { session })
 }
@@ -65,6 +87,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> PopClient<S> {
             session,
             current_mailbox: Vec::new(),
             unique_id_map: HashMap::new(),
+            max_attachment_size: DEFAULT_MAX_ATTACHMENT_SIZE,
         })
     }
 }
@@ -91,6 +114,25 @@ impl<S: AsyncRead + AsyncWrite + Unpin> PopSession<S> {
         Ok(mailbox)
     }
 
+    /// Download all new messages from the server using a given downloader, see `PopDownloader` for more info.
+    ///
+    /// This ends the session, because Pop only deletes messages once the client logs out.
+    pub async fn download(mut self, downloader: &mut PopDownloader) -> Result<Vec<Message>> {
+        let session = self.get_session_mut();
+
+        let downloaded = downloader.download(session).await?;
+
+        let mut messages = Vec::with_capacity(downloaded.len());
+
+        for message in downloaded {
+            let message = parse_message(message.unique_id(), message.body(), false).await?;
+
+            messages.push(message);
+        }
+
+        Ok(messages)
+    }
+
     async fn get_msg_number_from_msg_id(&mut self, msg_id: &str) -> Result<u32> {
         match self.unique_id_map.get(msg_id) {
             Some(msg_number) => return Ok(msg_number.clone()),
@@ -195,6 +237,11 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Send> IncomingSession for PopSession<S>
 
         let mut unique_id_map = HashMap::new();
 
+        let sizes: HashMap<u32, u64> = match session.list(None).await? {
+            StatsResponse::StatsList(stats) => stats.into_iter().collect(),
+            StatsResponse::Stats((msg_number, size)) => HashMap::from([(msg_number, size)]),
+        };
+
         for msg_number in sequence_start..sequence_end {
             let uidl_response = session.uidl(Some(msg_number)).await?;
 
@@ -209,19 +256,16 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Send> IncomingSession for PopSession<S>
             // just to get this message's msg_number.
             unique_id_map.insert(unique_id.clone(), msg_number);
 
-            let header_bytes = session.top(msg_number, 0).await?;
+            let message_bytes = session.top(msg_number, PREVIEW_LINES).await?;
 
-            let headers = parse_headers(&header_bytes)?;
+            let is_deleted = session.is_deleted(&msg_number);
 
-            let (from, mut flags, sent, subject) = parse_preview_from_headers(&headers)?;
+            let mut preview = parse_preview(unique_id, &message_bytes, is_deleted)?;
 
-            // If we have marked a message as deleted, we will add the corresponding flag
-            if session.is_deleted(&msg_number) {
-                flags.push(Flag::Deleted)
+            if let Some(size) = sizes.get(&msg_number) {
+                preview.set_size(*size);
             }
 
-            let preview = Preview::new(from, flags, unique_id, sent, subject);
-
             previews.push(preview)
         }
 
@@ -230,97 +274,293 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Send> IncomingSession for PopSession<S>
         Ok(previews)
     }
 
-    async fn get_message(&mut self, _: &str, msg_id: &str) -> Result<Message> {
+    // Pop does not keep track of which messages have been read, so there is nothing to mark.
+    async fn get_message(&mut self, _: &str, msg_id: &str, _: bool) -> Result<Message> {
         let msg_number = self.get_msg_number_from_msg_id(msg_id).await?;
 
         let session = self.get_session_mut();
 
         let message_bytes = session.retr(msg_number).await?;
 
-        let content = parse_rfc822(&message_bytes).await?;
+        let is_deleted = session.is_deleted(&msg_number);
 
-        let headers = parse_headers(&message_bytes)?;
+        parse_message(msg_id, &message_bytes, is_deleted).await
+    }
 
-        let (from, mut flags, sent, subject) = parse_preview_from_headers(&headers)?;
+    async fn get_attachment(
+        &mut self,
+        _: &str,
+        msg_id: &str,
+        attachment_id: &str,
+    ) -> Result<(Attachment, Vec<u8>)> {
+        let msg_number = self.get_msg_number_from_msg_id(msg_id).await?;
 
-        // If we have marked a message as deleted, we will add the corresponding flag
-        if session.is_deleted(&msg_number) {
-            flags.push(Flag::Deleted)
-        }
+        let session = self.get_session_mut();
 
-        let to = match headers.get("To") {
-            Some(to) => parse_address(to),
-            None => Vec::new(),
-        };
+        // Pop can only retrieve whole messages, so the attachment is taken from the full message.
+        let message_bytes = session.retr(msg_number).await?;
 
-        let cc = match headers.get("CC") {
-            Some(cc) => parse_address(cc),
-            None => Vec::new(),
+        let not_found = || {
+            Error::new(
+                ErrorKind::UnexpectedBehavior,
+                format!("Could not find an attachment with id '{}'", attachment_id),
+            )
         };
 
-        let bcc = match headers.get("BCC") {
-            Some(bcc) => parse_address(bcc),
-            None => Vec::new(),
-        };
+        let attachment = parse_attachments(&message_bytes)?
+            .into_iter()
+            .find(|attachment| attachment.id() == attachment_id)
+            .ok_or_else(not_found)?;
 
-        let message = Message::new(
-            from, to, cc, bcc, headers, flags, msg_id, sent, subject, content,
-        );
+        check_attachment_size(&attachment, self.max_attachment_size)?;
+
+        let data = parse_attachment_data(&message_bytes, attachment_id)?.ok_or_else(not_found)?;
+
+        Ok((attachment, data))
+    }
 
-        Ok(message)
+    fn set_max_attachment_size(&mut self, max_size: u64) {
+        self.max_attachment_size = max_size;
     }
 }
 
+/// Parse a full RFC 822 message that was retrieved from the server.
+async fn parse_message(msg_id: &str, message_bytes: &[u8], is_deleted: bool) -> Result<Message> {
+    let content = parse_rfc822(message_bytes).await?;
+
+    let headers = parse_headers(message_bytes)?;
+
+    let addresses = parse_addresses(message_bytes)?;
+
+    let (mut flags, sent, subject) = parse_preview_from_headers(&headers)?;
+
+    // If we have marked a message as deleted, we will add the corresponding flag
+    if is_deleted {
+        flags.push(Flag::Deleted)
+    }
+
+    let mut message = Message::new(addresses, headers, flags, msg_id, sent, subject, content);
+
+    message.set_attachments(parse_attachments(message_bytes)?);
+
+    Ok(message)
+}
+
 #[cfg(test)]
 mod test {
 
-    use super::PopSession;
+    use super::{PopDownloader, PopSession, UidlState};
+
+    use crate::{
+        client::incoming::IncomingSession,
+        types::{AddressListEntry, DownloadOptions, ErrorKind, Importance},
+    };
 
-    use crate::client::incoming::IncomingSession;
+    use async_pop3::test_support::MockServer;
+    use tokio::io::DuplexStream;
 
-    use async_native_tls::TlsStream;
-    use dotenv::dotenv;
-    use std::env;
-    use tokio::net::TcpStream;
+    const MESSAGE: &str =
+        "Subject: Hello\r\nDate: Sat, 11 Mar 2023 12:00:00 +0000\r\n\r\nHello world\r\n";
 
-    async fn create_test_session() -> PopSession<TlsStream<TcpStream>> {
-        dotenv().ok();
+    fn create_test_server() -> MockServer {
+        let mut server = MockServer::new();
 
-        let username = env::var("POP_USERNAME").unwrap();
-        let password = env::var("POP_PASSWORD").unwrap();
+        server
+            .add_message("17811", MESSAGE)
+            .add_message("17812", "Subject: Second\r\n\r\nSecond message\r\n");
 
-        let server = env::var("POP_SERVER").unwrap();
-        let port: u16 = 995;
+        server
+    }
 
-        let client = super::connect(server, port).await.unwrap();
+    async fn create_test_session(server: &MockServer) -> PopSession<DuplexStream> {
+        let client = super::new(server.connect()).await.unwrap();
 
-        let session = client.login(&username, &password).await.unwrap();
+        let session = client
+            .login(MockServer::DEFAULT_USERNAME, MockServer::DEFAULT_PASSWORD)
+            .await
+            .unwrap();
 
         session
     }
 
     #[tokio::test]
     async fn get_messages() {
-        let mut session = create_test_session().await;
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
 
         let previews = session.messages("Inbox", 0, 10).await.unwrap();
 
-        for preview in previews.iter() {
-            println!(
-                "{}: {:?}, \"{}\"",
-                preview.id(),
-                preview.sent(),
-                preview.subject().unwrap()
-            );
-        }
+        let ids: Vec<&str> = previews.iter().map(|preview| preview.id()).collect();
+
+        assert_eq!(ids, vec!["17811", "17812"]);
+
+        assert_eq!(previews[0].subject(), Some("Hello"));
+        assert_eq!(previews[0].sent(), Some(&1678536000));
+    }
+
+    #[tokio::test]
+    async fn message_previews() {
+        let mut server = create_test_server();
+
+        server.add_message(
+            "17813",
+            "Subject: Report\r\nMessage-Id: <report@example.com>\r\nIn-Reply-To: <question@example.com>\r\nImportance: low\r\nContent-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n--outer\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nThe report is attached\r\n\r\n> Where is the report?\r\n--outer\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"report.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--outer--\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        let previews = session.messages("Inbox", 0, 10).await.unwrap();
+
+        assert_eq!(previews[0].snippet(), Some("Hello world"));
+        assert_eq!(previews[0].size(), Some(&(MESSAGE.len() as u64)));
+        assert!(!previews[0].has_attachments());
+
+        let preview = &previews[2];
+
+        assert_eq!(preview.snippet(), Some("The report is attached"));
+        assert!(preview.has_attachments());
+        assert_eq!(preview.message_id(), Some("report@example.com"));
+        assert_eq!(preview.in_reply_to(), Some("question@example.com"));
+        assert_eq!(preview.importance(), &Importance::Low);
     }
 
     #[tokio::test]
     async fn get_message() {
-        let mut session = create_test_session().await;
+        let server = create_test_server();
+
+        let mut session = create_test_session(&server).await;
+
+        let message = session.get_message("Inbox", "17812", true).await.unwrap();
+
+        assert_eq!(message.id(), "17812");
+        assert_eq!(message.subject(), Some("Second"));
+    }
+
+    #[tokio::test]
+    async fn message_attachments() {
+        let mut server = create_test_server();
+
+        server.add_message(
+            "17813",
+            "Subject: Report\r\nContent-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n--outer\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nThe report is attached\r\n--outer\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"report.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--outer--\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        let message = session.get_message("Inbox", "17813", true).await.unwrap();
+
+        let attachment = &message.attachments()[0];
+
+        assert_eq!(message.attachments().len(), 1);
+        assert_eq!(attachment.id(), "2");
+        assert_eq!(attachment.filename(), Some("report.pdf"));
+        assert_eq!(attachment.content_type(), "application/pdf");
+
+        let (attachment, data) = session.get_attachment("Inbox", "17813", "2").await.unwrap();
+
+        assert_eq!(attachment.content_type(), "application/pdf");
+        assert_eq!(data, b"%PDF-");
+
+        assert!(session.get_attachment("Inbox", "17813", "1").await.is_err());
+
+        session.set_max_attachment_size(4);
+
+        let error = session
+            .get_attachment("Inbox", "17813", "2")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.kind(), ErrorKind::TooLarge));
+    }
+
+    #[tokio::test]
+    async fn message_addresses() {
+        let mut server = create_test_server();
+
+        server.add_message(
+            "17813",
+            "Subject: Addresses\r\nFrom: =?utf-8?q?Doe=2C_J=C3=B6rg?= <jorg@Example.com>\r\nReply-To: list@example.com\r\nTo: Friends: jane@example.com,\r\n \"Doe, John\" <john@example.com>;\r\nCC: undisclosed-recipients:;\r\n\r\nHello\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        let previews = session.messages("Inbox", 0, 10).await.unwrap();
+
+        assert_eq!(previews[2].from()[0].name(), Some("Doe, Jörg"));
+
+        let message = session.get_message("Inbox", "17813", true).await.unwrap();
+
+        assert_eq!(message.from()[0].address(), "jorg@example.com");
+        assert!(message.sender().is_none());
+        assert_eq!(
+            message.reply_to()[0].addresses()[0].address(),
+            "list@example.com"
+        );
+
+        match &message.to()[0] {
+            AddressListEntry::Group(group) => {
+                assert_eq!(group.name(), "Friends");
+                assert_eq!(group.members()[1].name(), Some("Doe, John"));
+            }
+            AddressListEntry::Address(_) => panic!("Expected a group"),
+        }
+
+        assert_eq!(message.cc().len(), 1);
+
+        server.add_message(
+            "17814",
+            "Subject: Authors\r\nFrom: jane@example.com, John <john@example.com>\r\nSender: Jane <jane@example.com>\r\nReply-To: John@example.com, jane@example.com\r\n\r\nHello\r\n",
+        );
+
+        let mut session = create_test_session(&server).await;
+
+        // Headers that only repeat the authors are left out, like they are for an IMAP envelope.
+        let message = session.get_message("Inbox", "17814", true).await.unwrap();
+
+        assert!(message.sender().is_none());
+        assert!(message.reply_to().is_empty());
+    }
+
+    #[tokio::test]
+    async fn download() {
+        let server = create_test_server();
+
+        let options = DownloadOptions::new(false, None, None);
+
+        let mut downloader = PopDownloader::new(options, UidlState::default());
+
+        let session = create_test_session(&server).await;
+
+        let messages = session.download(&mut downloader).await.unwrap();
+
+        assert_eq!(messages.len(), 2);
+
+        // The messages should have been deleted from the server after they were downloaded.
+        assert!(server.maildrop().is_empty());
+    }
+
+    #[tokio::test]
+    async fn download_leave_on_server() {
+        let server = create_test_server();
+
+        let options = DownloadOptions::new(true, None, None);
+
+        let mut downloader = PopDownloader::new(options, UidlState::default());
+
+        let session = create_test_session(&server).await;
+
+        let messages = session.download(&mut downloader).await.unwrap();
+
+        assert_eq!(messages.len(), 2);
+
+        assert_eq!(server.maildrop().len(), 2);
+
+        // Messages that were already downloaded should not be downloaded again.
+        let session = create_test_session(&server).await;
 
-        let message = session.get_message("Inbox", "17812").await.unwrap();
+        let messages = session.download(&mut downloader).await.unwrap();
 
-        println!("{:?}", message.to());
+        assert!(messages.is_empty());
     }
 }
@@ -1,23 +1,16 @@
 use chrono::DateTime;
 
-use crate::types::{Address, Flag, Headers, Result};
-
-pub fn parse_address(address_list: &str) -> Vec<Address> {
-    let split = address_list.split(",\r\t");
-
-    let mut addresses: Vec<Address> = Vec::new();
-
-    // TODO: Parse pop addressess
-    for address in split {}
-
-    // todo!()
-
-    addresses
-}
+use crate::{
+    parse::{
+        header_value, parse_addresses, parse_headers, parse_importance, parse_message_id,
+        parse_preview_content,
+    },
+    types::{Flag, Headers, Preview, Result},
+};
 
 pub fn parse_preview_from_headers(
     headers: &Headers,
-) -> Result<(Vec<Address>, Vec<Flag>, Option<i64>, Option<String>)> {
+) -> Result<(Vec<Flag>, Option<i64>, Option<String>)> {
     let subject = headers.get("Subject").cloned();
 
     let sent = match headers.get("Date") {
@@ -29,13 +22,48 @@ pub fn parse_preview_from_headers(
         None => None,
     };
 
-    let from = match headers.get("From") {
-        Some(from) => parse_address(from),
-        None => Vec::new(),
-    };
-
     // There is also no support for flags in Pop, so we mark every message as read by default.
     let flags = vec![Flag::Read];
 
-    Ok((from, flags, sent, subject))
+    Ok((flags, sent, subject))
+}
+
+/// Creates a preview from the start of a message, as it is retrieved with the TOP command.
+pub fn parse_preview<S: Into<String>>(
+    unique_id: S,
+    message_bytes: &[u8],
+    is_deleted: bool,
+) -> Result<Preview> {
+    let headers = parse_headers(message_bytes)?;
+
+    let addresses = parse_addresses(message_bytes)?;
+
+    let (mut flags, sent, subject) = parse_preview_from_headers(&headers)?;
+
+    // If we have marked a message as deleted, we will add the corresponding flag
+    if is_deleted {
+        flags.push(Flag::Deleted)
+    }
+
+    let mut preview = Preview::new(addresses, flags, unique_id, sent, subject);
+
+    let (snippet, has_attachments) = parse_preview_content(message_bytes)?;
+
+    if let Some(snippet) = snippet {
+        preview.set_snippet(snippet);
+    }
+
+    preview.set_has_attachments(has_attachments);
+
+    if let Some(message_id) = header_value(&headers, "Message-ID").and_then(parse_message_id) {
+        preview.set_message_id(message_id);
+    }
+
+    if let Some(in_reply_to) = header_value(&headers, "In-Reply-To").and_then(parse_message_id) {
+        preview.set_in_reply_to(in_reply_to);
+    }
+
+    preview.set_importance(parse_importance(&headers));
+
+    Ok(preview)
 }
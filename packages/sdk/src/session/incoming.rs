@@ -1,5 +1,6 @@
 use std::fmt::Debug;
 
+use async_tcp::Transcript;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::{
@@ -27,30 +28,42 @@ async fn create_session_from_client<
 
 /// Given some login options and a client type, create an incoming session.
 ///
+/// If a transcript is given, the traffic with the server is recorded in it.
+///
 /// This will automatically connect and login to the mail server specified in the login options using the credentials specified in the login options.
 pub async fn create_incoming_session(
     options: &LoginOptions,
     client_type: &IncomingClientType,
+    transcript: Option<Transcript>,
 ) -> Result<Box<dyn IncomingSession + Send>> {
     let mut builder = IncomingClientBuilder::new(client_type);
 
     builder
         .set_server(options.domain())
-        .set_port(options.port().clone());
+        .set_port(options.port().clone())
+        .set_tls(options.tls().clone())
+        .set_proxy(options.proxy().cloned())
+        .set_transcript(transcript);
 
-    match options.security() {
+    let mut session = match options.security() {
         ConnectionSecurity::Tls => {
             let client = builder.build().await?;
 
-            create_session_from_client(client, options.login_type()).await
+            create_session_from_client(client, options.login_type()).await?
         }
         ConnectionSecurity::Plain => {
             let client = builder.build_plain().await?;
 
-            create_session_from_client(client, options.login_type()).await
+            create_session_from_client(client, options.login_type()).await?
         }
         _ => {
             todo!()
         }
+    };
+
+    if let Some(max_attachment_size) = options.max_attachment_size() {
+        session.set_max_attachment_size(*max_attachment_size);
     }
+
+    Ok(session)
 }
@@ -1,3 +1,4 @@
+use async_tcp::{ProxyConfig, TlsConfig};
 use serde::{Deserialize, Serialize};
 
 use crate::types::{ConnectionSecurity, IncomingClientType, OAuthCredentials};
@@ -40,6 +41,12 @@ pub struct LoginOptions {
     domain: String,
     port: u16,
     security: ConnectionSecurity,
+    #[serde(default)]
+    tls: TlsConfig,
+    #[serde(default)]
+    proxy: Option<ProxyConfig>,
+    #[serde(default)]
+    max_attachment_size: Option<u64>,
 }
 
 impl LoginOptions {
@@ -66,6 +73,33 @@ impl LoginOptions {
     pub fn login_type(&self) -> &LoginType {
         &self.login_type
     }
+
+    /// The options used to secure the connection when the security is set to tls.
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    pub fn set_tls(&mut self, tls: TlsConfig) {
+        self.tls = tls;
+    }
+
+    /// The proxy server that the connection should be tunneled through, if any.
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    pub fn set_proxy(&mut self, proxy: Option<ProxyConfig>) {
+        self.proxy = proxy;
+    }
+
+    /// The size in bytes of the largest attachment that the session will download, if it differs from the default.
+    pub fn max_attachment_size(&self) -> Option<&u64> {
+        self.max_attachment_size.as_ref()
+    }
+
+    pub fn set_max_attachment_size(&mut self, max_attachment_size: Option<u64>) {
+        self.max_attachment_size = max_attachment_size;
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -86,6 +120,11 @@ impl FullLoginOptions {
     pub fn incoming_type(&self) -> &IncomingClientType {
         &self.incoming_type
     }
+
+    /// Trust the certificate with the given fingerprint for the incoming server, e.g. after the user accepted an untrusted certificate.
+    pub fn trust_certificate<F: AsRef<str>>(&mut self, fingerprint: F) {
+        self.incoming.tls.add_pinned_fingerprint(fingerprint);
+    }
 }
 
 pub struct FullLoginOptionsBuilder {
@@ -96,6 +135,9 @@ pub struct FullLoginOptionsBuilder {
     username: Option<String>,
     password: Option<String>,
     access_token: Option<String>,
+    tls: TlsConfig,
+    proxy: Option<ProxyConfig>,
+    max_attachment_size: Option<u64>,
 }
 
 impl FullLoginOptionsBuilder {
@@ -108,6 +150,9 @@ impl FullLoginOptionsBuilder {
             port: None,
             security: None,
             username: None,
+            tls: TlsConfig::default(),
+            proxy: None,
+            max_attachment_size: None,
         }
     }
 
@@ -147,6 +192,24 @@ impl FullLoginOptionsBuilder {
         self
     }
 
+    pub fn tls(&mut self, tls: TlsConfig) -> &mut Self {
+        self.tls = tls;
+
+        self
+    }
+
+    pub fn proxy(&mut self, proxy: ProxyConfig) -> &mut Self {
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    pub fn max_attachment_size(&mut self, max_attachment_size: u64) -> &mut Self {
+        self.max_attachment_size = Some(max_attachment_size);
+
+        self
+    }
+
     pub fn build(self) -> Option<FullLoginOptions> {
         let domain = self.domain?;
         let port = self.port?;
@@ -173,6 +236,9 @@ impl FullLoginOptionsBuilder {
             domain,
             port,
             security,
+            tls: self.tls,
+            proxy: self.proxy,
+            max_attachment_size: self.max_attachment_size,
         };
 
         Some(FullLoginOptions {
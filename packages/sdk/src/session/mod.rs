@@ -3,40 +3,66 @@ mod login;
 
 use std::sync::Arc;
 
+use async_tcp::Transcript;
 use tokio::sync::Mutex;
 
 use crate::{types::Result, IncomingSession};
 
 use self::incoming::create_incoming_session;
 
-pub use self::login::{FullLoginOptions, FullLoginOptionsBuilder, LoginType};
+pub use self::login::{FullLoginOptions, FullLoginOptionsBuilder, LoginType, PasswordCredentials};
 
 pub type ThreadSafeIncomingSession = Arc<Mutex<Box<dyn IncomingSession + Send>>>;
 
 pub struct MailSessions {
     incoming: ThreadSafeIncomingSession,
+    transcript: Option<Transcript>,
 }
 
 impl MailSessions {
-    pub fn new(incoming_session: Box<dyn IncomingSession + Send>) -> Self {
+    pub fn new(
+        incoming_session: Box<dyn IncomingSession + Send>,
+        transcript: Option<Transcript>,
+    ) -> Self {
         Self {
             incoming: Arc::new(Mutex::new(incoming_session)),
+            transcript,
         }
     }
 
     pub fn incoming(&self) -> &ThreadSafeIncomingSession {
         &self.incoming
     }
+
+    /// The transcript of the traffic with the mail servers, if it is being recorded.
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
 }
 
-pub async fn create_sessions(credentials: &FullLoginOptions) -> Result<MailSessions> {
+/// Logs in to the mail servers specified in the given credentials.
+///
+/// When `record_transcript` is set, the traffic with the servers is recorded (with the credentials and message contents redacted) so it can be attached to bug reports.
+pub async fn create_sessions(
+    credentials: &FullLoginOptions,
+    record_transcript: bool,
+) -> Result<MailSessions> {
+    let transcript = if record_transcript {
+        Some(Transcript::new())
+    } else {
+        None
+    };
+
     // Try to get a session for all of the given login options
-    let incoming_session =
-        create_incoming_session(credentials.incoming_options(), credentials.incoming_type())
-            .await?;
+    let incoming_session = create_incoming_session(
+        credentials.incoming_options(),
+        credentials.incoming_type(),
+        transcript.clone(),
+    )
+    .await?;
     // let outgoing_login_thread;
 
-    let mail_sessions = MailSessions::new(incoming_session);
+    let mail_sessions = MailSessions::new(incoming_session, transcript);
 
     Ok(mail_sessions)
 }
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Settings that specify how messages should be downloaded from a Pop server.
+pub struct DownloadOptions {
+    leave_messages_on_server: bool,
+    /// The time in seconds
+    time_to_leave_messages_on_server: Option<u64>,
+    /// The time in seconds
+    check_interval: Option<u64>,
+}
+
+impl DownloadOptions {
+    pub fn new(
+        leave_messages_on_server: bool,
+        time_to_leave_messages_on_server: Option<Duration>,
+        check_interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            leave_messages_on_server,
+            time_to_leave_messages_on_server: time_to_leave_messages_on_server
+                .map(|duration| duration.as_secs()),
+            check_interval: check_interval.map(|duration| duration.as_secs()),
+        }
+    }
+
+    /// Whether the messages should be left on the server after they have been downloaded.
+    pub fn leave_messages_on_server(&self) -> &bool {
+        &self.leave_messages_on_server
+    }
+
+    /// How long a downloaded message should be left on the server before it is deleted.
+    ///
+    /// If this is `None` the messages will be left on the server forever.
+    pub fn time_to_leave_messages_on_server(&self) -> Option<Duration> {
+        self.time_to_leave_messages_on_server
+            .map(Duration::from_secs)
+    }
+
+    /// The minimum amount of time between two checks for new messages.
+    pub fn check_interval(&self) -> Option<Duration> {
+        self.check_interval.map(Duration::from_secs)
+    }
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self::new(true, None, None)
+    }
+}
@@ -9,7 +9,10 @@ use async_imap::error::Error as ImapError;
 #[cfg(feature = "autoconfig")]
 use autoconfig::types::Error as AutoconfigError;
 
-use async_tcp::types::Error as TcpError;
+use async_tcp::{
+    types::{Error as TcpError, ErrorKind as TcpErrorKind},
+    CertificateInfo,
+};
 
 use async_native_tls::Error as TlsError;
 
@@ -33,6 +36,11 @@ pub enum ErrorKind {
     Pop(PopError),
     Tls(TlsError),
     Tcp(TcpError),
+    /// The server presented a certificate that is not trusted.
+    ///
+    /// Contains the certificates the server presented, starting with its own certificate.
+    /// The user can decide to trust it by pinning its fingerprint in the tls options of the login options.
+    UntrustedCertificate(Vec<CertificateInfo>),
     /// Failed to parse a date/time from the server.
     ParseTime(ParseTimeError),
     Timeout(Elapsed),
@@ -54,9 +62,13 @@ pub enum ErrorKind {
     SerializeJSON,
     /// Could not detect a config from the given email address.
     ConfigNotFound,
+    /// The mail server rejected the given credentials.
+    InvalidCredentials,
     SpawnAsync,
     MailBoxNotFound,
     NoClientAvailable,
+    /// The requested data is larger than the limit that was set for the session.
+    TooLarge,
 }
 
 #[derive(Debug)]
@@ -120,6 +132,13 @@ impl From<JoinError> for Error {
 
 impl From<TcpError> for Error {
     fn from(tcp_error: TcpError) -> Self {
+        if let TcpErrorKind::UntrustedCertificate(certificates) = tcp_error.kind() {
+            return Self::new(
+                ErrorKind::UntrustedCertificate(certificates.clone()),
+                tcp_error.to_string(),
+            );
+        }
+
         Self::new(
             ErrorKind::Tcp(tcp_error),
             "An error occurred with the remote connection",
@@ -1,70 +1,311 @@
+use dust_mail_utils::{Address as ParsedAddress, EmailAddress, Mailbox};
 use serde::Serialize;
 
 use crate::parse;
 
 use super::{Flag, Headers};
 
-#[derive(Debug, Serialize)]
+/// The characters that have a meaning in an address header, a display name that contains them has to be quoted.
+const SPECIALS: &str = "()<>[]:;@\\,.\"";
+
+/// A single email address with an optional display name.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
 pub struct Address {
     name: Option<String>,
-    address: Option<String>,
+    address: String,
 }
 
 impl Address {
-    pub fn new(name: Option<String>, address: Option<String>) -> Self {
-        Self { name, address }
+    pub fn new<A: Into<String>>(name: Option<String>, address: A) -> Self {
+        let name = name.filter(|name| !name.trim().is_empty());
+
+        Self {
+            name,
+            address: address.into(),
+        }
     }
 
-    pub fn name(&self) -> &Option<String> {
-        &self.name
+    /// Creates an address from its separate parts, like the ones in an IMAP envelope, normalizing it the same way a parsed header would be.
+    pub fn from_parts<L: AsRef<str>, D: AsRef<str>>(
+        name: Option<String>,
+        local_part: L,
+        domain: D,
+    ) -> Self {
+        let address = match EmailAddress::new(local_part.as_ref(), domain.as_ref()) {
+            Ok(address) => address.to_string(),
+            Err(_) => format!("{}@{}", local_part.as_ref(), domain.as_ref()),
+        };
+
+        Self::new(name, address)
+    }
+
+    /// The display name, with any encoded words decoded.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
     }
 
-    pub fn address(&self) -> &Option<String> {
+    pub fn address(&self) -> &str {
         &self.address
     }
 
-    pub fn full(&self) -> Option<String> {
-        if self.address.is_some() && self.name.is_some() {
-            Some(format!(
-                "{} <{}>",
-                self.name.as_ref().unwrap(),
-                self.address.as_ref().unwrap()
-            ))
-        } else {
-            None
+    /// Whether both refer to the same mailbox, regardless of their display names.
+    pub fn is_same_address(&self, other: &Address) -> bool {
+        self.address.eq_ignore_ascii_case(&other.address)
+    }
+
+    /// The address as it is written in a header, like `John Doe <john@example.com>`, or just the address if there is no display name.
+    pub fn full(&self) -> String {
+        match &self.name {
+            Some(name) if name.contains(|character| SPECIALS.contains(character)) => format!(
+                "\"{}\" <{}>",
+                name.replace('\\', "\\\\").replace('"', "\\\""),
+                self.address
+            ),
+            Some(name) => format!("{} <{}>", name, self.address),
+            None => self.address.clone(),
+        }
+    }
+}
+
+impl From<&Mailbox> for Address {
+    fn from(mailbox: &Mailbox) -> Self {
+        Self::new(
+            mailbox.name().map(String::from),
+            mailbox.address().to_string(),
+        )
+    }
+}
+
+/// A named group of addresses, like `Friends: john@example.com, jane@example.com;`.
+///
+/// The list of members can be empty, which is used to hide the recipients of a message, like `undisclosed-recipients:;`.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Group {
+    name: String,
+    members: Vec<Address>,
+}
+
+impl Group {
+    pub fn new<N: Into<String>>(name: N, members: Vec<Address>) -> Self {
+        Self {
+            name: name.into(),
+            members,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn members(&self) -> &Vec<Address> {
+        &self.members
+    }
+}
+
+/// An entry in an address list header like `To`, which is either a single address or a group of them.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum AddressListEntry {
+    Address(Address),
+    Group(Group),
+}
+
+impl AddressListEntry {
+    /// The addresses this entry refers to, which is either a single one or the members of a group.
+    pub fn addresses(&self) -> Vec<&Address> {
+        match self {
+            Self::Address(address) => vec![address],
+            Self::Group(group) => group.members().iter().collect(),
+        }
+    }
+}
+
+impl From<&ParsedAddress> for AddressListEntry {
+    fn from(address: &ParsedAddress) -> Self {
+        match address {
+            ParsedAddress::Mailbox(mailbox) => Self::Address(mailbox.into()),
+            ParsedAddress::Group { name, members } => Self::Group(Group::new(
+                name,
+                members.iter().map(Address::from).collect(),
+            )),
+        }
+    }
+}
+
+/// The addresses of everyone that is involved with a message.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Addresses {
+    from: Vec<Address>,
+    sender: Option<Address>,
+    reply_to: Vec<AddressListEntry>,
+    to: Vec<AddressListEntry>,
+    cc: Vec<AddressListEntry>,
+    bcc: Vec<AddressListEntry>,
+}
+
+impl Addresses {
+    pub fn new(
+        from: Vec<Address>,
+        sender: Option<Address>,
+        reply_to: Vec<AddressListEntry>,
+        to: Vec<AddressListEntry>,
+        cc: Vec<AddressListEntry>,
+        bcc: Vec<AddressListEntry>,
+    ) -> Self {
+        // IMAP servers fill in the sender and reply to with the authors if the message does not specify them, while a parsed
+        // header leaves them out, so we only keep them if they add an address that is not one of the authors.
+        let is_author =
+            |address: &Address| from.iter().any(|author| author.is_same_address(address));
+
+        let sender = sender.filter(|sender| !is_author(sender));
+
+        let is_from = reply_to.iter().all(|entry| match entry {
+            AddressListEntry::Address(address) => is_author(address),
+            AddressListEntry::Group(_) => false,
+        });
+
+        let reply_to = if is_from { Vec::new() } else { reply_to };
+
+        Self {
+            from,
+            sender,
+            reply_to,
+            to,
+            cc,
+            bcc,
         }
     }
+
+    /// The author(s) of the message.
+    pub fn from(&self) -> &Vec<Address> {
+        &self.from
+    }
+
+    /// Who actually sent the message, if that is someone else than the author, like a secretary or a mailing list.
+    pub fn sender(&self) -> Option<&Address> {
+        self.sender.as_ref()
+    }
+
+    /// Where replies should be sent to, if that is not the author of the message.
+    pub fn reply_to(&self) -> &Vec<AddressListEntry> {
+        &self.reply_to
+    }
+
+    pub fn to(&self) -> &Vec<AddressListEntry> {
+        &self.to
+    }
+
+    pub fn cc(&self) -> &Vec<AddressListEntry> {
+        &self.cc
+    }
+
+    pub fn bcc(&self) -> &Vec<AddressListEntry> {
+        &self.bcc
+    }
+}
+
+/// How important the sender marked a message, using headers like `Importance` and `X-Priority`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Importance {
+    Low,
+    #[default]
+    Normal,
+    High,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Preview {
-    from: Vec<Address>,
+    #[serde(flatten)]
+    addresses: Addresses,
     flags: Vec<Flag>,
     id: String,
     sent: Option<i64>,
     subject: Option<String>,
+    size: Option<u64>,
+    snippet: Option<String>,
+    has_attachments: bool,
+    message_id: Option<String>,
+    in_reply_to: Option<String>,
+    importance: Importance,
 }
 
 impl Preview {
     pub fn new<S: Into<String>>(
-        from: Vec<Address>,
+        addresses: Addresses,
         flags: Vec<Flag>,
         id: S,
         sent: Option<i64>,
         subject: Option<String>,
     ) -> Self {
         Self {
-            from,
+            addresses,
             flags,
             id: id.into(),
             sent,
             subject,
+            size: None,
+            snippet: None,
+            has_attachments: false,
+            message_id: None,
+            in_reply_to: None,
+            importance: Importance::default(),
         }
     }
 
+    pub fn set_size(&mut self, size: u64) -> &mut Self {
+        self.size = Some(size);
+
+        self
+    }
+
+    pub fn set_snippet<S: Into<String>>(&mut self, snippet: S) -> &mut Self {
+        self.snippet = Some(snippet.into());
+
+        self
+    }
+
+    pub fn set_has_attachments(&mut self, has_attachments: bool) -> &mut Self {
+        self.has_attachments = has_attachments;
+
+        self
+    }
+
+    pub fn set_message_id<S: Into<String>>(&mut self, message_id: S) -> &mut Self {
+        self.message_id = Some(message_id.into());
+
+        self
+    }
+
+    pub fn set_in_reply_to<S: Into<String>>(&mut self, in_reply_to: S) -> &mut Self {
+        self.in_reply_to = Some(in_reply_to.into());
+
+        self
+    }
+
+    pub fn set_importance(&mut self, importance: Importance) -> &mut Self {
+        self.importance = importance;
+
+        self
+    }
+
+    /// Everyone that is involved with the message.
+    pub fn addresses(&self) -> &Addresses {
+        &self.addresses
+    }
+
     /// The sender(s) of the message.
     pub fn from(&self) -> &Vec<Address> {
-        &self.from
+        self.addresses.from()
+    }
+
+    pub fn to(&self) -> &Vec<AddressListEntry> {
+        self.addresses.to()
+    }
+
+    pub fn cc(&self) -> &Vec<AddressListEntry> {
+        self.addresses.cc()
     }
 
     /// The messages flags that indicate whether the message has been read, deleted, etc.
@@ -90,6 +331,34 @@ impl Preview {
         }
     }
 
+    /// The size of the entire message in bytes.
+    pub fn size(&self) -> Option<&u64> {
+        self.size.as_ref()
+    }
+
+    /// The start of the message's text, without quoted replies and with the white space collapsed.
+    pub fn snippet(&self) -> Option<&str> {
+        self.snippet.as_deref()
+    }
+
+    pub fn has_attachments(&self) -> bool {
+        self.has_attachments
+    }
+
+    /// The id of the message from its `Message-ID` header, without the angle brackets.
+    pub fn message_id(&self) -> Option<&str> {
+        self.message_id.as_deref()
+    }
+
+    /// The id of the message that this message is a reply to, which can be used to group messages into threads.
+    pub fn in_reply_to(&self) -> Option<&str> {
+        self.in_reply_to.as_deref()
+    }
+
+    pub fn importance(&self) -> &Importance {
+        &self.importance
+    }
+
     pub fn to_json(&self) -> super::Result<String> {
         parse::to_json(self)
     }
@@ -99,14 +368,30 @@ impl Preview {
 pub struct Content {
     text: Option<String>,
     html: Option<String>,
+    truncated: bool,
 }
 
 impl Content {
     pub fn new(text: Option<String>, html: Option<String>) -> Self {
-        Self { text, html }
+        Self {
+            text,
+            html,
+            truncated: false,
+        }
     }
 
-    /// The message in pure text form.
+    /// Marks the text or html as cut off, because the message was too large to download all of it.
+    pub fn set_truncated(&mut self, truncated: bool) -> &mut Self {
+        self.truncated = truncated;
+
+        self
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The message in pure text form, which is not escaped and should never be rendered as html.
     pub fn text(&self) -> Option<&str> {
         match &self.text {
             Some(text) => Some(text),
@@ -114,7 +399,7 @@ impl Content {
         }
     }
 
-    /// The message as a html page.
+    /// The message as a html page, sanitized so it is safe to render.
     pub fn html(&self) -> Option<&str> {
         match &self.html {
             Some(html) => Some(html),
@@ -123,26 +408,91 @@ impl Content {
     }
 }
 
+/// A part of a message that is not shown as its content, like a file that was attached to it.
+///
+/// Only the description of the part is sent along with the message, its data is downloaded on demand using its id.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    id: String,
+    content_type: String,
+    filename: Option<String>,
+    size: Option<u64>,
+    content_id: Option<String>,
+}
+
+impl Attachment {
+    pub fn new<I: Into<String>, C: Into<String>>(id: I, content_type: C) -> Self {
+        Self {
+            id: id.into(),
+            content_type: content_type.into(),
+            filename: None,
+            size: None,
+            content_id: None,
+        }
+    }
+
+    pub fn set_filename<S: Into<String>>(&mut self, filename: S) -> &mut Self {
+        self.filename = Some(filename.into());
+
+        self
+    }
+
+    pub fn set_size(&mut self, size: u64) -> &mut Self {
+        self.size = Some(size);
+
+        self
+    }
+
+    pub fn set_content_id<S: Into<String>>(&mut self, content_id: S) -> &mut Self {
+        self.content_id = Some(content_id.into());
+
+        self
+    }
+
+    /// The part number of the attachment in the message, like `2` or `1.3`, used to download it.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The mime type of the attachment, like `application/pdf`.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// The size of the attachment in bytes, as it is stored on the server.
+    ///
+    /// This is the size before the transfer encoding is removed, so the downloaded data can be smaller.
+    pub fn size(&self) -> Option<&u64> {
+        self.size.as_ref()
+    }
+
+    /// The content id, without the angle brackets, that the html of the message uses to refer to the attachment, like an inline image.
+    pub fn content_id(&self) -> Option<&str> {
+        self.content_id.as_deref()
+    }
+}
+
 #[derive(Serialize)]
 pub struct Message {
-    from: Vec<Address>,
-    to: Vec<Address>,
-    cc: Vec<Address>,
-    bcc: Vec<Address>,
+    #[serde(flatten)]
+    addresses: Addresses,
     headers: Headers,
     flags: Vec<Flag>,
     id: String,
     sent: Option<i64>,
     subject: Option<String>,
     content: Content,
+    attachments: Vec<Attachment>,
 }
 
 impl Message {
     pub fn new<S: Into<String>>(
-        from: Vec<Address>,
-        to: Vec<Address>,
-        cc: Vec<Address>,
-        bcc: Vec<Address>,
+        addresses: Addresses,
         headers: Headers,
         flags: Vec<Flag>,
         id: S,
@@ -151,33 +501,50 @@ impl Message {
         content: Content,
     ) -> Self {
         Self {
-            from,
-            to,
-            cc,
-            bcc,
+            addresses,
             headers,
             flags,
             id: id.into(),
             sent,
             subject,
             content,
+            attachments: Vec::new(),
         }
     }
 
+    pub fn set_attachments(&mut self, attachments: Vec<Attachment>) -> &mut Self {
+        self.attachments = attachments;
+
+        self
+    }
+
+    /// Everyone that is involved with the message.
+    pub fn addresses(&self) -> &Addresses {
+        &self.addresses
+    }
+
     pub fn from(&self) -> &Vec<Address> {
-        &self.from
+        self.addresses.from()
     }
 
-    pub fn to(&self) -> &Vec<Address> {
-        &self.to
+    pub fn sender(&self) -> Option<&Address> {
+        self.addresses.sender()
     }
 
-    pub fn cc(&self) -> &Vec<Address> {
-        &self.cc
+    pub fn reply_to(&self) -> &Vec<AddressListEntry> {
+        self.addresses.reply_to()
     }
 
-    pub fn bcc(&self) -> &Vec<Address> {
-        &self.bcc
+    pub fn to(&self) -> &Vec<AddressListEntry> {
+        self.addresses.to()
+    }
+
+    pub fn cc(&self) -> &Vec<AddressListEntry> {
+        self.addresses.cc()
+    }
+
+    pub fn bcc(&self) -> &Vec<AddressListEntry> {
+        self.addresses.bcc()
     }
 
     /// The message's RFC 822 headers.
@@ -213,6 +580,11 @@ impl Message {
         &self.content
     }
 
+    /// The parts of the message that are not part of its content, these can be downloaded separately.
+    pub fn attachments(&self) -> &Vec<Attachment> {
+        &self.attachments
+    }
+
     pub fn to_json(&self) -> super::Result<String> {
         parse::to_json(self)
     }
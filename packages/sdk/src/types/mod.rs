@@ -1,5 +1,6 @@
 mod client;
 mod connection;
+mod download;
 mod error;
 mod flags;
 mod mailbox;
@@ -8,12 +9,19 @@ mod oauth2;
 
 use std::{collections::HashMap, result};
 
+pub use async_tcp::{
+    CertificateInfo, ProxyConfig, ProxyCredentials, ProxyType, TlsConfig, TlsVersion, Transcript,
+    TranscriptEntry,
+};
 pub use client::*;
 pub use connection::ConnectionSecurity;
+pub use download::DownloadOptions;
 pub use error::{Error, ErrorKind};
 pub use flags::Flag;
 pub use mailbox::{Counts, MailBox, MailBoxList};
-pub use message::{Address, Content, Message, Preview};
+pub use message::{
+    Address, AddressListEntry, Addresses, Attachment, Content, Group, Importance, Message, Preview,
+};
 pub use oauth2::OAuthCredentials;
 
 pub type Result<T> = result::Result<T, Error>;
@@ -0,0 +1,429 @@
+mod parser;
+
+use std::{fmt, str::FromStr};
+
+use crate::types::{Error, ErrorKind, Result};
+
+use parser::{is_atext, Parser};
+
+/// The maximum length of the local part of an address in octets, as defined in RFC 5321 section 4.5.3.1.1.
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+/// The maximum length of a domain in octets, as defined in RFC 5321 section 4.5.3.1.2.
+const MAX_DOMAIN_LENGTH: usize = 255;
+const MAX_LABEL_LENGTH: usize = 63;
+
+fn is_dot_atom_text(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for character in text.chars() {
+        if character == '"' || character == '\\' {
+            quoted.push('\\');
+        }
+
+        quoted.push(character);
+    }
+
+    quoted.push('"');
+
+    quoted
+}
+
+/// A display name as it can be written in a header, it only needs quotes if it contains special characters.
+fn display_phrase(phrase: &str) -> String {
+    if phrase
+        .split(' ')
+        .all(|word| !word.is_empty() && word.chars().all(is_atext))
+    {
+        phrase.to_string()
+    } else {
+        quote(phrase)
+    }
+}
+
+fn idna_error(domain: &str) -> Error {
+    Error::new(
+        ErrorKind::Idna,
+        format!("'{}' is not a valid domain name", domain),
+    )
+}
+
+/// Converts a domain to the ASCII form that is used in DNS, international domains are converted to punycode.
+fn domain_to_ascii(domain: &str) -> Result<String> {
+    let ascii = idna::domain_to_ascii(domain).map_err(|_| idna_error(domain))?;
+
+    if ascii.is_empty() {
+        return Err(idna_error(domain));
+    }
+
+    if ascii.len() > MAX_DOMAIN_LENGTH
+        || ascii.split('.').any(|label| label.len() > MAX_LABEL_LENGTH)
+    {
+        return Err(Error::new(
+            ErrorKind::TooLong,
+            format!("The domain '{}' is too long", domain),
+        ));
+    }
+
+    Ok(ascii)
+}
+
+/// A single email address, like `john@example.com`.
+///
+/// The domain is normalized, so it is lowercase and international domains are available in both their Unicode and punycode form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmailAddress {
+    local_part: String,
+    domain: String,
+    ascii_domain: String,
+}
+
+impl EmailAddress {
+    /// Creates an address from its parts, like the ones in an IMAP envelope, the local part is quoted when it is displayed if it needs to be.
+    pub fn new<L: Into<String>, D: Into<String>>(local_part: L, domain: D) -> Result<Self> {
+        let local_part = local_part.into();
+        let domain = domain.into();
+
+        // Domain literals like `[192.168.0.1]` are not domain names, so they are kept as they are.
+        if domain.starts_with('[') {
+            return Ok(Self {
+                local_part,
+                ascii_domain: domain.clone(),
+                domain,
+            });
+        }
+
+        let ascii_domain = domain_to_ascii(&domain)?;
+
+        let (domain, result) = idna::domain_to_unicode(&ascii_domain);
+
+        result.map_err(|_| idna_error(&ascii_domain))?;
+
+        Ok(Self {
+            local_part,
+            domain,
+            ascii_domain,
+        })
+    }
+
+    /// The part before the '@', without quotes.
+    pub fn local_part(&self) -> &str {
+        &self.local_part
+    }
+
+    /// The domain in its Unicode form, like `bücher.example`.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The domain in the ASCII form that is used in DNS, like `xn--bcher-kva.example`.
+    pub fn ascii_domain(&self) -> &str {
+        &self.ascii_domain
+    }
+
+    /// Whether the local part contains non ASCII characters, which only mail servers that support SMTPUTF8 accept.
+    pub fn is_internationalized(&self) -> bool {
+        !self.local_part.is_ascii()
+    }
+
+    /// The address with its domain in ASCII form, for servers that do not support international domain names.
+    pub fn to_ascii_string(&self) -> String {
+        format!("{}@{}", self.display_local_part(), self.ascii_domain)
+    }
+
+    fn display_local_part(&self) -> String {
+        if is_dot_atom_text(&self.local_part) {
+            self.local_part.clone()
+        } else {
+            quote(&self.local_part)
+        }
+    }
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.display_local_part(), self.domain)
+    }
+}
+
+impl FromStr for EmailAddress {
+    type Err = Error;
+
+    fn from_str(address: &str) -> Result<Self> {
+        parse_addr_spec(address)
+    }
+}
+
+/// An email address with an optional display name, like `John Doe <john@example.com>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mailbox {
+    name: Option<String>,
+    address: EmailAddress,
+}
+
+impl Mailbox {
+    pub fn new(name: Option<String>, address: EmailAddress) -> Self {
+        let name = name.filter(|name| !name.is_empty());
+
+        Self { name, address }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn address(&self) -> &EmailAddress {
+        &self.address
+    }
+}
+
+impl fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} <{}>", display_phrase(name), self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+impl FromStr for Mailbox {
+    type Err = Error;
+
+    fn from_str(mailbox: &str) -> Result<Self> {
+        parse_mailbox(mailbox)
+    }
+}
+
+/// An entry in an address list, which is either a single mailbox or a named group of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Address {
+    Mailbox(Mailbox),
+    /// A named group like `Friends: john@example.com, jane@example.com;`, the list of members can be empty to hide the recipients.
+    Group {
+        name: String,
+        members: Vec<Mailbox>,
+    },
+}
+
+impl Address {
+    /// The mailboxes this address refers to, which is either a single one or the members of a group.
+    pub fn mailboxes(&self) -> Vec<&Mailbox> {
+        match self {
+            Self::Mailbox(mailbox) => vec![mailbox],
+            Self::Group { members, .. } => members.iter().collect(),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mailbox(mailbox) => write!(f, "{}", mailbox),
+            Self::Group { name, members } => {
+                let members: Vec<String> = members.iter().map(Mailbox::to_string).collect();
+
+                write!(f, "{}: {};", display_phrase(name), members.join(", "))
+            }
+        }
+    }
+}
+
+/// Parses a single address without a display name, like `john@example.com`, surrounding white space and comments are allowed.
+///
+/// This is meant for an address a user entered, so unlike the other parsers it also rejects a local part that is too long to
+/// be delivered. Headers we receive are parsed as they are, even if the sender did not respect that limit.
+pub fn parse_addr_spec(address: &str) -> Result<EmailAddress> {
+    let mut parser = Parser::new(address);
+
+    let address = parser.addr_spec()?;
+
+    parser.finish()?;
+
+    if address.local_part().len() > MAX_LOCAL_PART_LENGTH {
+        return Err(Error::new(
+            ErrorKind::TooLong,
+            "The local part of an email address can be at most 64 characters long",
+        ));
+    }
+
+    Ok(address)
+}
+
+/// Parses a single address with an optional display name, like `John Doe <john@example.com>` or `john@example.com`.
+pub fn parse_mailbox(mailbox: &str) -> Result<Mailbox> {
+    let mut parser = Parser::new(mailbox);
+
+    let mailbox = parser.mailbox()?;
+
+    parser.finish()?;
+
+    Ok(mailbox)
+}
+
+/// Parses a comma separated list of mailboxes and groups, like the value of a `To` header.
+pub fn parse_address_list(list: &str) -> Result<Vec<Address>> {
+    let mut parser = Parser::new(list);
+
+    let addresses = parser.address_list()?;
+
+    parser.finish()?;
+
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn addr_spec() {
+        let address = parse_addr_spec("john.doe@Example.com").unwrap();
+
+        assert_eq!(address.local_part(), "john.doe");
+        assert_eq!(address.domain(), "example.com");
+        assert_eq!(address.to_string(), "john.doe@example.com");
+
+        let address = parse_addr_spec(" (work) john+mail@example.com (John) ").unwrap();
+
+        assert_eq!(address.local_part(), "john+mail");
+        assert_eq!(address.domain(), "example.com");
+
+        let address = parse_addr_spec("\"john@home \\\"doe\\\"\"@example.com").unwrap();
+
+        assert_eq!(address.local_part(), "john@home \"doe\"");
+        assert_eq!(address.to_string(), "\"john@home \\\"doe\\\"\"@example.com");
+
+        let address = parse_addr_spec("postmaster@[192.168.0.1]").unwrap();
+
+        assert_eq!(address.domain(), "[192.168.0.1]");
+
+        for invalid in [
+            "",
+            "john",
+            "john@",
+            "@example.com",
+            "john..doe@example.com",
+            "john.@example.com",
+            "john doe@example.com",
+            "john@example..com",
+            "John <john@example.com>",
+            "john@example.com, jane@example.com",
+            "\"john@example.com",
+        ] {
+            assert!(parse_addr_spec(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn internationalized() {
+        let address = parse_addr_spec("用户@Bücher.example").unwrap();
+
+        assert_eq!(address.local_part(), "用户");
+        assert_eq!(address.domain(), "bücher.example");
+        assert_eq!(address.ascii_domain(), "xn--bcher-kva.example");
+        assert!(address.is_internationalized());
+        assert_eq!(address.to_ascii_string(), "用户@xn--bcher-kva.example");
+
+        let address = parse_addr_spec("john@xn--bcher-kva.example").unwrap();
+
+        assert_eq!(address.domain(), "bücher.example");
+        assert!(!address.is_internationalized());
+    }
+
+    #[test]
+    fn too_long() {
+        let local_part = "a".repeat(65);
+
+        let error = parse_addr_spec(&format!("{}@example.com", local_part)).unwrap_err();
+
+        assert_eq!(error.kind(), &ErrorKind::TooLong);
+
+        // Only an address a user entered is checked, a header with a long local part can still be read.
+        let addresses = parse_address_list(&format!("{}@example.com", local_part)).unwrap();
+
+        assert_eq!(addresses.len(), 1);
+
+        let label = "a".repeat(64);
+
+        let error = parse_addr_spec(&format!("john@{}.com", label)).unwrap_err();
+
+        assert_eq!(error.kind(), &ErrorKind::TooLong);
+    }
+
+    #[test]
+    fn mailbox() {
+        let mailbox = parse_mailbox("John Q. Public <john@example.com>").unwrap();
+
+        assert_eq!(mailbox.name(), Some("John Q. Public"));
+        assert_eq!(mailbox.address().to_string(), "john@example.com");
+
+        let mailbox = parse_mailbox("\"Doe, John (@work)\" <john@example.com>").unwrap();
+
+        assert_eq!(mailbox.name(), Some("Doe, John (@work)"));
+        assert_eq!(
+            mailbox.to_string(),
+            "\"Doe, John (@work)\" <john@example.com>"
+        );
+
+        let mailbox = parse_mailbox("<john@example.com>").unwrap();
+
+        assert_eq!(mailbox.name(), None);
+
+        let mailbox = parse_mailbox("john@example.com").unwrap();
+
+        assert_eq!(mailbox.name(), None);
+        assert_eq!(mailbox.to_string(), "john@example.com");
+
+        let mailbox = parse_mailbox("=?utf-8?q?Doe=2C_J=C3=B6rg?= <jorg@example.com>").unwrap();
+
+        assert_eq!(mailbox.name(), Some("Doe, Jörg"));
+
+        assert!(parse_mailbox("John Doe").is_err());
+        assert!(parse_mailbox("John <john@example.com").is_err());
+    }
+
+    #[test]
+    fn address_list() {
+        let addresses = parse_address_list(
+            "\"Doe, John\" <john@example.com>, jane@example.com,\r\n Friends: alice@example.com, Bob <bob@example.com>;, undisclosed-recipients:;",
+        )
+        .unwrap();
+
+        assert_eq!(addresses.len(), 4);
+
+        assert_eq!(addresses[0].mailboxes()[0].name(), Some("Doe, John"));
+
+        assert_eq!(addresses[1].to_string(), "jane@example.com");
+
+        match &addresses[2] {
+            Address::Group { name, members } => {
+                assert_eq!(name, "Friends");
+                assert_eq!(members.len(), 2);
+                assert_eq!(members[1].name(), Some("Bob"));
+            }
+            Address::Mailbox(_) => panic!("Expected a group"),
+        }
+
+        assert_eq!(
+            addresses[2].to_string(),
+            "Friends: alice@example.com, Bob <bob@example.com>;"
+        );
+
+        assert_eq!(
+            addresses[3],
+            Address::Group {
+                name: String::from("undisclosed-recipients"),
+                members: Vec::new()
+            }
+        );
+
+        assert!(parse_address_list("").unwrap().is_empty());
+        assert!(parse_address_list("john@example.com jane@example.com").is_err());
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{
+    decode_encoded_words,
+    types::{Error, ErrorKind, Result},
+};
+
+use super::{Address, EmailAddress, Mailbox};
+
+const ATEXT_SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
+
+fn is_utf8_non_ascii(character: char) -> bool {
+    !character.is_ascii() && !character.is_control()
+}
+
+/// The characters that can be used in an atom without quoting, RFC 6532 extends these with all non ASCII characters.
+pub(super) fn is_atext(character: char) -> bool {
+    character.is_ascii_alphanumeric()
+        || ATEXT_SPECIALS.contains(character)
+        || is_utf8_non_ascii(character)
+}
+
+fn is_wsp(character: char) -> bool {
+    character == ' ' || character == '\t'
+}
+
+fn is_qtext(character: char) -> bool {
+    matches!(character, '\x21' | '\x23'..='\x5b' | '\x5d'..='\x7e') || is_utf8_non_ascii(character)
+}
+
+fn is_dtext(character: char) -> bool {
+    matches!(character, '\x21'..='\x5a' | '\x5e'..='\x7e') || is_utf8_non_ascii(character)
+}
+
+/// A recursive descent parser for the address syntax of RFC 5322 section 3.4, with the UTF-8 extensions of RFC 6532.
+pub(super) struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let character = self.peek()?;
+
+        self.position += character.len_utf8();
+
+        Some(character)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{}'", expected)))
+        }
+    }
+
+    fn error<S: AsRef<str>>(&self, message: S) -> Error {
+        let found = match self.peek() {
+            Some(character) => format!("'{}'", character),
+            None => String::from("the end of the input"),
+        };
+
+        Error::new(
+            ErrorKind::Syntax,
+            format!(
+                "{} at position {}, found {}",
+                message.as_ref(),
+                self.position,
+                found
+            ),
+        )
+    }
+
+    /// Fails if there is any input left that was not parsed.
+    pub fn finish(&self) -> Result<()> {
+        match self.peek() {
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Ok(()),
+        }
+    }
+
+    /// Skips folding white space, a line break only counts if it is followed by white space.
+    fn fws(&mut self) -> bool {
+        let start = self.position;
+
+        loop {
+            let rest = self.rest();
+
+            let line_break = if rest.starts_with("\r\n") {
+                2
+            } else if rest.starts_with('\n') {
+                1
+            } else {
+                0
+            };
+
+            match rest[line_break..].chars().next() {
+                Some(character) if is_wsp(character) => self.position += line_break + 1,
+                _ => break,
+            }
+        }
+
+        self.position > start
+    }
+
+    /// Skips a comment, which can contain nested comments. Expects to be at the opening parenthesis.
+    fn comment(&mut self) -> Result<()> {
+        self.expect('(')?;
+
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.bump() {
+                Some('(') => depth += 1,
+                Some(')') => depth -= 1,
+                Some('\\') => {
+                    if self.bump().is_none() {
+                        return Err(self.error("Unterminated comment"));
+                    }
+                }
+                Some(_) => {}
+                None => return Err(self.error("Unterminated comment")),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Skips comments and folding white space, returns whether anything was skipped.
+    fn cfws(&mut self) -> Result<bool> {
+        let start = self.position;
+
+        loop {
+            self.fws();
+
+            if self.peek() == Some('(') {
+                self.comment()?;
+            } else {
+                break;
+            }
+        }
+
+        Ok(self.position > start)
+    }
+
+    fn atext(&mut self) -> Option<&'a str> {
+        let start = self.position;
+
+        while matches!(self.peek(), Some(character) if is_atext(character)) {
+            self.bump();
+        }
+
+        if self.position > start {
+            Some(&self.input[start..self.position])
+        } else {
+            None
+        }
+    }
+
+    /// Atoms separated by periods, without the comments and white space around them.
+    fn dot_atom_text(&mut self) -> Result<Option<&'a str>> {
+        let start = self.position;
+
+        if self.atext().is_none() {
+            return Ok(None);
+        }
+
+        while self.eat('.') {
+            if self.atext().is_none() {
+                return Err(self.error("Expected text after '.'"));
+            }
+        }
+
+        Ok(Some(&self.input[start..self.position]))
+    }
+
+    /// The content of a quoted string, with the quoted pairs unescaped and the folded lines unfolded. Expects to be at the opening quote.
+    fn quoted_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+
+        let mut content = String::new();
+
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some(character) => content.push(character),
+                    None => return Err(self.error("Unterminated quoted string")),
+                },
+                Some('\r') | Some('\n') => {}
+                Some(character) if is_qtext(character) || is_wsp(character) => {
+                    content.push(character)
+                }
+                Some(character) => {
+                    self.position -= character.len_utf8();
+
+                    return Err(self.error("Invalid character in quoted string"));
+                }
+                None => return Err(self.error("Unterminated quoted string")),
+            }
+        }
+
+        Ok(content)
+    }
+
+    /// A display name, made up of atoms and quoted strings and, like in older messages, periods.
+    ///
+    /// The encoded words in it are decoded by the caller once it is known to be a display name.
+    fn phrase(&mut self) -> Result<Option<String>> {
+        let mut phrase: Option<String> = None;
+
+        loop {
+            let spaced = self.cfws()?;
+
+            let token = match self.peek() {
+                Some('"') => self.quoted_string()?,
+                Some('.') if phrase.is_some() => {
+                    self.bump();
+
+                    String::from(".")
+                }
+                Some(character) if is_atext(character) => self.atext().unwrap().to_string(),
+                _ => break,
+            };
+
+            match phrase.as_mut() {
+                Some(phrase) => {
+                    if spaced {
+                        phrase.push(' ');
+                    }
+
+                    phrase.push_str(&token);
+                }
+                None => phrase = Some(token),
+            }
+        }
+
+        Ok(phrase)
+    }
+
+    fn local_part(&mut self) -> Result<String> {
+        self.cfws()?;
+
+        let local_part = match self.peek() {
+            Some('"') => self.quoted_string()?,
+            _ => match self.dot_atom_text()? {
+                Some(text) => text.to_string(),
+                None => return Err(self.error("Expected the local part of an address")),
+            },
+        };
+
+        self.cfws()?;
+
+        Ok(local_part)
+    }
+
+    /// A domain literal like `[192.168.0.1]`, with the white space in it removed. Expects to be at the opening bracket.
+    fn domain_literal(&mut self) -> Result<String> {
+        let start = self.position;
+
+        self.expect('[')?;
+
+        loop {
+            self.fws();
+
+            match self.peek() {
+                Some(']') => {
+                    self.bump();
+
+                    break;
+                }
+                Some(character) if is_dtext(character) => {
+                    self.bump();
+                }
+                _ => return Err(self.error("Invalid character in domain literal")),
+            }
+        }
+
+        Ok(self.input[start..self.position]
+            .chars()
+            .filter(|character| !character.is_whitespace())
+            .collect())
+    }
+
+    fn domain(&mut self) -> Result<String> {
+        self.cfws()?;
+
+        let domain = match self.peek() {
+            Some('[') => self.domain_literal()?,
+            _ => match self.dot_atom_text()? {
+                Some(text) => text.to_string(),
+                None => return Err(self.error("Expected a domain after '@'")),
+            },
+        };
+
+        self.cfws()?;
+
+        Ok(domain)
+    }
+
+    /// An address like `john@example.com`, without a display name.
+    pub fn addr_spec(&mut self) -> Result<EmailAddress> {
+        let local_part = self.local_part()?;
+
+        self.expect('@')?;
+
+        let domain = self.domain()?;
+
+        EmailAddress::new(local_part, domain)
+    }
+
+    fn angle_addr(&mut self) -> Result<EmailAddress> {
+        self.expect('<')?;
+
+        let address = self.addr_spec()?;
+
+        self.expect('>')?;
+
+        self.cfws()?;
+
+        Ok(address)
+    }
+
+    /// An address with an optional display name, like `John Doe <john@example.com>`.
+    pub fn mailbox(&mut self) -> Result<Mailbox> {
+        let start = self.position;
+
+        let name = self.phrase()?;
+
+        if self.peek() == Some('<') {
+            let name = name.map(|name| decode_encoded_words(&name));
+
+            let address = self.angle_addr()?;
+
+            return Ok(Mailbox::new(name, address));
+        }
+
+        // Without angle brackets, what looked like a display name was the start of a plain address.
+        self.position = start;
+
+        let address = self.addr_spec()?;
+
+        Ok(Mailbox::new(None, address))
+    }
+
+    /// The members of a group, expects to be right after the colon.
+    fn group_list(&mut self) -> Result<Vec<Mailbox>> {
+        let mut members = Vec::new();
+
+        loop {
+            self.cfws()?;
+
+            if self.eat(';') {
+                break;
+            }
+
+            // Empty list elements are allowed by the obsolete syntax.
+            if self.eat(',') {
+                continue;
+            }
+
+            members.push(self.mailbox()?);
+
+            if self.eat(';') {
+                break;
+            }
+
+            if !self.eat(',') {
+                return Err(self.error("Expected ',' or ';' in a group"));
+            }
+        }
+
+        self.cfws()?;
+
+        Ok(members)
+    }
+
+    /// A mailbox or a group of mailboxes, like `Friends: john@example.com, jane@example.com;`.
+    pub fn address(&mut self) -> Result<Address> {
+        let start = self.position;
+
+        if let Some(name) = self.phrase()? {
+            if self.eat(':') {
+                let members = self.group_list()?;
+
+                return Ok(Address::Group {
+                    name: decode_encoded_words(&name),
+                    members,
+                });
+            }
+        }
+
+        self.position = start;
+
+        Ok(Address::Mailbox(self.mailbox()?))
+    }
+
+    /// A comma separated list of addresses, as found in headers like `To` and `Cc`.
+    pub fn address_list(&mut self) -> Result<Vec<Address>> {
+        let mut addresses = Vec::new();
+
+        loop {
+            self.cfws()?;
+
+            match self.peek() {
+                None => break,
+                // Empty list elements are allowed by the obsolete syntax.
+                Some(',') => {
+                    self.bump();
+
+                    continue;
+                }
+                Some(_) => {}
+            }
+
+            addresses.push(self.address()?);
+
+            match self.peek() {
+                None => break,
+                Some(',') => {
+                    self.bump();
+                }
+                Some(_) => return Err(self.error("Expected ',' between addresses")),
+            }
+        }
+
+        Ok(addresses)
+    }
+}
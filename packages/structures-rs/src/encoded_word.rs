@@ -0,0 +1,197 @@
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use charset::Charset;
+
+const START: &str = "=?";
+const END: &str = "?=";
+
+/// Some mail clients leave out the padding, so we accept base64 with or without it.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// The length of an encoded word, its charset and its decoded bytes.
+type EncodedWord = (usize, Charset, Vec<u8>);
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes the "Q" encoding, which is quoted printable with underscores for spaces.
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    let mut input = text.bytes();
+
+    while let Some(byte) = input.next() {
+        match byte {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let high = hex_value(input.next()?)?;
+                let low = hex_value(input.next()?)?;
+
+                bytes.push(high << 4 | low);
+            }
+            byte => bytes.push(byte),
+        }
+    }
+
+    Some(bytes)
+}
+
+/// Parses an encoded word like `=?utf-8?q?J=C3=B6rg?=` at the start of a given text, returning its length, charset and decoded bytes.
+fn parse_encoded_word(text: &str) -> Option<EncodedWord> {
+    let content = text.strip_prefix(START)?;
+
+    let (label, content) = content.split_once('?')?;
+    let (encoding, content) = content.split_once('?')?;
+
+    let end = content.find(END)?;
+    let encoded = &content[..end];
+
+    if encoded.contains(|character: char| character.is_whitespace()) {
+        return None;
+    }
+
+    // RFC 2231 allows a language after the charset, like `utf-8*en`.
+    let label = label.split('*').next()?;
+
+    let charset = Charset::for_label(label.as_bytes())?;
+
+    let bytes = match encoding {
+        "B" | "b" => BASE64.decode(encoded).ok()?,
+        "Q" | "q" => decode_q(encoded)?,
+        _ => return None,
+    };
+
+    let length = text.len() - content.len() + end + END.len();
+
+    Some((length, charset, bytes))
+}
+
+/// Finds the first valid encoded word in a given text, returning where it starts along with the parsed word.
+fn find_encoded_word(text: &str) -> Option<(usize, EncodedWord)> {
+    let mut offset = 0;
+
+    while let Some(start) = text[offset..].find(START) {
+        let start = offset + start;
+
+        if let Some(word) = parse_encoded_word(&text[start..]) {
+            return Some((start, word));
+        }
+
+        offset = start + START.len();
+    }
+
+    None
+}
+
+fn flush(decoded: &mut String, pending: &mut Option<(Charset, Vec<u8>)>) {
+    if let Some((charset, bytes)) = pending.take() {
+        let (text, _) = charset.decode_without_bom_handling(&bytes);
+
+        decoded.push_str(&text);
+    }
+}
+
+/// Decodes the encoded words of RFC 2047, like `=?utf-8?q?J=C3=B6rg?=`, that headers use for text that is not ASCII.
+///
+/// Words that are malformed or use an unknown charset are left as they are.
+pub fn decode_encoded_words(text: &str) -> String {
+    let mut decoded = String::new();
+
+    // Adjacent words in the same charset are decoded together, because a character can be split over multiple words.
+    let mut pending: Option<(Charset, Vec<u8>)> = None;
+
+    let mut rest = text;
+
+    while let Some((start, (length, charset, bytes))) = find_encoded_word(rest) {
+        let before = &rest[..start];
+
+        // White space between two encoded words is not part of the text.
+        if pending.is_none() || !before.chars().all(char::is_whitespace) {
+            flush(&mut decoded, &mut pending);
+
+            decoded.push_str(before);
+        }
+
+        match pending.as_mut() {
+            Some((pending_charset, pending_bytes)) if *pending_charset == charset => {
+                pending_bytes.extend(bytes)
+            }
+            _ => {
+                flush(&mut decoded, &mut pending);
+
+                pending = Some((charset, bytes));
+            }
+        }
+
+        rest = &rest[start + length..];
+    }
+
+    flush(&mut decoded, &mut pending);
+
+    decoded.push_str(rest);
+
+    decoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode_encoded_words;
+
+    #[test]
+    fn decode() {
+        assert_eq!(decode_encoded_words("John Doe"), "John Doe");
+        assert_eq!(decode_encoded_words("=?utf-8?q?J=C3=B6rg?="), "Jörg");
+        assert_eq!(decode_encoded_words("=?UTF-8?B?SsO2cmc=?= Doe"), "Jörg Doe");
+        assert_eq!(
+            decode_encoded_words("=?iso-8859-1?q?Andr=E9?= Pirard"),
+            "André Pirard"
+        );
+        assert_eq!(decode_encoded_words("=?utf-8?q?Doe=2C_John?="), "Doe, John");
+        assert_eq!(decode_encoded_words("=?utf-8*en?q?Hello?="), "Hello");
+    }
+
+    #[test]
+    fn adjacent_words() {
+        assert_eq!(decode_encoded_words("=?utf-8?q?a?= =?utf-8?q?b?="), "ab");
+        assert_eq!(decode_encoded_words("=?utf-8?q?a?=  b"), "a  b");
+
+        // The bytes of a character are split over two words.
+        assert_eq!(
+            decode_encoded_words("=?utf-8?b?5pel?=\r\n =?utf-8?b?5pys?="),
+            "日本"
+        );
+        assert_eq!(
+            decode_encoded_words("=?utf-8?q?J=C3?= =?utf-8?q?=B6rg?="),
+            "Jörg"
+        );
+    }
+
+    #[test]
+    fn invalid_words() {
+        assert_eq!(
+            decode_encoded_words("=?unknown?q?Hello?="),
+            "=?unknown?q?Hello?="
+        );
+        assert_eq!(
+            decode_encoded_words("=?utf-8?x?Hello?="),
+            "=?utf-8?x?Hello?="
+        );
+        assert_eq!(
+            decode_encoded_words("=?utf-8?q?Hi there?="),
+            "=?utf-8?q?Hi there?="
+        );
+        assert_eq!(decode_encoded_words("1 + 1 =? 2"), "1 + 1 =? 2");
+    }
+}
@@ -1,10 +1,14 @@
-use regex::Regex;
+pub mod address;
+mod encoded_word;
+pub mod types;
 
-const EMAIL_REGEX: &str =
-    r"^([a-z0-9_+]([a-z0-9_+.]*[a-z0-9_+])?)@([a-z0-9]+([\-\.]{1}[a-z0-9]+)*\.[a-z]{2,6})";
+pub use encoded_word::decode_encoded_words;
 
-pub fn validate_email(unknown_str: &str) -> bool {
-    let email_regex = Regex::new(EMAIL_REGEX).unwrap();
+pub use address::{
+    parse_addr_spec, parse_address_list, parse_mailbox, Address, EmailAddress, Mailbox,
+};
 
-    email_regex.is_match(unknown_str)
+/// Whether a given string is a single valid email address, like `john@example.com`.
+pub fn validate_email(unknown_str: &str) -> bool {
+    parse_addr_spec(unknown_str).is_ok()
 }
@@ -0,0 +1,48 @@
+use std::{error, fmt, result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The input does not follow the address syntax of RFC 5322.
+    Syntax,
+    /// The domain of an address is not a valid internationalized domain name.
+    Idna,
+    /// A part of an address is longer than mail servers accept.
+    TooLong,
+}
+
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Error {
+    pub fn new<S: Into<String>>(kind: ErrorKind, msg: S) -> Self {
+        Self {
+            kind,
+            message: msg.into(),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
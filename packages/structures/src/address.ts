@@ -2,7 +2,28 @@ import z from "zod";
 
 export const AddressModel = z.object({
 	name: z.string().nullable(),
-	address: z.string().nullable()
+	address: z.string()
 });
 
 export type Address = z.infer<typeof AddressModel>;
+
+export const GroupModel = z.object({
+	name: z.string(),
+	members: AddressModel.array()
+});
+
+export type Group = z.infer<typeof GroupModel>;
+
+export const AddressListEntryModel = z.union([AddressModel, GroupModel]);
+
+export type AddressListEntry = z.infer<typeof AddressListEntryModel>;
+
+/**
+ * The addresses in an address list, where the members of a group are included as separate addresses.
+ */
+export const flattenAddressList = (entries: AddressListEntry[]): Address[] =>
+	entries.reduce<Address[]>(
+		(addresses, entry) =>
+			addresses.concat("members" in entry ? entry.members : [entry]),
+		[]
+	);
@@ -0,0 +1,10 @@
+import z from "zod";
+
+export const CertificateInfoModel = z.object({
+	subject: z.string(),
+	issuer: z.string(),
+	notBefore: z.number(),
+	notAfter: z.number(),
+	fingerprint: z.string()
+});
+export type CertificateInfo = z.infer<typeof CertificateInfoModel>;
@@ -3,6 +3,7 @@ import z from "zod";
 import {
 	ConnectionSecurityModel,
 	IncomingMailServerTypeModel,
+	MailServerTypeModel,
 	OutgoingMailServerTypeModel,
 	IncomingServerTypeString,
 	OutgoingServerTypeString
@@ -17,11 +18,28 @@ export const AuthTypeModel = z.enum([
 ]);
 export type AuthType = z.infer<typeof AuthTypeModel>;
 
+export const ServerSourceModel = z.enum([
+	"LocalConfig",
+	"Ispdb",
+	"Autoconfig",
+	"Autodiscover",
+	"HttpAutoconfig",
+	"Srv",
+	"ExternalSrv",
+	"Guess"
+]);
+export type ServerSource = z.infer<typeof ServerSourceModel>;
+
 const ServerConfigModel = z.object({
 	port: z.number(),
 	domain: z.string(),
 	security: ConnectionSecurityModel,
-	authType: AuthTypeModel.array()
+	authType: AuthTypeModel.array(),
+	username: z.string().nullable().optional(),
+	source: ServerSourceModel,
+	verified: z.boolean(),
+	confidence: z.number(),
+	insecure: z.boolean()
 });
 export type ServerConfig = z.infer<typeof ServerConfigModel>;
 
@@ -51,10 +69,47 @@ export const OAuth2ConfigModel = z.object({
 });
 export type OAuth2Config = z.infer<typeof OAuth2ConfigModel>;
 
+export const InputFieldModel = z.object({
+	key: z.string(),
+	label: z.string().nullable()
+});
+export type InputField = z.infer<typeof InputFieldModel>;
+
 export const MailConfigModel = z.object({
 	type: ConfigTypeModel,
 	provider: z.string(),
 	oauth2: OAuth2ConfigModel.nullable(),
-	displayName: z.string()
+	displayName: z.string(),
+	inputFields: InputFieldModel.array()
 });
 export type MailConfig = z.infer<typeof MailConfigModel>;
+
+export const DetectSourceModel = z.object({
+	kind: z.enum(["ispdb", "autoconfig", "srv"]),
+	domain: z.string()
+});
+export type DetectSource = z.infer<typeof DetectSourceModel>;
+
+export const SocketModel = z.object({
+	domain: z.string(),
+	port: z.number(),
+	security: ConnectionSecurityModel,
+	source: ServerSourceModel
+});
+export type Socket = z.infer<typeof SocketModel>;
+
+export const DetectProgressModel = z.union([
+	z.object({ type: z.literal("trying"), source: DetectSourceModel }),
+	z.object({ type: z.literal("probing"), socket: SocketModel }),
+	z.object({
+		type: z.literal("probed"),
+		socket: SocketModel,
+		serverType: MailServerTypeModel.nullable(),
+		error: z.string().nullable()
+	}),
+	// A usable config that the user can already pick, while a more complete one is still being detected.
+	z.object({ type: z.literal("found"), config: MailConfigModel }),
+	z.object({ type: z.literal("done"), config: MailConfigModel }),
+	z.object({ type: z.literal("failed"), message: z.string() })
+]);
+export type DetectProgress = z.infer<typeof DetectProgressModel>;
@@ -1,13 +1,18 @@
 import z from "zod";
 
+import { CertificateInfo, CertificateInfoModel } from "./certificate";
+
 export interface AppError {
 	message: string;
 	kind: string | Record<string, AppError>;
+	/** The certificates an untrusted server presented, starting with its own certificate. */
+	certificates?: CertificateInfo[];
 }
 
 export const AppErrorModel: z.ZodType<AppError> = z.lazy(() =>
 	z.object({
 		message: z.string(),
-		kind: z.union([z.string(), z.record(z.string(), AppErrorModel)])
+		kind: z.union([z.string(), z.record(z.string(), AppErrorModel)]),
+		certificates: CertificateInfoModel.array().optional()
 	})
 );
@@ -1,4 +1,5 @@
 export * from "./address";
+export * from "./certificate";
 export * from "./config";
 export * from "./error";
 export * from "./flag";
@@ -69,7 +69,8 @@ export const LoginOptionsModel = z.object({
 	loginType: LoginTypeModel,
 	domain: z.string(),
 	port: z.number(),
-	security: ConnectionSecurityModel
+	security: ConnectionSecurityModel,
+	maxAttachmentSize: z.number().optional()
 });
 export type LoginOptions = z.infer<typeof LoginOptionsModel>;
 
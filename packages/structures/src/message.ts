@@ -1,19 +1,34 @@
 import z from "zod";
 
-import { AddressModel } from "./address";
 import { PreviewModel } from "./preview";
 
 export const ContentModel = z.object({
 	text: z.string().nullable(),
-	html: z.string().nullable()
+	html: z.string().nullable(),
+	// The message was too large to download all of it, so only the start of it is shown.
+	truncated: z.boolean()
 });
 export type Content = z.infer<typeof ContentModel>;
 
-export const MessageModel = PreviewModel.extend({
-	to: AddressModel.array(),
-	cc: AddressModel.array(),
-	bcc: AddressModel.array(),
+export const AttachmentModel = z.object({
+	id: z.string(),
+	contentType: z.string(),
+	filename: z.string().nullable(),
+	size: z.number().nullable(),
+	contentId: z.string().nullable()
+});
+export type Attachment = z.infer<typeof AttachmentModel>;
+
+export const MessageModel = PreviewModel.omit({
+	size: true,
+	snippet: true,
+	hasAttachments: true,
+	messageId: true,
+	inReplyTo: true,
+	importance: true
+}).extend({
 	headers: z.record(z.string(), z.string()),
-	content: ContentModel
+	content: ContentModel,
+	attachments: AttachmentModel.array()
 });
 export type Message = z.infer<typeof MessageModel>;
@@ -1,13 +1,28 @@
 import z from "zod";
 
-import { AddressModel } from "./address";
+import { AddressListEntryModel, AddressModel } from "./address";
 import { FlagModel } from "./flag";
 
+export const ImportanceModel = z.enum(["Low", "Normal", "High"]);
+
+export type Importance = z.infer<typeof ImportanceModel>;
+
 export const PreviewModel = z.object({
 	from: AddressModel.array(),
+	sender: AddressModel.nullable(),
+	replyTo: AddressListEntryModel.array(),
+	to: AddressListEntryModel.array(),
+	cc: AddressListEntryModel.array(),
+	bcc: AddressListEntryModel.array(),
 	flags: FlagModel.array(),
 	id: z.string(),
 	sent: z.number().nullable(),
-	subject: z.string().nullable()
+	subject: z.string().nullable(),
+	size: z.number().nullable(),
+	snippet: z.string().nullable(),
+	hasAttachments: z.boolean(),
+	messageId: z.string().nullable(),
+	inReplyTo: z.string().nullable(),
+	importance: ImportanceModel
 });
 export type Preview = z.infer<typeof PreviewModel>;